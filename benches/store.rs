@@ -0,0 +1,171 @@
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use otel_cli::proto::opentelemetry::proto::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+use otel_cli::proto::opentelemetry::proto::trace::v1::{ResourceSpans, ScopeSpans, Span};
+use otel_cli::store::ingest::Ingester;
+use otel_cli::store::{self, Shard, StorageBackend, Store};
+use tokio_util::sync::CancellationToken;
+
+const RESOURCE_SPANS_PER_TRACE: u64 = 10;
+
+fn resource_spans(trace: u64, part: u64) -> ResourceSpans {
+    let start = trace * 1_000 + part;
+    let mut trace_id = vec![0; 16];
+    trace_id[8..].copy_from_slice(&(trace + 1).to_be_bytes());
+    ResourceSpans {
+        scope_spans: vec![ScopeSpans {
+            spans: vec![Span {
+                trace_id,
+                span_id: (part + 1).to_be_bytes().to_vec(),
+                name: "GET /cart".into(),
+                start_time_unix_nano: start,
+                end_time_unix_nano: start + 500,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+fn trace(trace: u64) -> Vec<ResourceSpans> {
+    (0..RESOURCE_SPANS_PER_TRACE)
+        .map(|part| resource_spans(trace, part))
+        .collect()
+}
+
+/// Insert one trace into a full store, which evicts the oldest trace.
+fn insert_with_eviction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert_traces_full_store");
+    for max_spans in [10_000u64, 100_000] {
+        let max_traces = max_spans / RESOURCE_SPANS_PER_TRACE;
+        let (mut store, _rx) = Store::new(
+            max_traces as usize,
+            max_spans as usize,
+            usize::MAX,
+            usize::MAX,
+        );
+        for t in 0..max_traces {
+            store.insert_traces(trace(t));
+        }
+        let mut next = max_traces;
+        group.bench_with_input(
+            BenchmarkId::from_parameter(max_spans),
+            &max_spans,
+            |b, _| {
+                b.iter(|| {
+                    store.insert_traces(trace(next));
+                    next += 1;
+                })
+            },
+        );
+        assert_eq!(store.trace_count(), max_traces as usize);
+    }
+    group.finish();
+}
+
+const EXPORTERS: u64 = 8;
+const BATCHES_PER_EXPORTER: u64 = 50;
+
+fn logs(time: u64) -> Vec<ResourceLogs> {
+    vec![ResourceLogs {
+        scope_logs: vec![ScopeLogs {
+            log_records: (0..10)
+                .map(|i| LogRecord {
+                    time_unix_nano: time * 10 + i + 1,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }]
+}
+
+/// Hold the read lock for `scan` at a time, back to back like a stream of SQL
+/// queries over a large store, until `stop` is cancelled.
+fn read_continuously(
+    store: Shard,
+    scan: Duration,
+    stop: CancellationToken,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        while !stop.is_cancelled() {
+            let guard = store.blocking_read();
+            std::thread::sleep(scan);
+            drop(guard);
+        }
+    })
+}
+
+/// Log batches per second accepted from concurrent exporters, inserting
+/// straight into the store (the old path) or through an [`Ingester`], with
+/// the store idle or continuously read.
+fn ingest_throughput(c: &mut Criterion) {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(4)
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("ingest_logs");
+    group.throughput(Throughput::Elements(EXPORTERS * BATCHES_PER_EXPORTER));
+    group.sample_size(20);
+    for reading in [false, true] {
+        for ingester in [false, true] {
+            let name = format!(
+                "{}/{}",
+                if ingester { "ingester" } else { "write_lock" },
+                if reading { "reading" } else { "idle" }
+            );
+            let (store, _rx) = store::new_shared(usize::MAX, usize::MAX, 10_000, usize::MAX);
+            let stop = CancellationToken::new();
+            let reader = reading.then(|| {
+                read_continuously(store.logs.clone(), Duration::from_millis(5), stop.clone())
+            });
+            let ingest = rt.block_on(async { Ingester::new(store.clone()) });
+            let mut next = 0u64;
+            group.bench_function(name, |b| {
+                b.iter_custom(|iters| {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let start = Instant::now();
+                        rt.block_on(async {
+                            let exporters: Vec<_> = (0..EXPORTERS)
+                                .map(|exporter| {
+                                    let store = store.clone();
+                                    let ingest = ingest.clone();
+                                    let base = next + exporter * BATCHES_PER_EXPORTER;
+                                    tokio::spawn(async move {
+                                        for batch in base..base + BATCHES_PER_EXPORTER {
+                                            if ingester {
+                                                ingest.insert_logs(logs(batch)).await;
+                                            } else {
+                                                store.logs.write().await.insert_logs(logs(batch));
+                                            }
+                                        }
+                                    })
+                                })
+                                .collect();
+                            for exporter in exporters {
+                                exporter.await.unwrap();
+                            }
+                        });
+                        elapsed += start.elapsed();
+                        next += EXPORTERS * BATCHES_PER_EXPORTER;
+                    }
+                    elapsed
+                })
+            });
+            stop.cancel();
+            if let Some(reader) = reader {
+                reader.join().unwrap();
+            }
+            rt.block_on(ingest.flush());
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, insert_with_eviction, ingest_throughput);
+criterion_main!(benches);
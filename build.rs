@@ -12,6 +12,9 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "proto/opentelemetry-proto/opentelemetry/proto/collector/logs/v1/logs_service.proto",
                 "proto/opentelemetry-proto/opentelemetry/proto/collector/metrics/v1/metrics_service.proto",
                 "proto/query.proto",
+                "proto/zipkin/zipkin.proto",
+                "proto/jaeger/collector.proto",
+                "proto/prometheus/remote.proto",
             ],
             &["proto/opentelemetry-proto", "proto"],
         )?;
@@ -1,4 +1,7 @@
-use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser, Debug)]
 #[command(
@@ -27,6 +30,8 @@ pub struct Cli {
     pub command: Commands,
 }
 
+// Parsed once at startup, so the size of the `Server` variant does not matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Start OTLP server with TUI viewer
@@ -35,17 +40,25 @@ Examples:
   $ otel-cli server                              Interactive TUI mode
   $ otel-cli server --no-tui                     Headless mode
   $ otel-cli server --grpc-addr 0.0.0.0:5317     Custom gRPC port
-  $ otel-cli server --max-traces 5000             Larger store capacity")]
+  $ otel-cli server --mux-addr 0.0.0.0:4317      All APIs on one port
+  $ otel-cli server --max-traces 5000             Larger store capacity
+  $ otel-cli server --max-memory 512MiB           Cap stored data by size
+  $ otel-cli server --trace-retention 2h         Keep the last two hours of traces
+  $ otel-cli server --data-dir ./otel-data        Keep data across restarts")]
     Server {
-        /// gRPC listen address (OTLP collector)
+        /// gRPC listen address (OTLP collector); `host:port` or `unix:///path/to.sock`
         #[arg(long, default_value = "0.0.0.0:4317")]
         grpc_addr: String,
-        /// HTTP listen address (OTLP collector)
+        /// HTTP listen address (OTLP collector); `host:port` or `unix:///path/to.sock`
         #[arg(long, default_value = "0.0.0.0:4318")]
         http_addr: String,
-        /// Query API listen address
+        /// Query API listen address; `host:port` or `unix:///path/to.sock`
         #[arg(long, default_value = "0.0.0.0:4319")]
         query_addr: String,
+        /// Serve OTLP gRPC, OTLP/HTTP and the query API on this single address
+        /// instead of the three separate listeners
+        #[arg(long, conflicts_with_all = ["grpc_addr", "http_addr", "query_addr"])]
+        mux_addr: Option<String>,
         /// Maximum number of distinct traces to keep in store
         #[arg(long, default_value = "1000")]
         max_traces: usize,
@@ -58,6 +71,99 @@ Examples:
         /// Maximum number of ResourceMetrics to keep in store
         #[arg(long, default_value = "1000")]
         max_metrics: usize,
+        /// Memory budget for stored data (e.g. 512MiB, 2G), measured as encoded
+        /// protobuf size; the oldest data of the largest signal is evicted first
+        #[arg(long, value_parser = parse_byte_size)]
+        max_memory: Option<usize>,
+        /// Drop traces whose last span ended longer ago than this (e.g. 2h)
+        #[arg(long, value_parser = parse_duration)]
+        trace_retention: Option<Duration>,
+        /// Drop ResourceLogs whose newest record is older than this (e.g. 30m)
+        #[arg(long, value_parser = parse_duration)]
+        log_retention: Option<Duration>,
+        /// Drop ResourceMetrics whose newest data point is older than this (e.g. 1d)
+        #[arg(long, value_parser = parse_duration)]
+        metric_retention: Option<Duration>,
+        /// Drop spans, log records and metric data points that are already
+        /// stored, such as those resent by an exporter retrying an export; the
+        /// keys it keeps count towards --max-memory
+        #[arg(long)]
+        dedup: bool,
+        /// Persist received data in this directory and reload it on restart
+        #[arg(long)]
+        data_dir: Option<PathBuf>,
+        /// How often the data directory is compacted, dropping evicted data (e.g. 30s, 5m)
+        #[arg(long, default_value = "1m", value_parser = parse_duration, requires = "data_dir")]
+        snapshot_interval: Duration,
+        /// Maximum OTLP/HTTP request body size in bytes (after decompression)
+        #[arg(long, default_value = "67108864")]
+        max_body_size: usize,
+        /// Compress OTLP/HTTP responses when the client accepts it
+        #[arg(long)]
+        http_compression: bool,
+        /// Compression for query API responses (SQL results and follow streams)
+        #[arg(long, default_value = "none")]
+        query_compression: QueryCompression,
+        /// TLS certificate chain (PEM) for the gRPC, HTTP and query listeners
+        #[arg(long, requires = "tls_key")]
+        tls_cert: Option<PathBuf>,
+        /// TLS private key (PEM)
+        #[arg(long, requires = "tls_cert")]
+        tls_key: Option<PathBuf>,
+        /// CA certificate (PEM) used to require and verify client certificates (mTLS)
+        #[arg(long, requires = "tls_cert")]
+        client_ca: Option<PathBuf>,
+        /// Origin allowed to send OTLP/HTTP from a browser, or `*` for any (repeatable)
+        #[arg(long, value_delimiter = ',')]
+        cors_allowed_origin: Vec<String>,
+        /// Request header browsers may send to the OTLP/HTTP endpoints (repeatable)
+        #[arg(long, value_delimiter = ',', default_value = "content-type")]
+        cors_allowed_header: Vec<String>,
+        /// Bearer token required by the OTLP gRPC/HTTP listeners (repeatable)
+        #[arg(
+            long,
+            env = "OTEL_CLI_INGEST_TOKEN",
+            value_delimiter = ',',
+            hide_env_values = true
+        )]
+        ingest_token: Vec<String>,
+        /// Static header (NAME=VALUE) accepted by the OTLP listeners (repeatable)
+        #[arg(long, value_parser = parse_key_val)]
+        ingest_header: Vec<(String, String)>,
+        /// Bearer token with full query API access, including clear and shutdown (repeatable)
+        #[arg(
+            long,
+            env = "OTEL_CLI_QUERY_TOKEN",
+            value_delimiter = ',',
+            hide_env_values = true
+        )]
+        query_token: Vec<String>,
+        /// Bearer token limited to SQL queries, status and follow streams (repeatable)
+        #[arg(
+            long,
+            env = "OTEL_CLI_QUERY_READ_TOKEN",
+            value_delimiter = ',',
+            hide_env_values = true
+        )]
+        query_read_token: Vec<String>,
+        /// Static header (NAME=VALUE) with full query API access (repeatable)
+        #[arg(long, value_parser = parse_key_val)]
+        query_header: Vec<(String, String)>,
+        /// UDP listen address for StatsD/DogStatsD metrics (disabled if not set)
+        #[arg(long)]
+        statsd_addr: Option<String>,
+        /// How often aggregated StatsD metrics are written to the store (e.g. 500ms, 10s)
+        #[arg(long, default_value = "10s", value_parser = parse_duration)]
+        statsd_flush_interval: Duration,
+        /// UDP listen address for syslog messages (RFC 5424/3164, disabled if not set)
+        #[arg(long)]
+        syslog_udp_addr: Option<String>,
+        /// TCP listen address for syslog messages (RFC 5424/3164, disabled if not set)
+        #[arg(long)]
+        syslog_tcp_addr: Option<String>,
+        /// TCP listen address for the Fluent Forward protocol (disabled if not set)
+        #[arg(long)]
+        fluent_addr: Option<String>,
         /// Run without TUI (headless mode)
         #[arg(long)]
         no_tui: bool,
@@ -73,9 +179,11 @@ Examples:
   $ otel-cli logs --service myapp -f             Follow logs for a service
   $ otel-cli logs --format jsonl --since 10m      JSONL output, last 10 minutes")]
     Logs {
-        /// Server address
+        /// Server address (`http(s)://host:port` or `unix:///path/to.sock`)
         #[arg(long, default_value = "http://localhost:4319")]
         server: String,
+        #[command(flatten)]
+        connection: ConnectionArgs,
         /// Filter by service name
         #[arg(long)]
         service: Option<String>,
@@ -109,9 +217,11 @@ Examples:
   $ otel-cli traces --service myapp -f           Follow traces for a service
   $ otel-cli traces -f --full                    Follow with full trace groups")]
     Traces {
-        /// Server address
+        /// Server address (`http(s)://host:port` or `unix:///path/to.sock`)
         #[arg(long, default_value = "http://localhost:4319")]
         server: String,
+        #[command(flatten)]
+        connection: ConnectionArgs,
         /// Filter by service name
         #[arg(long)]
         service: Option<String>,
@@ -145,11 +255,17 @@ Examples:
 Examples:
   $ otel-cli clear --traces --logs --metrics     Clear all data
   $ otel-cli clear --traces                      Clear only traces
-  $ otel-cli clear --logs                        Clear only logs")]
+  $ otel-cli clear --logs                        Clear only logs
+  $ otel-cli clear --service checkout            Delete everything from one service
+  $ otel-cli clear --logs --before 10m           Delete logs older than 10 minutes
+  $ otel-cli clear --traces --where \"span_name = 'GET /health'\"
+                                                 Delete matching spans")]
     Clear {
-        /// Server address
+        /// Server address (`http(s)://host:port` or `unix:///path/to.sock`)
         #[arg(long, default_value = "http://localhost:4319")]
         server: String,
+        #[command(flatten)]
+        connection: ConnectionArgs,
         /// Clear traces
         #[arg(long)]
         traces: bool,
@@ -159,6 +275,15 @@ Examples:
         /// Clear metrics
         #[arg(long)]
         metrics: bool,
+        /// Only delete data from this service (all signals unless one is given)
+        #[arg(long)]
+        service: Option<String>,
+        /// Only delete data older than this (e.g. 30s, 5m, 1h, 2d, or RFC3339)
+        #[arg(long)]
+        before: Option<String>,
+        /// Only delete rows matching this SQL WHERE clause
+        #[arg(long = "where", value_name = "FILTER")]
+        filter: Option<String>,
     },
     /// Attach to a running server and display TUI
     #[command(after_long_help = "\
@@ -167,9 +292,11 @@ Examples:
   $ otel-cli view --server http://remote:4319    Attach to remote server
   $ otel-cli view --max-traces 500                Custom local store capacity")]
     View {
-        /// Query API server address
+        /// Query API server address (`http(s)://host:port` or `unix:///path/to.sock`)
         #[arg(long, default_value = "http://localhost:4319")]
         server: String,
+        #[command(flatten)]
+        connection: ConnectionArgs,
         /// Maximum number of distinct traces to keep in local store
         #[arg(long, default_value = "1000")]
         max_traces: usize,
@@ -191,9 +318,11 @@ Examples:
   $ otel-cli metrics --service myapp -f          Follow metrics for a service
   $ otel-cli metrics --format jsonl               JSONL output")]
     Metrics {
-        /// Server address
+        /// Server address (`http(s)://host:port` or `unix:///path/to.sock`)
         #[arg(long, default_value = "http://localhost:4319")]
         server: String,
+        #[command(flatten)]
+        connection: ConnectionArgs,
         /// Filter by service name
         #[arg(long)]
         service: Option<String>,
@@ -224,9 +353,11 @@ Examples:
   $ otel-cli sql -f \"SELECT * FROM logs\"            Follow mode
   $ otel-cli sql \"SELECT * FROM metrics\" --format jsonl")]
     Sql {
-        /// Server address
+        /// Server address (`http(s)://host:port` or `unix:///path/to.sock`)
         #[arg(long, default_value = "http://localhost:4319")]
         server: String,
+        #[command(flatten)]
+        connection: ConnectionArgs,
         /// SQL query string
         query: String,
         /// Output format
@@ -241,15 +372,76 @@ Examples:
     },
     /// Show server status
     Status {
-        /// Server address
+        /// Server address (`http(s)://host:port` or `unix:///path/to.sock`)
         #[arg(long, default_value = "http://localhost:4319")]
         server: String,
+        #[command(flatten)]
+        connection: ConnectionArgs,
     },
     /// Shutdown the server
     Shutdown {
-        /// Server address
+        /// Server address (`http(s)://host:port` or `unix:///path/to.sock`)
         #[arg(long, default_value = "http://localhost:4319")]
         server: String,
+        #[command(flatten)]
+        connection: ConnectionArgs,
+    },
+    /// Import OTLP data from files or stdin into a running server
+    #[command(after_long_help = "\
+Examples:
+  $ otel-cli import traces.jsonl logs.jsonl       Import collector file exporter output
+  $ cat capture.json | otel-cli import            Read stdin (format detected)
+  $ otel-cli import spans.pb --signal traces      Length-delimited TracesData messages
+  $ otel-cli import ci-artifact.jsonl --shift-to-now  Make old data look recent")]
+    Import {
+        /// OTLP gRPC endpoint of the server (`http(s)://host:port` or `unix:///path/to.sock`)
+        #[arg(long, default_value = "http://localhost:4317")]
+        endpoint: String,
+        #[command(flatten)]
+        connection: ConnectionArgs,
+        /// Files to import; reads stdin when none are given or for `-`
+        files: Vec<PathBuf>,
+        /// Input format [default: from the file extension, else detected from the content]
+        #[arg(long)]
+        format: Option<OtlpFileFormat>,
+        /// Signal contained in protobuf input
+        #[arg(long)]
+        signal: Option<ImportSignal>,
+        /// Shift all timestamps so that the newest record is at the current time
+        #[arg(long)]
+        shift_to_now: bool,
+    },
+    /// Export stored data as OTLP files
+    #[command(after_long_help = "\
+Examples:
+  $ otel-cli export -o capture.jsonl             Everything, one JSON document per line
+  $ otel-cli export --traces -o spans.pb         Traces as length-delimited protobuf
+  $ otel-cli export --logs --where \"service_name = 'checkout'\" > checkout-logs.jsonl
+  $ otel-cli export --format json | otel-cli import --endpoint http://other:4317")]
+    Export {
+        /// Server address (`http(s)://host:port` or `unix:///path/to.sock`)
+        #[arg(long, default_value = "http://localhost:4319")]
+        server: String,
+        #[command(flatten)]
+        connection: ConnectionArgs,
+        /// Output file; writes to stdout when omitted
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Output format [default: from the output file extension, else jsonl]
+        #[arg(long)]
+        format: Option<OtlpFileFormat>,
+        /// Export traces
+        #[arg(long)]
+        traces: bool,
+        /// Export logs
+        #[arg(long)]
+        logs: bool,
+        /// Export metrics
+        #[arg(long)]
+        metrics: bool,
+        /// SQL WHERE clause applied to the table of each exported signal
+        #[arg(long = "where", value_name = "FILTER")]
+        filter: Option<String>,
     },
     /// Install agent skill for AI-assisted operation
     #[command(after_long_help = "\
@@ -267,6 +459,26 @@ Examples:
     },
 }
 
+/// Options for connecting to the query API: TLS and credentials.
+#[derive(Args, Clone, Debug, Default, PartialEq)]
+pub struct ConnectionArgs {
+    /// CA certificate (PEM) used to verify an https:// server
+    #[arg(long, env = "OTEL_CLI_CA_CERT")]
+    pub ca_cert: Option<PathBuf>,
+    /// Client certificate (PEM) for mutual TLS
+    #[arg(long, env = "OTEL_CLI_CLIENT_CERT", requires = "client_key")]
+    pub client_cert: Option<PathBuf>,
+    /// Client private key (PEM) for mutual TLS
+    #[arg(long, env = "OTEL_CLI_CLIENT_KEY", requires = "client_cert")]
+    pub client_key: Option<PathBuf>,
+    /// Bearer token sent with every request
+    #[arg(long, env = "OTEL_CLI_TOKEN", hide_env_values = true)]
+    pub token: Option<String>,
+    /// Extra header (NAME=VALUE) sent with every request (repeatable)
+    #[arg(long = "header", value_parser = parse_key_val)]
+    pub headers: Vec<(String, String)>,
+}
+
 #[derive(clap::ValueEnum, Clone, Debug)]
 pub enum OutputFormat {
     /// Rich text display (trace/log/metric specific)
@@ -277,6 +489,30 @@ pub enum OutputFormat {
     Csv,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum QueryCompression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OtlpFileFormat {
+    /// OTLP JSON documents (`TracesData`, `LogsData` or `MetricsData`)
+    Json,
+    /// One OTLP JSON document per line, as written by the collector `file` exporter
+    Jsonl,
+    /// Varint length-delimited OTLP protobuf messages, one signal per file
+    Protobuf,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ImportSignal {
+    Traces,
+    Logs,
+    Metrics,
+}
+
 #[derive(clap::ValueEnum, Clone, Debug)]
 pub enum SqlOutputFormat {
     /// Aligned table with header
@@ -292,6 +528,51 @@ fn parse_key_val(s: &str) -> Result<(String, String), String> {
     Ok((s[..pos].to_string(), s[pos + 1..].to_string()))
 }
 
+/// Parse a duration such as `500ms`, `10s`, `5m`, `1h` or `2d`.
+/// Parse a byte size with an optional binary unit suffix (`K`, `M`, `G`,
+/// optionally followed by `iB` or `B`), e.g. `512MiB` or `2G`.
+pub fn parse_byte_size(s: &str) -> Result<usize, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let multiplier: usize = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        _ => return Err(format!("invalid size unit in `{s}` (use K, M or G)")),
+    };
+    number
+        .parse::<usize>()
+        .ok()
+        .filter(|n| *n > 0)
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size: `{s}`"))
+}
+
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    const UNITS: &[(&str, u64)] = &[
+        ("ms", 1),
+        ("s", 1000),
+        ("m", 60_000),
+        ("h", 3_600_000),
+        ("d", 86_400_000),
+    ];
+    for &(suffix, millis) in UNITS {
+        if let Some(n) = s.trim().strip_suffix(suffix) {
+            if let Ok(n) = n.parse::<u64>() {
+                if n == 0 {
+                    return Err(format!("duration must be greater than zero: `{s}`"));
+                }
+                return Ok(Duration::from_millis(n * millis));
+            }
+        }
+    }
+    Err(format!(
+        "invalid duration `{s}` (expected e.g. 500ms, 10s, 5m, 1h, 2d)"
+    ))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -305,20 +586,72 @@ mod tests {
                 grpc_addr,
                 http_addr,
                 query_addr,
+                mux_addr,
                 max_traces,
                 max_spans,
                 max_logs,
                 max_metrics,
+                max_memory,
+                trace_retention,
+                log_retention,
+                metric_retention,
+                dedup,
+                data_dir,
+                snapshot_interval,
+                max_body_size,
+                http_compression,
+                query_compression,
+                tls_cert,
+                tls_key,
+                client_ca,
+                cors_allowed_origin,
+                cors_allowed_header,
+                ingest_token,
+                ingest_header,
+                query_token,
+                query_read_token,
+                query_header,
+                statsd_addr,
+                statsd_flush_interval,
+                syslog_udp_addr,
+                syslog_tcp_addr,
+                fluent_addr,
                 no_tui,
                 otlp_endpoint,
             } => {
                 assert_eq!(grpc_addr, "0.0.0.0:4317");
                 assert_eq!(http_addr, "0.0.0.0:4318");
                 assert_eq!(query_addr, "0.0.0.0:4319");
+                assert!(mux_addr.is_none());
                 assert_eq!(max_traces, 1000);
                 assert_eq!(max_spans, 100000);
                 assert_eq!(max_logs, 1000);
                 assert_eq!(max_metrics, 1000);
+                assert!(max_memory.is_none());
+                assert!(trace_retention.is_none());
+                assert!(log_retention.is_none());
+                assert!(metric_retention.is_none());
+                assert!(!dedup);
+                assert!(data_dir.is_none());
+                assert_eq!(snapshot_interval, Duration::from_secs(60));
+                assert_eq!(max_body_size, 64 * 1024 * 1024);
+                assert!(!http_compression);
+                assert_eq!(query_compression, QueryCompression::None);
+                assert!(tls_cert.is_none());
+                assert!(tls_key.is_none());
+                assert!(client_ca.is_none());
+                assert!(cors_allowed_origin.is_empty());
+                assert_eq!(cors_allowed_header, vec!["content-type".to_string()]);
+                assert!(ingest_token.is_empty());
+                assert!(ingest_header.is_empty());
+                assert!(query_token.is_empty());
+                assert!(query_read_token.is_empty());
+                assert!(query_header.is_empty());
+                assert!(statsd_addr.is_none());
+                assert_eq!(statsd_flush_interval, Duration::from_secs(10));
+                assert!(syslog_udp_addr.is_none());
+                assert!(syslog_tcp_addr.is_none());
+                assert!(fluent_addr.is_none());
                 assert!(!no_tui);
                 assert!(otlp_endpoint.is_none());
             }
@@ -364,6 +697,179 @@ mod tests {
         }
     }
 
+    #[test]
+    fn server_subcommand_parses_retention() {
+        let cli = Cli::parse_from([
+            "otel-cli",
+            "server",
+            "--trace-retention",
+            "2h",
+            "--log-retention",
+            "30m",
+        ]);
+        match cli.command {
+            Commands::Server {
+                trace_retention,
+                log_retention,
+                metric_retention,
+                ..
+            } => {
+                assert_eq!(trace_retention, Some(Duration::from_secs(7200)));
+                assert_eq!(log_retention, Some(Duration::from_secs(1800)));
+                assert!(metric_retention.is_none());
+            }
+            _ => panic!("Expected Server command"),
+        }
+    }
+
+    #[test]
+    fn server_subcommand_snapshot_interval_requires_data_dir() {
+        let cli = Cli::parse_from([
+            "otel-cli",
+            "server",
+            "--data-dir",
+            "/var/lib/otel-cli",
+            "--snapshot-interval",
+            "30s",
+        ]);
+        match cli.command {
+            Commands::Server {
+                data_dir,
+                snapshot_interval,
+                ..
+            } => {
+                assert_eq!(data_dir, Some(PathBuf::from("/var/lib/otel-cli")));
+                assert_eq!(snapshot_interval, Duration::from_secs(30));
+            }
+            _ => panic!("Expected Server command"),
+        }
+        assert!(Cli::try_parse_from(["otel-cli", "server", "--snapshot-interval", "30s"]).is_err());
+    }
+
+    #[test]
+    fn server_subcommand_mux_addr_conflicts_with_separate_addrs() {
+        let cli = Cli::parse_from(["otel-cli", "server", "--mux-addr", "0.0.0.0:4317"]);
+        match cli.command {
+            Commands::Server { mux_addr, .. } => {
+                assert_eq!(mux_addr, Some("0.0.0.0:4317".to_string()));
+            }
+            _ => panic!("Expected Server command"),
+        }
+        assert!(Cli::try_parse_from([
+            "otel-cli",
+            "server",
+            "--mux-addr",
+            "0.0.0.0:4317",
+            "--query-addr",
+            "0.0.0.0:4319",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn server_subcommand_parses_statsd_args() {
+        let cli = Cli::parse_from([
+            "otel-cli",
+            "server",
+            "--statsd-addr",
+            "127.0.0.1:8125",
+            "--statsd-flush-interval",
+            "500ms",
+        ]);
+        match cli.command {
+            Commands::Server {
+                statsd_addr,
+                statsd_flush_interval,
+                ..
+            } => {
+                assert_eq!(statsd_addr, Some("127.0.0.1:8125".to_string()));
+                assert_eq!(statsd_flush_interval, Duration::from_millis(500));
+            }
+            _ => panic!("Expected Server command"),
+        }
+    }
+
+    #[test]
+    fn parse_byte_size_units() {
+        assert_eq!(parse_byte_size("4096"), Ok(4096));
+        assert_eq!(parse_byte_size("64K"), Ok(64 * 1024));
+        assert_eq!(parse_byte_size("512MiB"), Ok(512 * 1024 * 1024));
+        assert_eq!(parse_byte_size("2gb"), Ok(2 * 1024 * 1024 * 1024));
+        assert!(parse_byte_size("0").is_err());
+        assert!(parse_byte_size("10T").is_err());
+        assert!(parse_byte_size("MiB").is_err());
+    }
+
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("10s"), Ok(Duration::from_secs(10)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1h"), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("2d"), Ok(Duration::from_secs(172_800)));
+        assert!(parse_duration("0s").is_err());
+        assert!(parse_duration("10").is_err());
+        assert!(parse_duration("abc").is_err());
+    }
+
+    #[test]
+    fn server_subcommand_parses_auth_args() {
+        let cli = Cli::parse_from([
+            "otel-cli",
+            "server",
+            "--ingest-token",
+            "in1",
+            "--ingest-header",
+            "x-api-key=secret",
+            "--query-token",
+            "admin",
+            "--query-read-token",
+            "r1,r2",
+        ]);
+        match cli.command {
+            Commands::Server {
+                ingest_token,
+                ingest_header,
+                query_token,
+                query_read_token,
+                query_header,
+                ..
+            } => {
+                assert_eq!(ingest_token, vec!["in1".to_string()]);
+                assert_eq!(
+                    ingest_header,
+                    vec![("x-api-key".to_string(), "secret".to_string())]
+                );
+                assert_eq!(query_token, vec!["admin".to_string()]);
+                assert_eq!(query_read_token, vec!["r1".to_string(), "r2".to_string()]);
+                assert!(query_header.is_empty());
+            }
+            _ => panic!("Expected Server command"),
+        }
+    }
+
+    #[test]
+    fn client_connection_parses_credentials() {
+        let cli = Cli::parse_from([
+            "otel-cli",
+            "status",
+            "--token",
+            "abc",
+            "--header",
+            "x-tenant=dev",
+        ]);
+        match cli.command {
+            Commands::Status { connection, .. } => {
+                assert_eq!(connection.token, Some("abc".to_string()));
+                assert_eq!(
+                    connection.headers,
+                    vec![("x-tenant".to_string(), "dev".to_string())]
+                );
+            }
+            _ => panic!("Expected Status command"),
+        }
+    }
+
     #[test]
     fn logs_subcommand_parses_with_filters() {
         let cli = Cli::parse_from([
@@ -383,6 +889,7 @@ mod tests {
         match cli.command {
             Commands::Logs {
                 server,
+                connection,
                 service,
                 severity,
                 attribute,
@@ -393,6 +900,7 @@ mod tests {
                 until,
             } => {
                 assert_eq!(server, "http://localhost:4319");
+                assert_eq!(connection, ConnectionArgs::default());
                 assert_eq!(service, Some("my-service".to_string()));
                 assert_eq!(severity, Some("ERROR".to_string()));
                 assert_eq!(
@@ -422,6 +930,7 @@ mod tests {
         match cli.command {
             Commands::Traces {
                 server,
+                connection,
                 service,
                 trace_id,
                 attribute,
@@ -433,6 +942,7 @@ mod tests {
                 until,
             } => {
                 assert_eq!(server, "http://localhost:4319");
+                assert_eq!(connection, ConnectionArgs::default());
                 assert_eq!(service, Some("frontend".to_string()));
                 assert_eq!(trace_id, Some("abc123def456".to_string()));
                 assert!(attribute.is_empty());
@@ -462,6 +972,7 @@ mod tests {
         match cli.command {
             Commands::Metrics {
                 server,
+                connection,
                 service,
                 name,
                 limit,
@@ -471,6 +982,7 @@ mod tests {
                 until,
             } => {
                 assert_eq!(server, "http://localhost:4319");
+                assert_eq!(connection, ConnectionArgs::default());
                 assert_eq!(service, Some("api-gateway".to_string()));
                 assert_eq!(name, Some("http.request.duration".to_string()));
                 assert_eq!(limit, 200);
@@ -512,12 +1024,14 @@ mod tests {
         match cli.command {
             Commands::View {
                 server,
+                connection,
                 max_traces,
                 max_spans,
                 max_logs,
                 max_metrics,
             } => {
                 assert_eq!(server, "http://localhost:4319");
+                assert_eq!(connection, ConnectionArgs::default());
                 assert_eq!(max_traces, 1000);
                 assert_eq!(max_spans, 100000);
                 assert_eq!(max_logs, 1000);
@@ -1,8 +1,51 @@
-use crate::proto::otelcli::query::v1::query_service_client::QueryServiceClient;
-use crate::proto::otelcli::query::v1::{ClearLogsRequest, ClearMetricsRequest, ClearTracesRequest};
+use crate::cli::ConnectionArgs;
+use crate::proto::otelcli::query::v1::{
+    ClearLogsRequest, ClearMetricsRequest, ClearTracesRequest, DeleteRequest,
+};
 
-pub async fn clear(server: &str, traces: bool, logs: bool, metrics: bool) -> anyhow::Result<()> {
-    let mut client = QueryServiceClient::connect(server.to_string()).await?;
+#[allow(clippy::too_many_arguments)]
+pub async fn clear(
+    server: &str,
+    connection: &ConnectionArgs,
+    traces: bool,
+    logs: bool,
+    metrics: bool,
+    service: Option<String>,
+    before: Option<String>,
+    filter: Option<String>,
+) -> anyhow::Result<()> {
+    if service.is_some() || before.is_some() || filter.is_some() {
+        let before = before
+            .as_deref()
+            .map(super::parse_time_spec)
+            .transpose()?
+            .unwrap_or(0);
+        let mut client = super::connect(server, connection).await?;
+        let response = client
+            .delete(DeleteRequest {
+                traces,
+                logs,
+                metrics,
+                service: service.unwrap_or_default(),
+                before,
+                filter: filter.unwrap_or_default(),
+            })
+            .await?
+            .into_inner();
+        let all = !traces && !logs && !metrics;
+        if all || traces {
+            println!("Deleted {} spans.", response.spans);
+        }
+        if all || logs {
+            println!("Deleted {} log records.", response.log_records);
+        }
+        if all || metrics {
+            println!("Deleted {} metric data points.", response.metric_points);
+        }
+        return Ok(());
+    }
+
+    let mut client = super::connect(server, connection).await?;
 
     if traces {
         client.clear_traces(ClearTracesRequest {}).await?;
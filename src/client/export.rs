@@ -0,0 +1,233 @@
+use std::io::Write;
+use std::path::Path;
+
+use prost::Message;
+
+use crate::cli::{ConnectionArgs, OtlpFileFormat};
+use crate::proto::opentelemetry::proto::{
+    logs::v1::LogsData, metrics::v1::MetricsData, trace::v1::TracesData,
+};
+use crate::proto::otelcli::query::v1::{ExportRequest, ExportResponse};
+use crate::server::otlp_http::encode_json;
+use crate::store::data_point_count;
+
+#[allow(clippy::too_many_arguments)]
+pub async fn export(
+    server: &str,
+    connection: &ConnectionArgs,
+    output: Option<&Path>,
+    format: Option<OtlpFileFormat>,
+    traces: bool,
+    logs: bool,
+    metrics: bool,
+    filter: Option<String>,
+) -> anyhow::Result<()> {
+    let format = format
+        .or_else(|| output.and_then(super::import::format_from_extension))
+        .unwrap_or(OtlpFileFormat::Jsonl);
+    if format == OtlpFileFormat::Protobuf
+        && [traces, logs, metrics].iter().filter(|s| **s).count() != 1
+    {
+        anyhow::bail!(
+            "protobuf output holds a single signal; pass exactly one of --traces, --logs or --metrics"
+        );
+    }
+
+    let mut client = super::connect(server, connection)
+        .await?
+        .max_decoding_message_size(usize::MAX);
+    let mut stream = client
+        .export(ExportRequest {
+            traces,
+            logs,
+            metrics,
+            filter: filter.unwrap_or_default(),
+        })
+        .await?
+        .into_inner();
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(std::io::BufWriter::new(
+            std::fs::File::create(path)
+                .map_err(|e| anyhow::anyhow!("Failed to create {}: {}", path.display(), e))?,
+        )),
+        None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+    };
+
+    let mut merged = ExportResponse::default();
+    let (mut spans, mut records, mut points) = (0, 0, 0);
+    while let Some(response) = stream.message().await? {
+        spans += response
+            .resource_spans
+            .iter()
+            .flat_map(|rs| &rs.scope_spans)
+            .map(|ss| ss.spans.len())
+            .sum::<usize>();
+        records += response
+            .resource_logs
+            .iter()
+            .flat_map(|rl| &rl.scope_logs)
+            .map(|sl| sl.log_records.len())
+            .sum::<usize>();
+        points += response
+            .resource_metrics
+            .iter()
+            .flat_map(|rm| &rm.scope_metrics)
+            .flat_map(|sm| &sm.metrics)
+            .map(data_point_count)
+            .sum::<usize>();
+
+        if format == OtlpFileFormat::Json {
+            merged.resource_spans.extend(response.resource_spans);
+            merged.resource_logs.extend(response.resource_logs);
+            merged.resource_metrics.extend(response.resource_metrics);
+        } else {
+            write_response(&mut writer, format, response)?;
+        }
+    }
+    if format == OtlpFileFormat::Json {
+        write_response(&mut writer, format, merged)?;
+    }
+    writer.flush()?;
+
+    eprintln!(
+        "Exported {} spans, {} log records, {} metric data points.",
+        spans, records, points
+    );
+    Ok(())
+}
+
+/// Write one document (or length-delimited message) per signal present in
+/// `response`, in the form `otel-cli import` reads back.
+pub fn write_response(
+    writer: &mut impl Write,
+    format: OtlpFileFormat,
+    response: ExportResponse,
+) -> anyhow::Result<()> {
+    if !response.resource_spans.is_empty() {
+        write_message(
+            writer,
+            format,
+            &TracesData {
+                resource_spans: response.resource_spans,
+            },
+        )?;
+    }
+    if !response.resource_logs.is_empty() {
+        write_message(
+            writer,
+            format,
+            &LogsData {
+                resource_logs: response.resource_logs,
+            },
+        )?;
+    }
+    if !response.resource_metrics.is_empty() {
+        write_message(
+            writer,
+            format,
+            &MetricsData {
+                resource_metrics: response.resource_metrics,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn write_message<T: Message + serde::Serialize>(
+    writer: &mut impl Write,
+    format: OtlpFileFormat,
+    message: &T,
+) -> anyhow::Result<()> {
+    match format {
+        OtlpFileFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, &encode_json(message)?)?;
+            writeln!(writer)?;
+        }
+        OtlpFileFormat::Jsonl => {
+            serde_json::to_writer(&mut *writer, &encode_json(message)?)?;
+            writeln!(writer)?;
+        }
+        OtlpFileFormat::Protobuf => writer.write_all(&message.encode_length_delimited_to_vec())?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::import::{parse, ImportData};
+    use crate::proto::opentelemetry::proto::{
+        logs::v1::{LogRecord, ResourceLogs, ScopeLogs},
+        trace::v1::{ResourceSpans, ScopeSpans, Span},
+    };
+
+    fn response() -> ExportResponse {
+        ExportResponse {
+            resource_spans: vec![ResourceSpans {
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![Span {
+                        trace_id: (1..=16).collect(),
+                        span_id: vec![7; 8],
+                        parent_span_id: vec![8; 8],
+                        name: "GET /cart".into(),
+                        start_time_unix_nano: 1_000,
+                        end_time_unix_nano: 2_000,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            resource_logs: vec![ResourceLogs {
+                scope_logs: vec![ScopeLogs {
+                    log_records: vec![LogRecord {
+                        time_unix_nano: 3_000,
+                        trace_id: (1..=16).collect(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            resource_metrics: vec![],
+        }
+    }
+
+    #[test]
+    fn test_jsonl_uses_hex_ids_and_round_trips() {
+        let mut out = Vec::new();
+        write_response(&mut out, OtlpFileFormat::Jsonl, response()).unwrap();
+        let text = String::from_utf8(out.clone()).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.contains(r#""traceId":"0102030405060708090a0b0c0d0e0f10""#));
+        assert!(text.contains(r#""parentSpanId":"0808080808080808""#));
+
+        let mut data = ImportData::default();
+        parse(&out, OtlpFileFormat::Jsonl, None, &mut data).unwrap();
+        assert_eq!(data.traces, response().resource_spans);
+        assert_eq!(data.logs, response().resource_logs);
+    }
+
+    #[test]
+    fn test_protobuf_round_trips() {
+        let traces_only = ExportResponse {
+            resource_logs: vec![],
+            ..response()
+        };
+        let mut out = Vec::new();
+        write_response(&mut out, OtlpFileFormat::Protobuf, traces_only.clone()).unwrap();
+        write_response(&mut out, OtlpFileFormat::Protobuf, traces_only.clone()).unwrap();
+
+        let mut data = ImportData::default();
+        parse(
+            &out,
+            OtlpFileFormat::Protobuf,
+            Some(crate::cli::ImportSignal::Traces),
+            &mut data,
+        )
+        .unwrap();
+        assert_eq!(data.traces.len(), 2);
+        assert_eq!(data.traces[1], traces_only.resource_spans[0]);
+    }
+}
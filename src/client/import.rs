@@ -0,0 +1,490 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use prost::Message;
+use serde::de::IgnoredAny;
+use tonic::codec::CompressionEncoding;
+
+use super::Credentials;
+use crate::cli::{ConnectionArgs, ImportSignal, OtlpFileFormat};
+use crate::proto::opentelemetry::proto::collector::{
+    logs::v1::{logs_service_client::LogsServiceClient, ExportLogsServiceRequest},
+    metrics::v1::{metrics_service_client::MetricsServiceClient, ExportMetricsServiceRequest},
+    trace::v1::{trace_service_client::TraceServiceClient, ExportTraceServiceRequest},
+};
+use crate::proto::opentelemetry::proto::{
+    logs::v1::{LogsData, ResourceLogs},
+    metrics::v1::{metric, MetricsData, ResourceMetrics},
+    trace::v1::{ResourceSpans, TracesData},
+};
+use crate::server::otlp_http::decode_json;
+use crate::store::{batches, data_point_count};
+
+/// Export requests are split to stay well below the default 4 MiB gRPC
+/// message limit.
+const MAX_REQUEST_BYTES: usize = 1024 * 1024;
+
+/// Telemetry read from the import inputs.
+#[derive(Debug, Default)]
+pub struct ImportData {
+    pub traces: Vec<ResourceSpans>,
+    pub logs: Vec<ResourceLogs>,
+    pub metrics: Vec<ResourceMetrics>,
+}
+
+/// Top-level keys of an OTLP JSON document, used to pick the message type.
+#[derive(serde::Deserialize)]
+struct DocumentKeys {
+    #[serde(rename = "resourceSpans", alias = "resource_spans")]
+    resource_spans: Option<IgnoredAny>,
+    #[serde(rename = "resourceLogs", alias = "resource_logs")]
+    resource_logs: Option<IgnoredAny>,
+    #[serde(rename = "resourceMetrics", alias = "resource_metrics")]
+    resource_metrics: Option<IgnoredAny>,
+}
+
+pub async fn import(
+    endpoint: &str,
+    connection: &ConnectionArgs,
+    files: &[PathBuf],
+    format: Option<OtlpFileFormat>,
+    signal: Option<ImportSignal>,
+    shift_to_now: bool,
+) -> anyhow::Result<()> {
+    let stdin = [PathBuf::from("-")];
+    let files = if files.is_empty() { &stdin[..] } else { files };
+
+    let mut data = ImportData::default();
+    for path in files {
+        let bytes = read_input(path)?;
+        let format = format
+            .or_else(|| format_from_extension(path))
+            .unwrap_or_else(|| detect_format(&bytes));
+        parse(&bytes, format, signal, &mut data)
+            .map_err(|e| anyhow::anyhow!("Failed to import {}: {}", path.display(), e))?;
+    }
+
+    if shift_to_now {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)?
+            .as_nanos() as u64;
+        shift_timestamps(&mut data, now);
+    }
+
+    let counts = send(endpoint, connection, data).await?;
+    println!(
+        "Imported {} spans, {} log records, {} metric data points.",
+        counts.0, counts.1, counts.2
+    );
+    Ok(())
+}
+
+fn read_input(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if path.as_os_str() == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|e| anyhow::anyhow!("Failed to read stdin: {}", e))?;
+        return Ok(bytes);
+    }
+    std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))
+}
+
+pub(crate) fn format_from_extension(path: &Path) -> Option<OtlpFileFormat> {
+    match path.extension()?.to_str()? {
+        "json" => Some(OtlpFileFormat::Json),
+        "jsonl" | "ndjson" => Some(OtlpFileFormat::Jsonl),
+        "pb" | "binpb" | "protobuf" => Some(OtlpFileFormat::Protobuf),
+        _ => None,
+    }
+}
+
+/// OTLP JSON always starts with an object; anything else is treated as protobuf.
+fn detect_format(bytes: &[u8]) -> OtlpFileFormat {
+    match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'{') => OtlpFileFormat::Json,
+        _ => OtlpFileFormat::Protobuf,
+    }
+}
+
+/// Parse one input and append its contents to `data`.
+pub fn parse(
+    bytes: &[u8],
+    format: OtlpFileFormat,
+    signal: Option<ImportSignal>,
+    data: &mut ImportData,
+) -> anyhow::Result<()> {
+    match format {
+        // Both are a sequence of JSON documents; JSONL only adds newlines between them.
+        OtlpFileFormat::Json | OtlpFileFormat::Jsonl => parse_json(bytes, data),
+        OtlpFileFormat::Protobuf => {
+            let signal =
+                signal.ok_or_else(|| anyhow::anyhow!("protobuf input requires --signal"))?;
+            parse_protobuf(bytes, signal, data)
+        }
+    }
+}
+
+fn parse_json(bytes: &[u8], data: &mut ImportData) -> anyhow::Result<()> {
+    let mut documents = serde_json::Deserializer::from_slice(bytes).into_iter::<DocumentKeys>();
+    let mut start = 0;
+    while let Some(keys) = documents.next() {
+        let keys = keys.map_err(|e| anyhow::anyhow!("invalid OTLP JSON: {}", e))?;
+        let end = documents.byte_offset();
+        let document = &bytes[start..end];
+        start = end;
+
+        let decoded = if keys.resource_spans.is_some() {
+            decode_json::<TracesData>(document).map(|d| data.traces.extend(d.resource_spans))
+        } else if keys.resource_logs.is_some() {
+            decode_json::<LogsData>(document).map(|d| data.logs.extend(d.resource_logs))
+        } else if keys.resource_metrics.is_some() {
+            decode_json::<MetricsData>(document).map(|d| data.metrics.extend(d.resource_metrics))
+        } else {
+            anyhow::bail!(
+                "JSON document at byte {} has no resourceSpans, resourceLogs or resourceMetrics",
+                end
+            );
+        };
+        decoded.map_err(|e| anyhow::anyhow!("invalid OTLP JSON: {}", e))?;
+    }
+    Ok(())
+}
+
+fn parse_protobuf(
+    mut bytes: &[u8],
+    signal: ImportSignal,
+    data: &mut ImportData,
+) -> anyhow::Result<()> {
+    while !bytes.is_empty() {
+        let decoded = match signal {
+            ImportSignal::Traces => TracesData::decode_length_delimited(&mut bytes)
+                .map(|d| data.traces.extend(d.resource_spans)),
+            ImportSignal::Logs => LogsData::decode_length_delimited(&mut bytes)
+                .map(|d| data.logs.extend(d.resource_logs)),
+            ImportSignal::Metrics => MetricsData::decode_length_delimited(&mut bytes)
+                .map(|d| data.metrics.extend(d.resource_metrics)),
+        };
+        decoded.map_err(|e| anyhow::anyhow!("invalid OTLP protobuf: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Call `f` on every timestamp in `data`.
+fn for_each_timestamp(data: &mut ImportData, mut f: impl FnMut(&mut u64)) {
+    for span in data
+        .traces
+        .iter_mut()
+        .flat_map(|rs| &mut rs.scope_spans)
+        .flat_map(|ss| &mut ss.spans)
+    {
+        f(&mut span.start_time_unix_nano);
+        f(&mut span.end_time_unix_nano);
+        for event in &mut span.events {
+            f(&mut event.time_unix_nano);
+        }
+    }
+    for record in data
+        .logs
+        .iter_mut()
+        .flat_map(|rl| &mut rl.scope_logs)
+        .flat_map(|sl| &mut sl.log_records)
+    {
+        f(&mut record.time_unix_nano);
+        f(&mut record.observed_time_unix_nano);
+    }
+    for metric in data
+        .metrics
+        .iter_mut()
+        .flat_map(|rm| &mut rm.scope_metrics)
+        .flat_map(|sm| &mut sm.metrics)
+    {
+        match &mut metric.data {
+            Some(metric::Data::Gauge(gauge)) => {
+                for point in &mut gauge.data_points {
+                    f(&mut point.start_time_unix_nano);
+                    f(&mut point.time_unix_nano);
+                    point
+                        .exemplars
+                        .iter_mut()
+                        .for_each(|e| f(&mut e.time_unix_nano));
+                }
+            }
+            Some(metric::Data::Sum(sum)) => {
+                for point in &mut sum.data_points {
+                    f(&mut point.start_time_unix_nano);
+                    f(&mut point.time_unix_nano);
+                    point
+                        .exemplars
+                        .iter_mut()
+                        .for_each(|e| f(&mut e.time_unix_nano));
+                }
+            }
+            Some(metric::Data::Histogram(histogram)) => {
+                for point in &mut histogram.data_points {
+                    f(&mut point.start_time_unix_nano);
+                    f(&mut point.time_unix_nano);
+                    point
+                        .exemplars
+                        .iter_mut()
+                        .for_each(|e| f(&mut e.time_unix_nano));
+                }
+            }
+            Some(metric::Data::ExponentialHistogram(histogram)) => {
+                for point in &mut histogram.data_points {
+                    f(&mut point.start_time_unix_nano);
+                    f(&mut point.time_unix_nano);
+                    point
+                        .exemplars
+                        .iter_mut()
+                        .for_each(|e| f(&mut e.time_unix_nano));
+                }
+            }
+            Some(metric::Data::Summary(summary)) => {
+                for point in &mut summary.data_points {
+                    f(&mut point.start_time_unix_nano);
+                    f(&mut point.time_unix_nano);
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+/// Move all timestamps by the same offset so that the latest one becomes
+/// `now`, keeping durations and the ordering between records. Unset (zero)
+/// timestamps stay unset.
+pub fn shift_timestamps(data: &mut ImportData, now: u64) {
+    let mut latest = 0;
+    for_each_timestamp(data, |t| latest = latest.max(*t));
+    if latest == 0 {
+        return;
+    }
+    let offset = now as i64 - latest as i64;
+    for_each_timestamp(data, |t| {
+        if *t != 0 {
+            *t = t.saturating_add_signed(offset).max(1);
+        }
+    });
+}
+
+/// Export `data` over OTLP gRPC, returning the number of spans, log records
+/// and metric data points the server accepted.
+async fn send(
+    endpoint: &str,
+    connection: &ConnectionArgs,
+    data: ImportData,
+) -> anyhow::Result<(i64, i64, i64)> {
+    let channel = super::channel(endpoint, connection).await?;
+    let credentials = Credentials::from_args(connection)?;
+    let (mut spans, mut records, mut points) = (0, 0, 0);
+
+    let mut client = TraceServiceClient::with_interceptor(channel.clone(), credentials.clone())
+        .send_compressed(CompressionEncoding::Gzip);
+    for batch in batches(data.traces, MAX_REQUEST_BYTES) {
+        let count: usize = batch
+            .iter()
+            .flat_map(|rs| &rs.scope_spans)
+            .map(|ss| ss.spans.len())
+            .sum();
+        let request = ExportTraceServiceRequest {
+            resource_spans: batch,
+        };
+        let response = client.export(request).await?.into_inner();
+        let rejected = match response.partial_success {
+            Some(partial) if partial.rejected_spans > 0 => {
+                eprintln!(
+                    "warning: {} spans rejected: {}",
+                    partial.rejected_spans, partial.error_message
+                );
+                partial.rejected_spans
+            }
+            _ => 0,
+        };
+        spans += count as i64 - rejected;
+    }
+
+    let mut client = LogsServiceClient::with_interceptor(channel.clone(), credentials.clone())
+        .send_compressed(CompressionEncoding::Gzip);
+    for batch in batches(data.logs, MAX_REQUEST_BYTES) {
+        let count: usize = batch
+            .iter()
+            .flat_map(|rl| &rl.scope_logs)
+            .map(|sl| sl.log_records.len())
+            .sum();
+        let request = ExportLogsServiceRequest {
+            resource_logs: batch,
+        };
+        let response = client.export(request).await?.into_inner();
+        let rejected = match response.partial_success {
+            Some(partial) if partial.rejected_log_records > 0 => {
+                eprintln!(
+                    "warning: {} log records rejected: {}",
+                    partial.rejected_log_records, partial.error_message
+                );
+                partial.rejected_log_records
+            }
+            _ => 0,
+        };
+        records += count as i64 - rejected;
+    }
+
+    let mut client = MetricsServiceClient::with_interceptor(channel, credentials)
+        .send_compressed(CompressionEncoding::Gzip);
+    for batch in batches(data.metrics, MAX_REQUEST_BYTES) {
+        let count: usize = batch
+            .iter()
+            .flat_map(|rm| &rm.scope_metrics)
+            .flat_map(|sm| &sm.metrics)
+            .map(data_point_count)
+            .sum();
+        let request = ExportMetricsServiceRequest {
+            resource_metrics: batch,
+        };
+        let response = client.export(request).await?.into_inner();
+        let rejected = match response.partial_success {
+            Some(partial) if partial.rejected_data_points > 0 => {
+                eprintln!(
+                    "warning: {} metric data points rejected: {}",
+                    partial.rejected_data_points, partial.error_message
+                );
+                partial.rejected_data_points
+            }
+            _ => 0,
+        };
+        points += count as i64 - rejected;
+    }
+
+    Ok((spans, records, points))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::opentelemetry::proto::{
+        logs::v1::{LogRecord, ScopeLogs},
+        trace::v1::{span, ScopeSpans, Span},
+    };
+
+    const TRACES_LINE: &str = r#"{"resourceSpans":[{"resource":{"attributes":[{"key":"service.name","value":{"stringValue":"checkout"}}]},"scopeSpans":[{"spans":[{"traceId":"0102030405060708090a0b0c0d0e0f10","spanId":"0102030405060708","name":"GET /cart","startTimeUnixNano":"1000000000","endTimeUnixNano":"3000000000","events":[{"timeUnixNano":"2000000000","name":"cache miss"}]}]}]}]}"#;
+    const LOGS_LINE: &str = r#"{"resourceLogs":[{"scopeLogs":[{"logRecords":[{"timeUnixNano":"5000000000","body":{"stringValue":"hello"}}]}]}]}"#;
+
+    fn sample_data() -> ImportData {
+        ImportData {
+            traces: vec![ResourceSpans {
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![Span {
+                        start_time_unix_nano: 1_000,
+                        end_time_unix_nano: 3_000,
+                        events: vec![span::Event {
+                            time_unix_nano: 2_000,
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            logs: vec![ResourceLogs {
+                scope_logs: vec![ScopeLogs {
+                    log_records: vec![LogRecord {
+                        time_unix_nano: 5_000,
+                        observed_time_unix_nano: 0,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            metrics: vec![],
+        }
+    }
+
+    #[test]
+    fn test_parse_jsonl_and_pretty_json() {
+        let pretty: serde_json::Value = serde_json::from_str(LOGS_LINE).unwrap();
+        let input = format!(
+            "{}\n{}\n\n{}\n",
+            TRACES_LINE,
+            LOGS_LINE,
+            serde_json::to_string_pretty(&pretty).unwrap()
+        );
+        let mut data = ImportData::default();
+        parse(input.as_bytes(), OtlpFileFormat::Jsonl, None, &mut data).unwrap();
+
+        assert_eq!(data.traces.len(), 1);
+        assert_eq!(data.logs.len(), 2);
+        let span = &data.traces[0].scope_spans[0].spans[0];
+        assert_eq!(span.trace_id, (1..=16).collect::<Vec<u8>>());
+        assert_eq!(span.events[0].time_unix_nano, 2_000_000_000);
+    }
+
+    #[test]
+    fn test_parse_json_rejects_unknown_documents() {
+        let mut data = ImportData::default();
+        let err = parse(b"{\"foo\": []}", OtlpFileFormat::Json, None, &mut data).unwrap_err();
+        assert!(err.to_string().contains("no resourceSpans"), "{}", err);
+        assert!(parse(
+            b"{\"resourceSpans\": [",
+            OtlpFileFormat::Json,
+            None,
+            &mut data
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_parse_length_delimited_protobuf() {
+        let data = sample_data();
+        let mut bytes = Vec::new();
+        for _ in 0..2 {
+            TracesData {
+                resource_spans: data.traces.clone(),
+            }
+            .encode_length_delimited(&mut bytes)
+            .unwrap();
+        }
+        assert_eq!(detect_format(&bytes), OtlpFileFormat::Protobuf);
+
+        let mut parsed = ImportData::default();
+        parse(
+            &bytes,
+            OtlpFileFormat::Protobuf,
+            Some(ImportSignal::Traces),
+            &mut parsed,
+        )
+        .unwrap();
+        assert_eq!(parsed.traces, [data.traces.clone(), data.traces].concat());
+
+        let err = parse(&bytes, OtlpFileFormat::Protobuf, None, &mut parsed).unwrap_err();
+        assert!(err.to_string().contains("--signal"));
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(
+            detect_format(b"  \n{\"resourceLogs\":[]}"),
+            OtlpFileFormat::Json
+        );
+        assert_eq!(
+            format_from_extension(Path::new("otlp/traces.ndjson")),
+            Some(OtlpFileFormat::Jsonl)
+        );
+        assert_eq!(format_from_extension(Path::new("capture.txt")), None);
+    }
+
+    #[test]
+    fn test_shift_timestamps_to_now() {
+        let mut data = sample_data();
+        shift_timestamps(&mut data, 1_000_000);
+
+        let span = &data.traces[0].scope_spans[0].spans[0];
+        assert_eq!(span.start_time_unix_nano, 996_000);
+        assert_eq!(span.end_time_unix_nano, 998_000);
+        assert_eq!(span.events[0].time_unix_nano, 997_000);
+        let record = &data.logs[0].scope_logs[0].log_records[0];
+        assert_eq!(record.time_unix_nano, 1_000_000);
+        assert_eq!(record.observed_time_unix_nano, 0);
+    }
+}
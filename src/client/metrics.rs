@@ -1,11 +1,13 @@
-use crate::cli::OutputFormat;
+use crate::cli::{ConnectionArgs, OutputFormat};
 use crate::proto::otelcli::query::v1::Row as ProtoRow;
 use crate::query::sql::convert::metric_flags_to_sql;
 
 use super::{get_row_kvlist, get_row_string, get_row_timestamp, parse_time_spec, print_kvlist};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn query_metrics(
     server: &str,
+    connection: &ConnectionArgs,
     service: Option<String>,
     name: Option<String>,
     limit: i32,
@@ -22,11 +24,13 @@ pub async fn query_metrics(
         start_time_ns,
         end_time_ns,
     );
-    super::query_and_print(server, &sql, format, print_metric_rows_text).await
+    super::query_and_print(server, connection, &sql, format, print_metric_rows_text).await
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn follow_metrics(
     server: &str,
+    connection: &ConnectionArgs,
     service: Option<String>,
     name: Option<String>,
     limit: i32,
@@ -43,7 +47,7 @@ pub async fn follow_metrics(
         start_time_ns,
         end_time_ns,
     );
-    super::follow_and_print(server, &sql, format, print_metric_rows_text).await
+    super::follow_and_print(server, connection, &sql, format, print_metric_rows_text).await
 }
 
 pub fn print_metric_rows_text(rows: &[ProtoRow]) {
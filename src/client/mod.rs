@@ -1,4 +1,6 @@
 pub mod clear;
+pub mod export;
+pub mod import;
 pub mod log;
 pub mod metrics;
 pub mod shutdown;
@@ -91,18 +93,105 @@ pub fn parse_time_spec(s: &str) -> anyhow::Result<u64> {
 
 // --- Query + format helpers ---
 
-use crate::cli::OutputFormat;
+use crate::cli::{ConnectionArgs, OutputFormat};
 use crate::proto::otelcli::query::v1::query_service_client::QueryServiceClient;
 use crate::proto::otelcli::query::v1::Row as ProtoRow;
 use crate::proto::otelcli::query::v1::SqlQueryRequest;
+use tonic::codec::CompressionEncoding;
+use tonic::metadata::{AsciiMetadataKey, AsciiMetadataValue};
+use tonic::service::{interceptor::InterceptedService, Interceptor};
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity};
+
+pub type QueryClient = QueryServiceClient<InterceptedService<Channel, Credentials>>;
+
+/// Attaches `--token` and `--header` values to every query API request.
+#[derive(Clone, Debug, Default)]
+pub struct Credentials {
+    metadata: Vec<(AsciiMetadataKey, AsciiMetadataValue)>,
+}
+
+impl Credentials {
+    pub fn from_args(connection: &ConnectionArgs) -> anyhow::Result<Self> {
+        let mut metadata = Vec::new();
+        if let Some(token) = &connection.token {
+            let value = format!("Bearer {}", token)
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid token: {}", e))?;
+            metadata.push((AsciiMetadataKey::from_static("authorization"), value));
+        }
+        for (name, value) in &connection.headers {
+            let key = name
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid header name '{}': {}", name, e))?;
+            let value = value
+                .parse()
+                .map_err(|e| anyhow::anyhow!("invalid value for header '{}': {}", name, e))?;
+            metadata.push((key, value));
+        }
+        Ok(Self { metadata })
+    }
+}
+
+impl Interceptor for Credentials {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> Result<tonic::Request<()>, tonic::Status> {
+        for (key, value) in &self.metadata {
+            request.metadata_mut().insert(key.clone(), value.clone());
+        }
+        Ok(request)
+    }
+}
+
+/// Connect to the query API. Responses may be gzip- or zstd-compressed
+/// depending on the server's `--query-compression` setting.
+///
+/// `https://` addresses are verified against `--ca-cert` (or the bundled
+/// web PKI roots) and present a client certificate when one is given.
+/// `unix:///path/to.sock` connects to a server listening on a Unix socket.
+/// `--token`/`--header` credentials are sent with every request.
+pub async fn connect(server: &str, connection: &ConnectionArgs) -> anyhow::Result<QueryClient> {
+    let credentials = Credentials::from_args(connection)?;
+    let client =
+        QueryServiceClient::with_interceptor(channel(server, connection).await?, credentials)
+            .accept_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Zstd);
+    Ok(client)
+}
+
+/// Open a channel to `server` (`http://`, `https://` or `unix://`), using the
+/// TLS settings from `connection` for `https://` addresses.
+pub(crate) async fn channel(server: &str, connection: &ConnectionArgs) -> anyhow::Result<Channel> {
+    let mut endpoint = Endpoint::from_shared(server.to_string())?;
+    if endpoint.uri().scheme_str() == Some("https") {
+        endpoint = endpoint.tls_config(client_tls_config(connection)?)?;
+    }
+    Ok(endpoint.connect().await?)
+}
+
+fn client_tls_config(connection: &ConnectionArgs) -> anyhow::Result<ClientTlsConfig> {
+    let read = |path: &std::path::Path| {
+        std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))
+    };
+    let mut config = match &connection.ca_cert {
+        Some(path) => ClientTlsConfig::new().ca_certificate(Certificate::from_pem(read(path)?)),
+        None => ClientTlsConfig::new().with_enabled_roots(),
+    };
+    if let (Some(cert), Some(key)) = (&connection.client_cert, &connection.client_key) {
+        config = config.identity(Identity::from_pem(read(cert)?, read(key)?));
+    }
+    Ok(config)
+}
 
 pub async fn query_and_print(
     server: &str,
+    connection: &ConnectionArgs,
     sql: &str,
     format: &OutputFormat,
     print_text: fn(&[ProtoRow]),
 ) -> anyhow::Result<()> {
-    let mut client = QueryServiceClient::connect(server.to_string()).await?;
+    let mut client = connect(server, connection).await?;
     let response = client
         .sql_query(SqlQueryRequest {
             query: sql.to_string(),
@@ -114,11 +203,12 @@ pub async fn query_and_print(
 
 pub async fn follow_and_print(
     server: &str,
+    connection: &ConnectionArgs,
     sql: &str,
     format: &OutputFormat,
     print_text: fn(&[ProtoRow]),
 ) -> anyhow::Result<()> {
-    let mut client = QueryServiceClient::connect(server.to_string()).await?;
+    let mut client = connect(server, connection).await?;
     let mut stream = client
         .follow_sql(SqlQueryRequest {
             query: sql.to_string(),
@@ -1,8 +1,8 @@
-use crate::proto::otelcli::query::v1::query_service_client::QueryServiceClient;
+use crate::cli::ConnectionArgs;
 use crate::proto::otelcli::query::v1::ShutdownRequest;
 
-pub async fn shutdown(server: &str) -> anyhow::Result<()> {
-    let mut client = QueryServiceClient::connect(server.to_string()).await?;
+pub async fn shutdown(server: &str, connection: &ConnectionArgs) -> anyhow::Result<()> {
+    let mut client = super::connect(server, connection).await?;
     client.shutdown(ShutdownRequest {}).await?;
 
     println!("Server shutdown initiated.");
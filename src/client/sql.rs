@@ -1,14 +1,14 @@
-use crate::cli::SqlOutputFormat;
-use crate::proto::otelcli::query::v1::query_service_client::QueryServiceClient;
+use crate::cli::{ConnectionArgs, SqlOutputFormat};
 use crate::proto::otelcli::query::v1::{Row as ProtoRow, SqlQueryRequest};
 
 pub async fn query_sql(
     server: &str,
+    connection: &ConnectionArgs,
     query: &str,
     format: &SqlOutputFormat,
     show_trace_id: bool,
 ) -> anyhow::Result<()> {
-    let mut client = QueryServiceClient::connect(server.to_string()).await?;
+    let mut client = super::connect(server, connection).await?;
     let response = client
         .sql_query(SqlQueryRequest {
             query: query.to_string(),
@@ -27,11 +27,12 @@ pub async fn query_sql(
 
 pub async fn follow_sql(
     server: &str,
+    connection: &ConnectionArgs,
     query: &str,
     format: &SqlOutputFormat,
     show_trace_id: bool,
 ) -> anyhow::Result<()> {
-    let mut client = QueryServiceClient::connect(server.to_string()).await?;
+    let mut client = super::connect(server, connection).await?;
     let response = client
         .follow_sql(SqlQueryRequest {
             query: query.to_string(),
@@ -1,13 +1,63 @@
-use crate::proto::otelcli::query::v1::query_service_client::QueryServiceClient;
+use crate::cli::ConnectionArgs;
 use crate::proto::otelcli::query::v1::StatusRequest;
 
-pub async fn status(server: &str) -> anyhow::Result<()> {
-    let mut client = QueryServiceClient::connect(server.to_string()).await?;
+pub async fn status(server: &str, connection: &ConnectionArgs) -> anyhow::Result<()> {
+    let mut client = super::connect(server, connection).await?;
     let resp = client.status(StatusRequest {}).await?.into_inner();
 
-    println!("Traces:  {}", resp.trace_count);
-    println!("Logs:    {}", resp.log_count);
-    println!("Metrics: {}", resp.metric_count);
+    println!(
+        "Traces:  {} ({})",
+        resp.trace_count,
+        format_bytes(resp.trace_bytes)
+    );
+    println!(
+        "Logs:    {} ({})",
+        resp.log_count,
+        format_bytes(resp.log_bytes)
+    );
+    println!(
+        "Metrics: {} ({})",
+        resp.metric_count,
+        format_bytes(resp.metric_bytes)
+    );
+    let used = resp.trace_bytes + resp.log_bytes + resp.metric_bytes;
+    match resp.max_memory {
+        0 => println!("Memory:  {}", format_bytes(used)),
+        max => println!("Memory:  {} / {}", format_bytes(used), format_bytes(max)),
+    }
+    if resp.dedup {
+        println!(
+            "Duplicates dropped: {} spans, {} log records, {} metric points",
+            resp.duplicate_spans, resp.duplicate_log_records, resp.duplicate_metric_points
+        );
+    }
 
     Ok(())
 }
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KiB");
+        assert_eq!(format_bytes(512 * 1024 * 1024), "512.0 MiB");
+    }
+}
@@ -1,4 +1,4 @@
-use crate::cli::OutputFormat;
+use crate::cli::{ConnectionArgs, OutputFormat};
 use crate::proto::otelcli::query::v1::Row as ProtoRow;
 use crate::query::sql::convert::trace_flags_to_sql;
 
@@ -7,6 +7,7 @@ use super::{get_row_kvlist, get_row_string, get_row_timestamp, parse_time_spec,
 #[allow(clippy::too_many_arguments)]
 pub async fn query_traces(
     server: &str,
+    connection: &ConnectionArgs,
     service: Option<String>,
     trace_id: Option<String>,
     attributes: Vec<(String, String)>,
@@ -25,12 +26,13 @@ pub async fn query_traces(
         start_time_ns,
         end_time_ns,
     );
-    super::query_and_print(server, &sql, format, print_trace_rows_text).await
+    super::query_and_print(server, connection, &sql, format, print_trace_rows_text).await
 }
 
 #[allow(clippy::too_many_arguments)]
 pub async fn follow_traces(
     server: &str,
+    connection: &ConnectionArgs,
     service: Option<String>,
     trace_id: Option<String>,
     attributes: Vec<(String, String)>,
@@ -50,7 +52,7 @@ pub async fn follow_traces(
         start_time_ns,
         end_time_ns,
     );
-    super::follow_and_print(server, &sql, format, print_trace_rows_text).await
+    super::follow_and_print(server, connection, &sql, format, print_trace_rows_text).await
 }
 
 pub fn print_trace_rows_text(rows: &[ProtoRow]) {
@@ -1,9 +1,10 @@
-use crate::proto::otelcli::query::v1::query_service_client::QueryServiceClient;
+use crate::cli::ConnectionArgs;
 use crate::proto::otelcli::query::v1::FollowRequest;
 use crate::store;
 
 pub async fn run_view(
     server: &str,
+    connection: &ConnectionArgs,
     max_traces: usize,
     max_spans: usize,
     max_logs: usize,
@@ -11,37 +12,46 @@ pub async fn run_view(
 ) -> anyhow::Result<()> {
     let (store, event_rx) = store::new_shared(max_traces, max_spans, max_logs, max_metrics);
 
-    let mut client = QueryServiceClient::connect(server.to_string()).await?;
+    let mut client = super::connect(server, connection).await?;
 
-    let traces_store = store.clone();
+    let traces_store = store.traces.clone();
     let mut traces_stream = client.follow_traces(FollowRequest {}).await?.into_inner();
     tokio::spawn(async move {
         while let Ok(Some(msg)) = traces_stream.message().await {
+            let mut s = traces_store.write().await;
+            if msg.reset {
+                s.clear_traces();
+            }
             if !msg.resource_spans.is_empty() {
-                traces_store.write().await.insert_traces(msg.resource_spans);
+                s.insert_traces(msg.resource_spans);
             }
         }
     });
 
-    let logs_store = store.clone();
+    let logs_store = store.logs.clone();
     let mut logs_stream = client.follow_logs(FollowRequest {}).await?.into_inner();
     tokio::spawn(async move {
         while let Ok(Some(msg)) = logs_stream.message().await {
+            let mut s = logs_store.write().await;
+            if msg.reset {
+                s.clear_logs();
+            }
             if !msg.resource_logs.is_empty() {
-                logs_store.write().await.insert_logs(msg.resource_logs);
+                s.insert_logs(msg.resource_logs);
             }
         }
     });
 
-    let metrics_store = store.clone();
+    let metrics_store = store.metrics.clone();
     let mut metrics_stream = client.follow_metrics(FollowRequest {}).await?.into_inner();
     tokio::spawn(async move {
         while let Ok(Some(msg)) = metrics_stream.message().await {
+            let mut s = metrics_store.write().await;
+            if msg.reset {
+                s.clear_metrics();
+            }
             if !msg.resource_metrics.is_empty() {
-                metrics_store
-                    .write()
-                    .await
-                    .insert_metrics(msg.resource_metrics);
+                s.insert_metrics(msg.resource_metrics);
             }
         }
     });
@@ -1,5 +1,5 @@
 use clap::Parser;
-use otel_cli::cli::{Cli, Commands};
+use otel_cli::cli::{Cli, Commands, QueryCompression};
 use otel_cli::{client, server, store, telemetry};
 use tokio_util::sync::CancellationToken;
 
@@ -12,72 +12,305 @@ async fn main() -> anyhow::Result<()> {
             grpc_addr,
             http_addr,
             query_addr,
+            mux_addr,
             max_traces,
             max_spans,
             max_logs,
             max_metrics,
+            max_memory,
+            trace_retention,
+            log_retention,
+            metric_retention,
+            dedup,
+            data_dir,
+            snapshot_interval,
+            max_body_size,
+            http_compression,
+            query_compression,
+            tls_cert,
+            tls_key,
+            client_ca,
+            cors_allowed_origin,
+            cors_allowed_header,
+            ingest_token,
+            ingest_header,
+            query_token,
+            query_read_token,
+            query_header,
+            statsd_addr,
+            statsd_flush_interval,
+            syslog_udp_addr,
+            syslog_tcp_addr,
+            fluent_addr,
             no_tui,
             otlp_endpoint,
         } => {
             let provider = telemetry::init(otlp_endpoint.as_deref());
-            let (store, event_rx) = store::new_shared(max_traces, max_spans, max_logs, max_metrics);
+            let (mut store, event_rx) = match &data_dir {
+                Some(dir) => store::Store::open(dir, max_traces, max_spans, max_logs, max_metrics)?,
+                None => store::Store::new(max_traces, max_spans, max_logs, max_metrics),
+            };
+            if let Some(bytes) = max_memory {
+                store.set_max_memory(bytes);
+            }
+            if dedup {
+                store.enable_dedup();
+            }
+            let store = store::share(store);
             let _gauges = provider
                 .as_ref()
                 .map(|guard| telemetry::register_store_metrics(guard, store.clone()));
             let shutdown = CancellationToken::new();
 
-            let grpc_addr: std::net::SocketAddr = grpc_addr.parse()?;
-            let http_addr: std::net::SocketAddr = http_addr.parse()?;
-            let query_addr: std::net::SocketAddr = query_addr.parse()?;
+            let mut ingest_auth = server::auth::Authenticator::default();
+            for token in &ingest_token {
+                ingest_auth.add_bearer_token(token, server::auth::Access::Admin)?;
+            }
+            for (name, value) in &ingest_header {
+                ingest_auth.add_header(name, value, server::auth::Access::Admin)?;
+            }
+            let mut query_auth = server::auth::Authenticator::default();
+            for token in &query_token {
+                query_auth.add_bearer_token(token, server::auth::Access::Admin)?;
+            }
+            for token in &query_read_token {
+                query_auth.add_bearer_token(token, server::auth::Access::ReadOnly)?;
+            }
+            for (name, value) in &query_header {
+                query_auth.add_header(name, value, server::auth::Access::Admin)?;
+            }
+
+            let options = server::ServerOptions {
+                max_body_size,
+                http_compression,
+                query_compression: match query_compression {
+                    QueryCompression::None => None,
+                    QueryCompression::Gzip => Some(tonic::codec::CompressionEncoding::Gzip),
+                    QueryCompression::Zstd => Some(tonic::codec::CompressionEncoding::Zstd),
+                },
+                tls: match (&tls_cert, &tls_key) {
+                    (Some(cert), Some(key)) => Some(server::tls::TlsConfig::load(
+                        cert,
+                        key,
+                        client_ca.as_deref(),
+                    )?),
+                    _ => None,
+                },
+                cors: if cors_allowed_origin.is_empty() {
+                    None
+                } else {
+                    Some(server::otlp_http::CorsConfig::new(
+                        &cors_allowed_origin,
+                        &cors_allowed_header,
+                    )?)
+                },
+                ingest_auth,
+                query_auth,
+            };
+
+            let grpc_addr: server::ListenAddr = grpc_addr.parse()?;
+            let http_addr: server::ListenAddr = http_addr.parse()?;
+            let query_addr: server::ListenAddr = query_addr.parse()?;
+            let mux_addr: Option<server::ListenAddr> =
+                mux_addr.map(|addr| addr.parse()).transpose()?;
 
-            let (grpc_listener, http_listener, query_listener) =
-                server::bind_listeners(grpc_addr, http_addr, query_addr).await?;
+            let (mux_listener, listeners) = match &mux_addr {
+                Some(addr) => (Some(addr.bind("multiplexed").await?), None),
+                None => (
+                    None,
+                    Some(
+                        server::bind_listeners(
+                            grpc_addr.clone(),
+                            http_addr.clone(),
+                            query_addr.clone(),
+                        )
+                        .await?,
+                    ),
+                ),
+            };
+
+            let statsd_socket = match &statsd_addr {
+                Some(addr) => Some(server::bind_udp("StatsD", addr.parse()?).await?),
+                None => None,
+            };
+            let syslog_udp_socket = match &syslog_udp_addr {
+                Some(addr) => Some(server::bind_udp("syslog UDP", addr.parse()?).await?),
+                None => None,
+            };
+            let syslog_tcp_listener = match &syslog_tcp_addr {
+                Some(addr) => Some(server::bind_tcp("syslog TCP", addr.parse()?).await?),
+                None => None,
+            };
+            let fluent_listener = match &fluent_addr {
+                Some(addr) => Some(server::bind_tcp("Fluent Forward", addr.parse()?).await?),
+                None => None,
+            };
 
             let ctx = otel_cli::query::datafusion_ctx::create_context(store.clone());
 
-            let grpc_handle = tokio::spawn(server::run_grpc_server(
-                grpc_listener,
-                store.clone(),
-                shutdown.clone(),
-            ));
-            let http_handle = tokio::spawn(server::run_http_server(
-                http_listener,
-                store.clone(),
-                shutdown.clone(),
-            ));
-            let query_handle = tokio::spawn(server::run_query_server(
-                query_listener,
-                store.clone(),
-                ctx.clone(),
-                shutdown.clone(),
-            ));
+            let mut server_handles = Vec::new();
+            if let Some(listener) = mux_listener {
+                server_handles.push(tokio::spawn(server::run_mux_server(
+                    listener,
+                    store.clone(),
+                    ctx.clone(),
+                    options.clone(),
+                    shutdown.clone(),
+                )));
+            }
+            if let Some((grpc_listener, http_listener, query_listener)) = listeners {
+                server_handles.push(tokio::spawn(server::run_grpc_server(
+                    grpc_listener,
+                    store.clone(),
+                    options.clone(),
+                    shutdown.clone(),
+                )));
+                server_handles.push(tokio::spawn(server::run_http_server(
+                    http_listener,
+                    store.clone(),
+                    options.clone(),
+                    shutdown.clone(),
+                )));
+                server_handles.push(tokio::spawn(server::run_query_server(
+                    query_listener,
+                    store.clone(),
+                    ctx.clone(),
+                    options.clone(),
+                    shutdown.clone(),
+                )));
+            }
+
+            let statsd_handle = statsd_socket.map(|socket| {
+                tokio::spawn(server::statsd::run_statsd_server(
+                    socket,
+                    store.clone(),
+                    statsd_flush_interval,
+                    shutdown.clone(),
+                ))
+            });
+            let syslog_udp_handle = syslog_udp_socket.map(|socket| {
+                tokio::spawn(server::syslog::run_syslog_udp_server(
+                    socket,
+                    store.clone(),
+                    shutdown.clone(),
+                ))
+            });
+            let syslog_tcp_handle = syslog_tcp_listener.map(|listener| {
+                tokio::spawn(server::syslog::run_syslog_tcp_server(
+                    listener,
+                    store.clone(),
+                    shutdown.clone(),
+                ))
+            });
+            let retention = store::retention::Retention {
+                traces: trace_retention,
+                logs: log_retention,
+                metrics: metric_retention,
+            };
+            let retention_handle = retention.is_enabled().then(|| {
+                tokio::spawn(store::retention::run_retention(
+                    store.clone(),
+                    retention,
+                    shutdown.clone(),
+                ))
+            });
+            let snapshot_handle = data_dir.as_ref().map(|_| {
+                tokio::spawn(store::persist::run_snapshots(
+                    store.clone(),
+                    snapshot_interval,
+                    shutdown.clone(),
+                ))
+            });
+            let fluent_handle = fluent_listener.map(|listener| {
+                tokio::spawn(server::fluent::run_fluent_server(
+                    listener,
+                    store.clone(),
+                    shutdown.clone(),
+                ))
+            });
 
-            tracing::info!(
-                grpc = %grpc_addr,
-                http = %http_addr,
-                query = %query_addr,
-                "server started"
-            );
+            match &mux_addr {
+                Some(addr) => tracing::info!(mux = %addr, "server started"),
+                None => tracing::info!(
+                    grpc = %grpc_addr,
+                    http = %http_addr,
+                    query = %query_addr,
+                    "server started"
+                ),
+            }
 
             if no_tui {
-                eprintln!("gRPC server listening on {}", grpc_addr);
-                eprintln!("HTTP server listening on {}", http_addr);
-                eprintln!("Query server listening on {}", query_addr);
+                match &mux_addr {
+                    Some(addr) => {
+                        eprintln!("gRPC, HTTP and query server listening on {}", addr);
+                    }
+                    None => {
+                        eprintln!("gRPC server listening on {}", grpc_addr);
+                        eprintln!("HTTP server listening on {}", http_addr);
+                        eprintln!("Query server listening on {}", query_addr);
+                    }
+                }
+                if let Some(addr) = &statsd_addr {
+                    eprintln!("StatsD server listening on {} (udp)", addr);
+                }
+                if let Some(addr) = &syslog_udp_addr {
+                    eprintln!("Syslog server listening on {} (udp)", addr);
+                }
+                if let Some(addr) = &syslog_tcp_addr {
+                    eprintln!("Syslog server listening on {} (tcp)", addr);
+                }
+                if let Some(addr) = &fluent_addr {
+                    eprintln!("Fluent Forward server listening on {}", addr);
+                }
+                if let Some(dir) = &data_dir {
+                    eprintln!("Persisting data to {}", dir.display());
+                }
                 tokio::signal::ctrl_c().await.ok();
                 eprintln!("\nShutting down...");
             } else {
-                eprintln!(
-                    "Starting OTLP server (gRPC: {}, HTTP: {}, Query: {})",
-                    grpc_addr, http_addr, query_addr
-                );
+                match &mux_addr {
+                    Some(addr) => eprintln!("Starting OTLP server (gRPC/HTTP/Query: {})", addr),
+                    None => eprintln!(
+                        "Starting OTLP server (gRPC: {}, HTTP: {}, Query: {})",
+                        grpc_addr, http_addr, query_addr
+                    ),
+                }
                 otel_cli::tui::run(store.clone(), ctx.clone(), event_rx).await?;
             }
 
             tracing::info!("shutting down");
             shutdown.cancel();
-            let _ = grpc_handle.await;
-            let _ = http_handle.await;
-            let _ = query_handle.await;
+            for handle in server_handles {
+                let _ = handle.await;
+            }
+            match &mux_addr {
+                Some(addr) => addr.cleanup(),
+                None => {
+                    for addr in [&grpc_addr, &http_addr, &query_addr] {
+                        addr.cleanup();
+                    }
+                }
+            }
+            for handle in [
+                statsd_handle,
+                syslog_udp_handle,
+                syslog_tcp_handle,
+                fluent_handle,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                let _ = handle.await;
+            }
+            if let Some(handle) = retention_handle {
+                let _ = handle.await;
+            }
+            if let Some(handle) = snapshot_handle {
+                let _ = handle.await;
+                if let Err(e) = store.snapshot().await {
+                    tracing::warn!(error = %e, "failed to write snapshot");
+                }
+            }
 
             telemetry::shutdown(provider);
 
@@ -85,6 +318,7 @@ async fn main() -> anyhow::Result<()> {
         }
         Commands::Logs {
             server,
+            connection,
             service,
             severity,
             attribute,
@@ -96,12 +330,28 @@ async fn main() -> anyhow::Result<()> {
         } => {
             if follow {
                 client::log::follow_logs(
-                    &server, service, severity, attribute, limit, &format, since, until,
+                    &server,
+                    &connection,
+                    service,
+                    severity,
+                    attribute,
+                    limit,
+                    &format,
+                    since,
+                    until,
                 )
                 .await?;
             } else {
                 client::log::query_logs(
-                    &server, service, severity, attribute, limit, &format, since, until,
+                    &server,
+                    &connection,
+                    service,
+                    severity,
+                    attribute,
+                    limit,
+                    &format,
+                    since,
+                    until,
                 )
                 .await?;
             }
@@ -109,6 +359,7 @@ async fn main() -> anyhow::Result<()> {
         }
         Commands::Traces {
             server,
+            connection,
             service,
             trace_id,
             attribute,
@@ -121,12 +372,29 @@ async fn main() -> anyhow::Result<()> {
         } => {
             if follow {
                 client::trace::follow_traces(
-                    &server, service, trace_id, attribute, limit, &format, since, until, !full,
+                    &server,
+                    &connection,
+                    service,
+                    trace_id,
+                    attribute,
+                    limit,
+                    &format,
+                    since,
+                    until,
+                    !full,
                 )
                 .await?;
             } else {
                 client::trace::query_traces(
-                    &server, service, trace_id, attribute, limit, &format, since, until,
+                    &server,
+                    &connection,
+                    service,
+                    trace_id,
+                    attribute,
+                    limit,
+                    &format,
+                    since,
+                    until,
                 )
                 .await?;
             }
@@ -134,43 +402,104 @@ async fn main() -> anyhow::Result<()> {
         }
         Commands::Clear {
             server,
+            connection,
+            traces,
+            logs,
+            metrics,
+            service,
+            before,
+            filter,
+        } => {
+            client::clear::clear(
+                &server,
+                &connection,
+                traces,
+                logs,
+                metrics,
+                service,
+                before,
+                filter,
+            )
+            .await?;
+            Ok(())
+        }
+        Commands::Import {
+            endpoint,
+            connection,
+            files,
+            format,
+            signal,
+            shift_to_now,
+        } => {
+            client::import::import(&endpoint, &connection, &files, format, signal, shift_to_now)
+                .await?;
+            Ok(())
+        }
+        Commands::Export {
+            server,
+            connection,
+            output,
+            format,
             traces,
             logs,
             metrics,
+            filter,
         } => {
-            client::clear::clear(&server, traces, logs, metrics).await?;
+            client::export::export(
+                &server,
+                &connection,
+                output.as_deref(),
+                format,
+                traces,
+                logs,
+                metrics,
+                filter,
+            )
+            .await?;
             Ok(())
         }
         Commands::View {
             server,
+            connection,
             max_traces,
             max_spans,
             max_logs,
             max_metrics,
         } => {
-            client::view::run_view(&server, max_traces, max_spans, max_logs, max_metrics).await?;
+            client::view::run_view(
+                &server,
+                &connection,
+                max_traces,
+                max_spans,
+                max_logs,
+                max_metrics,
+            )
+            .await?;
             Ok(())
         }
         Commands::Sql {
             server,
+            connection,
             query,
             format,
             follow,
             show_trace_id,
         } => {
             if follow {
-                client::sql::follow_sql(&server, &query, &format, show_trace_id).await?;
+                client::sql::follow_sql(&server, &connection, &query, &format, show_trace_id)
+                    .await?;
             } else {
-                client::sql::query_sql(&server, &query, &format, show_trace_id).await?;
+                client::sql::query_sql(&server, &connection, &query, &format, show_trace_id)
+                    .await?;
             }
             Ok(())
         }
-        Commands::Status { server } => {
-            client::status::status(&server).await?;
+        Commands::Status { server, connection } => {
+            client::status::status(&server, &connection).await?;
             Ok(())
         }
-        Commands::Shutdown { server } => {
-            client::shutdown::shutdown(&server).await?;
+        Commands::Shutdown { server, connection } => {
+            client::shutdown::shutdown(&server, &connection).await?;
             Ok(())
         }
         Commands::SkillInstall { global, force } => {
@@ -179,6 +508,7 @@ async fn main() -> anyhow::Result<()> {
         }
         Commands::Metrics {
             server,
+            connection,
             service,
             name,
             limit,
@@ -189,12 +519,26 @@ async fn main() -> anyhow::Result<()> {
         } => {
             if follow {
                 client::metrics::follow_metrics(
-                    &server, service, name, limit, &format, since, until,
+                    &server,
+                    &connection,
+                    service,
+                    name,
+                    limit,
+                    &format,
+                    since,
+                    until,
                 )
                 .await?;
             } else {
                 client::metrics::query_metrics(
-                    &server, service, name, limit, &format, since, until,
+                    &server,
+                    &connection,
+                    service,
+                    name,
+                    limit,
+                    &format,
+                    since,
+                    until,
                 )
                 .await?;
             }
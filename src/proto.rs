@@ -84,3 +84,19 @@ pub mod otelcli {
         }
     }
 }
+
+pub mod zipkin {
+    pub mod proto3 {
+        tonic::include_proto!("zipkin.proto3");
+    }
+}
+
+pub mod jaeger {
+    pub mod api_v2 {
+        tonic::include_proto!("jaeger.api_v2");
+    }
+}
+
+pub mod prometheus {
+    tonic::include_proto!("prometheus");
+}
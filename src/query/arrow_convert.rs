@@ -10,7 +10,7 @@ use crate::client::{
 };
 use crate::proto::opentelemetry::proto::common::v1::KeyValue;
 use crate::proto::opentelemetry::proto::metrics::v1::{metric, number_data_point};
-use crate::store::Store;
+use crate::store::{StorageBackend, TimeBounds};
 
 use super::arrow_schema;
 
@@ -27,7 +27,8 @@ fn append_kv_map(builder: &mut MapBuilder<StringBuilder, StringBuilder>, kvs: &[
     builder.append(true).unwrap();
 }
 
-pub fn traces_to_batch(store: &Store) -> RecordBatch {
+/// The spans starting within `bounds`, one row each.
+pub fn traces_to_batch(store: &dyn StorageBackend, bounds: TimeBounds) -> RecordBatch {
     let schema = arrow_schema::traces_schema();
 
     let mut trace_id = StringBuilder::new();
@@ -44,7 +45,7 @@ pub fn traces_to_batch(store: &Store) -> RecordBatch {
     let mut attributes = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
     let mut resource = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
 
-    for rs in store.all_traces() {
+    for rs in store.scan_traces(bounds) {
         let svc = get_service_name(&rs.resource);
         let res_attrs = get_resource_attributes(&rs.resource);
         for ss in &rs.scope_spans {
@@ -96,7 +97,8 @@ pub fn traces_to_batch(store: &Store) -> RecordBatch {
     RecordBatch::try_new(schema, columns).expect("schema mismatch in traces_to_batch")
 }
 
-pub fn logs_to_batch(store: &Store) -> RecordBatch {
+/// The log records of the batches within `bounds`, one row each.
+pub fn logs_to_batch(store: &dyn StorageBackend, bounds: TimeBounds) -> RecordBatch {
     let schema = arrow_schema::logs_schema();
 
     let mut timestamp = UInt64Builder::new();
@@ -109,7 +111,7 @@ pub fn logs_to_batch(store: &Store) -> RecordBatch {
     let mut attributes = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
     let mut resource = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
 
-    for rl in store.all_logs() {
+    for rl in store.scan_logs(bounds) {
         let svc = get_service_name(&rl.resource);
         let res_attrs = get_resource_attributes(&rl.resource);
         for sl in &rl.scope_logs {
@@ -230,11 +232,12 @@ fn number_dp_value(
     }
 }
 
-pub fn metrics_to_batch(store: &Store) -> RecordBatch {
+/// The data points of the batches within `bounds`, one row each.
+pub fn metrics_to_batch(store: &dyn StorageBackend, bounds: TimeBounds) -> RecordBatch {
     let schema = arrow_schema::metrics_schema();
     let mut b = MetricRowBuilders::new();
 
-    for rm in store.all_metrics() {
+    for rm in store.scan_metrics(bounds) {
         let svc = get_service_name(&rm.resource);
         let res_attrs = get_resource_attributes(&rm.resource);
         for sm in &rm.scope_metrics {
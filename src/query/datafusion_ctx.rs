@@ -14,7 +14,7 @@ pub fn create_context(store: SharedStore) -> SessionContext {
     ctx.register_table(
         "traces",
         Arc::new(OtelTable::new(
-            store.clone(),
+            store.traces,
             TableKind::Traces,
             arrow_schema::traces_schema(),
         )),
@@ -23,7 +23,7 @@ pub fn create_context(store: SharedStore) -> SessionContext {
     ctx.register_table(
         "logs",
         Arc::new(OtelTable::new(
-            store.clone(),
+            store.logs,
             TableKind::Logs,
             arrow_schema::logs_schema(),
         )),
@@ -32,7 +32,7 @@ pub fn create_context(store: SharedStore) -> SessionContext {
     ctx.register_table(
         "metrics",
         Arc::new(OtelTable::new(
-            store,
+            store.metrics,
             TableKind::Metrics,
             arrow_schema::metrics_schema(),
         )),
@@ -0,0 +1,325 @@
+//! Filtered deletes for the `Delete` RPC.
+//!
+//! The conditions are turned into a SQL `WHERE` clause over the `traces`,
+//! `logs` or `metrics` table, and the matching rows are removed as the spans,
+//! log records and data points they came from. The table is built and
+//! filtered under the read lock, so receivers and queries carry on meanwhile,
+//! with each row tagged by the [`RowId`] of its span, record or point. Those
+//! rows are then removed under the write lock. Batches stored, expired or
+//! evicted in between do not move the remaining rows, but another delete of
+//! the same signal can, so the delete is rejected with
+//! [`DeleteError::Changed`] if one ran meanwhile.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use datafusion::arrow::record_batch::RecordBatch;
+use tokio::sync::broadcast::error::TryRecvError;
+
+use crate::store::{
+    data_point_count, RowId, Shard, SharedStore, StorageBackend, StoreEvent, TimeBounds,
+};
+
+use super::{arrow_convert, export::row_mask};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeleteError {
+    /// The conditions are not a valid filter for the table.
+    Filter(String),
+    /// Another delete ran while the rows to delete were found.
+    Changed,
+}
+
+impl fmt::Display for DeleteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeleteError::Filter(e) => write!(f, "SQL error: {}", e),
+            DeleteError::Changed => write!(f, "the store changed during the delete; retry"),
+        }
+    }
+}
+
+/// Conditions a row must all meet to be deleted.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Conditions<'a> {
+    /// Value of the `service_name` column.
+    pub service: Option<&'a str>,
+    /// Rows with a start time or timestamp before this, in Unix nanoseconds.
+    pub before: Option<u64>,
+    /// SQL WHERE clause applied to the table.
+    pub filter: Option<&'a str>,
+}
+
+impl Conditions<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.service.is_none() && self.before.is_none() && self.filter.is_none()
+    }
+
+    /// The conditions as one WHERE clause, with `time_column` compared to
+    /// `before`.
+    fn where_clause(&self, time_column: &str) -> String {
+        let mut clauses = Vec::new();
+        if let Some(service) = self.service {
+            clauses.push(format!("service_name = '{}'", service.replace('\'', "''")));
+        }
+        if let Some(before) = self.before {
+            clauses.push(format!("{} < {}", time_column, before));
+        }
+        if let Some(filter) = self.filter {
+            clauses.push(format!("({})", filter));
+        }
+        if clauses.is_empty() {
+            "true".to_string()
+        } else {
+            clauses.join(" AND ")
+        }
+    }
+}
+
+/// Delete the matching spans, returning how many were removed.
+pub async fn spans(store: &SharedStore, conditions: &Conditions<'_>) -> Result<usize, DeleteError> {
+    delete(
+        &store.traces,
+        "traces",
+        &conditions.where_clause("start_time"),
+        arrow_convert::traces_to_batch,
+        |s| {
+            s.scan_traces_with_seq(TimeBounds::ALL)
+                .flat_map(|(seq, rs)| {
+                    let spans = rs.scope_spans.iter().map(|ss| ss.spans.len()).sum();
+                    row_ids(seq, spans)
+                })
+                .collect()
+        },
+        |event| matches!(event, StoreEvent::TracesDeleted),
+        |s, rows| s.delete_spans(rows),
+    )
+    .await
+}
+
+/// Delete the matching log records, returning how many were removed.
+pub async fn log_records(
+    store: &SharedStore,
+    conditions: &Conditions<'_>,
+) -> Result<usize, DeleteError> {
+    delete(
+        &store.logs,
+        "logs",
+        &conditions.where_clause("timestamp"),
+        arrow_convert::logs_to_batch,
+        |s| {
+            s.scan_logs_with_seq(TimeBounds::ALL)
+                .flat_map(|(seq, rl)| {
+                    let records = rl.scope_logs.iter().map(|sl| sl.log_records.len()).sum();
+                    row_ids(seq, records)
+                })
+                .collect()
+        },
+        |event| matches!(event, StoreEvent::LogsDeleted),
+        |s, rows| s.delete_log_records(rows),
+    )
+    .await
+}
+
+/// Delete the matching metric data points, returning how many were removed.
+pub async fn metric_points(
+    store: &SharedStore,
+    conditions: &Conditions<'_>,
+) -> Result<usize, DeleteError> {
+    delete(
+        &store.metrics,
+        "metrics",
+        &conditions.where_clause("timestamp"),
+        arrow_convert::metrics_to_batch,
+        |s| {
+            s.scan_metrics_with_seq(TimeBounds::ALL)
+                .flat_map(|(seq, rm)| {
+                    let points = rm
+                        .scope_metrics
+                        .iter()
+                        .flat_map(|sm| &sm.metrics)
+                        .map(data_point_count)
+                        .sum();
+                    row_ids(seq, points)
+                })
+                .collect()
+        },
+        |event| matches!(event, StoreEvent::MetricsDeleted),
+        |s, rows| s.delete_metric_points(rows),
+    )
+    .await
+}
+
+/// The ids of the `rows` rows of the batch stored as `seq`.
+fn row_ids(seq: u64, rows: usize) -> impl Iterator<Item = RowId> {
+    (0..rows).map(move |index| RowId { seq, index })
+}
+
+async fn delete(
+    store: &Shard,
+    table: &str,
+    filter: &str,
+    to_batch: fn(&dyn StorageBackend, TimeBounds) -> RecordBatch,
+    to_row_ids: fn(&dyn StorageBackend) -> Vec<RowId>,
+    is_delete: fn(&StoreEvent) -> bool,
+    remove_rows: fn(&mut dyn StorageBackend, &BTreeSet<RowId>) -> usize,
+) -> Result<usize, DeleteError> {
+    let (batch, ids, mut events) = {
+        let s = store.read().await;
+        (
+            to_batch(&*s, TimeBounds::ALL),
+            to_row_ids(&*s),
+            s.subscribe(),
+        )
+    };
+    let mask = row_mask(table, batch, filter)
+        .await
+        .map_err(DeleteError::Filter)?;
+    let rows: BTreeSet<RowId> = ids
+        .into_iter()
+        .zip(mask)
+        .filter_map(|(id, remove)| remove.then_some(id))
+        .collect();
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let mut s = store.write().await;
+    // Another delete may have shifted the indexes within a batch; anything
+    // else only adds or drops whole batches.
+    loop {
+        match events.try_recv() {
+            Ok(event) if is_delete(&event) => return Err(DeleteError::Changed),
+            Ok(_) => {}
+            Err(TryRecvError::Empty) => break,
+            Err(_) => return Err(DeleteError::Changed),
+        }
+    }
+    Ok(remove_rows(&mut *s, &rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::opentelemetry::proto::{
+        common::v1::{any_value, AnyValue, KeyValue},
+        resource::v1::Resource,
+        trace::v1::{ResourceSpans, ScopeSpans, Span},
+    };
+    use crate::store::{self, StoreEvent, TimeBounds};
+    use prost::Message;
+
+    fn resource_spans(service: &str, trace: u8, start: u64, end: u64) -> ResourceSpans {
+        ResourceSpans {
+            resource: Some(Resource {
+                attributes: vec![KeyValue {
+                    key: "service.name".into(),
+                    value: Some(AnyValue {
+                        value: Some(any_value::Value::StringValue(service.into())),
+                    }),
+                }],
+                ..Default::default()
+            }),
+            scope_spans: vec![ScopeSpans {
+                spans: vec![
+                    Span {
+                        trace_id: vec![trace; 16],
+                        span_id: vec![1; 8],
+                        name: "root".into(),
+                        start_time_unix_nano: start,
+                        end_time_unix_nano: end,
+                        ..Default::default()
+                    },
+                    Span {
+                        trace_id: vec![trace; 16],
+                        span_id: vec![2; 8],
+                        name: "child".into(),
+                        start_time_unix_nano: start + 10,
+                        end_time_unix_nano: end + 10,
+                        ..Default::default()
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_where_clause() {
+        let conditions = Conditions {
+            service: Some("o'brien"),
+            before: Some(100),
+            filter: Some("name = 'a' OR name = 'b'"),
+        };
+        assert_eq!(
+            conditions.where_clause("timestamp"),
+            "service_name = 'o''brien' AND timestamp < 100 AND (name = 'a' OR name = 'b')"
+        );
+        assert_eq!(Conditions::default().where_clause("timestamp"), "true");
+    }
+
+    #[tokio::test]
+    async fn test_delete_spans_by_service_and_filter() {
+        let (store, mut rx) = store::new_shared(100, 100, 100, 100);
+        store.traces.write().await.insert_traces(vec![
+            resource_spans("noisy", 1, 100, 200),
+            resource_spans("api", 2, 150, 300),
+        ]);
+        let _ = rx.recv().await;
+
+        let removed = spans(
+            &store,
+            &Conditions {
+                service: Some("noisy"),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(removed, 2);
+        assert_eq!(rx.recv().await.unwrap(), StoreEvent::TracesDeleted);
+
+        let removed = spans(
+            &store,
+            &Conditions {
+                filter: Some("span_name = 'child'"),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(removed, 1);
+
+        let s = store.traces.read().await;
+        assert_eq!(s.trace_count(), 1);
+        let traces: Vec<_> = s.scan_traces(TimeBounds::ALL).collect();
+        let names: Vec<&str> = traces
+            .iter()
+            .flat_map(|rs| &rs.scope_spans)
+            .flat_map(|ss| &ss.spans)
+            .map(|span| span.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["root"]);
+        assert_eq!(s.trace_bytes(), traces[0].encoded_len());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_filter_deletes_nothing() {
+        let (store, _rx) = store::new_shared(100, 100, 100, 100);
+        store
+            .traces
+            .write()
+            .await
+            .insert_traces(vec![resource_spans("api", 1, 100, 200)]);
+        let conditions = Conditions {
+            filter: Some("no_such_column = 1"),
+            ..Default::default()
+        };
+        assert!(matches!(
+            spans(&store, &conditions).await,
+            Err(DeleteError::Filter(_))
+        ));
+        assert_eq!(store.traces.read().await.trace_count(), 1);
+    }
+}
@@ -0,0 +1,276 @@
+//! Store snapshots for the `Export` RPC, optionally filtered with a SQL
+//! `WHERE` clause over the `traces`, `logs` or `metrics` table.
+//!
+//! The filter runs against the same rows the SQL tables expose, plus a row
+//! number column. Matching rows are mapped back to the spans, log records and
+//! data points they came from, so exported data keeps its original resources
+//! and scopes.
+
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use datafusion::arrow::array::{ArrayRef, AsArray, UInt64Array};
+use datafusion::arrow::datatypes::{DataType, Field, Schema, UInt64Type};
+use datafusion::arrow::record_batch::RecordBatch;
+use datafusion::prelude::SessionContext;
+
+use crate::proto::opentelemetry::proto::{
+    logs::v1::ResourceLogs, metrics::v1::ResourceMetrics, trace::v1::ResourceSpans,
+};
+use crate::store::{retain_data_points, SharedStore, TimeBounds};
+
+use super::{arrow_convert, datafusion_ctx};
+
+const ROW_COLUMN: &str = "__export_row";
+
+pub async fn traces(
+    store: &SharedStore,
+    filter: Option<&str>,
+) -> Result<Vec<ResourceSpans>, String> {
+    let (mut data, batch) = {
+        let s = store.traces.read().await;
+        let data: Vec<ResourceSpans> = s
+            .scan_traces(TimeBounds::ALL)
+            .map(Cow::into_owned)
+            .collect();
+        (
+            data,
+            filter.map(|_| arrow_convert::traces_to_batch(&*s, TimeBounds::ALL)),
+        )
+    };
+    let (Some(filter), Some(batch)) = (filter, batch) else {
+        return Ok(data);
+    };
+
+    let mut keep = row_mask("traces", batch, filter).await?.into_iter();
+    data.retain_mut(|rs| {
+        for ss in &mut rs.scope_spans {
+            ss.spans.retain(|_| keep.next().unwrap_or(false));
+        }
+        rs.scope_spans.retain(|ss| !ss.spans.is_empty());
+        !rs.scope_spans.is_empty()
+    });
+    Ok(data)
+}
+
+pub async fn logs(store: &SharedStore, filter: Option<&str>) -> Result<Vec<ResourceLogs>, String> {
+    let (mut data, batch) = {
+        let s = store.logs.read().await;
+        let data: Vec<ResourceLogs> = s.scan_logs(TimeBounds::ALL).map(Cow::into_owned).collect();
+        (
+            data,
+            filter.map(|_| arrow_convert::logs_to_batch(&*s, TimeBounds::ALL)),
+        )
+    };
+    let (Some(filter), Some(batch)) = (filter, batch) else {
+        return Ok(data);
+    };
+
+    let mut keep = row_mask("logs", batch, filter).await?.into_iter();
+    data.retain_mut(|rl| {
+        for sl in &mut rl.scope_logs {
+            sl.log_records.retain(|_| keep.next().unwrap_or(false));
+        }
+        rl.scope_logs.retain(|sl| !sl.log_records.is_empty());
+        !rl.scope_logs.is_empty()
+    });
+    Ok(data)
+}
+
+pub async fn metrics(
+    store: &SharedStore,
+    filter: Option<&str>,
+) -> Result<Vec<ResourceMetrics>, String> {
+    let (mut data, batch) = {
+        let s = store.metrics.read().await;
+        let data: Vec<ResourceMetrics> = s
+            .scan_metrics(TimeBounds::ALL)
+            .map(Cow::into_owned)
+            .collect();
+        (
+            data,
+            filter.map(|_| arrow_convert::metrics_to_batch(&*s, TimeBounds::ALL)),
+        )
+    };
+    let (Some(filter), Some(batch)) = (filter, batch) else {
+        return Ok(data);
+    };
+
+    let mut keep = row_mask("metrics", batch, filter).await?.into_iter();
+    data.retain_mut(|rm| {
+        for sm in &mut rm.scope_metrics {
+            for m in &mut sm.metrics {
+                retain_data_points(m, || keep.next().unwrap_or(false));
+            }
+            sm.metrics.retain(|m| crate::store::data_point_count(m) > 0);
+        }
+        rm.scope_metrics.retain(|sm| !sm.metrics.is_empty());
+        !rm.scope_metrics.is_empty()
+    });
+    Ok(data)
+}
+
+/// Run `filter` over `batch` registered as `table`, returning which rows match.
+pub(super) async fn row_mask(
+    table: &str,
+    batch: RecordBatch,
+    filter: &str,
+) -> Result<Vec<bool>, String> {
+    let rows = batch.num_rows();
+    let schema = batch.schema();
+    let mut fields: Vec<Field> = schema.fields().iter().map(|f| f.as_ref().clone()).collect();
+    fields.push(Field::new(ROW_COLUMN, DataType::UInt64, false));
+    let mut columns: Vec<ArrayRef> = batch.columns().to_vec();
+    columns.push(Arc::new(UInt64Array::from_iter_values(0..rows as u64)));
+    let batch =
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).map_err(|e| e.to_string())?;
+
+    let ctx = SessionContext::new();
+    ctx.register_batch(table, batch)
+        .map_err(|e| e.to_string())?;
+    let sql = format!("SELECT {} FROM {} WHERE {}", ROW_COLUMN, table, filter);
+    let batches = datafusion_ctx::execute_sql(&ctx, &sql).await?;
+
+    let mut mask = vec![false; rows];
+    for batch in &batches {
+        let column = batch
+            .column(0)
+            .as_primitive_opt::<UInt64Type>()
+            .ok_or_else(|| format!("filter must not change the selected columns: {}", filter))?;
+        for row in column.values() {
+            if let Some(keep) = mask.get_mut(*row as usize) {
+                *keep = true;
+            }
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::opentelemetry::proto::{
+        common::v1::{any_value, AnyValue, KeyValue},
+        logs::v1::{LogRecord, ScopeLogs},
+        metrics::v1::{metric, Gauge, Metric, NumberDataPoint, ScopeMetrics},
+        resource::v1::Resource,
+        trace::v1::{ScopeSpans, Span},
+    };
+    use crate::store;
+
+    fn resource(service: &str) -> Option<Resource> {
+        Some(Resource {
+            attributes: vec![KeyValue {
+                key: "service.name".into(),
+                value: Some(AnyValue {
+                    value: Some(any_value::Value::StringValue(service.into())),
+                }),
+            }],
+            ..Default::default()
+        })
+    }
+
+    fn span(id: u8, name: &str) -> Span {
+        Span {
+            trace_id: vec![id; 16],
+            span_id: vec![id; 8],
+            name: name.into(),
+            start_time_unix_nano: 1_000,
+            end_time_unix_nano: 2_000,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_traces_filter_keeps_resource_and_scope() {
+        let (store, _rx) = store::new_shared(100, 1000, 100, 100);
+        store.traces.write().await.insert_traces(vec![
+            ResourceSpans {
+                resource: resource("checkout"),
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![span(1, "GET /cart"), span(2, "SELECT")],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+            ResourceSpans {
+                resource: resource("payments"),
+                scope_spans: vec![ScopeSpans {
+                    spans: vec![span(3, "charge")],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        ]);
+
+        assert_eq!(traces(&store, None).await.unwrap().len(), 2);
+
+        let filtered = traces(&store, Some("span_name = 'SELECT'")).await.unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].resource, resource("checkout"));
+        assert_eq!(filtered[0].scope_spans[0].spans, vec![span(2, "SELECT")]);
+
+        assert!(traces(&store, Some("no_such_column = 1")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_logs_and_metrics_filters() {
+        let (store, _rx) = store::new_shared(100, 1000, 100, 100);
+        let record = |time: u64| LogRecord {
+            time_unix_nano: time,
+            ..Default::default()
+        };
+        store.logs.write().await.insert_logs(vec![ResourceLogs {
+            resource: resource("checkout"),
+            scope_logs: vec![ScopeLogs {
+                log_records: vec![record(1_000), record(2_000)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }]);
+        let point = |time: u64| NumberDataPoint {
+            time_unix_nano: time,
+            ..Default::default()
+        };
+        store
+            .metrics
+            .write()
+            .await
+            .insert_metrics(vec![ResourceMetrics {
+                resource: resource("checkout"),
+                scope_metrics: vec![ScopeMetrics {
+                    metrics: vec![
+                        Metric {
+                            name: "a".into(),
+                            data: Some(metric::Data::Gauge(Gauge {
+                                data_points: vec![point(1_000), point(2_000)],
+                            })),
+                            ..Default::default()
+                        },
+                        Metric {
+                            name: "b".into(),
+                            data: Some(metric::Data::Gauge(Gauge {
+                                data_points: vec![point(1_000)],
+                            })),
+                            ..Default::default()
+                        },
+                    ],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }]);
+
+        let filtered = logs(&store, Some("timestamp > 1500")).await.unwrap();
+        assert_eq!(filtered[0].scope_logs[0].log_records, vec![record(2_000)]);
+        assert!(logs(&store, Some("service_name = 'other'"))
+            .await
+            .unwrap()
+            .is_empty());
+
+        let filtered = metrics(&store, Some("timestamp > 1500")).await.unwrap();
+        let metrics = &filtered[0].scope_metrics[0].metrics;
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].name, "a");
+        assert_eq!(crate::store::data_point_count(&metrics[0]), 1);
+    }
+}
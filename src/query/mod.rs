@@ -1,5 +1,7 @@
 pub mod arrow_convert;
 pub mod arrow_schema;
 pub mod datafusion_ctx;
+pub mod delete;
+pub mod export;
 pub mod sql;
 pub mod table_provider;
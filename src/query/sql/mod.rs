@@ -121,7 +121,7 @@ mod tests {
         resource::v1::Resource,
         trace::v1::{ResourceSpans, ScopeSpans, Span, Status},
     };
-    use crate::store::Store;
+    use crate::store::{StorageBackend, Store, TimeBounds};
 
     fn make_kv(key: &str, value: &str) -> KeyValue {
         KeyValue {
@@ -285,9 +285,9 @@ mod tests {
 
     fn setup_ctx(store: &Store) -> SessionContext {
         let ctx = SessionContext::new();
-        let traces_batch = crate::query::arrow_convert::traces_to_batch(store);
-        let logs_batch = crate::query::arrow_convert::logs_to_batch(store);
-        let metrics_batch = crate::query::arrow_convert::metrics_to_batch(store);
+        let traces_batch = crate::query::arrow_convert::traces_to_batch(store, TimeBounds::ALL);
+        let logs_batch = crate::query::arrow_convert::logs_to_batch(store, TimeBounds::ALL);
+        let metrics_batch = crate::query::arrow_convert::metrics_to_batch(store, TimeBounds::ALL);
 
         ctx.register_batch("traces", traces_batch).unwrap();
         ctx.register_batch("logs", logs_batch).unwrap();
@@ -2,16 +2,19 @@ use std::any::Any;
 use std::fmt;
 use std::sync::Arc;
 
-use datafusion::arrow::datatypes::SchemaRef;
+use datafusion::arrow::datatypes::{DataType, SchemaRef};
 use datafusion::catalog::Session;
+use datafusion::common::ScalarValue;
 use datafusion::datasource::memory::MemorySourceConfig;
 use datafusion::datasource::TableProvider;
 use datafusion::error::Result;
-use datafusion::logical_expr::TableType;
+use datafusion::logical_expr::{
+    BinaryExpr, Cast, Operator, TableProviderFilterPushDown, TableType,
+};
 use datafusion::physical_plan::ExecutionPlan;
 use datafusion::prelude::Expr;
 
-use crate::store::SharedStore;
+use crate::store::{Shard, TimeBounds};
 
 use super::arrow_convert;
 
@@ -23,7 +26,8 @@ pub enum TableKind {
 }
 
 pub struct OtelTable {
-    store: SharedStore,
+    /// The store of the signal `kind` lists.
+    store: Shard,
     kind: TableKind,
     schema: SchemaRef,
 }
@@ -38,13 +42,74 @@ impl fmt::Debug for OtelTable {
 }
 
 impl OtelTable {
-    pub fn new(store: SharedStore, kind: TableKind, schema: SchemaRef) -> Self {
+    pub fn new(store: Shard, kind: TableKind, schema: SchemaRef) -> Self {
         Self {
             store,
             kind,
             schema,
         }
     }
+
+    /// The column the store's time bounds apply to.
+    fn time_column(&self) -> &'static str {
+        match self.kind {
+            TableKind::Traces => "start_time",
+            TableKind::Logs | TableKind::Metrics => "timestamp",
+        }
+    }
+}
+
+/// The time bounds a scan with `filters` needs, so batches that cannot match
+/// are skipped. Batches are keyed by their earliest time, so only upper
+/// bounds on `column` narrow the scan.
+fn scan_bounds(filters: &[Expr], column: &str) -> TimeBounds {
+    let end = filters
+        .iter()
+        .filter_map(|filter| upper_bound(filter, column))
+        .min()
+        .unwrap_or(u64::MAX);
+    TimeBounds { start: 0, end }
+}
+
+/// The largest value of `column` that can satisfy `filter`, for comparisons
+/// of the column with an integer literal.
+fn upper_bound(filter: &Expr, column: &str) -> Option<u64> {
+    let Expr::BinaryExpr(BinaryExpr { left, op, right }) = filter else {
+        return None;
+    };
+    let (op, value) = if is_column(left, column) {
+        (*op, integer_literal(right)?)
+    } else if is_column(right, column) {
+        (op.swap()?, integer_literal(left)?)
+    } else {
+        return None;
+    };
+    match op {
+        Operator::Lt => Some(value.saturating_sub(1)),
+        Operator::LtEq | Operator::Eq => Some(value),
+        _ => None,
+    }
+}
+
+fn is_column(expr: &Expr, column: &str) -> bool {
+    match expr {
+        Expr::Column(c) => c.name == column,
+        Expr::Cast(Cast { expr, .. }) => is_column(expr, column),
+        _ => false,
+    }
+}
+
+fn integer_literal(expr: &Expr) -> Option<u64> {
+    let Expr::Literal(value, _) = expr else {
+        return None;
+    };
+    if !value.data_type().is_integer() {
+        return None;
+    }
+    match value.cast_to(&DataType::UInt64) {
+        Ok(ScalarValue::UInt64(v)) => v,
+        _ => None,
+    }
 }
 
 impl TableProvider for OtelTable {
@@ -60,11 +125,25 @@ impl TableProvider for OtelTable {
         TableType::Base
     }
 
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> Result<Vec<TableProviderFilterPushDown>> {
+        let column = self.time_column();
+        Ok(filters
+            .iter()
+            .map(|filter| match upper_bound(filter, column) {
+                Some(_) => TableProviderFilterPushDown::Inexact,
+                None => TableProviderFilterPushDown::Unsupported,
+            })
+            .collect())
+    }
+
     fn scan<'life0, 'life1, 'life2, 'life3, 'async_trait>(
         &'life0 self,
         _state: &'life1 dyn Session,
         projection: Option<&'life2 Vec<usize>>,
-        _filters: &'life3 [Expr],
+        filters: &'life3 [Expr],
         _limit: Option<usize>,
     ) -> std::pin::Pin<
         Box<dyn std::future::Future<Output = Result<Arc<dyn ExecutionPlan>>> + Send + 'async_trait>,
@@ -77,12 +156,13 @@ impl TableProvider for OtelTable {
         Self: 'async_trait,
     {
         let projection = projection.cloned();
+        let bounds = scan_bounds(filters, self.time_column());
         Box::pin(async move {
             let store = self.store.read().await;
             let batch = match self.kind {
-                TableKind::Traces => arrow_convert::traces_to_batch(&store),
-                TableKind::Logs => arrow_convert::logs_to_batch(&store),
-                TableKind::Metrics => arrow_convert::metrics_to_batch(&store),
+                TableKind::Traces => arrow_convert::traces_to_batch(&*store, bounds),
+                TableKind::Logs => arrow_convert::logs_to_batch(&*store, bounds),
+                TableKind::Metrics => arrow_convert::metrics_to_batch(&*store, bounds),
             };
             drop(store);
 
@@ -104,3 +184,65 @@ impl fmt::Display for OtelTable {
         write!(f, "OtelTable({:?})", self.kind)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::prelude::{col, lit};
+
+    #[test]
+    fn test_scan_bounds_use_upper_time_bounds() {
+        let filters = [
+            col("timestamp").lt(lit(100u64)),
+            lit(50i64).gt_eq(col("timestamp")),
+            col("timestamp").gt(lit(10u64)),
+            col("severity").eq(lit("ERROR")),
+        ];
+        assert_eq!(
+            scan_bounds(&filters, "timestamp"),
+            TimeBounds { start: 0, end: 50 }
+        );
+        assert_eq!(scan_bounds(&filters[2..], "timestamp"), TimeBounds::ALL);
+        assert_eq!(
+            upper_bound(&col("timestamp").lt(lit(1.5)), "timestamp"),
+            None
+        );
+        assert_eq!(
+            upper_bound(&col("timestamp").lt(lit(-1i64)), "timestamp"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pushed_down_filter_keeps_exact_rows() {
+        use crate::proto::opentelemetry::proto::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+        use crate::query::datafusion_ctx::{create_context, execute_sql};
+
+        let (store, _rx) = crate::store::new_shared(100, 100, 100, 100);
+        let batch = |times: &[u64]| ResourceLogs {
+            scope_logs: vec![ScopeLogs {
+                log_records: times
+                    .iter()
+                    .map(|&t| LogRecord {
+                        time_unix_nano: t,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        store
+            .logs
+            .write()
+            .await
+            .insert_logs(vec![batch(&[10, 300]), batch(&[200])]);
+
+        let ctx = create_context(store);
+        let batches = execute_sql(&ctx, "SELECT timestamp FROM logs WHERE timestamp < 250")
+            .await
+            .unwrap();
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 2);
+    }
+}
@@ -0,0 +1,214 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tonic::{service::Interceptor, Status};
+
+use crate::server::otlp_http;
+
+/// What an authenticated caller may do on the query API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Access {
+    /// `SqlQuery`, `Status` and the follow streams.
+    ReadOnly,
+    /// Everything, including `Clear*` and `Shutdown`.
+    Admin,
+}
+
+#[derive(Clone, Debug)]
+struct Credential {
+    header: HeaderName,
+    value: HeaderValue,
+    access: Access,
+}
+
+/// Checks requests against a set of bearer tokens and static headers.
+///
+/// With no credentials configured every request is granted [`Access::Admin`],
+/// which keeps the server open by default.
+#[derive(Clone, Debug, Default)]
+pub struct Authenticator {
+    credentials: Vec<Credential>,
+}
+
+impl Authenticator {
+    /// Accept `Authorization: Bearer <token>`.
+    pub fn add_bearer_token(&mut self, token: &str, access: Access) -> anyhow::Result<()> {
+        self.add_header(
+            header::AUTHORIZATION.as_str(),
+            &format!("Bearer {}", token),
+            access,
+        )
+    }
+
+    /// Accept a request carrying `name: value`.
+    pub fn add_header(&mut self, name: &str, value: &str, access: Access) -> anyhow::Result<()> {
+        let header = HeaderName::try_from(name)
+            .map_err(|e| anyhow::anyhow!("invalid auth header name '{}': {}", name, e))?;
+        let value = HeaderValue::try_from(value)
+            .map_err(|e| anyhow::anyhow!("invalid auth header value for '{}': {}", name, e))?;
+        self.credentials.push(Credential {
+            header,
+            value,
+            access,
+        });
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.credentials.is_empty()
+    }
+
+    /// Return the highest access level granted by `headers`, or `None` if
+    /// no configured credential matches.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Option<Access> {
+        if !self.is_enabled() {
+            return Some(Access::Admin);
+        }
+        self.credentials
+            .iter()
+            .filter(|c| {
+                headers
+                    .get_all(&c.header)
+                    .iter()
+                    .any(|v| constant_time_eq(v.as_bytes(), c.value.as_bytes()))
+            })
+            .map(|c| c.access)
+            .max()
+    }
+}
+
+/// Rejects unauthenticated gRPC calls and records the caller's [`Access`] in
+/// the request extensions for per-method checks.
+impl Interceptor for Authenticator {
+    fn call(&mut self, mut request: tonic::Request<()>) -> Result<tonic::Request<()>, Status> {
+        match self.authenticate(request.metadata().as_ref()) {
+            Some(access) => {
+                request.extensions_mut().insert(access);
+                Ok(request)
+            }
+            None => Err(Status::unauthenticated("missing or invalid credentials")),
+        }
+    }
+}
+
+/// Fail with `PERMISSION_DENIED` unless the interceptor granted admin access.
+pub fn require_admin<T>(request: &tonic::Request<T>) -> Result<(), Status> {
+    match request.extensions().get::<Access>() {
+        Some(Access::Admin) => Ok(()),
+        _ => Err(Status::permission_denied("admin access required")),
+    }
+}
+
+/// axum middleware rejecting OTLP/HTTP requests without valid credentials.
+pub async fn require_http(
+    State(auth): State<Authenticator>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match auth.authenticate(request.headers()) {
+        Some(_) => next.run(request).await,
+        None => {
+            let is_json = otlp_http::is_json_content_type(request.headers());
+            (
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                otlp_http::ExportError::new(
+                    StatusCode::UNAUTHORIZED,
+                    "missing or invalid credentials",
+                    is_json,
+                ),
+            )
+                .into_response()
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (k, v) in pairs {
+            map.append(
+                HeaderName::try_from(*k).unwrap(),
+                HeaderValue::try_from(*v).unwrap(),
+            );
+        }
+        map
+    }
+
+    #[test]
+    fn test_disabled_grants_admin() {
+        let auth = Authenticator::default();
+        assert!(!auth.is_enabled());
+        assert_eq!(auth.authenticate(&HeaderMap::new()), Some(Access::Admin));
+    }
+
+    #[test]
+    fn test_bearer_token_access_levels() {
+        let mut auth = Authenticator::default();
+        auth.add_bearer_token("admin-secret", Access::Admin)
+            .unwrap();
+        auth.add_bearer_token("reader", Access::ReadOnly).unwrap();
+        assert_eq!(
+            auth.authenticate(&headers(&[("authorization", "Bearer admin-secret")])),
+            Some(Access::Admin)
+        );
+        assert_eq!(
+            auth.authenticate(&headers(&[("authorization", "Bearer reader")])),
+            Some(Access::ReadOnly)
+        );
+        assert_eq!(
+            auth.authenticate(&headers(&[("authorization", "Bearer wrong")])),
+            None
+        );
+        assert_eq!(auth.authenticate(&HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_static_header() {
+        let mut auth = Authenticator::default();
+        auth.add_header("X-Api-Key", "k1", Access::Admin).unwrap();
+        assert_eq!(
+            auth.authenticate(&headers(&[("x-api-key", "k1")])),
+            Some(Access::Admin)
+        );
+        assert_eq!(auth.authenticate(&headers(&[("x-api-key", "k2")])), None);
+    }
+
+    #[test]
+    fn test_invalid_header_name() {
+        assert!(Authenticator::default()
+            .add_header("bad header", "v", Access::Admin)
+            .is_err());
+    }
+
+    #[test]
+    fn test_interceptor_records_access() {
+        let mut auth = Authenticator::default();
+        auth.add_bearer_token("reader", Access::ReadOnly).unwrap();
+        let mut request = tonic::Request::new(());
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer reader".parse().unwrap());
+        let request = auth.call(request).unwrap();
+        assert_eq!(
+            request.extensions().get::<Access>(),
+            Some(&Access::ReadOnly)
+        );
+        assert_eq!(
+            require_admin(&request).unwrap_err().code(),
+            tonic::Code::PermissionDenied
+        );
+
+        let err = auth.call(tonic::Request::new(())).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    }
+}
@@ -0,0 +1,632 @@
+//! Fluent Forward protocol receiver (msgpack over TCP), as spoken by Fluent Bit
+//! and fluentd's `forward` output.
+//!
+//! Message, Forward and (optionally gzip-compressed) PackedForward modes are
+//! decoded into one `ResourceLogs` per tag. The record's `log`, `message` or
+//! `msg` field becomes the body, `level`/`severity` the severity, and the other
+//! fields become attributes. When an event carries the `chunk` option, the
+//! server waits until the events are stored and answers with `{"ack": chunk}`.
+//! Events without it are queued for storage as they arrive.
+
+use std::io::Read;
+
+use bytes::BytesMut;
+use rmpv::Value;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::sync::CancellationToken;
+
+use crate::proto::opentelemetry::proto::{
+    common::v1::{any_value, AnyValue, ArrayValue, KeyValue, KeyValueList},
+    logs::v1::{LogRecord, ResourceLogs, ScopeLogs},
+    resource::v1::Resource,
+};
+use crate::store::{self, ingest::Ingester, SharedStore};
+
+/// Largest single forward message accepted; bigger ones close the connection.
+const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Record fields used as the log body, in order of preference.
+const BODY_KEYS: [&str; 3] = ["log", "message", "msg"];
+
+/// Record fields used as the severity, in order of preference.
+const SEVERITY_KEYS: [&str; 2] = ["level", "severity"];
+
+/// A decoded forward message: its tag, events and the `chunk` id to acknowledge.
+#[derive(Debug, Default, PartialEq)]
+pub struct ForwardMessage {
+    pub tag: String,
+    /// `(time in ns, record)` pairs.
+    pub entries: Vec<(u64, Value)>,
+    pub chunk: Option<String>,
+}
+
+/// Decode one forward-protocol message (an array whose second element
+/// determines the mode).
+pub fn decode_message(value: Value) -> anyhow::Result<ForwardMessage> {
+    let Value::Array(mut items) = value else {
+        anyhow::bail!("forward message is not an array");
+    };
+    if items.len() < 2 {
+        anyhow::bail!("forward message has {} elements", items.len());
+    }
+    let tag = items[0]
+        .as_str()
+        .ok_or_else(|| anyhow::anyhow!("forward message tag is not a string"))?
+        .to_string();
+
+    let (entries, options) = match items.remove(1) {
+        // Forward mode: [tag, [[time, record], ...], option?]
+        Value::Array(entries) => {
+            let entries = entries
+                .into_iter()
+                .map(decode_entry)
+                .collect::<anyhow::Result<_>>()?;
+            (entries, items.get(1).cloned())
+        }
+        // PackedForward mode: [tag, bin|str of concatenated entries, option?]
+        Value::Binary(packed) => (decode_packed(packed, items.get(1))?, items.get(1).cloned()),
+        Value::String(packed) => (
+            decode_packed(packed.into_bytes(), items.get(1))?,
+            items.get(1).cloned(),
+        ),
+        // Message mode: [tag, time, record, option?]
+        time => {
+            let record = items
+                .get(1)
+                .cloned()
+                .ok_or_else(|| anyhow::anyhow!("message mode event has no record"))?;
+            (vec![(decode_time(&time)?, record)], items.get(2).cloned())
+        }
+    };
+
+    let chunk = options
+        .as_ref()
+        .and_then(|o| option(o, "chunk"))
+        .and_then(|c| c.as_str())
+        .map(str::to_string);
+    Ok(ForwardMessage {
+        tag,
+        entries,
+        chunk,
+    })
+}
+
+fn decode_entry(entry: Value) -> anyhow::Result<(u64, Value)> {
+    match entry {
+        Value::Array(mut pair) if pair.len() == 2 => {
+            let record = pair.pop().unwrap_or(Value::Nil);
+            Ok((decode_time(&pair[0])?, record))
+        }
+        _ => anyhow::bail!("forward entry is not a [time, record] pair"),
+    }
+}
+
+fn decode_packed(packed: Vec<u8>, options: Option<&Value>) -> anyhow::Result<Vec<(u64, Value)>> {
+    let compressed = options.and_then(|o| option(o, "compressed"));
+    let data = match compressed.and_then(|c| c.as_str()) {
+        Some("gzip") => {
+            let mut data = Vec::new();
+            flate2::read::MultiGzDecoder::new(packed.as_slice())
+                .take(MAX_MESSAGE_SIZE as u64 + 1)
+                .read_to_end(&mut data)
+                .map_err(|e| anyhow::anyhow!("invalid gzip in CompressedPackedForward: {}", e))?;
+            if data.len() > MAX_MESSAGE_SIZE {
+                anyhow::bail!(
+                    "decompressed forward entries exceed {} bytes",
+                    MAX_MESSAGE_SIZE
+                );
+            }
+            data
+        }
+        Some(other) => anyhow::bail!("unsupported forward compression '{}'", other),
+        None => packed,
+    };
+    let mut reader = data.as_slice();
+    let mut entries = Vec::new();
+    while !reader.is_empty() {
+        let entry = rmpv::decode::read_value(&mut reader)
+            .map_err(|e| anyhow::anyhow!("invalid PackedForward entry: {}", e))?;
+        entries.push(decode_entry(entry)?);
+    }
+    Ok(entries)
+}
+
+/// Event time is either integer seconds or the EventTime extension
+/// (type 0: big-endian u32 seconds and u32 nanoseconds). Times that don't fit
+/// in u64 nanoseconds decode as 0, which is replaced by the receive time.
+fn decode_time(value: &Value) -> anyhow::Result<u64> {
+    match value {
+        Value::Integer(seconds) => Ok(seconds
+            .as_u64()
+            .and_then(|seconds| seconds.checked_mul(1_000_000_000))
+            .unwrap_or_default()),
+        Value::F64(seconds) => Ok((seconds.max(0.0) * 1e9) as u64),
+        Value::Ext(0, data) if data.len() == 8 => {
+            let seconds = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as u64;
+            let nanos = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as u64;
+            Ok(seconds
+                .checked_mul(1_000_000_000)
+                .and_then(|time| time.checked_add(nanos))
+                .unwrap_or_default())
+        }
+        _ => anyhow::bail!("invalid forward event time {}", value),
+    }
+}
+
+fn option<'a>(options: &'a Value, key: &str) -> Option<&'a Value> {
+    options
+        .as_map()?
+        .iter()
+        .find(|(k, _)| k.as_str() == Some(key))
+        .map(|(_, v)| v)
+}
+
+/// Convert forward messages to OTLP, with one `ResourceLogs` per tag.
+pub fn to_resource_logs(messages: Vec<ForwardMessage>, observed_time: u64) -> Vec<ResourceLogs> {
+    let mut groups: Vec<(String, Vec<LogRecord>)> = Vec::new();
+    for message in messages {
+        let records = message
+            .entries
+            .into_iter()
+            .map(|(time, record)| to_log_record(time, record, observed_time));
+        match groups.iter_mut().find(|(tag, _)| *tag == message.tag) {
+            Some((_, log_records)) => log_records.extend(records),
+            None => groups.push((message.tag, records.collect())),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(tag, log_records)| ResourceLogs {
+            resource: Some(Resource {
+                attributes: vec![
+                    string_attribute("service.name", &tag),
+                    string_attribute("fluent.tag", &tag),
+                ],
+                dropped_attributes_count: 0,
+                entity_refs: vec![],
+            }),
+            scope_logs: vec![ScopeLogs {
+                scope: None,
+                log_records,
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        })
+        .collect()
+}
+
+fn to_log_record(time: u64, record: Value, observed_time: u64) -> LogRecord {
+    let mut log_record = LogRecord {
+        time_unix_nano: if time == 0 { observed_time } else { time },
+        observed_time_unix_nano: observed_time,
+        ..Default::default()
+    };
+    let fields = match record {
+        Value::Map(fields) => fields,
+        other => {
+            log_record.body = to_any_value(other);
+            return log_record;
+        }
+    };
+
+    let mut fields: Vec<(String, Value)> = fields
+        .into_iter()
+        .map(|(k, v)| (key_string(k), v))
+        .collect();
+    let mut take = |keys: &[&str]| -> Option<Value> {
+        let pos = keys
+            .iter()
+            .find_map(|key| fields.iter().position(|(k, _)| k == key))?;
+        Some(fields.remove(pos).1)
+    };
+    log_record.body = take(&BODY_KEYS).and_then(to_any_value);
+    if let Some(severity) = take(&SEVERITY_KEYS) {
+        let text = match &severity {
+            Value::String(s) => s.as_str().unwrap_or_default().to_string(),
+            other => other.to_string(),
+        };
+        log_record.severity_number = store::severity_text_to_number(&text).unwrap_or_default();
+        log_record.severity_text = text;
+    }
+    log_record.attributes = fields
+        .into_iter()
+        .filter_map(|(key, value)| {
+            Some(KeyValue {
+                key,
+                value: Some(to_any_value(value)?),
+            })
+        })
+        .collect();
+    log_record
+}
+
+fn key_string(key: Value) -> String {
+    match key {
+        Value::String(s) => s.into_str().unwrap_or_default(),
+        other => other.to_string(),
+    }
+}
+
+/// Convert a msgpack value; `nil` has no OTLP equivalent and yields `None`.
+fn to_any_value(value: Value) -> Option<AnyValue> {
+    let value = match value {
+        Value::Nil => return None,
+        Value::Boolean(b) => any_value::Value::BoolValue(b),
+        Value::Integer(i) => match i.as_i64() {
+            Some(i) => any_value::Value::IntValue(i),
+            None => any_value::Value::DoubleValue(i.as_f64().unwrap_or_default()),
+        },
+        Value::F32(f) => any_value::Value::DoubleValue(f as f64),
+        Value::F64(f) => any_value::Value::DoubleValue(f),
+        Value::String(s) => match s.into_str() {
+            Some(s) => any_value::Value::StringValue(s),
+            None => return None,
+        },
+        Value::Binary(b) => any_value::Value::BytesValue(b),
+        Value::Array(values) => any_value::Value::ArrayValue(ArrayValue {
+            values: values.into_iter().filter_map(to_any_value).collect(),
+        }),
+        Value::Map(fields) => any_value::Value::KvlistValue(KeyValueList {
+            values: fields
+                .into_iter()
+                .filter_map(|(k, v)| {
+                    Some(KeyValue {
+                        key: key_string(k),
+                        value: Some(to_any_value(v)?),
+                    })
+                })
+                .collect(),
+        }),
+        Value::Ext(_, data) => any_value::Value::BytesValue(data),
+    };
+    Some(AnyValue { value: Some(value) })
+}
+
+fn string_attribute(key: &str, value: &str) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value.to_string())),
+        }),
+    }
+}
+
+fn now_nanos() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+/// Accept Fluent Forward connections and store the events they carry.
+pub async fn run_fluent_server(
+    listener: TcpListener,
+    store: SharedStore,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let ingester = Ingester::new(store);
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = super::accept_tcp(&listener) => accepted,
+            _ = shutdown.cancelled() => {
+                ingester.flush().await;
+                return Ok(());
+            }
+        };
+        let ingester = ingester.clone();
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                result = handle_connection(stream, ingester) => {
+                    if let Err(e) = result {
+                        tracing::debug!(%peer, error = %e, "closing Fluent Forward connection");
+                    }
+                }
+                _ = shutdown.cancelled() => {}
+            }
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, ingester: Ingester) -> anyhow::Result<()> {
+    let mut buf = BytesMut::with_capacity(64 * 1024);
+    loop {
+        // Decode every complete message already buffered before reading more.
+        while let Some(len) = value_len(&buf)? {
+            let frame = buf.split_to(len);
+            let value = rmpv::decode::read_value(&mut &frame[..])
+                .map_err(|e| anyhow::anyhow!("invalid msgpack: {}", e))?;
+            let message = decode_message(value)?;
+            let chunk = message.chunk.clone();
+            tracing::debug!(
+                tag = %message.tag,
+                count = message.entries.len(),
+                "received Fluent Forward events"
+            );
+            let resource_logs = to_resource_logs(vec![message], now_nanos());
+            ingester.insert_logs(resource_logs).await;
+            if let Some(chunk) = chunk {
+                // The client retries unacknowledged chunks, so only ack
+                // what is actually in the store.
+                ingester.flush().await;
+                let ack = Value::Map(vec![(Value::from("ack"), Value::from(chunk))]);
+                let mut out = Vec::new();
+                rmpv::encode::write_value(&mut out, &ack)?;
+                stream.write_all(&out).await?;
+            }
+        }
+        if stream.read_buf(&mut buf).await? == 0 {
+            if !buf.is_empty() {
+                anyhow::bail!("connection closed mid-message");
+            }
+            return Ok(());
+        }
+    }
+}
+
+/// Length of the first complete msgpack value in `buf`, or `None` if more
+/// data is needed. Only markers and length prefixes are read, so rescanning a
+/// partially received message is cheap even when it carries a large payload.
+fn value_len(buf: &[u8]) -> anyhow::Result<Option<usize>> {
+    fn be(buf: &[u8], pos: usize, n: usize) -> Option<usize> {
+        let bytes = buf.get(pos..pos + n)?;
+        Some(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize))
+    }
+
+    let mut pos = 0;
+    let mut pending: usize = 1;
+    while pending > 0 {
+        pending -= 1;
+        let Some(&marker) = buf.get(pos) else {
+            return Ok(None);
+        };
+        // (header length, payload length, child values)
+        let (header, payload, children) = match marker {
+            0x00..=0x7f | 0xc0 | 0xc2 | 0xc3 | 0xe0..=0xff => (1, 0, 0),
+            0x80..=0x8f => (1, 0, 2 * (marker & 0x0f) as usize),
+            0x90..=0x9f => (1, 0, (marker & 0x0f) as usize),
+            0xa0..=0xbf => (1, (marker & 0x1f) as usize, 0),
+            0xc4 | 0xd9 => match be(buf, pos + 1, 1) {
+                Some(len) => (2, len, 0),
+                None => return Ok(None),
+            },
+            0xc5 | 0xda => match be(buf, pos + 1, 2) {
+                Some(len) => (3, len, 0),
+                None => return Ok(None),
+            },
+            0xc6 | 0xdb => match be(buf, pos + 1, 4) {
+                Some(len) => (5, len, 0),
+                None => return Ok(None),
+            },
+            0xc7 => match be(buf, pos + 1, 1) {
+                Some(len) => (3, len, 0),
+                None => return Ok(None),
+            },
+            0xc8 => match be(buf, pos + 1, 2) {
+                Some(len) => (4, len, 0),
+                None => return Ok(None),
+            },
+            0xc9 => match be(buf, pos + 1, 4) {
+                Some(len) => (6, len, 0),
+                None => return Ok(None),
+            },
+            0xca => (5, 0, 0),
+            0xcb => (9, 0, 0),
+            0xcc | 0xd0 => (2, 0, 0),
+            0xcd | 0xd1 => (3, 0, 0),
+            0xce | 0xd2 => (5, 0, 0),
+            0xcf | 0xd3 => (9, 0, 0),
+            0xd4 => (3, 0, 0),
+            0xd5 => (4, 0, 0),
+            0xd6 => (6, 0, 0),
+            0xd7 => (10, 0, 0),
+            0xd8 => (18, 0, 0),
+            0xdc => match be(buf, pos + 1, 2) {
+                Some(n) => (3, 0, n),
+                None => return Ok(None),
+            },
+            0xdd => match be(buf, pos + 1, 4) {
+                Some(n) => (5, 0, n),
+                None => return Ok(None),
+            },
+            0xde => match be(buf, pos + 1, 2) {
+                Some(n) => (3, 0, 2 * n),
+                None => return Ok(None),
+            },
+            0xdf => match be(buf, pos + 1, 4) {
+                Some(n) => (5, 0, 2 * n),
+                None => return Ok(None),
+            },
+            0xc1 => anyhow::bail!("invalid msgpack marker 0xc1"),
+        };
+        pos += header + payload;
+        pending += children;
+        // Every pending value takes at least one more byte.
+        if pos + pending > MAX_MESSAGE_SIZE {
+            anyhow::bail!("forward message exceeds {} bytes", MAX_MESSAGE_SIZE);
+        }
+    }
+    Ok((pos <= buf.len()).then_some(pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn record(fields: &[(&str, Value)]) -> Value {
+        Value::Map(
+            fields
+                .iter()
+                .map(|(k, v)| (Value::from(*k), v.clone()))
+                .collect(),
+        )
+    }
+
+    fn event_time(seconds: u32, nanos: u32) -> Value {
+        let mut data = seconds.to_be_bytes().to_vec();
+        data.extend(nanos.to_be_bytes());
+        Value::Ext(0, data)
+    }
+
+    fn encode(value: &Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        rmpv::encode::write_value(&mut out, value).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_message_mode() {
+        let message = decode_message(Value::Array(vec![
+            Value::from("app.access"),
+            Value::from(1_700_000_000u64),
+            record(&[("log", Value::from("GET /"))]),
+            Value::Map(vec![(Value::from("chunk"), Value::from("abc"))]),
+        ]))
+        .unwrap();
+        assert_eq!(message.tag, "app.access");
+        assert_eq!(message.entries[0].0, 1_700_000_000_000_000_000);
+        assert_eq!(message.chunk.as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn test_out_of_range_time_uses_receive_time() {
+        let message = decode_message(Value::Array(vec![
+            Value::from("app"),
+            Value::from(u64::MAX),
+            record(&[]),
+        ]))
+        .unwrap();
+        assert_eq!(message.entries[0].0, 0);
+        let resource_logs = to_resource_logs(vec![message], 42);
+        let record = &resource_logs[0].scope_logs[0].log_records[0];
+        assert_eq!(record.time_unix_nano, 42);
+    }
+
+    #[test]
+    fn test_forward_mode() {
+        let message = decode_message(Value::Array(vec![
+            Value::from("app"),
+            Value::Array(vec![
+                Value::Array(vec![event_time(10, 5), record(&[("a", Value::from(1))])]),
+                Value::Array(vec![Value::from(11), record(&[("a", Value::from(2))])]),
+            ]),
+        ]))
+        .unwrap();
+        assert_eq!(message.entries.len(), 2);
+        assert_eq!(message.entries[0].0, 10_000_000_005);
+        assert_eq!(message.chunk, None);
+    }
+
+    #[test]
+    fn test_packed_forward_modes() {
+        let mut packed = encode(&Value::Array(vec![event_time(1, 0), record(&[])]));
+        packed.extend(encode(&Value::Array(vec![event_time(2, 0), record(&[])])));
+
+        let message = decode_message(Value::Array(vec![
+            Value::from("packed"),
+            Value::Binary(packed.clone()),
+            Value::Map(vec![(Value::from("size"), Value::from(2))]),
+        ]))
+        .unwrap();
+        assert_eq!(message.entries.len(), 2);
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&packed).unwrap();
+        let message = decode_message(Value::Array(vec![
+            Value::from("compressed"),
+            Value::Binary(gz.finish().unwrap()),
+            Value::Map(vec![
+                (Value::from("compressed"), Value::from("gzip")),
+                (Value::from("chunk"), Value::from("c1")),
+            ]),
+        ]))
+        .unwrap();
+        assert_eq!(message.entries[1].0, 2_000_000_000);
+        assert_eq!(message.chunk.as_deref(), Some("c1"));
+    }
+
+    #[test]
+    fn test_invalid_messages() {
+        assert!(decode_message(Value::from("not an array")).is_err());
+        assert!(decode_message(Value::Array(vec![Value::from(1), Value::from(2)])).is_err());
+        assert!(decode_message(Value::Array(vec![
+            Value::from("tag"),
+            Value::Array(vec![Value::from("not a pair")]),
+        ]))
+        .is_err());
+        assert!(decode_message(Value::Array(vec![
+            Value::from("tag"),
+            Value::Binary(vec![]),
+            Value::Map(vec![(Value::from("compressed"), Value::from("lz4"))]),
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_to_resource_logs() {
+        let messages = vec![
+            ForwardMessage {
+                tag: "docker.web".into(),
+                entries: vec![(
+                    1,
+                    record(&[
+                        ("log", Value::from("hello")),
+                        ("level", Value::from("warn")),
+                        ("container_id", Value::from("abc")),
+                        ("nested", record(&[("k", Value::from(true))])),
+                        ("empty", Value::Nil),
+                    ]),
+                )],
+                chunk: None,
+            },
+            ForwardMessage {
+                tag: "docker.web".into(),
+                entries: vec![(2, record(&[("message", Value::from("again"))]))],
+                chunk: None,
+            },
+        ];
+        let resource_logs = to_resource_logs(messages, 99);
+        assert_eq!(resource_logs.len(), 1);
+        let resource = resource_logs[0].resource.as_ref().unwrap();
+        assert_eq!(resource.attributes[1].key, "fluent.tag");
+
+        let records = &resource_logs[0].scope_logs[0].log_records;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].severity_text, "warn");
+        assert_eq!(records[0].severity_number, 13);
+        assert_eq!(records[0].observed_time_unix_nano, 99);
+        let keys: Vec<_> = records[0]
+            .attributes
+            .iter()
+            .map(|a| a.key.as_str())
+            .collect();
+        assert_eq!(keys, ["container_id", "nested"]);
+        assert_eq!(
+            records[1].body.as_ref().unwrap().value,
+            Some(any_value::Value::StringValue("again".into()))
+        );
+    }
+
+    #[test]
+    fn test_value_len_waits_for_complete_message() {
+        let value = Value::Array(vec![
+            Value::from("tag"),
+            Value::Binary(vec![7; 300]),
+            Value::Map(vec![(Value::from("chunk"), Value::from(u64::MAX))]),
+            Value::from(-1.5),
+            event_time(1, 2),
+        ]);
+        let data = encode(&value);
+        for end in 0..data.len() {
+            assert_eq!(value_len(&data[..end]).unwrap(), None, "prefix {}", end);
+        }
+        let mut twice = data.clone();
+        twice.extend(&data);
+        assert_eq!(value_len(&twice).unwrap(), Some(data.len()));
+
+        assert!(value_len(&[0xc1]).is_err());
+        assert!(value_len(&[0xc6, 0xff, 0xff, 0xff, 0xff]).is_err());
+    }
+}
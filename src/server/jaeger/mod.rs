@@ -0,0 +1,331 @@
+//! Jaeger collector endpoints: Thrift over HTTP (`POST /api/traces`) and the
+//! gRPC `jaeger.api_v2.CollectorService`. Batches are converted to OTLP
+//! `ResourceSpans` before they reach the store.
+
+pub mod thrift;
+
+use axum::{body::Bytes, extract::State, http::StatusCode};
+use tonic::{Request, Response, Status};
+use tracing::instrument;
+
+use crate::proto::jaeger::api_v2::{
+    self, collector_service_server::CollectorService, PostSpansRequest, PostSpansResponse,
+    SpanRefType, ValueType,
+};
+use crate::proto::opentelemetry::proto::{
+    common::v1::{any_value, AnyValue, KeyValue},
+    resource::v1::Resource,
+    trace::v1::{span, status, ResourceSpans, ScopeSpans, Span},
+};
+use crate::server::validate;
+use crate::store::ingest::Ingester;
+
+#[instrument(name = "jaeger.http.traces", skip_all, fields(http.route = "/api/traces"))]
+pub(crate) async fn handle_thrift(
+    State(ingester): State<Ingester>,
+    body: Bytes,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let batch = thrift::decode_batch(&body).map_err(|e| {
+        tracing::warn!(error = %e, "failed to decode Jaeger Thrift batch");
+        (
+            StatusCode::BAD_REQUEST,
+            format!("invalid Jaeger Thrift batch: {}", e),
+        )
+    })?;
+    tracing::debug!(count = batch.spans.len(), "received Jaeger batch via HTTP");
+    let mut resource_spans = to_resource_spans(batch);
+    validate::traces(&mut resource_spans);
+    ingester.insert_traces(resource_spans).await;
+    Ok(StatusCode::ACCEPTED)
+}
+
+pub struct JaegerGrpcService {
+    ingester: Ingester,
+}
+
+impl JaegerGrpcService {
+    pub fn new(ingester: Ingester) -> Self {
+        Self { ingester }
+    }
+}
+
+#[tonic::async_trait]
+impl CollectorService for JaegerGrpcService {
+    #[instrument(name = "jaeger.grpc.post_spans", skip_all, fields(spans.count))]
+    async fn post_spans(
+        &self,
+        request: Request<PostSpansRequest>,
+    ) -> Result<Response<PostSpansResponse>, Status> {
+        let batch = request.into_inner().batch.unwrap_or_default();
+        let count = batch.spans.len();
+        tracing::Span::current().record("spans.count", count);
+        tracing::debug!(count, "received Jaeger batch via gRPC");
+        let mut resource_spans = to_resource_spans(batch);
+        validate::traces(&mut resource_spans);
+        self.ingester.insert_traces(resource_spans).await;
+        Ok(Response::new(PostSpansResponse {}))
+    }
+}
+
+/// Convert a Jaeger batch to OTLP, with one `ResourceSpans` per distinct process.
+/// Spans without their own process use the batch's.
+pub fn to_resource_spans(batch: api_v2::Batch) -> Vec<ResourceSpans> {
+    let batch_process = batch.process.unwrap_or_default();
+    let mut groups: Vec<(api_v2::Process, Vec<Span>)> = Vec::new();
+    for mut jaeger_span in batch.spans {
+        let process = jaeger_span
+            .process
+            .take()
+            .unwrap_or_else(|| batch_process.clone());
+        let span = convert_span(jaeger_span);
+        match groups.iter_mut().find(|(p, _)| *p == process) {
+            Some((_, spans)) => spans.push(span),
+            None => groups.push((process, vec![span])),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(process, spans)| ResourceSpans {
+            resource: Some(convert_process(process)),
+            scope_spans: vec![ScopeSpans {
+                scope: None,
+                spans,
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        })
+        .collect()
+}
+
+fn convert_process(process: api_v2::Process) -> Resource {
+    let mut attributes = Vec::with_capacity(process.tags.len() + 1);
+    if !process.service_name.is_empty() {
+        attributes.push(KeyValue {
+            key: "service.name".into(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(process.service_name)),
+            }),
+        });
+    }
+    attributes.extend(process.tags.into_iter().map(convert_tag));
+    Resource {
+        attributes,
+        dropped_attributes_count: 0,
+        entity_refs: vec![],
+    }
+}
+
+fn convert_span(jaeger_span: api_v2::Span) -> Span {
+    let start_time_unix_nano = jaeger_span
+        .start_time
+        .map(|t| to_nanos(t.seconds, t.nanos))
+        .unwrap_or_default();
+    let duration = jaeger_span
+        .duration
+        .map(|d| to_nanos(d.seconds, d.nanos))
+        .unwrap_or_default();
+
+    let mut span = Span {
+        trace_id: jaeger_span.trace_id,
+        span_id: jaeger_span.span_id,
+        name: jaeger_span.operation_name,
+        start_time_unix_nano,
+        end_time_unix_nano: start_time_unix_nano.saturating_add(duration),
+        flags: jaeger_span.flags,
+        ..Default::default()
+    };
+
+    // The first CHILD_OF reference within the same trace is the parent; every
+    // other reference becomes a link.
+    for reference in jaeger_span.references {
+        let is_parent = span.parent_span_id.is_empty()
+            && reference.ref_type == SpanRefType::ChildOf as i32
+            && reference.trace_id == span.trace_id;
+        if is_parent {
+            span.parent_span_id = reference.span_id;
+        } else {
+            span.links.push(span::Link {
+                trace_id: reference.trace_id,
+                span_id: reference.span_id,
+                ..Default::default()
+            });
+        }
+    }
+
+    let mut status_code = None;
+    let mut status_message = String::new();
+    for tag in jaeger_span.tags {
+        match tag.key.as_str() {
+            "span.kind" => {
+                span.kind = match tag.v_str.as_str() {
+                    "client" => span::SpanKind::Client,
+                    "server" => span::SpanKind::Server,
+                    "producer" => span::SpanKind::Producer,
+                    "consumer" => span::SpanKind::Consumer,
+                    "internal" => span::SpanKind::Internal,
+                    _ => span::SpanKind::Unspecified,
+                } as i32
+            }
+            "error" if tag.v_bool || tag.v_str == "true" => {
+                status_code.get_or_insert(status::StatusCode::Error);
+            }
+            "otel.status_code" => {
+                status_code = match tag.v_str.as_str() {
+                    "OK" => Some(status::StatusCode::Ok),
+                    "ERROR" => Some(status::StatusCode::Error),
+                    _ => status_code,
+                }
+            }
+            "otel.status_description" => status_message = tag.v_str,
+            _ => span.attributes.push(convert_tag(tag)),
+        }
+    }
+    if let Some(code) = status_code {
+        span.status = Some(crate::proto::opentelemetry::proto::trace::v1::Status {
+            message: status_message,
+            code: code as i32,
+        });
+    }
+
+    span.events = jaeger_span
+        .logs
+        .into_iter()
+        .map(|log| {
+            let mut event = span::Event {
+                time_unix_nano: log
+                    .timestamp
+                    .map(|t| to_nanos(t.seconds, t.nanos))
+                    .unwrap_or_default(),
+                ..Default::default()
+            };
+            for field in log.fields {
+                if field.key == "event" && field.v_type == ValueType::String as i32 {
+                    event.name = field.v_str;
+                } else {
+                    event.attributes.push(convert_tag(field));
+                }
+            }
+            event
+        })
+        .collect();
+    span
+}
+
+fn convert_tag(tag: api_v2::KeyValue) -> KeyValue {
+    let value = match tag.v_type() {
+        ValueType::String => any_value::Value::StringValue(tag.v_str),
+        ValueType::Bool => any_value::Value::BoolValue(tag.v_bool),
+        ValueType::Int64 => any_value::Value::IntValue(tag.v_int64),
+        ValueType::Float64 => any_value::Value::DoubleValue(tag.v_float64),
+        ValueType::Binary => any_value::Value::BytesValue(tag.v_binary),
+    };
+    KeyValue {
+        key: tag.key,
+        value: Some(AnyValue { value: Some(value) }),
+    }
+}
+
+/// Negative times clamp to zero and out-of-range ones saturate.
+fn to_nanos(seconds: i64, nanos: i32) -> u64 {
+    (seconds.max(0) as u64)
+        .saturating_mul(1_000_000_000)
+        .saturating_add(nanos.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string_value(kv: &KeyValue) -> &str {
+        match kv.value.as_ref().and_then(|v| v.value.as_ref()) {
+            Some(any_value::Value::StringValue(s)) => s,
+            _ => panic!("not a string: {:?}", kv),
+        }
+    }
+
+    #[test]
+    fn test_out_of_range_times_saturate() {
+        assert_eq!(to_nanos(i64::MAX, i32::MAX), u64::MAX);
+        assert_eq!(to_nanos(-1, -1), 0);
+        assert_eq!(to_nanos(2, 5), 2_000_000_005);
+    }
+
+    #[test]
+    fn test_thrift_batch_conversion() {
+        let batch = thrift::decode_batch(&thrift::tests::sample_batch()).unwrap();
+        let resource_spans = to_resource_spans(batch);
+        assert_eq!(resource_spans.len(), 1);
+
+        let attributes = &resource_spans[0].resource.as_ref().unwrap().attributes;
+        assert_eq!(attributes[0].key, "service.name");
+        assert_eq!(string_value(&attributes[0]), "thrift-svc");
+        assert_eq!(attributes[1].key, "hostname");
+
+        let span = &resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(span.name, "GET /users");
+        assert_eq!(hex::encode(&span.parent_span_id), "3132333435363738");
+        assert!(span.links.is_empty());
+        assert_eq!(span.kind, span::SpanKind::Server as i32);
+        assert_eq!(span.start_time_unix_nano, 1_700_000_000_000_000_000);
+        assert_eq!(span.end_time_unix_nano, 1_700_000_000_001_500_000);
+        assert_eq!(
+            span.status.as_ref().unwrap().code,
+            status::StatusCode::Error as i32
+        );
+        assert!(span.attributes.is_empty());
+
+        assert_eq!(span.events.len(), 1);
+        assert_eq!(span.events[0].name, "cache miss");
+        assert_eq!(span.events[0].time_unix_nano, 1_700_000_000_000_500_000);
+        assert_eq!(span.events[0].attributes[0].key, "retries");
+    }
+
+    #[test]
+    fn test_references_and_span_process() {
+        let trace_id = vec![1; 16];
+        let batch = api_v2::Batch {
+            process: Some(api_v2::Process {
+                service_name: "batch-svc".into(),
+                tags: vec![],
+            }),
+            spans: vec![
+                api_v2::Span {
+                    trace_id: trace_id.clone(),
+                    span_id: vec![2; 8],
+                    references: vec![
+                        api_v2::SpanRef {
+                            trace_id: trace_id.clone(),
+                            span_id: vec![3; 8],
+                            ref_type: SpanRefType::FollowsFrom as i32,
+                        },
+                        api_v2::SpanRef {
+                            trace_id: trace_id.clone(),
+                            span_id: vec![4; 8],
+                            ref_type: SpanRefType::ChildOf as i32,
+                        },
+                    ],
+                    ..Default::default()
+                },
+                api_v2::Span {
+                    trace_id,
+                    span_id: vec![5; 8],
+                    process: Some(api_v2::Process {
+                        service_name: "own-svc".into(),
+                        tags: vec![],
+                    }),
+                    ..Default::default()
+                },
+            ],
+        };
+        let resource_spans = to_resource_spans(batch);
+        assert_eq!(resource_spans.len(), 2);
+
+        let span = &resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(span.parent_span_id, vec![4; 8]);
+        assert_eq!(span.links.len(), 1);
+        assert_eq!(span.links[0].span_id, vec![3; 8]);
+
+        let own = resource_spans[1].resource.as_ref().unwrap();
+        assert_eq!(string_value(&own.attributes[0]), "own-svc");
+    }
+}
@@ -0,0 +1,422 @@
+//! Decoder for Jaeger `Batch` structs in the Thrift binary protocol, as posted
+//! by Jaeger clients to the collector's `/api/traces` endpoint.
+//!
+//! Batches are decoded straight into the api_v2 protobuf model so both
+//! transports share one conversion to OTLP.
+
+use prost_types::{Duration, Timestamp};
+
+use crate::proto::jaeger::api_v2::{
+    Batch, KeyValue, Log, Process, Span, SpanRef, SpanRefType, ValueType,
+};
+
+const T_STOP: u8 = 0;
+const T_BOOL: u8 = 2;
+const T_BYTE: u8 = 3;
+const T_DOUBLE: u8 = 4;
+const T_I16: u8 = 6;
+const T_I32: u8 = 8;
+const T_I64: u8 = 10;
+const T_STRING: u8 = 11;
+const T_STRUCT: u8 = 12;
+const T_MAP: u8 = 13;
+const T_SET: u8 = 14;
+const T_LIST: u8 = 15;
+
+/// Deepest struct/container nesting accepted when skipping unknown fields.
+const MAX_DEPTH: usize = 32;
+
+/// Decode a Thrift binary-encoded `jaeger.thrift` `Batch`.
+pub fn decode_batch(buf: &[u8]) -> anyhow::Result<Batch> {
+    let mut r = Reader { buf };
+    let mut batch = Batch::default();
+    while let Some((ty, id)) = r.field_header()? {
+        match (id, ty) {
+            (1, T_STRUCT) => batch.process = Some(read_process(&mut r)?),
+            (2, T_LIST) => batch.spans = r.list(T_STRUCT, read_span)?,
+            _ => r.skip(ty, 0)?,
+        }
+    }
+    Ok(batch)
+}
+
+fn read_process(r: &mut Reader) -> anyhow::Result<Process> {
+    let mut process = Process::default();
+    while let Some((ty, id)) = r.field_header()? {
+        match (id, ty) {
+            (1, T_STRING) => process.service_name = r.string()?,
+            (2, T_LIST) => process.tags = r.list(T_STRUCT, read_tag)?,
+            _ => r.skip(ty, 0)?,
+        }
+    }
+    Ok(process)
+}
+
+fn read_span(r: &mut Reader) -> anyhow::Result<Span> {
+    let (mut trace_id_low, mut trace_id_high, mut span_id, mut parent_span_id) = (0, 0, 0, 0);
+    let (mut start_time, mut duration) = (0, 0);
+    let mut span = Span::default();
+    while let Some((ty, id)) = r.field_header()? {
+        match (id, ty) {
+            (1, T_I64) => trace_id_low = r.i64()?,
+            (2, T_I64) => trace_id_high = r.i64()?,
+            (3, T_I64) => span_id = r.i64()?,
+            (4, T_I64) => parent_span_id = r.i64()?,
+            (5, T_STRING) => span.operation_name = r.string()?,
+            (6, T_LIST) => span.references = r.list(T_STRUCT, read_span_ref)?,
+            (7, T_I32) => span.flags = r.i32()? as u32,
+            (8, T_I64) => start_time = r.i64()?,
+            (9, T_I64) => duration = r.i64()?,
+            (10, T_LIST) => span.tags = r.list(T_STRUCT, read_tag)?,
+            (11, T_LIST) => span.logs = r.list(T_STRUCT, read_log)?,
+            _ => r.skip(ty, 0)?,
+        }
+    }
+    span.trace_id = trace_id(trace_id_high, trace_id_low);
+    span.span_id = span_id.to_be_bytes().to_vec();
+    // Thrift carries the parent separately; api_v2 only has references.
+    if parent_span_id != 0 {
+        let parent = parent_span_id.to_be_bytes().to_vec();
+        if !span.references.iter().any(|r| r.span_id == parent) {
+            span.references.insert(
+                0,
+                SpanRef {
+                    trace_id: span.trace_id.clone(),
+                    span_id: parent,
+                    ref_type: SpanRefType::ChildOf as i32,
+                },
+            );
+        }
+    }
+    span.start_time = Some(micros_to_timestamp(start_time));
+    span.duration = Some(Duration {
+        seconds: duration / 1_000_000,
+        nanos: (duration % 1_000_000 * 1000) as i32,
+    });
+    Ok(span)
+}
+
+fn read_span_ref(r: &mut Reader) -> anyhow::Result<SpanRef> {
+    let (mut ref_type, mut low, mut high, mut span_id) = (0, 0, 0, 0);
+    while let Some((ty, id)) = r.field_header()? {
+        match (id, ty) {
+            (1, T_I32) => ref_type = r.i32()?,
+            (2, T_I64) => low = r.i64()?,
+            (3, T_I64) => high = r.i64()?,
+            (4, T_I64) => span_id = r.i64()?,
+            _ => r.skip(ty, 0)?,
+        }
+    }
+    Ok(SpanRef {
+        trace_id: trace_id(high, low),
+        span_id: span_id.to_be_bytes().to_vec(),
+        // Thrift and api_v2 agree: CHILD_OF = 0, FOLLOWS_FROM = 1.
+        ref_type,
+    })
+}
+
+fn read_log(r: &mut Reader) -> anyhow::Result<Log> {
+    let mut log = Log::default();
+    while let Some((ty, id)) = r.field_header()? {
+        match (id, ty) {
+            (1, T_I64) => log.timestamp = Some(micros_to_timestamp(r.i64()?)),
+            (2, T_LIST) => log.fields = r.list(T_STRUCT, read_tag)?,
+            _ => r.skip(ty, 0)?,
+        }
+    }
+    Ok(log)
+}
+
+fn read_tag(r: &mut Reader) -> anyhow::Result<KeyValue> {
+    let mut kv = KeyValue::default();
+    while let Some((ty, id)) = r.field_header()? {
+        match (id, ty) {
+            (1, T_STRING) => kv.key = r.string()?,
+            // Thrift TagType: STRING, DOUBLE, BOOL, LONG, BINARY.
+            (2, T_I32) => {
+                kv.v_type = match r.i32()? {
+                    1 => ValueType::Float64,
+                    2 => ValueType::Bool,
+                    3 => ValueType::Int64,
+                    4 => ValueType::Binary,
+                    _ => ValueType::String,
+                } as i32
+            }
+            (3, T_STRING) => kv.v_str = r.string()?,
+            (4, T_DOUBLE) => kv.v_float64 = r.f64()?,
+            (5, T_BOOL) => kv.v_bool = r.u8()? != 0,
+            (6, T_I64) => kv.v_int64 = r.i64()?,
+            (7, T_STRING) => kv.v_binary = r.binary()?.to_vec(),
+            _ => r.skip(ty, 0)?,
+        }
+    }
+    Ok(kv)
+}
+
+fn trace_id(high: i64, low: i64) -> Vec<u8> {
+    [high.to_be_bytes(), low.to_be_bytes()].concat()
+}
+
+fn micros_to_timestamp(micros: i64) -> Timestamp {
+    Timestamp {
+        seconds: micros.div_euclid(1_000_000),
+        nanos: (micros.rem_euclid(1_000_000) * 1000) as i32,
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        if self.buf.len() < n {
+            anyhow::bail!("unexpected end of Thrift data");
+        }
+        let (head, tail) = self.buf.split_at(n);
+        self.buf = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn i16(&mut self) -> anyhow::Result<i16> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    fn i64(&mut self) -> anyhow::Result<i64> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn f64(&mut self) -> anyhow::Result<f64> {
+        Ok(f64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+
+    fn len(&mut self) -> anyhow::Result<usize> {
+        let len = self.i32()?;
+        usize::try_from(len).map_err(|_| anyhow::anyhow!("negative Thrift length {}", len))
+    }
+
+    fn binary(&mut self) -> anyhow::Result<&'a [u8]> {
+        let len = self.len()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        Ok(String::from_utf8_lossy(self.binary()?).into_owned())
+    }
+
+    /// Next field's (type, id), or `None` at the end of the struct.
+    fn field_header(&mut self) -> anyhow::Result<Option<(u8, i16)>> {
+        match self.u8()? {
+            T_STOP => Ok(None),
+            ty => Ok(Some((ty, self.i16()?))),
+        }
+    }
+
+    fn list<T>(
+        &mut self,
+        elem_type: u8,
+        read: fn(&mut Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<Vec<T>> {
+        let ty = self.u8()?;
+        let size = self.len()?;
+        if ty != elem_type {
+            anyhow::bail!("expected Thrift list of type {}, got {}", elem_type, ty);
+        }
+        // Every element takes at least one byte; reject sizes that cannot fit.
+        if size > self.buf.len() {
+            anyhow::bail!("Thrift list size {} exceeds remaining data", size);
+        }
+        (0..size).map(|_| read(self)).collect()
+    }
+
+    fn skip(&mut self, ty: u8, depth: usize) -> anyhow::Result<()> {
+        if depth > MAX_DEPTH {
+            anyhow::bail!("Thrift data nested too deeply");
+        }
+        match ty {
+            T_BOOL | T_BYTE => {
+                self.take(1)?;
+            }
+            T_I16 => {
+                self.take(2)?;
+            }
+            T_I32 => {
+                self.take(4)?;
+            }
+            T_DOUBLE | T_I64 => {
+                self.take(8)?;
+            }
+            T_STRING => {
+                self.binary()?;
+            }
+            T_STRUCT => {
+                while let Some((ty, _)) = self.field_header()? {
+                    self.skip(ty, depth + 1)?;
+                }
+            }
+            T_MAP => {
+                let (key, value) = (self.u8()?, self.u8()?);
+                for _ in 0..self.len()? {
+                    self.skip(key, depth + 1)?;
+                    self.skip(value, depth + 1)?;
+                }
+            }
+            T_SET | T_LIST => {
+                let elem = self.u8()?;
+                for _ in 0..self.len()? {
+                    self.skip(elem, depth + 1)?;
+                }
+            }
+            _ => anyhow::bail!("unknown Thrift type {}", ty),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Minimal Thrift binary encoder for building test batches.
+    #[derive(Default)]
+    pub(crate) struct Writer {
+        pub(crate) buf: Vec<u8>,
+    }
+
+    impl Writer {
+        pub(crate) fn field(&mut self, ty: u8, id: i16) -> &mut Self {
+            self.buf.push(ty);
+            self.buf.extend(id.to_be_bytes());
+            self
+        }
+        pub(crate) fn stop(&mut self) -> &mut Self {
+            self.buf.push(T_STOP);
+            self
+        }
+        pub(crate) fn i32(&mut self, id: i16, v: i32) -> &mut Self {
+            self.field(T_I32, id);
+            self.buf.extend(v.to_be_bytes());
+            self
+        }
+        pub(crate) fn i64(&mut self, id: i16, v: i64) -> &mut Self {
+            self.field(T_I64, id);
+            self.buf.extend(v.to_be_bytes());
+            self
+        }
+        pub(crate) fn string(&mut self, id: i16, v: &str) -> &mut Self {
+            self.field(T_STRING, id);
+            self.buf.extend((v.len() as i32).to_be_bytes());
+            self.buf.extend(v.as_bytes());
+            self
+        }
+        pub(crate) fn bool(&mut self, id: i16, v: bool) -> &mut Self {
+            self.field(T_BOOL, id);
+            self.buf.push(v as u8);
+            self
+        }
+        pub(crate) fn struct_list(&mut self, id: i16, len: i32) -> &mut Self {
+            self.field(T_LIST, id);
+            self.buf.push(T_STRUCT);
+            self.buf.extend(len.to_be_bytes());
+            self
+        }
+        pub(crate) fn struct_begin(&mut self, id: i16) -> &mut Self {
+            self.field(T_STRUCT, id)
+        }
+    }
+
+    /// A batch for service "thrift-svc" with one tagged child span that has a log.
+    pub(crate) fn sample_batch() -> Vec<u8> {
+        let mut w = Writer::default();
+        w.struct_begin(1)
+            .string(1, "thrift-svc")
+            .struct_list(2, 1)
+            .string(1, "hostname")
+            .i32(2, 0)
+            .string(3, "host-1")
+            .stop()
+            .stop();
+        w.struct_list(2, 1)
+            .i64(1, 0x0102030405060708)
+            .i64(2, 0x1112131415161718)
+            .i64(3, 0x2122232425262728)
+            .i64(4, 0x3132333435363738)
+            .string(5, "GET /users")
+            .i32(7, 1)
+            .i64(8, 1_700_000_000_000_000)
+            .i64(9, 1500)
+            .struct_list(10, 2)
+            .string(1, "span.kind")
+            .i32(2, 0)
+            .string(3, "server")
+            .stop()
+            .string(1, "error")
+            .i32(2, 2)
+            .bool(5, true)
+            .stop()
+            .struct_list(11, 1)
+            .i64(1, 1_700_000_000_000_500)
+            .struct_list(2, 2)
+            .string(1, "event")
+            .i32(2, 0)
+            .string(3, "cache miss")
+            .stop()
+            .string(1, "retries")
+            .i32(2, 3)
+            .i64(6, 3)
+            .stop()
+            .stop()
+            // Unknown field, skipped.
+            .string(99, "ignored")
+            .stop();
+        // Unknown batch field (seqNo).
+        w.i64(3, 7).stop();
+        w.buf
+    }
+
+    #[test]
+    fn test_decode_batch() {
+        let batch = decode_batch(&sample_batch()).unwrap();
+        let process = batch.process.unwrap();
+        assert_eq!(process.service_name, "thrift-svc");
+        assert_eq!(process.tags[0].key, "hostname");
+        assert_eq!(process.tags[0].v_str, "host-1");
+
+        let span = &batch.spans[0];
+        assert_eq!(
+            hex::encode(&span.trace_id),
+            "11121314151617180102030405060708"
+        );
+        assert_eq!(hex::encode(&span.span_id), "2122232425262728");
+        assert_eq!(span.operation_name, "GET /users");
+        assert_eq!(span.references.len(), 1);
+        assert_eq!(hex::encode(&span.references[0].span_id), "3132333435363738");
+        assert_eq!(span.start_time.unwrap().seconds, 1_700_000_000);
+        assert_eq!(span.duration.unwrap().nanos, 1_500_000);
+        assert_eq!(span.tags[1].v_type, ValueType::Bool as i32);
+        assert!(span.tags[1].v_bool);
+        assert_eq!(span.logs[0].fields[1].v_int64, 3);
+        assert_eq!(span.logs[0].timestamp.unwrap().nanos, 500_000);
+    }
+
+    #[test]
+    fn test_truncated_input() {
+        let data = sample_batch();
+        assert!(decode_batch(&data[..data.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn test_oversized_list() {
+        let mut w = Writer::default();
+        w.struct_list(2, i32::MAX);
+        assert!(decode_batch(&w.buf).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+//! Listen addresses for the OTLP and query servers: TCP socket addresses or
+//! Unix domain sockets written as `unix:///path/to.sock` (or `unix:path`).
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use tokio::net::{TcpListener, UnixListener};
+
+/// Where a server listens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ListenAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl FromStr for ListenAddr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s
+            .strip_prefix("unix://")
+            .or_else(|| s.strip_prefix("unix:"))
+        {
+            if path.is_empty() {
+                anyhow::bail!("invalid listen address '{}': missing socket path", s);
+            }
+            return Ok(ListenAddr::Unix(PathBuf::from(path)));
+        }
+        s.parse()
+            .map(ListenAddr::Tcp)
+            .map_err(|e| anyhow::anyhow!("invalid listen address '{}': {}", s, e))
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "{}", addr),
+            ListenAddr::Unix(path) => write!(f, "unix://{}", path.display()),
+        }
+    }
+}
+
+impl From<SocketAddr> for ListenAddr {
+    fn from(addr: SocketAddr) -> Self {
+        ListenAddr::Tcp(addr)
+    }
+}
+
+impl ListenAddr {
+    /// Bind the address; `name` is used in the error message.
+    ///
+    /// A socket file left behind by a server that is no longer running is
+    /// replaced, but one that still accepts connections is reported as in use.
+    pub async fn bind(&self, name: &str) -> anyhow::Result<Listener> {
+        let bind_error =
+            |e: std::io::Error| anyhow::anyhow!("Failed to bind {} address {}: {}", name, self, e);
+        match self {
+            ListenAddr::Tcp(addr) => Ok(Listener::Tcp(
+                TcpListener::bind(addr).await.map_err(bind_error)?,
+            )),
+            ListenAddr::Unix(path) => {
+                if path.exists() {
+                    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                        return Err(bind_error(std::io::ErrorKind::AddrInUse.into()));
+                    }
+                    std::fs::remove_file(path).map_err(bind_error)?;
+                }
+                Ok(Listener::Unix(
+                    UnixListener::bind(path).map_err(bind_error)?,
+                ))
+            }
+        }
+    }
+
+    /// Remove the socket file of a Unix address once its server has stopped.
+    pub fn cleanup(&self) {
+        if let ListenAddr::Unix(path) = self {
+            if let Err(e) = std::fs::remove_file(path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!(error = %e, path = %path.display(), "failed to remove socket file");
+                }
+            }
+        }
+    }
+}
+
+/// A bound TCP or Unix domain socket listener.
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+impl From<TcpListener> for Listener {
+    fn from(listener: TcpListener) -> Self {
+        Listener::Tcp(listener)
+    }
+}
+
+impl From<UnixListener> for Listener {
+    fn from(listener: UnixListener) -> Self {
+        Listener::Unix(listener)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_listen_addr() {
+        assert_eq!(
+            "127.0.0.1:4317".parse::<ListenAddr>().unwrap(),
+            ListenAddr::Tcp("127.0.0.1:4317".parse().unwrap())
+        );
+        assert_eq!(
+            "unix:///tmp/otel.sock".parse::<ListenAddr>().unwrap(),
+            ListenAddr::Unix(PathBuf::from("/tmp/otel.sock"))
+        );
+        assert_eq!(
+            "unix:otel.sock".parse::<ListenAddr>().unwrap(),
+            ListenAddr::Unix(PathBuf::from("otel.sock"))
+        );
+        assert!("unix://".parse::<ListenAddr>().is_err());
+        assert!("localhost".parse::<ListenAddr>().is_err());
+        assert_eq!(
+            "unix:///tmp/otel.sock"
+                .parse::<ListenAddr>()
+                .unwrap()
+                .to_string(),
+            "unix:///tmp/otel.sock"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bind_replaces_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = ListenAddr::Unix(dir.path().join("query.sock"));
+
+        let listener = addr.bind("query").await.unwrap();
+        let err = addr.bind("query").await.unwrap_err();
+        assert!(err.to_string().contains("Failed to bind query address"));
+
+        // The file outlives the listener, as after a crash.
+        drop(listener);
+        assert!(dir.path().join("query.sock").exists());
+        addr.bind("query").await.unwrap();
+
+        addr.cleanup();
+        assert!(!dir.path().join("query.sock").exists());
+    }
+}
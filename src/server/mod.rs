@@ -1,84 +1,323 @@
+pub mod auth;
+pub mod fluent;
+pub mod jaeger;
+pub mod listen;
 pub mod otlp_grpc;
 pub mod otlp_http;
+pub mod prometheus;
 pub mod query_grpc;
+pub mod statsd;
+pub mod syslog;
+pub mod tls;
+pub mod validate;
+pub mod zipkin;
+
+pub use listen::{ListenAddr, Listener};
 
 use std::sync::Arc;
 
+use crate::proto::jaeger::api_v2::collector_service_server::CollectorServiceServer;
 use crate::proto::opentelemetry::proto::collector::{
     logs::v1::logs_service_server::LogsServiceServer,
     metrics::v1::metrics_service_server::MetricsServiceServer,
     trace::v1::trace_service_server::TraceServiceServer,
 };
 use crate::proto::otelcli::query::v1::query_service_server::QueryServiceServer;
+use crate::store::ingest::Ingester;
 use crate::store::SharedStore;
 use datafusion::prelude::SessionContext;
 use tokio_util::sync::CancellationToken;
+use tonic::codec::CompressionEncoding;
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::RoutesBuilder;
+use tower::ServiceExt;
+
+/// Options shared by the listeners started by `otel-cli server`.
+#[derive(Clone, Debug)]
+pub struct ServerOptions {
+    /// Maximum size of an OTLP/HTTP request body after decompression, in bytes.
+    pub max_body_size: usize,
+    /// Compress OTLP/HTTP responses when the client sends `Accept-Encoding`.
+    pub http_compression: bool,
+    /// Encoding used for query API responses (`SqlQuery` and follow streams).
+    /// Only applied when the client advertises support for it.
+    pub query_compression: Option<CompressionEncoding>,
+    /// Serve all listeners over TLS (and require client certificates if a CA is set).
+    pub tls: Option<tls::TlsConfig>,
+    /// Allow browsers on these origins to POST to the OTLP/HTTP endpoints.
+    pub cors: Option<otlp_http::CorsConfig>,
+    /// Credentials required by the OTLP gRPC and HTTP listeners.
+    pub ingest_auth: auth::Authenticator,
+    /// Credentials required by the query API; read-only callers cannot clear or shut down.
+    pub query_auth: auth::Authenticator,
+}
 
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            max_body_size: 64 * 1024 * 1024,
+            http_compression: false,
+            query_compression: None,
+            tls: None,
+            cors: None,
+            ingest_auth: auth::Authenticator::default(),
+            query_auth: auth::Authenticator::default(),
+        }
+    }
+}
+
+/// OTLP exporters may compress requests with gzip or zstd; responses are
+/// compressed with whichever of those the exporter accepts. The Jaeger
+/// collector service is served alongside the OTLP services.
 pub async fn run_grpc_server(
-    listener: tokio::net::TcpListener,
+    listener: impl Into<Listener>,
     store: SharedStore,
+    options: ServerOptions,
     shutdown: CancellationToken,
 ) -> anyhow::Result<()> {
-    let otlp_service = Arc::new(otlp_grpc::OtlpGrpcService::new(store));
-
-    let incoming = tonic::transport::server::TcpIncoming::from(listener);
-    tonic::transport::Server::builder()
-        .add_service(TraceServiceServer::from_arc(otlp_service.clone()))
-        .add_service(LogsServiceServer::from_arc(otlp_service.clone()))
-        .add_service(MetricsServiceServer::from_arc(otlp_service))
-        .serve_with_incoming_shutdown(incoming, shutdown.cancelled())
-        .await?;
-
-    Ok(())
+    let ingester = Ingester::new(store);
+    let mut routes = RoutesBuilder::default();
+    add_ingest_services(&mut routes, ingester.clone(), &options);
+    let router = grpc_builder(&options)?.add_routes(routes.routes());
+    let result = serve_grpc(router, listener.into(), shutdown).await;
+    ingester.flush().await;
+    result
 }
 
 pub async fn run_query_server(
-    listener: tokio::net::TcpListener,
+    listener: impl Into<Listener>,
     store: SharedStore,
     ctx: SessionContext,
+    options: ServerOptions,
     shutdown: CancellationToken,
 ) -> anyhow::Result<()> {
-    let query_service = query_grpc::QueryGrpcService::new(store, ctx, shutdown.clone());
+    let mut routes = RoutesBuilder::default();
+    add_query_service(&mut routes, store, ctx, &options, shutdown.clone());
+    let router = grpc_builder(&options)?.add_routes(routes.routes());
+    serve_grpc(router, listener.into(), shutdown).await
+}
 
-    let incoming = tonic::transport::server::TcpIncoming::from(listener);
-    tonic::transport::Server::builder()
-        .add_service(QueryServiceServer::new(query_service))
-        .serve_with_incoming_shutdown(incoming, shutdown.cancelled())
-        .await?;
+/// Serve the OTLP gRPC services, the OTLP/HTTP routes and the query API on a
+/// single listener. Requests with an `application/grpc` content type are
+/// routed to the gRPC services by path; everything else goes to the
+/// OTLP/HTTP router. gRPC clients need HTTP/2, which is negotiated over TLS
+/// via ALPN or used with prior knowledge on plaintext connections.
+pub async fn run_mux_server(
+    listener: impl Into<Listener>,
+    store: SharedStore,
+    ctx: SessionContext,
+    options: ServerOptions,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let ingester = Ingester::new(store.clone());
+    let mut routes = RoutesBuilder::default();
+    add_ingest_services(&mut routes, ingester.clone(), &options);
+    add_query_service(&mut routes, store, ctx, &options, shutdown.clone());
+    let grpc = routes.routes().prepare().into_axum_router();
+    let http = otlp_http::router(ingester.clone(), &options);
+    let dispatch = tower::service_fn(move |request: axum::extract::Request| {
+        let is_grpc = request
+            .headers()
+            .get(axum::http::header::CONTENT_TYPE)
+            .is_some_and(|v| v.as_bytes().starts_with(b"application/grpc"));
+        let router = if is_grpc { grpc.clone() } else { http.clone() };
+        router.oneshot(request)
+    });
+    let result = serve_app(
+        axum::Router::new().fallback_service(dispatch),
+        listener.into(),
+        &options,
+        shutdown,
+    )
+    .await;
+    ingester.flush().await;
+    result
+}
 
-    Ok(())
+fn add_ingest_services(routes: &mut RoutesBuilder, ingester: Ingester, options: &ServerOptions) {
+    let jaeger_server =
+        CollectorServiceServer::new(jaeger::JaegerGrpcService::new(ingester.clone()))
+            .accept_compressed(CompressionEncoding::Gzip)
+            .accept_compressed(CompressionEncoding::Zstd);
+    let otlp_service = Arc::new(otlp_grpc::OtlpGrpcService::new(ingester));
+    let trace_server = TraceServiceServer::from_arc(otlp_service.clone())
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd)
+        .send_compressed(CompressionEncoding::Gzip)
+        .send_compressed(CompressionEncoding::Zstd);
+    let logs_server = LogsServiceServer::from_arc(otlp_service.clone())
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd)
+        .send_compressed(CompressionEncoding::Gzip)
+        .send_compressed(CompressionEncoding::Zstd);
+    let metrics_server = MetricsServiceServer::from_arc(otlp_service)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd)
+        .send_compressed(CompressionEncoding::Gzip)
+        .send_compressed(CompressionEncoding::Zstd);
+
+    routes
+        .add_service(InterceptedService::new(
+            trace_server,
+            options.ingest_auth.clone(),
+        ))
+        .add_service(InterceptedService::new(
+            logs_server,
+            options.ingest_auth.clone(),
+        ))
+        .add_service(InterceptedService::new(
+            metrics_server,
+            options.ingest_auth.clone(),
+        ))
+        .add_service(InterceptedService::new(
+            jaeger_server,
+            options.ingest_auth.clone(),
+        ));
+}
+
+fn add_query_service(
+    routes: &mut RoutesBuilder,
+    store: SharedStore,
+    ctx: SessionContext,
+    options: &ServerOptions,
+    shutdown: CancellationToken,
+) {
+    let query_service = query_grpc::QueryGrpcService::new(store, ctx, shutdown);
+    let mut query_server = QueryServiceServer::new(query_service)
+        .accept_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Zstd);
+    if let Some(encoding) = options.query_compression {
+        query_server = query_server.send_compressed(encoding);
+    }
+    routes.add_service(InterceptedService::new(
+        query_server,
+        options.query_auth.clone(),
+    ));
 }
 
 pub async fn run_http_server(
-    listener: tokio::net::TcpListener,
+    listener: impl Into<Listener>,
     store: SharedStore,
+    options: ServerOptions,
     shutdown: CancellationToken,
 ) -> anyhow::Result<()> {
-    let app = otlp_http::router(store);
+    let ingester = Ingester::new(store);
+    let app = otlp_http::router(ingester.clone(), &options);
+    let result = serve_app(app, listener.into(), &options, shutdown).await;
+    ingester.flush().await;
+    result
+}
+
+async fn serve_app(
+    app: axum::Router,
+    listener: Listener,
+    options: &ServerOptions,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let acceptor = options.tls.as_ref().map(|tls| tls.acceptor()).transpose()?;
+    match (listener, acceptor) {
+        (Listener::Tcp(listener), Some(acceptor)) => {
+            serve_http(tls::TlsListener::new(listener, acceptor), app, shutdown).await
+        }
+        (Listener::Tcp(listener), None) => serve_http(listener, app, shutdown).await,
+        (Listener::Unix(listener), Some(acceptor)) => {
+            serve_http(tls::TlsListener::new(listener, acceptor), app, shutdown).await
+        }
+        (Listener::Unix(listener), None) => serve_http(listener, app, shutdown).await,
+    }
+}
+
+async fn serve_grpc(
+    router: tonic::transport::server::Router,
+    listener: Listener,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    match listener {
+        Listener::Tcp(listener) => {
+            let incoming = tonic::transport::server::TcpIncoming::from(listener);
+            router
+                .serve_with_incoming_shutdown(incoming, shutdown.cancelled())
+                .await?
+        }
+        Listener::Unix(listener) => {
+            let incoming = tokio_stream::wrappers::UnixListenerStream::new(listener);
+            router
+                .serve_with_incoming_shutdown(incoming, shutdown.cancelled())
+                .await?
+        }
+    }
+    Ok(())
+}
+
+async fn serve_http<L>(
+    listener: L,
+    app: axum::Router,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()>
+where
+    L: axum::serve::Listener,
+    L::Addr: std::fmt::Debug,
+{
     axum::serve(listener, app)
         .with_graceful_shutdown(async move { shutdown.cancelled().await })
         .await?;
     Ok(())
 }
 
-/// Bind TCP listeners for all ports upfront, returning an error if any port is in use.
+fn grpc_builder(options: &ServerOptions) -> anyhow::Result<tonic::transport::Server> {
+    let builder = tonic::transport::Server::builder();
+    Ok(match &options.tls {
+        Some(tls) => builder.tls_config(tls.server_tls_config())?,
+        None => builder,
+    })
+}
+
+/// Bind listeners for all ports upfront, returning an error if any port is in use.
+/// Addresses may be TCP socket addresses or `unix://` socket paths.
 pub async fn bind_listeners(
-    grpc_addr: std::net::SocketAddr,
-    http_addr: std::net::SocketAddr,
-    query_addr: std::net::SocketAddr,
-) -> anyhow::Result<(
-    tokio::net::TcpListener,
-    tokio::net::TcpListener,
-    tokio::net::TcpListener,
-)> {
-    let grpc_listener = tokio::net::TcpListener::bind(grpc_addr)
-        .await
-        .map_err(|e| anyhow::anyhow!("Failed to bind gRPC address {}: {}", grpc_addr, e))?;
-    let http_listener = tokio::net::TcpListener::bind(http_addr)
+    grpc_addr: ListenAddr,
+    http_addr: ListenAddr,
+    query_addr: ListenAddr,
+) -> anyhow::Result<(Listener, Listener, Listener)> {
+    let grpc_listener = grpc_addr.bind("gRPC").await?;
+    let http_listener = http_addr.bind("HTTP").await?;
+    let query_listener = query_addr.bind("query").await?;
+    Ok((grpc_listener, http_listener, query_listener))
+}
+
+/// Bind a UDP socket for an optional listener; `name` is used in the error message.
+pub async fn bind_udp(
+    name: &str,
+    addr: std::net::SocketAddr,
+) -> anyhow::Result<tokio::net::UdpSocket> {
+    tokio::net::UdpSocket::bind(addr)
         .await
-        .map_err(|e| anyhow::anyhow!("Failed to bind HTTP address {}: {}", http_addr, e))?;
-    let query_listener = tokio::net::TcpListener::bind(query_addr)
+        .map_err(|e| anyhow::anyhow!("Failed to bind {} address {}: {}", name, addr, e))
+}
+
+/// Bind a TCP listener for an optional listener; `name` is used in the error message.
+pub async fn bind_tcp(
+    name: &str,
+    addr: std::net::SocketAddr,
+) -> anyhow::Result<tokio::net::TcpListener> {
+    tokio::net::TcpListener::bind(addr)
         .await
-        .map_err(|e| anyhow::anyhow!("Failed to bind query address {}: {}", query_addr, e))?;
-    Ok((grpc_listener, http_listener, query_listener))
+        .map_err(|e| anyhow::anyhow!("Failed to bind {} address {}: {}", name, addr, e))
+}
+
+/// Accept the next TCP connection. Accept errors, such as running out of file
+/// descriptors, are logged and retried after a pause instead of ending the
+/// listener, as hyper and tonic do.
+pub(crate) async fn accept_tcp(
+    listener: &tokio::net::TcpListener,
+) -> (tokio::net::TcpStream, std::net::SocketAddr) {
+    loop {
+        match listener.accept().await {
+            Ok(accepted) => return accepted,
+            Err(e) => {
+                tracing::warn!(error = %e, addr = ?listener.local_addr().ok(), "failed to accept connection");
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        }
+    }
 }
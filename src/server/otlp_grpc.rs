@@ -13,15 +13,16 @@ use crate::proto::opentelemetry::proto::collector::{
         trace_service_server::TraceService, ExportTraceServiceRequest, ExportTraceServiceResponse,
     },
 };
-use crate::store::SharedStore;
+use crate::server::validate;
+use crate::store::ingest::Ingester;
 
 pub struct OtlpGrpcService {
-    store: SharedStore,
+    ingester: Ingester,
 }
 
 impl OtlpGrpcService {
-    pub fn new(store: SharedStore) -> Self {
-        Self { store }
+    pub fn new(ingester: Ingester) -> Self {
+        Self { ingester }
     }
 }
 
@@ -32,14 +33,14 @@ impl TraceService for OtlpGrpcService {
         &self,
         request: Request<ExportTraceServiceRequest>,
     ) -> Result<Response<ExportTraceServiceResponse>, Status> {
-        let msg = request.into_inner();
+        let mut msg = request.into_inner();
+        let rejected = validate::traces(&mut msg.resource_spans);
         let count = msg.resource_spans.len();
         tracing::Span::current().record("resource_spans.count", count);
         tracing::debug!(count, "received trace export via gRPC");
-        let mut store = self.store.write().await;
-        store.insert_traces(msg.resource_spans);
+        self.ingester.insert_traces(msg.resource_spans).await;
         Ok(Response::new(ExportTraceServiceResponse {
-            partial_success: None,
+            partial_success: rejected.trace_partial_success(),
         }))
     }
 }
@@ -51,14 +52,14 @@ impl LogsService for OtlpGrpcService {
         &self,
         request: Request<ExportLogsServiceRequest>,
     ) -> Result<Response<ExportLogsServiceResponse>, Status> {
-        let msg = request.into_inner();
+        let mut msg = request.into_inner();
+        let rejected = validate::logs(&mut msg.resource_logs);
         let count = msg.resource_logs.len();
         tracing::Span::current().record("resource_logs.count", count);
         tracing::debug!(count, "received log export via gRPC");
-        let mut store = self.store.write().await;
-        store.insert_logs(msg.resource_logs);
+        self.ingester.insert_logs(msg.resource_logs).await;
         Ok(Response::new(ExportLogsServiceResponse {
-            partial_success: None,
+            partial_success: rejected.logs_partial_success(),
         }))
     }
 }
@@ -70,14 +71,14 @@ impl MetricsService for OtlpGrpcService {
         &self,
         request: Request<ExportMetricsServiceRequest>,
     ) -> Result<Response<ExportMetricsServiceResponse>, Status> {
-        let msg = request.into_inner();
+        let mut msg = request.into_inner();
+        let rejected = validate::metrics(&mut msg.resource_metrics);
         let count = msg.resource_metrics.len();
         tracing::Span::current().record("resource_metrics.count", count);
         tracing::debug!(count, "received metric export via gRPC");
-        let mut store = self.store.write().await;
-        store.insert_metrics(msg.resource_metrics);
+        self.ingester.insert_metrics(msg.resource_metrics).await;
         Ok(Response::new(ExportMetricsServiceResponse {
-            partial_success: None,
+            partial_success: rejected.metrics_partial_success(),
         }))
     }
 }
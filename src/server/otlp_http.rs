@@ -1,14 +1,20 @@
 use axum::{
     body::Bytes,
-    extract::State,
-    http::{header, HeaderMap, StatusCode},
-    response::IntoResponse,
+    extract::{rejection::BytesRejection, DefaultBodyLimit, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
     routing::post,
     Router,
 };
+use std::time::Duration;
+
 use base64::Engine;
 use prost::Message;
 use serde::de::DeserializeOwned;
+use tower_http::compression::CompressionLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::decompression::RequestDecompressionLayer;
 use tracing::instrument;
 
 use crate::proto::opentelemetry::proto::collector::{
@@ -16,101 +22,210 @@ use crate::proto::opentelemetry::proto::collector::{
     metrics::v1::{ExportMetricsServiceRequest, ExportMetricsServiceResponse},
     trace::v1::{ExportTraceServiceRequest, ExportTraceServiceResponse},
 };
-use crate::store::SharedStore;
+use crate::server::{auth, jaeger, prometheus, validate, zipkin, ServerOptions};
+use crate::store::ingest::Ingester;
+
+/// Browser access (CORS) to the OTLP/HTTP endpoints, for web SDKs exporting
+/// straight to otel-cli.
+#[derive(Clone, Debug)]
+pub struct CorsConfig {
+    origins: AllowOrigin,
+    headers: Vec<HeaderName>,
+}
+
+impl CorsConfig {
+    /// `origins` are exact origins such as `https://app.example.com`, or `*`
+    /// to allow any origin. `headers` are the request headers the browser may send.
+    pub fn new(origins: &[String], headers: &[String]) -> anyhow::Result<Self> {
+        let origins = if origins.iter().any(|o| o == "*") {
+            AllowOrigin::any()
+        } else {
+            AllowOrigin::list(
+                origins
+                    .iter()
+                    .map(|o| {
+                        HeaderValue::try_from(o.as_str())
+                            .map_err(|e| anyhow::anyhow!("invalid CORS origin '{}': {}", o, e))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?,
+            )
+        };
+        let headers = headers
+            .iter()
+            .map(|h| {
+                HeaderName::try_from(h.as_str())
+                    .map_err(|e| anyhow::anyhow!("invalid CORS header '{}': {}", h, e))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Self { origins, headers })
+    }
+
+    fn layer(&self) -> CorsLayer {
+        CorsLayer::new()
+            .allow_origin(self.origins.clone())
+            .allow_methods([Method::POST])
+            .allow_headers(self.headers.clone())
+            .max_age(Duration::from_secs(3600))
+    }
+}
 
-pub fn router(store: SharedStore) -> Router {
-    Router::new()
+/// Build the OTLP/HTTP router.
+///
+/// Request bodies are transparently decompressed according to `Content-Encoding`
+/// (gzip, deflate, zstd) and the decompressed size is capped at `max_body_size`.
+/// Prometheus remote_write bodies are snappy-compressed and decoded by their handler.
+/// Requests without valid ingest credentials are rejected with 401 before decoding.
+/// With CORS configured, preflight `OPTIONS` requests are answered before auth runs.
+pub fn router(ingester: Ingester, options: &ServerOptions) -> Router {
+    let router = Router::new()
         .route("/v1/traces", post(handle_traces))
         .route("/v1/logs", post(handle_logs))
         .route("/v1/metrics", post(handle_metrics))
-        .with_state(store)
+        .route("/api/v2/spans", post(zipkin::handle_spans))
+        .route("/api/traces", post(jaeger::handle_thrift))
+        .layer(RequestDecompressionLayer::new())
+        .merge(prometheus::router(options.max_body_size))
+        .with_state(ingester)
+        .layer(DefaultBodyLimit::max(options.max_body_size))
+        .layer(middleware::from_fn_with_state(
+            options.ingest_auth.clone(),
+            auth::require_http,
+        ));
+    let router = if options.http_compression {
+        router.layer(CompressionLayer::new())
+    } else {
+        router
+    };
+    match &options.cors {
+        Some(cors) => router.layer(cors.layer()),
+        None => router,
+    }
 }
 
 #[instrument(name = "otlp.http.export_traces", skip_all, fields(http.route = "/v1/traces"))]
 async fn handle_traces(
-    State(store): State<SharedStore>,
+    State(ingester): State<Ingester>,
     headers: HeaderMap,
-    body: Bytes,
-) -> Result<impl IntoResponse, StatusCode> {
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Response, ExportError> {
     let is_json = is_json_content_type(&headers);
-    let request: ExportTraceServiceRequest = if is_json {
-        decode_json(&body)?
-    } else {
-        ExportTraceServiceRequest::decode(body).map_err(|e| {
-            tracing::warn!(error = %e, "failed to decode protobuf trace request");
-            StatusCode::BAD_REQUEST
-        })?
-    };
+    let mut request: ExportTraceServiceRequest = decode_request(body, is_json)?;
+    let rejected = validate::traces(&mut request.resource_spans);
     tracing::debug!(
         count = request.resource_spans.len(),
         is_json,
         "received trace export via HTTP"
     );
-    let mut s = store.write().await;
-    s.insert_traces(request.resource_spans);
+    ingester.insert_traces(request.resource_spans).await;
     let response = ExportTraceServiceResponse {
-        partial_success: None,
+        partial_success: rejected.trace_partial_success(),
     };
     encode_response(&response, is_json)
 }
 
 #[instrument(name = "otlp.http.export_logs", skip_all, fields(http.route = "/v1/logs"))]
 async fn handle_logs(
-    State(store): State<SharedStore>,
+    State(ingester): State<Ingester>,
     headers: HeaderMap,
-    body: Bytes,
-) -> Result<impl IntoResponse, StatusCode> {
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Response, ExportError> {
     let is_json = is_json_content_type(&headers);
-    let request: ExportLogsServiceRequest = if is_json {
-        decode_json(&body)?
-    } else {
-        ExportLogsServiceRequest::decode(body).map_err(|e| {
-            tracing::warn!(error = %e, "failed to decode protobuf log request");
-            StatusCode::BAD_REQUEST
-        })?
-    };
+    let mut request: ExportLogsServiceRequest = decode_request(body, is_json)?;
+    let rejected = validate::logs(&mut request.resource_logs);
     tracing::debug!(
         count = request.resource_logs.len(),
         is_json,
         "received log export via HTTP"
     );
-    let mut s = store.write().await;
-    s.insert_logs(request.resource_logs);
+    ingester.insert_logs(request.resource_logs).await;
     let response = ExportLogsServiceResponse {
-        partial_success: None,
+        partial_success: rejected.logs_partial_success(),
     };
     encode_response(&response, is_json)
 }
 
 #[instrument(name = "otlp.http.export_metrics", skip_all, fields(http.route = "/v1/metrics"))]
 async fn handle_metrics(
-    State(store): State<SharedStore>,
+    State(ingester): State<Ingester>,
     headers: HeaderMap,
-    body: Bytes,
-) -> Result<impl IntoResponse, StatusCode> {
+    body: Result<Bytes, BytesRejection>,
+) -> Result<Response, ExportError> {
     let is_json = is_json_content_type(&headers);
-    let request: ExportMetricsServiceRequest = if is_json {
-        decode_json(&body)?
-    } else {
-        ExportMetricsServiceRequest::decode(body).map_err(|e| {
-            tracing::warn!(error = %e, "failed to decode protobuf metric request");
-            StatusCode::BAD_REQUEST
-        })?
-    };
+    let mut request: ExportMetricsServiceRequest = decode_request(body, is_json)?;
+    let rejected = validate::metrics(&mut request.resource_metrics);
     tracing::debug!(
         count = request.resource_metrics.len(),
         is_json,
         "received metric export via HTTP"
     );
-    let mut s = store.write().await;
-    s.insert_metrics(request.resource_metrics);
+    ingester.insert_metrics(request.resource_metrics).await;
     let response = ExportMetricsServiceResponse {
-        partial_success: None,
+        partial_success: rejected.metrics_partial_success(),
     };
     encode_response(&response, is_json)
 }
 
-fn is_json_content_type(headers: &HeaderMap) -> bool {
+/// `google.rpc.Status`, the body OTLP/HTTP requires for 4xx/5xx responses.
+/// `details` is never populated.
+#[derive(Clone, PartialEq, Message, serde::Serialize)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+}
+
+/// A failed OTLP/HTTP request, answered with an [`RpcStatus`] body encoded the
+/// same way as the request (JSON or protobuf).
+pub(crate) struct ExportError {
+    status: StatusCode,
+    message: String,
+    is_json: bool,
+}
+
+impl ExportError {
+    pub(crate) fn new(status: StatusCode, message: impl Into<String>, is_json: bool) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            is_json,
+        }
+    }
+}
+
+impl IntoResponse for ExportError {
+    fn into_response(self) -> Response {
+        let code = match self.status {
+            StatusCode::BAD_REQUEST => tonic::Code::InvalidArgument,
+            StatusCode::UNAUTHORIZED => tonic::Code::Unauthenticated,
+            StatusCode::PAYLOAD_TOO_LARGE => tonic::Code::ResourceExhausted,
+            s if s.is_server_error() => tonic::Code::Internal,
+            _ => tonic::Code::Unknown,
+        };
+        let status = RpcStatus {
+            code: code as i32,
+            message: self.message,
+        };
+        if self.is_json {
+            let body = serde_json::to_vec(&status).unwrap_or_default();
+            (
+                self.status,
+                [(header::CONTENT_TYPE, "application/json")],
+                body,
+            )
+                .into_response()
+        } else {
+            (
+                self.status,
+                [(header::CONTENT_TYPE, "application/x-protobuf")],
+                status.encode_to_vec(),
+            )
+                .into_response()
+        }
+    }
+}
+
+pub(crate) fn is_json_content_type(headers: &HeaderMap) -> bool {
     headers
         .get(header::CONTENT_TYPE)
         .and_then(|v| v.to_str().ok())
@@ -118,40 +233,80 @@ fn is_json_content_type(headers: &HeaderMap) -> bool {
         .unwrap_or(false)
 }
 
-/// Decode a JSON body, converting OTLP hex-encoded trace_id/span_id fields to base64.
-fn decode_json<T: DeserializeOwned>(body: &[u8]) -> Result<T, StatusCode> {
-    let mut value: serde_json::Value = serde_json::from_slice(body).map_err(|e| {
-        tracing::warn!(error = %e, "failed to parse JSON body");
-        StatusCode::BAD_REQUEST
+fn decode_request<T: DeserializeOwned + Message + Default>(
+    body: Result<Bytes, BytesRejection>,
+    is_json: bool,
+) -> Result<T, ExportError> {
+    let body = body.map_err(|e| {
+        tracing::warn!(error = %e, "failed to read request body");
+        ExportError::new(e.status(), e.body_text(), is_json)
     })?;
+    if is_json {
+        decode_json(&body).map_err(|e| {
+            tracing::warn!(error = %e, "failed to decode JSON request");
+            ExportError::new(
+                StatusCode::BAD_REQUEST,
+                format!("invalid OTLP JSON: {}", e),
+                is_json,
+            )
+        })
+    } else {
+        T::decode(body).map_err(|e| {
+            tracing::warn!(error = %e, "failed to decode protobuf request");
+            ExportError::new(
+                StatusCode::BAD_REQUEST,
+                format!("invalid OTLP protobuf: {}", e),
+                is_json,
+            )
+        })
+    }
+}
+
+/// Decode a JSON body, converting OTLP hex-encoded trace_id/span_id fields to base64.
+///
+/// Also used by `otel-cli import`, so files are read exactly as the
+/// OTLP/HTTP endpoints would read them.
+pub fn decode_json<T: DeserializeOwned>(body: &[u8]) -> Result<T, serde_json::Error> {
+    let mut value: serde_json::Value = serde_json::from_slice(body)?;
     convert_hex_ids_to_base64(&mut value);
-    serde_json::from_value(value).map_err(|e| {
-        tracing::warn!(error = %e, "failed to deserialize JSON into OTLP message");
-        StatusCode::BAD_REQUEST
-    })
+    serde_json::from_value(value)
+}
+
+/// Encode a message as OTLP JSON, converting trace_id/span_id fields to hex.
+/// The counterpart of [`decode_json`], used by `otel-cli export`.
+pub fn encode_json<T: serde::Serialize>(
+    message: &T,
+) -> Result<serde_json::Value, serde_json::Error> {
+    let mut value = serde_json::to_value(message)?;
+    convert_base64_ids_to_hex(&mut value);
+    Ok(value)
 }
 
 fn encode_response<T: serde::Serialize + Message>(
     response: &T,
     is_json: bool,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Response, ExportError> {
     if is_json {
-        let body = serde_json::to_vec(response).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        Ok(([(header::CONTENT_TYPE, "application/json")], body))
+        let body = serde_json::to_vec(response).map_err(|e| {
+            ExportError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string(), is_json)
+        })?;
+        Ok(([(header::CONTENT_TYPE, "application/json")], body).into_response())
     } else {
         Ok((
             [(header::CONTENT_TYPE, "application/x-protobuf")],
             response.encode_to_vec(),
-        ))
+        )
+            .into_response())
     }
 }
 
+/// Fields that OTLP JSON encodes as hex rather than base64.
+const HEX_ID_FIELDS: &[&str] = &["traceId", "spanId", "parentSpanId"];
+
 /// OTLP JSON uses hex encoding for traceId, spanId, and parentSpanId,
 /// but pbjson expects base64 for bytes fields. This function recursively
 /// converts these specific fields from hex to base64.
 fn convert_hex_ids_to_base64(value: &mut serde_json::Value) {
-    const HEX_ID_FIELDS: &[&str] = &["traceId", "spanId", "parentSpanId"];
-
     match value {
         serde_json::Value::Object(map) => {
             for (key, val) in map.iter_mut() {
@@ -174,3 +329,28 @@ fn convert_hex_ids_to_base64(value: &mut serde_json::Value) {
         _ => {}
     }
 }
+
+/// The inverse of [`convert_hex_ids_to_base64`], for JSON produced by pbjson.
+fn convert_base64_ids_to_hex(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, val) in map.iter_mut() {
+                if HEX_ID_FIELDS.contains(&key.as_str()) {
+                    if let serde_json::Value::String(s) = val {
+                        if let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(&s) {
+                            *s = hex::encode(bytes);
+                        }
+                    }
+                } else {
+                    convert_base64_ids_to_hex(val);
+                }
+            }
+        }
+        serde_json::Value::Array(arr) => {
+            for item in arr {
+                convert_base64_ids_to_hex(item);
+            }
+        }
+        _ => {}
+    }
+}
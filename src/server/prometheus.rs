@@ -0,0 +1,372 @@
+//! Prometheus remote_write receiver (`POST /api/v1/write`).
+//!
+//! Time series are converted to OTLP metrics: `job` and `instance` become the
+//! `service.name` and `service.instance.id` resource attributes, the remaining
+//! labels become data point attributes. Counters (per the request metadata, or
+//! by the `_total` suffix when no metadata is sent) become monotonic cumulative
+//! sums; everything else becomes a gauge.
+
+use std::collections::HashMap;
+
+use axum::{body::Bytes, extract::State, http::StatusCode, routing::post, Router};
+use prost::Message;
+use tracing::instrument;
+
+use crate::proto::opentelemetry::proto::{
+    common::v1::{any_value, AnyValue, KeyValue},
+    metrics::v1::{
+        metric, number_data_point, AggregationTemporality, Gauge, Metric, NumberDataPoint,
+        ResourceMetrics, ScopeMetrics, Sum,
+    },
+    resource::v1::Resource,
+};
+use crate::proto::prometheus::{metric_metadata::MetricType, MetricMetadata, WriteRequest};
+use crate::server::validate;
+use crate::store::ingest::Ingester;
+
+/// Prometheus marks series that disappeared with this NaN; it carries no value.
+const STALE_NAN: u64 = 0x7ff0_0000_0000_0002;
+
+/// Suffixes of the cumulative series Prometheus derives from one metric family.
+const CUMULATIVE_SUFFIXES: [&str; 4] = ["_total", "_bucket", "_count", "_sum"];
+
+/// Routes for the remote_write receiver. Bodies are snappy block-compressed
+/// regardless of `Content-Encoding`, so this router must not sit behind the
+/// generic request decompression layer; `max_body_size` caps the
+/// decompressed size instead.
+pub(crate) fn router(max_body_size: usize) -> Router<Ingester> {
+    Router::new().route(
+        "/api/v1/write",
+        post(move |State(ingester): State<Ingester>, body: Bytes| {
+            handle_write(ingester, body, max_body_size)
+        }),
+    )
+}
+
+#[instrument(name = "prometheus.http.write", skip_all, fields(http.route = "/api/v1/write"))]
+async fn handle_write(
+    ingester: Ingester,
+    body: Bytes,
+    max_body_size: usize,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let request = decode_write_request(&body, max_body_size).map_err(|e| {
+        tracing::warn!(error = %e, "failed to decode remote_write request");
+        (StatusCode::BAD_REQUEST, e.to_string())
+    })?;
+    tracing::debug!(
+        count = request.timeseries.len(),
+        "received Prometheus remote_write request"
+    );
+    let mut resource_metrics = to_resource_metrics(request);
+    validate::metrics(&mut resource_metrics);
+    ingester.insert_metrics(resource_metrics).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn decode_write_request(body: &[u8], max_body_size: usize) -> anyhow::Result<WriteRequest> {
+    let len = snap::raw::decompress_len(body)
+        .map_err(|e| anyhow::anyhow!("invalid snappy data: {}", e))?;
+    if len > max_body_size {
+        anyhow::bail!(
+            "decompressed body of {} bytes exceeds the limit of {} bytes",
+            len,
+            max_body_size
+        );
+    }
+    let data = snap::raw::Decoder::new()
+        .decompress_vec(body)
+        .map_err(|e| anyhow::anyhow!("invalid snappy data: {}", e))?;
+    WriteRequest::decode(data.as_slice())
+        .map_err(|e| anyhow::anyhow!("invalid remote_write protobuf: {}", e))
+}
+
+/// Convert a remote_write request to OTLP, with one `ResourceMetrics` per
+/// `(job, instance)` pair and one `Metric` per metric name within it.
+pub fn to_resource_metrics(request: WriteRequest) -> Vec<ResourceMetrics> {
+    let metadata: HashMap<&str, &MetricMetadata> = request
+        .metadata
+        .iter()
+        .map(|m| (m.metric_family_name.as_str(), m))
+        .collect();
+
+    let mut groups: Vec<((String, String), Vec<Metric>)> = Vec::new();
+    for series in request.timeseries {
+        let mut name = String::new();
+        let mut job = String::new();
+        let mut instance = String::new();
+        let mut attributes = Vec::new();
+        for label in series.labels {
+            match label.name.as_str() {
+                "__name__" => name = label.value,
+                "job" => job = label.value,
+                "instance" => instance = label.value,
+                _ => attributes.push(string_attribute(label.name, label.value)),
+            }
+        }
+
+        let data_points: Vec<NumberDataPoint> = series
+            .samples
+            .iter()
+            .filter(|s| s.value.to_bits() != STALE_NAN)
+            .map(|s| NumberDataPoint {
+                attributes: attributes.clone(),
+                time_unix_nano: (s.timestamp.max(0) as u64).saturating_mul(1_000_000),
+                value: Some(number_data_point::Value::AsDouble(s.value)),
+                ..Default::default()
+            })
+            .collect();
+        if data_points.is_empty() {
+            continue;
+        }
+
+        let key = (job, instance);
+        let metrics = match groups.iter().position(|(k, _)| *k == key) {
+            Some(i) => &mut groups[i].1,
+            None => {
+                groups.push((key, Vec::new()));
+                &mut groups.last_mut().unwrap().1
+            }
+        };
+        match metrics.iter_mut().find(|m| m.name == name) {
+            Some(metric) => match &mut metric.data {
+                Some(metric::Data::Sum(sum)) => sum.data_points.extend(data_points),
+                Some(metric::Data::Gauge(gauge)) => gauge.data_points.extend(data_points),
+                _ => {}
+            },
+            None => metrics.push(new_metric(name, data_points, &metadata)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|((job, instance), metrics)| {
+            let mut attributes = Vec::new();
+            if !job.is_empty() {
+                attributes.push(string_attribute("service.name".into(), job));
+            }
+            if !instance.is_empty() {
+                attributes.push(string_attribute("service.instance.id".into(), instance));
+            }
+            ResourceMetrics {
+                resource: Some(Resource {
+                    attributes,
+                    dropped_attributes_count: 0,
+                    entity_refs: vec![],
+                }),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: None,
+                    metrics,
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }
+        })
+        .collect()
+}
+
+fn new_metric(
+    name: String,
+    data_points: Vec<NumberDataPoint>,
+    metadata: &HashMap<&str, &MetricMetadata>,
+) -> Metric {
+    // Series of a histogram or summary are named after the family plus a suffix.
+    let family = metadata.get(name.as_str()).copied().or_else(|| {
+        CUMULATIVE_SUFFIXES
+            .iter()
+            .filter_map(|suffix| name.strip_suffix(suffix))
+            .find_map(|base| metadata.get(base).copied())
+    });
+    let cumulative = match family {
+        Some(family) => match family.r#type() {
+            MetricType::Counter => true,
+            MetricType::Histogram | MetricType::Summary => CUMULATIVE_SUFFIXES[1..]
+                .iter()
+                .any(|suffix| name.ends_with(suffix)),
+            _ => false,
+        },
+        None => name.ends_with("_total"),
+    };
+
+    let data = if cumulative {
+        metric::Data::Sum(Sum {
+            data_points,
+            aggregation_temporality: AggregationTemporality::Cumulative as i32,
+            is_monotonic: true,
+        })
+    } else {
+        metric::Data::Gauge(Gauge { data_points })
+    };
+    Metric {
+        description: family.map(|f| f.help.clone()).unwrap_or_default(),
+        unit: family.map(|f| f.unit.clone()).unwrap_or_default(),
+        name,
+        data: Some(data),
+        ..Default::default()
+    }
+}
+
+fn string_attribute(key: String, value: String) -> KeyValue {
+    KeyValue {
+        key,
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::prometheus::{Label, Sample, TimeSeries};
+
+    fn series(labels: &[(&str, &str)], samples: &[(f64, i64)]) -> TimeSeries {
+        TimeSeries {
+            labels: labels
+                .iter()
+                .map(|(name, value)| Label {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            samples: samples
+                .iter()
+                .map(|&(value, timestamp)| Sample { value, timestamp })
+                .collect(),
+        }
+    }
+
+    fn string_value(kv: &KeyValue) -> &str {
+        match kv.value.as_ref().and_then(|v| v.value.as_ref()) {
+            Some(any_value::Value::StringValue(s)) => s,
+            _ => panic!("not a string: {:?}", kv),
+        }
+    }
+
+    #[test]
+    fn test_series_grouped_by_job_and_instance() {
+        let request = WriteRequest {
+            timeseries: vec![
+                series(
+                    &[("__name__", "up"), ("instance", "a:9100"), ("job", "node")],
+                    &[(1.0, 1000)],
+                ),
+                series(
+                    &[
+                        ("__name__", "http_requests_total"),
+                        ("code", "200"),
+                        ("instance", "a:9100"),
+                        ("job", "node"),
+                    ],
+                    &[(5.0, 1000), (7.0, 2000)],
+                ),
+                series(
+                    &[("__name__", "up"), ("instance", "b:9100"), ("job", "node")],
+                    &[(0.0, 1000)],
+                ),
+            ],
+            metadata: vec![],
+        };
+        let resource_metrics = to_resource_metrics(request);
+        assert_eq!(resource_metrics.len(), 2);
+
+        let resource = resource_metrics[0].resource.as_ref().unwrap();
+        assert_eq!(resource.attributes[0].key, "service.name");
+        assert_eq!(string_value(&resource.attributes[0]), "node");
+        assert_eq!(string_value(&resource.attributes[1]), "a:9100");
+
+        let metrics = &resource_metrics[0].scope_metrics[0].metrics;
+        assert_eq!(metrics.len(), 2);
+        assert!(matches!(metrics[0].data, Some(metric::Data::Gauge(_))));
+        let Some(metric::Data::Sum(sum)) = &metrics[1].data else {
+            panic!("expected a sum: {:?}", metrics[1]);
+        };
+        assert!(sum.is_monotonic);
+        assert_eq!(sum.data_points.len(), 2);
+        assert_eq!(sum.data_points[1].time_unix_nano, 2_000_000_000);
+        assert_eq!(sum.data_points[0].attributes[0].key, "code");
+    }
+
+    #[test]
+    fn test_out_of_range_timestamp_saturates() {
+        let request = WriteRequest {
+            timeseries: vec![series(
+                &[("__name__", "up"), ("job", "node")],
+                &[(1.0, i64::MAX), (1.0, -5)],
+            )],
+            metadata: vec![],
+        };
+        let resource_metrics = to_resource_metrics(request);
+        let Some(metric::Data::Gauge(gauge)) =
+            &resource_metrics[0].scope_metrics[0].metrics[0].data
+        else {
+            panic!("expected a gauge");
+        };
+        assert_eq!(gauge.data_points[0].time_unix_nano, u64::MAX);
+        assert_eq!(gauge.data_points[1].time_unix_nano, 0);
+    }
+
+    #[test]
+    fn test_metadata_and_stale_markers() {
+        let request = WriteRequest {
+            timeseries: vec![
+                series(
+                    &[("__name__", "jobs_processed"), ("job", "worker")],
+                    &[(3.0, 1000)],
+                ),
+                series(
+                    &[("__name__", "latency_seconds_bucket"), ("le", "0.1")],
+                    &[(4.0, 1000)],
+                ),
+                series(
+                    &[("__name__", "queue_depth"), ("job", "worker")],
+                    &[(f64::from_bits(STALE_NAN), 1000)],
+                ),
+            ],
+            metadata: vec![
+                MetricMetadata {
+                    r#type: MetricType::Counter as i32,
+                    metric_family_name: "jobs_processed".into(),
+                    help: "Jobs processed.".into(),
+                    unit: String::new(),
+                },
+                MetricMetadata {
+                    r#type: MetricType::Histogram as i32,
+                    metric_family_name: "latency_seconds".into(),
+                    help: String::new(),
+                    unit: "seconds".into(),
+                },
+            ],
+        };
+        let resource_metrics = to_resource_metrics(request);
+        assert_eq!(resource_metrics.len(), 2);
+
+        let worker = &resource_metrics[0].scope_metrics[0].metrics;
+        assert_eq!(worker.len(), 1);
+        assert_eq!(worker[0].description, "Jobs processed.");
+        assert!(matches!(worker[0].data, Some(metric::Data::Sum(_))));
+
+        assert!(resource_metrics[1]
+            .resource
+            .as_ref()
+            .unwrap()
+            .attributes
+            .is_empty());
+        let bucket = &resource_metrics[1].scope_metrics[0].metrics[0];
+        assert_eq!(bucket.unit, "seconds");
+        assert!(matches!(bucket.data, Some(metric::Data::Sum(_))));
+    }
+
+    #[test]
+    fn test_decode_rejects_oversized_body() {
+        let request = WriteRequest {
+            timeseries: vec![series(&[("__name__", "up")], &[(1.0, 1000)])],
+            metadata: vec![],
+        };
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&request.encode_to_vec())
+            .unwrap();
+        assert_eq!(decode_write_request(&body, 1024).unwrap(), request);
+        assert!(decode_write_request(&body, 4).is_err());
+        assert!(decode_write_request(b"not snappy", 1024).is_err());
+    }
+}
@@ -1,18 +1,27 @@
+use std::borrow::Cow;
+use std::ops::Range;
 use std::pin::Pin;
 
 use datafusion::prelude::SessionContext;
-use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+use tokio_stream::{Stream, StreamExt};
 use tokio_util::sync::CancellationToken;
 use tonic::{Request, Response, Status};
 use tracing::instrument;
 
 use crate::proto::otelcli::query::v1::{
     query_service_server::QueryService as QueryServiceTrait, ClearLogsRequest, ClearMetricsRequest,
-    ClearResponse, ClearTracesRequest, FollowLogsResponse, FollowMetricsResponse, FollowRequest,
-    FollowTracesResponse, ShutdownRequest, ShutdownResponse, SqlQueryRequest, SqlQueryResponse,
-    StatusRequest, StatusResponse,
+    ClearResponse, ClearTracesRequest, DeleteRequest, DeleteResponse, ExportRequest,
+    ExportResponse, FollowLogsResponse, FollowMetricsResponse, FollowRequest, FollowTracesResponse,
+    ShutdownRequest, ShutdownResponse, SqlQueryRequest, SqlQueryResponse, StatusRequest,
+    StatusResponse,
 };
-use crate::store::{SharedStore, Store, StoreEvent};
+use crate::server::auth;
+use crate::store::{self, Shard, SharedStore, StorageBackend, StoreEvent, TimeBounds};
+
+/// Export responses are split to stay well below the default 4 MiB gRPC
+/// message limit.
+const EXPORT_CHUNK_BYTES: usize = 1024 * 1024;
 
 pub struct QueryGrpcService {
     store: SharedStore,
@@ -40,6 +49,8 @@ impl QueryServiceTrait for QueryGrpcService {
         Pin<Box<dyn Stream<Item = Result<FollowLogsResponse, Status>> + Send + 'static>>;
     type FollowMetricsStream =
         Pin<Box<dyn Stream<Item = Result<FollowMetricsResponse, Status>> + Send + 'static>>;
+    type ExportStream =
+        Pin<Box<dyn Stream<Item = Result<ExportResponse, Status>> + Send + 'static>>;
 
     #[instrument(name = "query.sql_query", skip_all, fields(db.statement))]
     async fn sql_query(
@@ -83,34 +94,26 @@ impl QueryServiceTrait for QueryGrpcService {
             Status::invalid_argument(format!("SQL error: {}", e))
         })?;
 
-        let event_rx = store.read().await.subscribe();
+        let event_rx = store.subscribe();
         let event_stream = BroadcastStream::new(event_rx);
 
         let stream = async_stream::try_stream! {
             // Send initial batch
-            if !initial_rows.is_empty() {
+            let mut shown = !initial_rows.is_empty();
+            if shown {
                 yield SqlQueryResponse { rows: initial_rows };
             }
 
-            // Wait for new events and re-execute the full query each time
+            // Re-execute the full query on every event: each one adds or
+            // removes data, and a lagged receiver may have missed either.
             tokio::pin!(event_stream);
-            while let Some(event_result) = event_stream.next().await {
-                let event = match event_result {
-                    Ok(e) => e,
-                    Err(_) => continue,
-                };
-
-                let is_data_event = matches!(
-                    event,
-                    StoreEvent::TracesAdded | StoreEvent::LogsAdded | StoreEvent::MetricsAdded
-                );
-                if !is_data_event {
-                    continue;
-                }
-
+            while event_stream.next().await.is_some() {
                 match crate::query::sql::execute(&ctx, &sql).await {
                     Ok(rows) => {
-                        if !rows.is_empty() {
+                        // An empty result is only sent to replace rows that
+                        // are gone.
+                        if shown || !rows.is_empty() {
+                            shown = !rows.is_empty();
                             yield SqlQueryResponse { rows };
                         }
                     }
@@ -137,13 +140,21 @@ impl QueryServiceTrait for QueryGrpcService {
     ) -> Result<Response<Self::FollowTracesStream>, Status> {
         tracing::debug!("starting follow_traces stream");
         let stream = build_follow_stream(
-            self.store.clone(),
-            |s| s.all_traces().iter().cloned().collect(),
-            crate::store::rs_sort_key,
-            StoreEvent::TracesAdded,
-            Store::query_traces_since,
-            |items| FollowTracesResponse {
+            self.store.traces.clone(),
+            |s| {
+                s.scan_traces(TimeBounds::ALL)
+                    .map(Cow::into_owned)
+                    .collect()
+            },
+            |event| match event {
+                StoreEvent::TracesAdded(seqs) => Some(Change::Added(seqs)),
+                StoreEvent::TracesDeleted | StoreEvent::TracesCleared => Some(Change::Removed),
+                _ => None,
+            },
+            |s, seq| s.traces_after(seq),
+            |items, reset| FollowTracesResponse {
                 resource_spans: items,
+                reset,
             },
         )
         .await;
@@ -157,13 +168,17 @@ impl QueryServiceTrait for QueryGrpcService {
     ) -> Result<Response<Self::FollowLogsStream>, Status> {
         tracing::debug!("starting follow_logs stream");
         let stream = build_follow_stream(
-            self.store.clone(),
-            |s| s.all_logs().iter().cloned().collect(),
-            crate::store::log_sort_key,
-            StoreEvent::LogsAdded,
-            Store::query_logs_since,
-            |items| FollowLogsResponse {
+            self.store.logs.clone(),
+            |s| s.scan_logs(TimeBounds::ALL).map(Cow::into_owned).collect(),
+            |event| match event {
+                StoreEvent::LogsAdded(seqs) => Some(Change::Added(seqs)),
+                StoreEvent::LogsDeleted | StoreEvent::LogsCleared => Some(Change::Removed),
+                _ => None,
+            },
+            |s, seq| s.logs_after(seq),
+            |items, reset| FollowLogsResponse {
                 resource_logs: items,
+                reset,
             },
         )
         .await;
@@ -177,13 +192,21 @@ impl QueryServiceTrait for QueryGrpcService {
     ) -> Result<Response<Self::FollowMetricsStream>, Status> {
         tracing::debug!("starting follow_metrics stream");
         let stream = build_follow_stream(
-            self.store.clone(),
-            |s| s.all_metrics().iter().cloned().collect(),
-            crate::store::metric_sort_key,
-            StoreEvent::MetricsAdded,
-            Store::query_metrics_since,
-            |items| FollowMetricsResponse {
+            self.store.metrics.clone(),
+            |s| {
+                s.scan_metrics(TimeBounds::ALL)
+                    .map(Cow::into_owned)
+                    .collect()
+            },
+            |event| match event {
+                StoreEvent::MetricsAdded(seqs) => Some(Change::Added(seqs)),
+                StoreEvent::MetricsDeleted | StoreEvent::MetricsCleared => Some(Change::Removed),
+                _ => None,
+            },
+            |s, seq| s.metrics_after(seq),
+            |items, reset| FollowMetricsResponse {
                 resource_metrics: items,
+                reset,
             },
         )
         .await;
@@ -193,97 +216,278 @@ impl QueryServiceTrait for QueryGrpcService {
     #[instrument(name = "query.clear_traces", skip_all)]
     async fn clear_traces(
         &self,
-        _request: Request<ClearTracesRequest>,
+        request: Request<ClearTracesRequest>,
     ) -> Result<Response<ClearResponse>, Status> {
+        auth::require_admin(&request)?;
         tracing::debug!("clearing traces");
-        self.store.write().await.clear_traces();
+        self.store.traces.write().await.clear_traces();
         Ok(Response::new(ClearResponse {}))
     }
 
     #[instrument(name = "query.clear_logs", skip_all)]
     async fn clear_logs(
         &self,
-        _request: Request<ClearLogsRequest>,
+        request: Request<ClearLogsRequest>,
     ) -> Result<Response<ClearResponse>, Status> {
+        auth::require_admin(&request)?;
         tracing::debug!("clearing logs");
-        self.store.write().await.clear_logs();
+        self.store.logs.write().await.clear_logs();
         Ok(Response::new(ClearResponse {}))
     }
 
     #[instrument(name = "query.clear_metrics", skip_all)]
     async fn clear_metrics(
         &self,
-        _request: Request<ClearMetricsRequest>,
+        request: Request<ClearMetricsRequest>,
     ) -> Result<Response<ClearResponse>, Status> {
+        auth::require_admin(&request)?;
         tracing::debug!("clearing metrics");
-        self.store.write().await.clear_metrics();
+        self.store.metrics.write().await.clear_metrics();
         Ok(Response::new(ClearResponse {}))
     }
 
+    #[instrument(name = "query.delete", skip_all, fields(db.statement))]
+    async fn delete(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        auth::require_admin(&request)?;
+        let req = request.into_inner();
+        let all = !req.traces && !req.logs && !req.metrics;
+        let conditions = crate::query::delete::Conditions {
+            service: (!req.service.is_empty()).then_some(req.service.as_str()),
+            before: (req.before > 0).then_some(req.before),
+            filter: (!req.filter.is_empty()).then_some(req.filter.as_str()),
+        };
+        if conditions.is_empty() {
+            return Err(Status::invalid_argument(
+                "no delete condition given; use ClearTraces, ClearLogs or ClearMetrics to delete everything",
+            ));
+        }
+        if let Some(filter) = conditions.filter {
+            tracing::Span::current().record("db.statement", filter);
+        }
+        let delete_error = |e: crate::query::delete::DeleteError| {
+            tracing::warn!(error = %e, ?conditions, "delete failed");
+            match e {
+                crate::query::delete::DeleteError::Filter(_) => {
+                    Status::invalid_argument(e.to_string())
+                }
+                crate::query::delete::DeleteError::Changed => Status::aborted(e.to_string()),
+            }
+        };
+
+        let mut response = DeleteResponse::default();
+        if all || req.traces {
+            response.spans = crate::query::delete::spans(&self.store, &conditions)
+                .await
+                .map_err(delete_error)? as u64;
+        }
+        if all || req.logs {
+            response.log_records = crate::query::delete::log_records(&self.store, &conditions)
+                .await
+                .map_err(delete_error)? as u64;
+        }
+        if all || req.metrics {
+            response.metric_points = crate::query::delete::metric_points(&self.store, &conditions)
+                .await
+                .map_err(delete_error)? as u64;
+        }
+        tracing::debug!(
+            spans = response.spans,
+            log_records = response.log_records,
+            metric_points = response.metric_points,
+            "deleted"
+        );
+        Ok(Response::new(response))
+    }
+
     #[instrument(name = "query.status", skip_all)]
     async fn status(
         &self,
         _request: Request<StatusRequest>,
     ) -> Result<Response<StatusResponse>, Status> {
         tracing::debug!("status request");
-        let store = self.store.read().await;
+        // Each signal's store counts the duplicates of its own signal.
+        let (trace_count, trace_bytes, max_memory, trace_dedup) = {
+            let s = self.store.traces.read().await;
+            (
+                s.trace_count(),
+                s.trace_bytes(),
+                s.max_memory(),
+                s.dedup_stats(),
+            )
+        };
+        let (log_count, log_bytes, log_dedup) = {
+            let s = self.store.logs.read().await;
+            (s.log_count(), s.log_bytes(), s.dedup_stats())
+        };
+        let (metric_count, metric_bytes, metric_dedup) = {
+            let s = self.store.metrics.read().await;
+            (s.metric_count(), s.metric_bytes(), s.dedup_stats())
+        };
         Ok(Response::new(StatusResponse {
-            trace_count: store.trace_count() as u64,
-            log_count: store.log_count() as u64,
-            metric_count: store.metric_count() as u64,
+            trace_count: trace_count as u64,
+            log_count: log_count as u64,
+            metric_count: metric_count as u64,
+            trace_bytes: trace_bytes as u64,
+            log_bytes: log_bytes as u64,
+            metric_bytes: metric_bytes as u64,
+            max_memory: match max_memory {
+                usize::MAX => 0,
+                bytes => bytes as u64,
+            },
+            dedup: trace_dedup.is_some(),
+            duplicate_spans: trace_dedup.map_or(0, |d| d.duplicate_spans),
+            duplicate_log_records: log_dedup.map_or(0, |d| d.duplicate_log_records),
+            duplicate_metric_points: metric_dedup.map_or(0, |d| d.duplicate_metric_points),
         }))
     }
 
+    #[instrument(name = "query.export", skip_all, fields(db.statement))]
+    async fn export(
+        &self,
+        request: Request<ExportRequest>,
+    ) -> Result<Response<Self::ExportStream>, Status> {
+        let req = request.into_inner();
+        let all = !req.traces && !req.logs && !req.metrics;
+        let filter = (!req.filter.is_empty()).then_some(req.filter.as_str());
+        if let Some(filter) = filter {
+            tracing::Span::current().record("db.statement", filter);
+        }
+        let sql_error = |e: String| {
+            tracing::warn!(error = %e, filter = ?filter, "export filter error");
+            Status::invalid_argument(format!("SQL error: {}", e))
+        };
+
+        // Snapshot every requested signal up front so filter errors are
+        // reported before streaming starts; messages are encoded lazily.
+        let traces = if all || req.traces {
+            crate::query::export::traces(&self.store, filter)
+                .await
+                .map_err(sql_error)?
+        } else {
+            Vec::new()
+        };
+        let logs = if all || req.logs {
+            crate::query::export::logs(&self.store, filter)
+                .await
+                .map_err(sql_error)?
+        } else {
+            Vec::new()
+        };
+        let metrics = if all || req.metrics {
+            crate::query::export::metrics(&self.store, filter)
+                .await
+                .map_err(sql_error)?
+        } else {
+            Vec::new()
+        };
+        tracing::debug!(
+            traces = traces.len(),
+            logs = logs.len(),
+            metrics = metrics.len(),
+            "exporting store contents"
+        );
+
+        let responses = store::batches(traces, EXPORT_CHUNK_BYTES)
+            .map(|resource_spans| ExportResponse {
+                resource_spans,
+                ..Default::default()
+            })
+            .chain(
+                store::batches(logs, EXPORT_CHUNK_BYTES).map(|resource_logs| ExportResponse {
+                    resource_logs,
+                    ..Default::default()
+                }),
+            )
+            .chain(
+                store::batches(metrics, EXPORT_CHUNK_BYTES).map(|resource_metrics| {
+                    ExportResponse {
+                        resource_metrics,
+                        ..Default::default()
+                    }
+                }),
+            );
+        let stream = tokio_stream::iter(responses.map(Ok));
+        Ok(Response::new(Box::pin(stream)))
+    }
+
     #[instrument(name = "query.shutdown", skip_all)]
     async fn shutdown(
         &self,
-        _request: Request<ShutdownRequest>,
+        request: Request<ShutdownRequest>,
     ) -> Result<Response<ShutdownResponse>, Status> {
+        auth::require_admin(&request)?;
         tracing::info!("shutdown requested via RPC");
         self.shutdown.cancel();
         Ok(Response::new(ShutdownResponse {}))
     }
 }
 
+/// A store event that matters to a follow stream.
+enum Change<'a> {
+    /// Batches with these sequence numbers were stored.
+    Added(&'a Range<u64>),
+    /// Stored data was deleted or cleared.
+    Removed,
+}
+
+/// Stream the current contents of a signal, then everything stored after it
+/// in ingest order. Progress is tracked by sequence number rather than
+/// timestamp, so late data with an old timestamp is still delivered and
+/// nothing is sent twice. When data is deleted or cleared, or the event
+/// subscription lags so a removal may have been missed, the whole signal is
+/// sent again with `reset` set.
 async fn build_follow_stream<T, R>(
-    store: SharedStore,
-    get_initial: fn(&Store) -> Vec<T>,
-    sort_key_fn: fn(&T) -> u64,
-    event_filter: StoreEvent,
-    query_since_fn: fn(&Store, u64) -> Vec<T>,
-    wrap_fn: fn(Vec<T>) -> R,
+    store: Shard,
+    get_initial: fn(&dyn StorageBackend) -> Vec<T>,
+    classify: fn(&StoreEvent) -> Option<Change<'_>>,
+    query_after_fn: fn(&dyn StorageBackend, u64) -> Vec<T>,
+    wrap_fn: fn(Vec<T>, bool) -> R,
 ) -> Pin<Box<dyn Stream<Item = Result<R, Status>> + Send + 'static>>
 where
     T: Send + 'static,
     R: Send + 'static,
 {
-    let initial = {
+    // Take the snapshot and subscribe under one lock so no insert falls in between.
+    let (initial, mut last_seq, event_rx) = {
         let s = store.read().await;
-        get_initial(&s)
+        (get_initial(&*s), s.last_seq(), s.subscribe())
     };
-    let mut last_ts: u64 = initial.iter().map(sort_key_fn).max().unwrap_or(0);
-    let event_rx = store.read().await.subscribe();
     let event_stream = BroadcastStream::new(event_rx);
 
     let stream = async_stream::try_stream! {
         if !initial.is_empty() {
-            yield wrap_fn(initial);
+            yield wrap_fn(initial, false);
         }
 
         tokio::pin!(event_stream);
         while let Some(event_result) = event_stream.next().await {
-            if !matches!(&event_result, Ok(e) if *e == event_filter) {
-                continue;
-            }
-            let s = store.read().await;
-            let items = query_since_fn(&s, last_ts + 1);
-            if items.is_empty() {
-                continue;
-            }
-            if let Some(max_ts) = items.iter().map(sort_key_fn).max() {
-                last_ts = max_ts;
+            let reset = match &event_result {
+                Ok(event) => match classify(event) {
+                    Some(Change::Added(seqs)) if seqs.end > last_seq + 1 => false,
+                    Some(Change::Removed) => true,
+                    _ => continue,
+                },
+                Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                    tracing::debug!(skipped, "follow stream lagged, resending everything");
+                    true
+                }
+            };
+            let items = {
+                let s = store.read().await;
+                let items = if reset {
+                    get_initial(&*s)
+                } else {
+                    query_after_fn(&*s, last_seq)
+                };
+                last_seq = s.last_seq();
+                items
+            };
+            if reset || !items.is_empty() {
+                yield wrap_fn(items, reset);
             }
-            yield wrap_fn(items);
         }
     };
 
@@ -0,0 +1,591 @@
+//! StatsD/DogStatsD receiver.
+//!
+//! Lines of the form `name:value|type[|@rate][|#tag:value,...]` are aggregated
+//! in memory and flushed to the store as OTLP metrics every flush interval:
+//! counters become delta sums, gauges keep their last value, timers,
+//! histograms and distributions become delta histograms and sets report the
+//! number of unique members. A `service` tag selects the resource's
+//! `service.name`; other tags become data point attributes.
+//!
+//! A gauge not updated for [`GAUGE_IDLE_FLUSHES`] flushes is forgotten, so
+//! clients that put ids in metric names do not grow memory without bound. A
+//! `+N`/`-N` update after that starts again from zero.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+use tokio_util::sync::CancellationToken;
+
+use crate::proto::opentelemetry::proto::{
+    common::v1::{any_value, AnyValue, KeyValue},
+    metrics::v1::{
+        metric, number_data_point, AggregationTemporality, Gauge, Histogram, HistogramDataPoint,
+        Metric, NumberDataPoint, ResourceMetrics, ScopeMetrics, Sum,
+    },
+    resource::v1::Resource,
+};
+use crate::store::ingest::Ingester;
+use crate::store::SharedStore;
+
+/// Histogram bucket boundaries, in the unit of the samples (milliseconds for timers).
+const EXPLICIT_BOUNDS: [f64; 15] = [
+    0.0, 5.0, 10.0, 25.0, 50.0, 75.0, 100.0, 250.0, 500.0, 750.0, 1000.0, 2500.0, 5000.0, 7500.0,
+    10000.0,
+];
+
+const MAX_PACKET_SIZE: usize = 65535;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    /// Timers (`ms`), histograms (`h`) and DogStatsD distributions (`d`).
+    Histogram,
+    Set,
+}
+
+/// A single parsed StatsD sample.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    pub name: String,
+    pub kind: MetricKind,
+    /// Raw value; set members are kept as strings.
+    pub value: String,
+    pub sample_rate: f64,
+    /// Sorted tags; tags without a value map to an empty string.
+    pub tags: BTreeMap<String, String>,
+}
+
+/// Parse one StatsD line. DogStatsD packed values (`name:1:2:3|h`) yield one
+/// sample per value; events (`_e{`) and service checks (`_sc|`) are ignored.
+pub fn parse_line(line: &str) -> anyhow::Result<Vec<Sample>> {
+    if line.starts_with("_e{") || line.starts_with("_sc|") {
+        return Ok(vec![]);
+    }
+    let mut sections = line.split('|');
+    let head = sections.next().unwrap_or_default();
+    let (name, values) = head
+        .split_once(':')
+        .ok_or_else(|| anyhow::anyhow!("missing ':' in StatsD line '{}'", line))?;
+    if name.is_empty() {
+        anyhow::bail!("empty metric name in StatsD line '{}'", line);
+    }
+    let kind = match sections.next() {
+        Some("c") => MetricKind::Counter,
+        Some("g") => MetricKind::Gauge,
+        Some("ms" | "h" | "d") => MetricKind::Histogram,
+        Some("s") => MetricKind::Set,
+        Some(other) => anyhow::bail!("unknown StatsD metric type '{}'", other),
+        None => anyhow::bail!("missing metric type in StatsD line '{}'", line),
+    };
+
+    let mut sample_rate = 1.0;
+    let mut tags = BTreeMap::new();
+    for section in sections {
+        if let Some(rate) = section.strip_prefix('@') {
+            sample_rate = rate
+                .parse::<f64>()
+                .ok()
+                .filter(|r| *r > 0.0 && *r <= 1.0)
+                .ok_or_else(|| anyhow::anyhow!("invalid sample rate '{}'", rate))?;
+        } else if let Some(tag_list) = section.strip_prefix('#') {
+            for tag in tag_list.split(',').filter(|t| !t.is_empty()) {
+                let (key, value) = tag.split_once(':').unwrap_or((tag, ""));
+                tags.insert(key.to_string(), value.to_string());
+            }
+        }
+        // Other DogStatsD extensions (container id `c:`, timestamp `T`) are ignored.
+    }
+
+    let values: Vec<&str> = if kind == MetricKind::Set {
+        vec![values]
+    } else {
+        values.split(':').collect()
+    };
+    values
+        .into_iter()
+        .map(|value| {
+            if kind != MetricKind::Set {
+                value
+                    .parse::<f64>()
+                    .map_err(|_| anyhow::anyhow!("invalid value '{}' for '{}'", value, name))?;
+            }
+            Ok(Sample {
+                name: name.to_string(),
+                kind,
+                value: value.to_string(),
+                sample_rate,
+                tags: tags.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Flushes without an update after which a gauge is forgotten.
+pub const GAUGE_IDLE_FLUSHES: u32 = 60;
+
+type SeriesKey = (String, BTreeMap<String, String>);
+
+struct GaugeState {
+    value: f64,
+    /// Flushes since the last update; reported while 0.
+    idle_flushes: u32,
+}
+
+#[derive(Default)]
+struct HistogramState {
+    count: f64,
+    sum: f64,
+    min: f64,
+    max: f64,
+    bucket_counts: Vec<f64>,
+}
+
+/// Accumulates samples between flushes.
+#[derive(Default)]
+pub struct Aggregator {
+    counters: HashMap<SeriesKey, f64>,
+    /// Gauges persist across flushes so `+N`/`-N` updates apply to the last value.
+    gauges: HashMap<SeriesKey, GaugeState>,
+    histograms: HashMap<SeriesKey, HistogramState>,
+    sets: HashMap<SeriesKey, HashSet<String>>,
+    interval_start: u64,
+}
+
+impl Aggregator {
+    pub fn new(now: u64) -> Self {
+        Self {
+            interval_start: now,
+            ..Default::default()
+        }
+    }
+
+    pub fn add(&mut self, sample: Sample) {
+        let key = (sample.name, sample.tags);
+        match sample.kind {
+            MetricKind::Counter => {
+                let value: f64 = sample.value.parse().unwrap_or_default();
+                *self.counters.entry(key).or_default() += value / sample.sample_rate;
+            }
+            MetricKind::Gauge => {
+                let value: f64 = sample.value.parse().unwrap_or_default();
+                let is_delta = sample.value.starts_with(['+', '-']);
+                let gauge = self.gauges.entry(key).or_insert(GaugeState {
+                    value: 0.0,
+                    idle_flushes: 0,
+                });
+                if is_delta {
+                    gauge.value += value;
+                } else {
+                    gauge.value = value;
+                }
+                gauge.idle_flushes = 0;
+            }
+            MetricKind::Histogram => {
+                let value: f64 = sample.value.parse().unwrap_or_default();
+                let weight = 1.0 / sample.sample_rate;
+                let state = self
+                    .histograms
+                    .entry(key)
+                    .or_insert_with(|| HistogramState {
+                        min: f64::INFINITY,
+                        max: f64::NEG_INFINITY,
+                        bucket_counts: vec![0.0; EXPLICIT_BOUNDS.len() + 1],
+                        ..Default::default()
+                    });
+                state.count += weight;
+                state.sum += value * weight;
+                state.min = state.min.min(value);
+                state.max = state.max.max(value);
+                let bucket = EXPLICIT_BOUNDS.partition_point(|bound| *bound < value);
+                state.bucket_counts[bucket] += weight;
+            }
+            MetricKind::Set => {
+                self.sets.entry(key).or_default().insert(sample.value);
+            }
+        }
+    }
+
+    /// Drain everything aggregated since the last flush into OTLP metrics,
+    /// grouped into one `ResourceMetrics` per `service` tag.
+    pub fn flush(&mut self, now: u64) -> Vec<ResourceMetrics> {
+        let start = std::mem::replace(&mut self.interval_start, now);
+        let mut services = Services::new();
+        for ((name, tags), value) in self.counters.drain() {
+            let point = number_point(&tags, start, now, value);
+            push_metric(
+                &mut services,
+                name,
+                &tags,
+                metric::Data::Sum(Sum {
+                    data_points: vec![point],
+                    aggregation_temporality: AggregationTemporality::Delta as i32,
+                    is_monotonic: false,
+                }),
+            );
+        }
+        for ((name, tags), gauge) in &self.gauges {
+            if gauge.idle_flushes > 0 {
+                continue;
+            }
+            let point = number_point(tags, 0, now, gauge.value);
+            push_metric(
+                &mut services,
+                name.clone(),
+                tags,
+                metric::Data::Gauge(Gauge {
+                    data_points: vec![point],
+                }),
+            );
+        }
+        self.gauges.retain(|_, gauge| {
+            gauge.idle_flushes += 1;
+            gauge.idle_flushes <= GAUGE_IDLE_FLUSHES
+        });
+        for ((name, tags), members) in self.sets.drain() {
+            let point = number_point(&tags, 0, now, members.len() as f64);
+            push_metric(
+                &mut services,
+                name,
+                &tags,
+                metric::Data::Gauge(Gauge {
+                    data_points: vec![point],
+                }),
+            );
+        }
+        for ((name, tags), state) in self.histograms.drain() {
+            let point = HistogramDataPoint {
+                attributes: attributes(&tags),
+                start_time_unix_nano: start,
+                time_unix_nano: now,
+                count: state.count.round() as u64,
+                sum: Some(state.sum),
+                bucket_counts: state
+                    .bucket_counts
+                    .iter()
+                    .map(|c| c.round() as u64)
+                    .collect(),
+                explicit_bounds: EXPLICIT_BOUNDS.to_vec(),
+                min: Some(state.min),
+                max: Some(state.max),
+                ..Default::default()
+            };
+            push_metric(
+                &mut services,
+                name,
+                &tags,
+                metric::Data::Histogram(Histogram {
+                    data_points: vec![point],
+                    aggregation_temporality: AggregationTemporality::Delta as i32,
+                }),
+            );
+        }
+
+        services
+            .into_iter()
+            .map(|(service, metrics)| {
+                let mut resource_attributes = Vec::new();
+                if !service.is_empty() {
+                    resource_attributes.push(string_attribute("service.name", &service));
+                }
+                ResourceMetrics {
+                    resource: Some(Resource {
+                        attributes: resource_attributes,
+                        dropped_attributes_count: 0,
+                        entity_refs: vec![],
+                    }),
+                    scope_metrics: vec![ScopeMetrics {
+                        scope: None,
+                        metrics: metrics.into_values().collect(),
+                        schema_url: String::new(),
+                    }],
+                    schema_url: String::new(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Metrics by name, grouped by `service` tag.
+type Services = BTreeMap<String, BTreeMap<String, Metric>>;
+
+/// Add `data`'s points to the metric `name`. A name reused with a different
+/// type keeps the first type seen in the interval.
+fn push_metric(
+    services: &mut Services,
+    name: String,
+    tags: &BTreeMap<String, String>,
+    data: metric::Data,
+) {
+    let service = tags.get("service").cloned().unwrap_or_default();
+    let metric = services
+        .entry(service)
+        .or_default()
+        .entry(name.clone())
+        .or_insert_with(|| Metric {
+            name,
+            ..Default::default()
+        });
+    match (&mut metric.data, data) {
+        (None, data) => metric.data = Some(data),
+        (Some(metric::Data::Sum(a)), metric::Data::Sum(b)) => a.data_points.extend(b.data_points),
+        (Some(metric::Data::Gauge(a)), metric::Data::Gauge(b)) => {
+            a.data_points.extend(b.data_points)
+        }
+        (Some(metric::Data::Histogram(a)), metric::Data::Histogram(b)) => {
+            a.data_points.extend(b.data_points)
+        }
+        (_, _) => tracing::debug!(name = %metric.name, "StatsD metric reported with mixed types"),
+    }
+}
+
+fn number_point(
+    tags: &BTreeMap<String, String>,
+    start: u64,
+    now: u64,
+    value: f64,
+) -> NumberDataPoint {
+    NumberDataPoint {
+        attributes: attributes(tags),
+        start_time_unix_nano: start,
+        time_unix_nano: now,
+        value: Some(number_data_point::Value::AsDouble(value)),
+        ..Default::default()
+    }
+}
+
+fn attributes(tags: &BTreeMap<String, String>) -> Vec<KeyValue> {
+    tags.iter()
+        .filter(|(key, _)| key.as_str() != "service")
+        .map(|(key, value)| string_attribute(key, value))
+        .collect()
+}
+
+fn string_attribute(key: &str, value: &str) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value.to_string())),
+        }),
+    }
+}
+
+fn now_nanos() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+/// Receive StatsD packets on `socket` and store aggregated metrics every
+/// `flush_interval`. Pending data is flushed on shutdown.
+pub async fn run_statsd_server(
+    socket: UdpSocket,
+    store: SharedStore,
+    flush_interval: Duration,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let ingester = Ingester::new(store);
+    let mut aggregator = Aggregator::new(now_nanos());
+    let mut ticker = tokio::time::interval(flush_interval);
+    ticker.tick().await;
+    let mut buf = vec![0u8; MAX_PACKET_SIZE];
+
+    loop {
+        tokio::select! {
+            received = socket.recv_from(&mut buf) => {
+                let (len, peer) = received?;
+                let packet = String::from_utf8_lossy(&buf[..len]);
+                for line in packet.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                    match parse_line(line) {
+                        Ok(samples) => samples.into_iter().for_each(|s| aggregator.add(s)),
+                        Err(e) => tracing::debug!(%peer, error = %e, "dropping StatsD line"),
+                    }
+                }
+            }
+            _ = ticker.tick() => flush(&mut aggregator, &ingester).await,
+            _ = shutdown.cancelled() => {
+                flush(&mut aggregator, &ingester).await;
+                ingester.flush().await;
+                return Ok(());
+            }
+        }
+    }
+}
+
+async fn flush(aggregator: &mut Aggregator, ingester: &Ingester) {
+    let resource_metrics = aggregator.flush(now_nanos());
+    if resource_metrics.is_empty() {
+        return;
+    }
+    tracing::debug!(
+        count = resource_metrics.len(),
+        "flushing aggregated StatsD metrics"
+    );
+    ingester.insert_metrics(resource_metrics).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flush_one(aggregator: &mut Aggregator) -> Vec<Metric> {
+        let mut resource_metrics = aggregator.flush(2_000);
+        assert_eq!(resource_metrics.len(), 1);
+        resource_metrics.remove(0).scope_metrics.remove(0).metrics
+    }
+
+    fn add_lines(aggregator: &mut Aggregator, lines: &[&str]) {
+        for line in lines {
+            for sample in parse_line(line).unwrap() {
+                aggregator.add(sample);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_dogstatsd_line() {
+        let samples = parse_line("page.views:1|c|@0.5|#env:prod,canary").unwrap();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].name, "page.views");
+        assert_eq!(samples[0].kind, MetricKind::Counter);
+        assert_eq!(samples[0].sample_rate, 0.5);
+        assert_eq!(samples[0].tags["env"], "prod");
+        assert_eq!(samples[0].tags["canary"], "");
+
+        let packed = parse_line("latency:10:20:30|h").unwrap();
+        assert_eq!(packed.len(), 3);
+        assert_eq!(packed[2].value, "30");
+
+        let set = parse_line("users:alice:1|s").unwrap();
+        assert_eq!(set.len(), 1);
+        assert_eq!(set[0].value, "alice:1");
+
+        assert!(parse_line("_e{5,4}:title|text").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_invalid_lines() {
+        assert!(parse_line("no-separator").is_err());
+        assert!(parse_line("name:1").is_err());
+        assert!(parse_line("name:abc|c").is_err());
+        assert!(parse_line("name:1|x").is_err());
+        assert!(parse_line(":1|c").is_err());
+        assert!(parse_line("name:1|c|@0").is_err());
+    }
+
+    #[test]
+    fn test_counter_and_gauge_aggregation() {
+        let mut aggregator = Aggregator::new(1_000);
+        add_lines(
+            &mut aggregator,
+            &["hits:1|c", "hits:2|c|@0.5", "temp:20|g", "temp:+5|g"],
+        );
+        let metrics = flush_one(&mut aggregator);
+        assert_eq!(metrics.len(), 2);
+
+        let Some(metric::Data::Sum(sum)) = &metrics[0].data else {
+            panic!("expected a sum: {:?}", metrics[0]);
+        };
+        assert_eq!(
+            sum.data_points[0].value,
+            Some(number_data_point::Value::AsDouble(5.0))
+        );
+        assert_eq!(sum.data_points[0].start_time_unix_nano, 1_000);
+        assert_eq!(sum.data_points[0].time_unix_nano, 2_000);
+
+        let Some(metric::Data::Gauge(gauge)) = &metrics[1].data else {
+            panic!("expected a gauge: {:?}", metrics[1]);
+        };
+        assert_eq!(
+            gauge.data_points[0].value,
+            Some(number_data_point::Value::AsDouble(25.0))
+        );
+
+        // Gauges are only reported again after an update, relative to the last value.
+        assert!(aggregator.flush(3_000).is_empty());
+        add_lines(&mut aggregator, &["temp:-10|g"]);
+        let metrics = flush_one(&mut aggregator);
+        let Some(metric::Data::Gauge(gauge)) = &metrics[0].data else {
+            panic!("expected a gauge: {:?}", metrics[0]);
+        };
+        assert_eq!(
+            gauge.data_points[0].value,
+            Some(number_data_point::Value::AsDouble(15.0))
+        );
+
+        // Gauges idle for too long are forgotten.
+        for _ in 0..GAUGE_IDLE_FLUSHES {
+            assert!(aggregator.flush(4_000).is_empty());
+        }
+        assert!(aggregator.gauges.is_empty());
+        add_lines(&mut aggregator, &["temp:+1|g"]);
+        let metrics = flush_one(&mut aggregator);
+        let Some(metric::Data::Gauge(gauge)) = &metrics[0].data else {
+            panic!("expected a gauge: {:?}", metrics[0]);
+        };
+        assert_eq!(
+            gauge.data_points[0].value,
+            Some(number_data_point::Value::AsDouble(1.0))
+        );
+    }
+
+    #[test]
+    fn test_histogram_and_set_aggregation() {
+        let mut aggregator = Aggregator::new(1_000);
+        add_lines(
+            &mut aggregator,
+            &[
+                "req:3|ms",
+                "req:40|ms",
+                "req:20000|ms",
+                "visitors:a|s",
+                "visitors:b|s",
+                "visitors:a|s",
+            ],
+        );
+        let metrics = flush_one(&mut aggregator);
+        let Some(metric::Data::Histogram(histogram)) = &metrics[0].data else {
+            panic!("expected a histogram: {:?}", metrics[0]);
+        };
+        let point = &histogram.data_points[0];
+        assert_eq!(point.count, 3);
+        assert_eq!(point.sum, Some(20043.0));
+        assert_eq!(point.min, Some(3.0));
+        assert_eq!(point.max, Some(20000.0));
+        assert_eq!(point.bucket_counts[1], 1);
+        assert_eq!(point.bucket_counts[4], 1);
+        assert_eq!(point.bucket_counts[EXPLICIT_BOUNDS.len()], 1);
+
+        let Some(metric::Data::Gauge(gauge)) = &metrics[1].data else {
+            panic!("expected a gauge: {:?}", metrics[1]);
+        };
+        assert_eq!(
+            gauge.data_points[0].value,
+            Some(number_data_point::Value::AsDouble(2.0))
+        );
+    }
+
+    #[test]
+    fn test_service_tag_selects_resource() {
+        let mut aggregator = Aggregator::new(1_000);
+        add_lines(
+            &mut aggregator,
+            &[
+                "jobs:1|c|#service:worker,queue:high",
+                "jobs:1|c|#service:api",
+            ],
+        );
+        let resource_metrics = aggregator.flush(2_000);
+        assert_eq!(resource_metrics.len(), 2);
+        let resource = resource_metrics[1].resource.as_ref().unwrap();
+        assert_eq!(resource.attributes[0].key, "service.name");
+        let point = match &resource_metrics[1].scope_metrics[0].metrics[0].data {
+            Some(metric::Data::Sum(sum)) => &sum.data_points[0],
+            other => panic!("expected a sum: {:?}", other),
+        };
+        assert_eq!(point.attributes.len(), 1);
+        assert_eq!(point.attributes[0].key, "queue");
+    }
+}
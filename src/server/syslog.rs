@@ -0,0 +1,536 @@
+//! Syslog receiver for RFC 5424 and RFC 3164 (BSD) messages over UDP and TCP.
+//!
+//! Each message becomes a `LogRecord`: the syslog severity maps to
+//! `severity_number`, the hostname and app-name become the `host.name` and
+//! `service.name` resource attributes, and RFC 5424 structured data becomes
+//! `<SD-ID>.<PARAM>` attributes. TCP accepts both octet-counted and
+//! newline-delimited framing (RFC 6587).
+
+use chrono::{Datelike, TimeZone, Utc};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio_util::sync::CancellationToken;
+
+use crate::proto::opentelemetry::proto::{
+    common::v1::{any_value, AnyValue, KeyValue},
+    logs::v1::{LogRecord, ResourceLogs, ScopeLogs},
+    resource::v1::Resource,
+};
+use crate::store::ingest::Ingester;
+use crate::store::SharedStore;
+
+/// Longest message accepted over TCP; longer frames close the connection.
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A parsed syslog message. Empty strings stand for absent (`-`) fields.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Message {
+    pub facility: u8,
+    pub severity: u8,
+    /// 1 for RFC 5424, 0 for RFC 3164.
+    pub version: u8,
+    pub timestamp: Option<u64>,
+    pub hostname: String,
+    pub app_name: String,
+    pub proc_id: String,
+    pub msg_id: String,
+    /// `(SD-ID, [(PARAM-NAME, PARAM-VALUE)])` in message order.
+    pub structured_data: Vec<(String, Vec<(String, String)>)>,
+    pub body: String,
+}
+
+/// Parse a single RFC 5424 or RFC 3164 message, telling them apart by the
+/// version digit that follows the PRI in RFC 5424.
+pub fn parse_message(input: &str) -> anyhow::Result<Message> {
+    let input = input.trim_end_matches(['\r', '\n', '\0']);
+    let rest = input
+        .strip_prefix('<')
+        .ok_or_else(|| anyhow::anyhow!("syslog message does not start with a PRI"))?;
+    let (pri, rest) = rest
+        .split_once('>')
+        .ok_or_else(|| anyhow::anyhow!("unterminated syslog PRI"))?;
+    let pri: u8 = pri
+        .parse()
+        .ok()
+        .filter(|p| *p <= 191)
+        .ok_or_else(|| anyhow::anyhow!("invalid syslog PRI '{}'", pri))?;
+    let mut message = Message {
+        facility: pri / 8,
+        severity: pri % 8,
+        ..Default::default()
+    };
+    match rest.strip_prefix("1 ") {
+        Some(rest) => parse_rfc5424(rest, &mut message)?,
+        None => parse_rfc3164(rest, &mut message),
+    }
+    Ok(message)
+}
+
+fn parse_rfc5424(input: &str, message: &mut Message) -> anyhow::Result<()> {
+    message.version = 1;
+    let mut fields = input.splitn(6, ' ');
+    let mut next = |name: &str| -> anyhow::Result<String> {
+        match fields.next() {
+            Some("-") => Ok(String::new()),
+            Some(value) => Ok(value.to_string()),
+            None => anyhow::bail!("RFC 5424 message is missing {}", name),
+        }
+    };
+    let timestamp = next("TIMESTAMP")?;
+    message.hostname = next("HOSTNAME")?;
+    message.app_name = next("APP-NAME")?;
+    message.proc_id = next("PROCID")?;
+    message.msg_id = next("MSGID")?;
+    if !timestamp.is_empty() {
+        let parsed = chrono::DateTime::parse_from_rfc3339(&timestamp)
+            .map_err(|e| anyhow::anyhow!("invalid RFC 5424 timestamp '{}': {}", timestamp, e))?;
+        message.timestamp = parsed.timestamp_nanos_opt().map(|t| t.max(0) as u64);
+    }
+
+    // STRUCTURED-DATA [SP MSG]
+    let rest = fields.next().unwrap_or_default();
+    let rest = match rest.strip_prefix('-') {
+        Some(rest) => rest,
+        None if rest.is_empty() => rest,
+        None => parse_structured_data(rest, &mut message.structured_data)?,
+    };
+    let body = rest.strip_prefix(' ').unwrap_or(rest);
+    message.body = body.strip_prefix('\u{feff}').unwrap_or(body).to_string();
+    Ok(())
+}
+
+/// Parse `[id param="value" ...]...` and return the input that follows it.
+fn parse_structured_data<'a>(
+    mut input: &'a str,
+    out: &mut Vec<(String, Vec<(String, String)>)>,
+) -> anyhow::Result<&'a str> {
+    if !input.starts_with('[') {
+        anyhow::bail!("invalid RFC 5424 structured data");
+    }
+    while let Some(element) = input.strip_prefix('[') {
+        let id_end = element
+            .find([' ', ']'])
+            .ok_or_else(|| anyhow::anyhow!("unterminated structured data element"))?;
+        let id = element[..id_end].to_string();
+        let mut rest = &element[id_end..];
+        let mut params = Vec::new();
+        loop {
+            rest = rest.trim_start_matches(' ');
+            if let Some(after) = rest.strip_prefix(']') {
+                rest = after;
+                break;
+            }
+            let (name, after) = rest
+                .split_once("=\"")
+                .ok_or_else(|| anyhow::anyhow!("invalid structured data parameter in [{}]", id))?;
+            let mut value = String::new();
+            let mut chars = after.char_indices();
+            let end = loop {
+                match chars.next() {
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c @ ('"' | '\\' | ']'))) => value.push(c),
+                        Some((_, c)) => {
+                            value.push('\\');
+                            value.push(c);
+                        }
+                        None => break None,
+                    },
+                    Some((i, '"')) => break Some(i),
+                    Some((_, c)) => value.push(c),
+                    None => break None,
+                }
+            };
+            let end =
+                end.ok_or_else(|| anyhow::anyhow!("unterminated parameter value in [{}]", id))?;
+            params.push((name.to_string(), value));
+            rest = &after[end + 1..];
+        }
+        out.push((id, params));
+        input = rest;
+    }
+    Ok(input)
+}
+
+/// RFC 3164 is loosely specified; anything that does not match
+/// `Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG` is kept as the message body.
+fn parse_rfc3164(input: &str, message: &mut Message) {
+    let Some((timestamp, rest)) = parse_bsd_timestamp(input) else {
+        message.body = input.to_string();
+        return;
+    };
+    message.timestamp = Some(timestamp);
+    let rest = rest.trim_start_matches(' ');
+    let (hostname, rest) = rest.split_once(' ').unwrap_or((rest, ""));
+    message.hostname = hostname.to_string();
+
+    // TAG is alphanumeric up to 32 characters, optionally followed by [PID] and ':'.
+    let tag_end = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || "-_./".contains(c)))
+        .unwrap_or(rest.len());
+    let after_tag = &rest[tag_end..];
+    let (proc_id, after) = match after_tag.strip_prefix('[').and_then(|s| s.split_once(']')) {
+        Some((pid, after)) => (pid, after),
+        None => ("", after_tag),
+    };
+    match after.strip_prefix(':') {
+        Some(body) if tag_end > 0 => {
+            message.app_name = rest[..tag_end].to_string();
+            message.proc_id = proc_id.to_string();
+            message.body = body.trim_start_matches(' ').to_string();
+        }
+        _ => message.body = rest.to_string(),
+    }
+}
+
+/// Parse `Mmm dd hh:mm:ss`, assuming UTC and the most recent year that does
+/// not put the timestamp more than a day in the future.
+fn parse_bsd_timestamp(input: &str) -> Option<(u64, &str)> {
+    let month = MONTHS.iter().position(|m| input.starts_with(m))? as u32 + 1;
+    let day: u32 = input.get(4..6)?.trim_start().parse().ok()?;
+    let time = input.get(7..15)?;
+    let mut hms = time.split(':').map(|p| p.parse::<u32>().ok());
+    let (h, m, s) = (hms.next()??, hms.next()??, hms.next()??);
+    let now = Utc::now();
+    let at = |year| Utc.with_ymd_and_hms(year, month, day, h, m, s).single();
+    let mut ts = at(now.year())?;
+    if ts > now + chrono::Duration::days(1) {
+        ts = at(now.year() - 1)?;
+    }
+    Some((ts.timestamp_nanos_opt()?.max(0) as u64, &input[15..]))
+}
+
+fn severity(severity: u8) -> (i32, &'static str) {
+    match severity {
+        0 => (22, "emerg"),
+        1 => (21, "alert"),
+        2 => (19, "crit"),
+        3 => (17, "err"),
+        4 => (13, "warning"),
+        5 => (10, "notice"),
+        6 => (9, "info"),
+        _ => (5, "debug"),
+    }
+}
+
+/// Convert messages to OTLP, with one `ResourceLogs` per (hostname, app-name).
+pub fn to_resource_logs(messages: Vec<Message>, observed_time: u64) -> Vec<ResourceLogs> {
+    let mut groups: Vec<((String, String), Vec<LogRecord>)> = Vec::new();
+    for message in messages {
+        let (severity_number, severity_text) = severity(message.severity);
+        let mut attributes = vec![
+            int_attribute("syslog.facility", message.facility as i64),
+            int_attribute("syslog.version", message.version as i64),
+        ];
+        if !message.proc_id.is_empty() {
+            attributes.push(string_attribute("syslog.procid", message.proc_id));
+        }
+        if !message.msg_id.is_empty() {
+            attributes.push(string_attribute("syslog.msgid", message.msg_id));
+        }
+        for (id, params) in message.structured_data {
+            for (name, value) in params {
+                attributes.push(string_attribute(&format!("{}.{}", id, name), value));
+            }
+        }
+        let record = LogRecord {
+            time_unix_nano: message.timestamp.unwrap_or_default(),
+            observed_time_unix_nano: observed_time,
+            severity_number,
+            severity_text: severity_text.to_string(),
+            body: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(message.body)),
+            }),
+            attributes,
+            ..Default::default()
+        };
+        let key = (message.hostname, message.app_name);
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, records)) => records.push(record),
+            None => groups.push((key, vec![record])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|((hostname, app_name), log_records)| {
+            let mut attributes = Vec::new();
+            if !app_name.is_empty() {
+                attributes.push(string_attribute("service.name", app_name));
+            }
+            if !hostname.is_empty() {
+                attributes.push(string_attribute("host.name", hostname));
+            }
+            ResourceLogs {
+                resource: Some(Resource {
+                    attributes,
+                    dropped_attributes_count: 0,
+                    entity_refs: vec![],
+                }),
+                scope_logs: vec![ScopeLogs {
+                    scope: None,
+                    log_records,
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }
+        })
+        .collect()
+}
+
+fn string_attribute(key: &str, value: String) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value)),
+        }),
+    }
+}
+
+fn int_attribute(key: &str, value: i64) -> KeyValue {
+    KeyValue {
+        key: key.to_string(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::IntValue(value)),
+        }),
+    }
+}
+
+fn now_nanos() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+async fn store_message(ingester: &Ingester, raw: &str, peer: std::net::SocketAddr) {
+    match parse_message(raw) {
+        Ok(message) => {
+            let resource_logs = to_resource_logs(vec![message], now_nanos());
+            ingester.insert_logs(resource_logs).await;
+        }
+        Err(e) => tracing::debug!(%peer, error = %e, "dropping syslog message"),
+    }
+}
+
+/// Receive one syslog message per UDP datagram.
+pub async fn run_syslog_udp_server(
+    socket: UdpSocket,
+    store: SharedStore,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let ingester = Ingester::new(store);
+    let mut buf = vec![0u8; 65535];
+    loop {
+        tokio::select! {
+            received = socket.recv_from(&mut buf) => {
+                let (len, peer) = received?;
+                store_message(&ingester, &String::from_utf8_lossy(&buf[..len]), peer).await;
+            }
+            _ = shutdown.cancelled() => {
+                ingester.flush().await;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Accept syslog connections, each carrying a stream of framed messages.
+pub async fn run_syslog_tcp_server(
+    listener: TcpListener,
+    store: SharedStore,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let ingester = Ingester::new(store);
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = super::accept_tcp(&listener) => accepted,
+            _ = shutdown.cancelled() => {
+                ingester.flush().await;
+                return Ok(());
+            }
+        };
+        let ingester = ingester.clone();
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            let mut reader = BufReader::new(stream);
+            loop {
+                let frame = tokio::select! {
+                    frame = read_frame(&mut reader) => frame,
+                    _ = shutdown.cancelled() => return,
+                };
+                match frame {
+                    Ok(Some(frame)) => {
+                        store_message(&ingester, &String::from_utf8_lossy(&frame), peer).await
+                    }
+                    Ok(None) => return,
+                    Err(e) => {
+                        tracing::debug!(%peer, error = %e, "closing syslog connection");
+                        return;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Read the next octet-counted (`LEN SP MSG`) or newline-terminated frame.
+/// Returns `None` at end of stream.
+async fn read_frame<R: AsyncBufRead + Unpin>(reader: &mut R) -> anyhow::Result<Option<Vec<u8>>> {
+    loop {
+        let buf = reader.fill_buf().await?;
+        let Some(&first) = buf.first() else {
+            return Ok(None);
+        };
+        if first == b'\n' || first == b'\r' {
+            reader.consume(1);
+            continue;
+        }
+
+        if first.is_ascii_digit() {
+            let mut len = Vec::new();
+            (&mut *reader).take(8).read_until(b' ', &mut len).await?;
+            let len: usize = std::str::from_utf8(len.strip_suffix(b" ").unwrap_or_default())
+                .ok()
+                .and_then(|l| l.parse().ok())
+                .ok_or_else(|| anyhow::anyhow!("invalid syslog octet count"))?;
+            if len > MAX_MESSAGE_SIZE {
+                anyhow::bail!("syslog frame of {} bytes exceeds the limit", len);
+            }
+            let mut frame = vec![0; len];
+            reader.read_exact(&mut frame).await?;
+            return Ok(Some(frame));
+        }
+
+        let mut frame = Vec::new();
+        (&mut *reader)
+            .take(MAX_MESSAGE_SIZE as u64 + 1)
+            .read_until(b'\n', &mut frame)
+            .await?;
+        if frame.len() > MAX_MESSAGE_SIZE {
+            anyhow::bail!("syslog line exceeds {} bytes", MAX_MESSAGE_SIZE);
+        }
+        return Ok(Some(frame));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rfc5424() {
+        let message = parse_message(
+            "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 \
+             [exampleSDID@32473 iut=\"3\" eventSource=\"Application\" eventID=\"1011\"]\
+             [examplePriority@32473 class=\"high \\\"x\\\"\"] \u{feff}An application event",
+        )
+        .unwrap();
+        assert_eq!(message.facility, 20);
+        assert_eq!(message.severity, 5);
+        assert_eq!(message.version, 1);
+        assert_eq!(message.timestamp, Some(1_065_910_455_003_000_000));
+        assert_eq!(message.hostname, "mymachine.example.com");
+        assert_eq!(message.app_name, "evntslog");
+        assert_eq!(message.proc_id, "");
+        assert_eq!(message.msg_id, "ID47");
+        assert_eq!(message.structured_data.len(), 2);
+        assert_eq!(message.structured_data[0].1[1].1, "Application");
+        assert_eq!(message.structured_data[1].1[0].1, "high \"x\"");
+        assert_eq!(message.body, "An application event");
+    }
+
+    #[test]
+    fn test_parse_rfc5424_without_structured_data() {
+        let message =
+            parse_message("<34>1 2003-10-11T22:14:15Z host su 123 - - 'su root' failed\n").unwrap();
+        assert_eq!(message.severity, 2);
+        assert_eq!(message.proc_id, "123");
+        assert!(message.structured_data.is_empty());
+        assert_eq!(message.body, "'su root' failed");
+
+        let empty = parse_message("<14>1 - - - - - -").unwrap();
+        assert_eq!(empty.timestamp, None);
+        assert_eq!(empty.body, "");
+    }
+
+    #[test]
+    fn test_parse_rfc3164() {
+        let message =
+            parse_message("<34>Oct 11 22:14:15 mymachine su[42]: 'su root' failed").unwrap();
+        assert_eq!(message.facility, 4);
+        assert_eq!(message.severity, 2);
+        assert_eq!(message.version, 0);
+        assert!(message.timestamp.is_some());
+        assert_eq!(message.hostname, "mymachine");
+        assert_eq!(message.app_name, "su");
+        assert_eq!(message.proc_id, "42");
+        assert_eq!(message.body, "'su root' failed");
+
+        let untagged = parse_message("<13>Feb  5 17:32:18 10.0.0.99 Use the BFG!").unwrap();
+        assert_eq!(untagged.hostname, "10.0.0.99");
+        assert_eq!(untagged.app_name, "");
+        assert_eq!(untagged.body, "Use the BFG!");
+
+        let bare = parse_message("<13>just a message").unwrap();
+        assert_eq!(bare.timestamp, None);
+        assert_eq!(bare.body, "just a message");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse_message("no pri").is_err());
+        assert!(parse_message("<999>1 - - - - - -").is_err());
+        assert!(parse_message("<14>1 not-a-time host app - - -").is_err());
+        assert!(parse_message("<14>1 - host app - - [unterminated").is_err());
+    }
+
+    #[test]
+    fn test_to_resource_logs() {
+        let messages = vec![
+            parse_message("<11>1 2003-10-11T22:14:15Z web nginx - - [meta@1 path=\"/\"] boom")
+                .unwrap(),
+            parse_message("<14>1 2003-10-11T22:14:16Z web nginx - - - ok").unwrap(),
+            parse_message("<14>1 2003-10-11T22:14:16Z db postgres - - - ready").unwrap(),
+        ];
+        let resource_logs = to_resource_logs(messages, 42);
+        assert_eq!(resource_logs.len(), 2);
+
+        let resource = resource_logs[0].resource.as_ref().unwrap();
+        assert_eq!(resource.attributes[0].key, "service.name");
+        assert_eq!(resource.attributes[1].key, "host.name");
+
+        let records = &resource_logs[0].scope_logs[0].log_records;
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].severity_number, 17);
+        assert_eq!(records[0].severity_text, "err");
+        assert_eq!(records[0].observed_time_unix_nano, 42);
+        assert!(records[0].attributes.iter().any(|a| a.key == "meta@1.path"));
+        assert_eq!(records[1].severity_text, "info");
+    }
+
+    #[tokio::test]
+    async fn test_read_frames() {
+        let data: &[u8] = b"11 <14>1 - - -\n<13>line one\n\n<13>line two";
+        let mut reader = BufReader::new(data);
+        assert_eq!(
+            read_frame(&mut reader).await.unwrap().unwrap(),
+            b"<14>1 - - -"
+        );
+        assert_eq!(
+            read_frame(&mut reader).await.unwrap().unwrap(),
+            b"<13>line one\n"
+        );
+        assert_eq!(
+            read_frame(&mut reader).await.unwrap().unwrap(),
+            b"<13>line two"
+        );
+        assert!(read_frame(&mut reader).await.unwrap().is_none());
+
+        let oversized: &[u8] = b"99999999 <14>";
+        assert!(read_frame(&mut BufReader::new(oversized)).await.is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+use tokio::task::JoinSet;
+use tokio_rustls::rustls::{
+    self,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// PEM material for serving TLS, optionally requiring client certificates (mTLS).
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    cert_pem: Vec<u8>,
+    key_pem: Vec<u8>,
+    client_ca_pem: Option<Vec<u8>>,
+}
+
+impl TlsConfig {
+    /// Read the certificate chain, private key and optional client CA from PEM files.
+    pub fn load(cert: &Path, key: &Path, client_ca: Option<&Path>) -> anyhow::Result<Self> {
+        let read = |path: &Path| {
+            std::fs::read(path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))
+        };
+        Ok(Self {
+            cert_pem: read(cert)?,
+            key_pem: read(key)?,
+            client_ca_pem: client_ca.map(read).transpose()?,
+        })
+    }
+
+    /// TLS settings for the tonic-based gRPC listeners.
+    pub fn server_tls_config(&self) -> ServerTlsConfig {
+        let config =
+            ServerTlsConfig::new().identity(Identity::from_pem(&self.cert_pem, &self.key_pem));
+        match &self.client_ca_pem {
+            Some(ca) => config.client_ca_root(Certificate::from_pem(ca)),
+            None => config,
+        }
+    }
+
+    /// rustls acceptor for the OTLP/HTTP and multiplexed listeners.
+    pub fn acceptor(&self) -> anyhow::Result<TlsAcceptor> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let certs = CertificateDer::pem_slice_iter(&self.cert_pem)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("invalid TLS certificate: {}", e))?;
+        let key = PrivateKeyDer::from_pem_slice(&self.key_pem)
+            .map_err(|e| anyhow::anyhow!("invalid TLS private key: {}", e))?;
+
+        let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()?;
+        let builder = match &self.client_ca_pem {
+            Some(ca) => {
+                let mut roots = RootCertStore::empty();
+                for cert in CertificateDer::pem_slice_iter(ca) {
+                    roots.add(cert.map_err(|e| anyhow::anyhow!("invalid client CA: {}", e))?)?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                        .build()?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_single_cert(certs, key)?;
+        // gRPC clients of the multiplexed listener require h2.
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+/// A connection whose TLS handshake completed, with its peer address.
+type Accepted<L> = (
+    TlsStream<<L as axum::serve::Listener>::Io>,
+    <L as axum::serve::Listener>::Addr,
+);
+
+/// A TCP (or Unix domain socket) listener that performs a TLS handshake on
+/// every accepted connection, for use with `axum::serve`.
+///
+/// Handshakes run in their own tasks while the listener keeps accepting, so a
+/// client that connects and then stalls only holds up its own connection.
+pub struct TlsListener<L: axum::serve::Listener = TcpListener> {
+    inner: L,
+    acceptor: TlsAcceptor,
+    handshakes: JoinSet<Option<Accepted<L>>>,
+}
+
+impl<L: axum::serve::Listener> TlsListener<L> {
+    pub fn new(inner: L, acceptor: TlsAcceptor) -> Self {
+        Self {
+            inner,
+            acceptor,
+            handshakes: JoinSet::new(),
+        }
+    }
+}
+
+impl<L> axum::serve::Listener for TlsListener<L>
+where
+    L: axum::serve::Listener,
+    L::Addr: std::fmt::Debug + 'static,
+{
+    type Io = TlsStream<L::Io>;
+    type Addr = L::Addr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            tokio::select! {
+                (stream, addr) = self.inner.accept() => {
+                    let acceptor = self.acceptor.clone();
+                    self.handshakes.spawn(async move {
+                        match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                            Ok(Ok(tls_stream)) => Some((tls_stream, addr)),
+                            Ok(Err(e)) => {
+                                tracing::debug!(error = %e, ?addr, "TLS handshake failed");
+                                None
+                            }
+                            Err(_) => {
+                                tracing::debug!(?addr, "TLS handshake timed out");
+                                None
+                            }
+                        }
+                    });
+                }
+                Some(done) = self.handshakes.join_next() => {
+                    if let Ok(Some(accepted)) = done {
+                        return accepted;
+                    }
+                }
+            }
+        }
+    }
+
+    fn local_addr(&self) -> std::io::Result<Self::Addr> {
+        self.inner.local_addr()
+    }
+}
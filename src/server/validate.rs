@@ -0,0 +1,437 @@
+//! Per-record validation of OTLP export requests.
+//!
+//! Invalid records are removed from the request before it reaches the store and
+//! counted so the handlers can report them through `partial_success`.
+
+use crate::proto::opentelemetry::proto::{
+    collector::{
+        logs::v1::ExportLogsPartialSuccess, metrics::v1::ExportMetricsPartialSuccess,
+        trace::v1::ExportTracePartialSuccess,
+    },
+    logs::v1::{LogRecord, ResourceLogs},
+    metrics::v1::{
+        metric, ExponentialHistogramDataPoint, HistogramDataPoint, NumberDataPoint,
+        ResourceMetrics, SummaryDataPoint,
+    },
+    trace::v1::{ResourceSpans, Span},
+};
+
+use crate::store::data_point_count;
+
+/// Records dropped by validation, along with the reason for the first one.
+#[derive(Debug, Default, PartialEq)]
+pub struct Rejected {
+    pub count: i64,
+    pub message: String,
+}
+
+impl Rejected {
+    fn record(&mut self, count: usize, reason: impl FnOnce() -> String) {
+        if count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            self.message = reason();
+        }
+        self.count += count as i64;
+    }
+
+    fn error_message(&self) -> Option<String> {
+        match self.count {
+            0 => None,
+            1 => Some(self.message.clone()),
+            n => Some(format!("{} (and {} more)", self.message, n - 1)),
+        }
+    }
+
+    /// `partial_success` for a trace export; `None` when everything was accepted.
+    pub fn trace_partial_success(&self) -> Option<ExportTracePartialSuccess> {
+        self.error_message()
+            .map(|error_message| ExportTracePartialSuccess {
+                rejected_spans: self.count,
+                error_message,
+            })
+    }
+
+    /// `partial_success` for a log export; `None` when everything was accepted.
+    pub fn logs_partial_success(&self) -> Option<ExportLogsPartialSuccess> {
+        self.error_message()
+            .map(|error_message| ExportLogsPartialSuccess {
+                rejected_log_records: self.count,
+                error_message,
+            })
+    }
+
+    /// `partial_success` for a metric export; `None` when everything was accepted.
+    pub fn metrics_partial_success(&self) -> Option<ExportMetricsPartialSuccess> {
+        self.error_message()
+            .map(|error_message| ExportMetricsPartialSuccess {
+                rejected_data_points: self.count,
+                error_message,
+            })
+    }
+}
+
+/// Drop spans with malformed IDs, missing names or bad timestamps.
+pub fn traces(resource_spans: &mut Vec<ResourceSpans>) -> Rejected {
+    let mut rejected = Rejected::default();
+    resource_spans.retain_mut(|rs| {
+        let before = rs.scope_spans.len();
+        rs.scope_spans.retain_mut(|ss| {
+            let before = ss.spans.len();
+            ss.spans.retain(|span| match check_span(span) {
+                Ok(()) => true,
+                Err(reason) => {
+                    rejected.record(1, || reason);
+                    false
+                }
+            });
+            before == ss.spans.len() || !ss.spans.is_empty()
+        });
+        before == rs.scope_spans.len() || !rs.scope_spans.is_empty()
+    });
+    if rejected.count > 0 {
+        tracing::warn!(rejected = rejected.count, reason = %rejected.message, "rejected invalid spans");
+    }
+    rejected
+}
+
+/// Drop log records with malformed IDs or no timestamp at all.
+pub fn logs(resource_logs: &mut Vec<ResourceLogs>) -> Rejected {
+    let mut rejected = Rejected::default();
+    resource_logs.retain_mut(|rl| {
+        let before = rl.scope_logs.len();
+        rl.scope_logs.retain_mut(|sl| {
+            let before = sl.log_records.len();
+            sl.log_records
+                .retain(|record| match check_log_record(record) {
+                    Ok(()) => true,
+                    Err(reason) => {
+                        rejected.record(1, || reason);
+                        false
+                    }
+                });
+            before == sl.log_records.len() || !sl.log_records.is_empty()
+        });
+        before == rl.scope_logs.len() || !rl.scope_logs.is_empty()
+    });
+    if rejected.count > 0 {
+        tracing::warn!(rejected = rejected.count, reason = %rejected.message, "rejected invalid log records");
+    }
+    rejected
+}
+
+/// Drop unnamed metrics and data points with bad timestamps. Counts are in
+/// data points, matching `rejected_data_points`.
+pub fn metrics(resource_metrics: &mut Vec<ResourceMetrics>) -> Rejected {
+    let mut rejected = Rejected::default();
+    resource_metrics.retain_mut(|rm| {
+        let before = rm.scope_metrics.len();
+        rm.scope_metrics.retain_mut(|sm| {
+            let before = sm.metrics.len();
+            sm.metrics.retain_mut(|m| {
+                let before = data_point_count(m);
+                if m.name.is_empty() {
+                    rejected.record(before, || "metric name is empty".into());
+                    return false;
+                }
+                let name = &m.name;
+                match &mut m.data {
+                    Some(metric::Data::Gauge(g)) => {
+                        retain_points(&mut g.data_points, name, &mut rejected)
+                    }
+                    Some(metric::Data::Sum(s)) => {
+                        retain_points(&mut s.data_points, name, &mut rejected)
+                    }
+                    Some(metric::Data::Histogram(h)) => {
+                        retain_points(&mut h.data_points, name, &mut rejected)
+                    }
+                    Some(metric::Data::ExponentialHistogram(h)) => {
+                        retain_points(&mut h.data_points, name, &mut rejected)
+                    }
+                    Some(metric::Data::Summary(s)) => {
+                        retain_points(&mut s.data_points, name, &mut rejected)
+                    }
+                    None => {}
+                }
+                before == data_point_count(m) || data_point_count(m) > 0
+            });
+            before == sm.metrics.len() || !sm.metrics.is_empty()
+        });
+        before == rm.scope_metrics.len() || !rm.scope_metrics.is_empty()
+    });
+    if rejected.count > 0 {
+        tracing::warn!(rejected = rejected.count, reason = %rejected.message, "rejected invalid data points");
+    }
+    rejected
+}
+
+trait DataPoint {
+    fn start_time_unix_nano(&self) -> u64;
+    fn time_unix_nano(&self) -> u64;
+}
+
+macro_rules! impl_data_point {
+    ($($ty:ty),*) => {
+        $(impl DataPoint for $ty {
+            fn start_time_unix_nano(&self) -> u64 {
+                self.start_time_unix_nano
+            }
+            fn time_unix_nano(&self) -> u64 {
+                self.time_unix_nano
+            }
+        })*
+    };
+}
+
+impl_data_point!(
+    NumberDataPoint,
+    HistogramDataPoint,
+    ExponentialHistogramDataPoint,
+    SummaryDataPoint
+);
+
+fn retain_points<P: DataPoint>(points: &mut Vec<P>, metric: &str, rejected: &mut Rejected) {
+    points.retain(|p| {
+        let reason = if p.time_unix_nano() == 0 {
+            "data point time is not set"
+        } else if p.start_time_unix_nano() > p.time_unix_nano() {
+            "data point start time is after its time"
+        } else {
+            return true;
+        };
+        rejected.record(1, || format!("metric {:?}: {}", metric, reason));
+        false
+    });
+}
+
+fn check_span(span: &Span) -> Result<(), String> {
+    let what = || format!("span {:?}", span.name);
+    check_id("trace_id", &span.trace_id, 16).map_err(|e| format!("{}: {}", what(), e))?;
+    check_id("span_id", &span.span_id, 8).map_err(|e| format!("{}: {}", what(), e))?;
+    if !span.parent_span_id.is_empty() && span.parent_span_id.len() != 8 {
+        return Err(format!(
+            "{}: parent_span_id must be 8 bytes, got {}",
+            what(),
+            span.parent_span_id.len()
+        ));
+    }
+    if span.name.is_empty() {
+        return Err(format!(
+            "span {}: name is empty",
+            hex::encode(&span.span_id)
+        ));
+    }
+    if span.start_time_unix_nano == 0 || span.end_time_unix_nano == 0 {
+        return Err(format!("{}: start and end time must be set", what()));
+    }
+    if span.end_time_unix_nano < span.start_time_unix_nano {
+        return Err(format!("{}: end time is before start time", what()));
+    }
+    Ok(())
+}
+
+fn check_log_record(record: &LogRecord) -> Result<(), String> {
+    if !record.trace_id.is_empty() {
+        check_id("trace_id", &record.trace_id, 16).map_err(|e| format!("log record: {}", e))?;
+    }
+    if !record.span_id.is_empty() {
+        check_id("span_id", &record.span_id, 8).map_err(|e| format!("log record: {}", e))?;
+    }
+    if record.time_unix_nano == 0 && record.observed_time_unix_nano == 0 {
+        return Err("log record: neither time nor observed time is set".into());
+    }
+    Ok(())
+}
+
+fn check_id(field: &str, id: &[u8], len: usize) -> Result<(), String> {
+    if id.len() != len {
+        return Err(format!("{} must be {} bytes, got {}", field, len, id.len()));
+    }
+    if id.iter().all(|&b| b == 0) {
+        return Err(format!("{} is all zeros", field));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::opentelemetry::proto::{
+        logs::v1::ScopeLogs,
+        metrics::v1::{Gauge, Metric, ScopeMetrics},
+        trace::v1::ScopeSpans,
+    };
+
+    fn valid_span(name: &str) -> Span {
+        Span {
+            trace_id: vec![1; 16],
+            span_id: vec![2; 8],
+            name: name.into(),
+            start_time_unix_nano: 100,
+            end_time_unix_nano: 200,
+            ..Default::default()
+        }
+    }
+
+    fn resource_spans(spans: Vec<Span>) -> Vec<ResourceSpans> {
+        vec![ResourceSpans {
+            resource: None,
+            scope_spans: vec![ScopeSpans {
+                scope: None,
+                spans,
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }]
+    }
+
+    #[test]
+    fn test_valid_spans_are_kept() {
+        let mut rs = resource_spans(vec![valid_span("a"), valid_span("b")]);
+        let rejected = traces(&mut rs);
+        assert_eq!(rejected, Rejected::default());
+        assert!(rejected.trace_partial_success().is_none());
+        assert_eq!(rs[0].scope_spans[0].spans.len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_spans_are_rejected() {
+        let cases = [
+            (
+                Span {
+                    trace_id: vec![1; 3],
+                    ..valid_span("short-trace")
+                },
+                "trace_id must be 16 bytes, got 3",
+            ),
+            (
+                Span {
+                    trace_id: vec![0; 16],
+                    ..valid_span("zero-trace")
+                },
+                "trace_id is all zeros",
+            ),
+            (
+                Span {
+                    span_id: vec![],
+                    ..valid_span("no-span-id")
+                },
+                "span_id must be 8 bytes, got 0",
+            ),
+            (
+                Span {
+                    parent_span_id: vec![1; 4],
+                    ..valid_span("bad-parent")
+                },
+                "parent_span_id must be 8 bytes, got 4",
+            ),
+            (valid_span(""), "name is empty"),
+            (
+                Span {
+                    start_time_unix_nano: 0,
+                    ..valid_span("no-start")
+                },
+                "start and end time must be set",
+            ),
+            (
+                Span {
+                    end_time_unix_nano: 50,
+                    ..valid_span("backwards")
+                },
+                "end time is before start time",
+            ),
+        ];
+        for (span, reason) in cases {
+            let mut rs = resource_spans(vec![span, valid_span("ok")]);
+            let rejected = traces(&mut rs);
+            assert_eq!(rejected.count, 1, "{}", reason);
+            assert!(rejected.message.contains(reason), "{}", rejected.message);
+            assert_eq!(rs[0].scope_spans[0].spans.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_fully_rejected_groups_are_dropped() {
+        let mut rs = resource_spans(vec![valid_span(""), valid_span("")]);
+        let partial = traces(&mut rs).trace_partial_success().unwrap();
+        assert_eq!(partial.rejected_spans, 2);
+        assert_eq!(
+            partial.error_message,
+            "span 0202020202020202: name is empty (and 1 more)"
+        );
+        assert!(rs.is_empty());
+
+        // Groups that were already empty are left alone.
+        let mut rs = resource_spans(vec![]);
+        assert_eq!(traces(&mut rs).count, 0);
+        assert_eq!(rs.len(), 1);
+    }
+
+    #[test]
+    fn test_log_records() {
+        let record = LogRecord {
+            observed_time_unix_nano: 100,
+            ..Default::default()
+        };
+        let mut rl = vec![ResourceLogs {
+            resource: None,
+            scope_logs: vec![ScopeLogs {
+                scope: None,
+                log_records: vec![
+                    record.clone(),
+                    LogRecord::default(),
+                    LogRecord {
+                        span_id: vec![1; 3],
+                        ..record
+                    },
+                ],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }];
+        let partial = logs(&mut rl).logs_partial_success().unwrap();
+        assert_eq!(partial.rejected_log_records, 2);
+        assert!(partial
+            .error_message
+            .contains("neither time nor observed time"));
+        assert_eq!(rl[0].scope_logs[0].log_records.len(), 1);
+    }
+
+    #[test]
+    fn test_metric_data_points() {
+        let point = |start, time| NumberDataPoint {
+            start_time_unix_nano: start,
+            time_unix_nano: time,
+            ..Default::default()
+        };
+        let gauge = |name: &str, points| Metric {
+            name: name.into(),
+            data: Some(metric::Data::Gauge(Gauge {
+                data_points: points,
+            })),
+            ..Default::default()
+        };
+        let mut rm = vec![ResourceMetrics {
+            resource: None,
+            scope_metrics: vec![ScopeMetrics {
+                scope: None,
+                metrics: vec![
+                    gauge("ok", vec![point(0, 10), point(5, 10), point(0, 0)]),
+                    gauge("", vec![point(0, 10), point(0, 10)]),
+                    gauge("backwards", vec![point(20, 10)]),
+                ],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }];
+        let partial = metrics(&mut rm).metrics_partial_success().unwrap();
+        assert_eq!(partial.rejected_data_points, 4);
+        assert!(partial
+            .error_message
+            .starts_with("metric \"ok\": data point time is not set"));
+        let kept = &rm[0].scope_metrics[0].metrics;
+        assert_eq!(kept.len(), 1);
+        assert_eq!(data_point_count(&kept[0]), 2);
+    }
+}
@@ -0,0 +1,407 @@
+//! Zipkin v2 span ingestion (`POST /api/v2/spans`), accepting the JSON and
+//! proto3 encodings and storing the spans as OTLP `ResourceSpans`.
+
+use std::collections::{BTreeMap, HashMap};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+};
+use prost::Message;
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::proto::opentelemetry::proto::{
+    common::v1::{any_value, AnyValue, KeyValue},
+    resource::v1::Resource,
+    trace::v1::{span, status, ResourceSpans, ScopeSpans, Span, Status},
+};
+use crate::proto::zipkin::proto3;
+use crate::server::validate;
+use crate::store::ingest::Ingester;
+
+#[instrument(name = "zipkin.http.spans", skip_all, fields(http.route = "/api/v2/spans"))]
+pub(crate) async fn handle_spans(
+    State(ingester): State<Ingester>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let spans = if is_protobuf_content_type(&headers) {
+        proto3::ListOfSpans::decode(body)
+            .map(|list| list.spans)
+            .map_err(|e| format!("invalid Zipkin proto3 body: {}", e))
+    } else {
+        decode_json(&body)
+    }
+    .map_err(|e| {
+        tracing::warn!(error = %e, "failed to decode Zipkin spans");
+        (StatusCode::BAD_REQUEST, e)
+    })?;
+    tracing::debug!(count = spans.len(), "received Zipkin spans via HTTP");
+
+    let mut resource_spans = to_resource_spans(spans);
+    validate::traces(&mut resource_spans);
+    ingester.insert_traces(resource_spans).await;
+    Ok(StatusCode::ACCEPTED)
+}
+
+fn is_protobuf_content_type(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-protobuf"))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonSpan {
+    trace_id: String,
+    id: String,
+    parent_id: Option<String>,
+    kind: Option<String>,
+    name: Option<String>,
+    timestamp: Option<u64>,
+    duration: Option<u64>,
+    local_endpoint: Option<JsonEndpoint>,
+    remote_endpoint: Option<JsonEndpoint>,
+    #[serde(default)]
+    annotations: Vec<JsonAnnotation>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    debug: bool,
+    #[serde(default)]
+    shared: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonEndpoint {
+    service_name: Option<String>,
+    ipv4: Option<String>,
+    ipv6: Option<String>,
+    port: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct JsonAnnotation {
+    timestamp: u64,
+    value: String,
+}
+
+/// Decode a Zipkin v2 JSON array into the proto3 model.
+fn decode_json(body: &[u8]) -> Result<Vec<proto3::Span>, String> {
+    let spans: Vec<JsonSpan> =
+        serde_json::from_slice(body).map_err(|e| format!("invalid Zipkin JSON: {}", e))?;
+    spans.into_iter().map(json_span_to_proto).collect()
+}
+
+fn json_span_to_proto(span: JsonSpan) -> Result<proto3::Span, String> {
+    let hex_id = |field: &str, value: &str| {
+        hex::decode(value).map_err(|e| format!("invalid {} {:?}: {}", field, value, e))
+    };
+    let kind = match span.kind.as_deref() {
+        Some("CLIENT") => proto3::span::Kind::Client,
+        Some("SERVER") => proto3::span::Kind::Server,
+        Some("PRODUCER") => proto3::span::Kind::Producer,
+        Some("CONSUMER") => proto3::span::Kind::Consumer,
+        _ => proto3::span::Kind::SpanKindUnspecified,
+    };
+    Ok(proto3::Span {
+        trace_id: hex_id("traceId", &span.trace_id)?,
+        parent_id: span
+            .parent_id
+            .as_deref()
+            .map(|id| hex_id("parentId", id))
+            .transpose()?
+            .unwrap_or_default(),
+        id: hex_id("id", &span.id)?,
+        kind: kind as i32,
+        name: span.name.unwrap_or_default(),
+        timestamp: span.timestamp.unwrap_or_default(),
+        duration: span.duration.unwrap_or_default(),
+        local_endpoint: span.local_endpoint.map(json_endpoint_to_proto),
+        remote_endpoint: span.remote_endpoint.map(json_endpoint_to_proto),
+        annotations: span
+            .annotations
+            .into_iter()
+            .map(|a| proto3::Annotation {
+                timestamp: a.timestamp,
+                value: a.value,
+            })
+            .collect(),
+        tags: span.tags,
+        debug: span.debug,
+        shared: span.shared,
+    })
+}
+
+fn json_endpoint_to_proto(endpoint: JsonEndpoint) -> proto3::Endpoint {
+    proto3::Endpoint {
+        service_name: endpoint.service_name.unwrap_or_default(),
+        ipv4: endpoint
+            .ipv4
+            .and_then(|ip| ip.parse::<Ipv4Addr>().ok())
+            .map(|ip| ip.octets().to_vec())
+            .unwrap_or_default(),
+        ipv6: endpoint
+            .ipv6
+            .and_then(|ip| ip.parse::<Ipv6Addr>().ok())
+            .map(|ip| ip.octets().to_vec())
+            .unwrap_or_default(),
+        port: endpoint.port.unwrap_or_default(),
+    }
+}
+
+/// Convert Zipkin spans to OTLP, with one `ResourceSpans` per local service name.
+pub fn to_resource_spans(spans: Vec<proto3::Span>) -> Vec<ResourceSpans> {
+    let mut by_service: BTreeMap<String, Vec<Span>> = BTreeMap::new();
+    for span in spans {
+        let service_name = span
+            .local_endpoint
+            .as_ref()
+            .map(|e| e.service_name.clone())
+            .unwrap_or_default();
+        by_service
+            .entry(service_name)
+            .or_default()
+            .push(convert_span(span));
+    }
+    by_service
+        .into_iter()
+        .map(|(service_name, spans)| ResourceSpans {
+            resource: Some(Resource {
+                attributes: if service_name.is_empty() {
+                    vec![]
+                } else {
+                    vec![string_attribute("service.name", service_name)]
+                },
+                dropped_attributes_count: 0,
+                entity_refs: vec![],
+            }),
+            scope_spans: vec![ScopeSpans {
+                scope: None,
+                spans,
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        })
+        .collect()
+}
+
+fn convert_span(span: proto3::Span) -> Span {
+    let kind = match span.kind() {
+        proto3::span::Kind::Client => span::SpanKind::Client,
+        proto3::span::Kind::Server => span::SpanKind::Server,
+        proto3::span::Kind::Producer => span::SpanKind::Producer,
+        proto3::span::Kind::Consumer => span::SpanKind::Consumer,
+        proto3::span::Kind::SpanKindUnspecified => span::SpanKind::Unspecified,
+    };
+    // Zipkin times are in microseconds; saturate rather than overflow on
+    // out-of-range values.
+    let start_time_unix_nano = span.timestamp.saturating_mul(1000);
+    let mut attributes = Vec::new();
+    let mut status = None;
+    let mut tags: Vec<_> = span.tags.into_iter().collect();
+    tags.sort();
+    for (key, value) in tags {
+        match key.as_str() {
+            // Zipkin marks failed spans with an `error` tag holding the message.
+            "error" => {
+                status = Some(Status {
+                    message: value,
+                    code: status::StatusCode::Error as i32,
+                })
+            }
+            _ => attributes.push(string_attribute(&key, value)),
+        }
+    }
+    if let Some(remote) = span.remote_endpoint {
+        if !remote.service_name.is_empty() {
+            attributes.push(string_attribute("peer.service", remote.service_name));
+        }
+        let ip = if remote.ipv4.len() == 4 {
+            <[u8; 4]>::try_from(remote.ipv4.as_slice())
+                .map(|b| Ipv4Addr::from(b).to_string())
+                .ok()
+        } else {
+            <[u8; 16]>::try_from(remote.ipv6.as_slice())
+                .map(|b| Ipv6Addr::from(b).to_string())
+                .ok()
+        };
+        if let Some(ip) = ip {
+            attributes.push(string_attribute("network.peer.address", ip));
+        }
+        if remote.port != 0 {
+            attributes.push(KeyValue {
+                key: "network.peer.port".into(),
+                value: Some(AnyValue {
+                    value: Some(any_value::Value::IntValue(remote.port as i64)),
+                }),
+            });
+        }
+    }
+    Span {
+        trace_id: pad_trace_id(span.trace_id),
+        span_id: span.id,
+        parent_span_id: span.parent_id,
+        name: span.name,
+        kind: kind as i32,
+        start_time_unix_nano,
+        end_time_unix_nano: start_time_unix_nano.saturating_add(span.duration.saturating_mul(1000)),
+        attributes,
+        events: span
+            .annotations
+            .into_iter()
+            .map(|a| span::Event {
+                time_unix_nano: a.timestamp.saturating_mul(1000),
+                name: a.value,
+                ..Default::default()
+            })
+            .collect(),
+        status,
+        ..Default::default()
+    }
+}
+
+/// Zipkin allows 64-bit trace IDs; OTLP requires 128 bits, so left-pad with zeros.
+fn pad_trace_id(trace_id: Vec<u8>) -> Vec<u8> {
+    if trace_id.len() == 8 {
+        let mut padded = vec![0; 8];
+        padded.extend(trace_id);
+        padded
+    } else {
+        trace_id
+    }
+}
+
+fn string_attribute(key: &str, value: String) -> KeyValue {
+    KeyValue {
+        key: key.into(),
+        value: Some(AnyValue {
+            value: Some(any_value::Value::StringValue(value)),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"[{
+        "traceId": "5af7183fb1d4cf5f",
+        "parentId": "6b221d5bc9e6496c",
+        "id": "352bff9a74ca9ad2",
+        "kind": "CLIENT",
+        "name": "get /api",
+        "timestamp": 1556604172355737,
+        "duration": 1431,
+        "localEndpoint": {"serviceName": "frontend", "ipv4": "192.168.99.1"},
+        "remoteEndpoint": {"serviceName": "backend", "ipv4": "172.19.0.2", "port": 8080},
+        "annotations": [{"timestamp": 1556604172355800, "value": "ws"}],
+        "tags": {"http.method": "GET", "http.path": "/api", "error": "timeout"}
+    }]"#;
+
+    #[test]
+    fn test_json_conversion() {
+        let spans = decode_json(SAMPLE.as_bytes()).unwrap();
+        let resource_spans = to_resource_spans(spans);
+        assert_eq!(resource_spans.len(), 1);
+        let resource = resource_spans[0].resource.as_ref().unwrap();
+        assert_eq!(
+            resource.attributes,
+            vec![string_attribute("service.name", "frontend".into())]
+        );
+
+        let span = &resource_spans[0].scope_spans[0].spans[0];
+        assert_eq!(
+            hex::encode(&span.trace_id),
+            "00000000000000005af7183fb1d4cf5f"
+        );
+        assert_eq!(hex::encode(&span.span_id), "352bff9a74ca9ad2");
+        assert_eq!(hex::encode(&span.parent_span_id), "6b221d5bc9e6496c");
+        assert_eq!(span.name, "get /api");
+        assert_eq!(span.kind, span::SpanKind::Client as i32);
+        assert_eq!(span.start_time_unix_nano, 1556604172355737000);
+        assert_eq!(span.end_time_unix_nano, 1556604172355737000 + 1431000);
+        assert_eq!(span.events.len(), 1);
+        assert_eq!(span.events[0].name, "ws");
+        assert_eq!(span.events[0].time_unix_nano, 1556604172355800000);
+
+        let status = span.status.as_ref().unwrap();
+        assert_eq!(status.code, status::StatusCode::Error as i32);
+        assert_eq!(status.message, "timeout");
+
+        let keys: Vec<_> = span.attributes.iter().map(|kv| kv.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "http.method",
+                "http.path",
+                "peer.service",
+                "network.peer.address",
+                "network.peer.port"
+            ]
+        );
+        assert_eq!(
+            span.attributes[3],
+            string_attribute("network.peer.address", "172.19.0.2".into())
+        );
+    }
+
+    #[test]
+    fn test_groups_by_service() {
+        let spans = vec![
+            proto3::Span {
+                local_endpoint: Some(proto3::Endpoint {
+                    service_name: "b".into(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            proto3::Span {
+                local_endpoint: Some(proto3::Endpoint {
+                    service_name: "a".into(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            proto3::Span {
+                local_endpoint: Some(proto3::Endpoint {
+                    service_name: "b".into(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        ];
+        let resource_spans = to_resource_spans(spans);
+        let counts: Vec<_> = resource_spans
+            .iter()
+            .map(|rs| rs.scope_spans[0].spans.len())
+            .collect();
+        assert_eq!(counts, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_out_of_range_times_saturate() {
+        let body = format!(
+            r#"[{{"traceId": "5af7183fb1d4cf5f", "id": "352bff9a74ca9ad2",
+                "timestamp": {max}, "duration": {max},
+                "annotations": [{{"timestamp": {max}, "value": "ws"}}]}}]"#,
+            max = u64::MAX
+        );
+        let spans = decode_json(body.as_bytes()).unwrap();
+        let span = &to_resource_spans(spans)[0].scope_spans[0].spans[0];
+        assert_eq!(span.start_time_unix_nano, u64::MAX);
+        assert_eq!(span.end_time_unix_nano, u64::MAX);
+        assert_eq!(span.events[0].time_unix_nano, u64::MAX);
+    }
+
+    #[test]
+    fn test_invalid_hex_id() {
+        let err = decode_json(br#"[{"traceId": "zz", "id": "352bff9a74ca9ad2"}]"#).unwrap_err();
+        assert!(err.contains("invalid traceId"));
+    }
+}
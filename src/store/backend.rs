@@ -0,0 +1,167 @@
+//! The interface between stored telemetry and everything that reads or
+//! writes it.
+//!
+//! Receivers, the query API, the SQL tables and the TUI only use a
+//! [`SharedStore`](super::SharedStore), which holds one [`StorageBackend`]
+//! per signal. The in-memory [`Store`](super::Store) is the one
+//! implementation; another backend (on-disk segments, Parquet files,
+//! compressed memory) implements this trait and is shared the same way.
+//!
+//! Data is stored as ResourceSpans, ResourceLogs and ResourceMetrics batches.
+//! Each batch gets an ingest sequence number, increasing per signal, that
+//! follow streams use to ask for what arrived since they last looked.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use tokio::sync::broadcast;
+
+use crate::proto::opentelemetry::proto::{
+    logs::v1::ResourceLogs, metrics::v1::ResourceMetrics, trace::v1::ResourceSpans,
+};
+
+use super::StoreEvent;
+
+/// Batches yielded by a scan, borrowed when the backend keeps them decoded.
+pub type Scan<'a, T> = Box<dyn Iterator<Item = Cow<'a, T>> + 'a>;
+
+/// Batches yielded by a scan along with their ingest sequence numbers.
+pub type SeqScan<'a, T> = Box<dyn Iterator<Item = (u64, Cow<'a, T>)> + 'a>;
+
+/// A stored span, log record or metric data point: the sequence number of
+/// its batch and its position within the batch, counted in scan order. It
+/// stays valid while other batches are stored or removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RowId {
+    pub seq: u64,
+    pub index: usize,
+}
+
+/// What deduplication has dropped so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    pub duplicate_spans: u64,
+    pub duplicate_log_records: u64,
+    pub duplicate_metric_points: u64,
+}
+
+/// Inclusive time bounds of a scan, in Unix nanoseconds, matched against the
+/// earliest timestamp in each batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeBounds {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl TimeBounds {
+    pub const ALL: TimeBounds = TimeBounds {
+        start: 0,
+        end: u64::MAX,
+    };
+
+    pub fn since(start: u64) -> Self {
+        TimeBounds {
+            start,
+            end: u64::MAX,
+        }
+    }
+
+    pub fn contains(&self, time: u64) -> bool {
+        self.start <= time && time <= self.end
+    }
+}
+
+pub trait StorageBackend: Send + Sync {
+    /// Receive a [`StoreEvent`] for every change to the stored data.
+    fn subscribe(&self) -> broadcast::Receiver<StoreEvent>;
+
+    /// Store a batch, evicting older data as the backend's limits require.
+    fn insert_traces(&mut self, resource_spans: Vec<ResourceSpans>);
+    fn insert_logs(&mut self, resource_logs: Vec<ResourceLogs>);
+    fn insert_metrics(&mut self, resource_metrics: Vec<ResourceMetrics>);
+
+    /// Stored batches within `bounds` with their sequence numbers, ordered
+    /// by their earliest timestamp. This is the order the SQL tables list
+    /// spans, log records and data points in.
+    fn scan_traces_with_seq(&self, bounds: TimeBounds) -> SeqScan<'_, ResourceSpans>;
+    fn scan_logs_with_seq(&self, bounds: TimeBounds) -> SeqScan<'_, ResourceLogs>;
+    fn scan_metrics_with_seq(&self, bounds: TimeBounds) -> SeqScan<'_, ResourceMetrics>;
+
+    /// Stored batches within `bounds`, ordered by their earliest timestamp.
+    fn scan_traces(&self, bounds: TimeBounds) -> Scan<'_, ResourceSpans> {
+        Box::new(self.scan_traces_with_seq(bounds).map(|(_, rs)| rs))
+    }
+    fn scan_logs(&self, bounds: TimeBounds) -> Scan<'_, ResourceLogs> {
+        Box::new(self.scan_logs_with_seq(bounds).map(|(_, rl)| rl))
+    }
+    fn scan_metrics(&self, bounds: TimeBounds) -> Scan<'_, ResourceMetrics> {
+        Box::new(self.scan_metrics_with_seq(bounds).map(|(_, rm)| rm))
+    }
+
+    /// Sequence number of the latest stored batch, 0 before the first.
+    fn last_seq(&self) -> u64;
+
+    /// Batches stored after sequence number `seq`, in ingest order.
+    fn traces_after(&self, seq: u64) -> Vec<ResourceSpans>;
+    fn logs_after(&self, seq: u64) -> Vec<ResourceLogs>;
+    fn metrics_after(&self, seq: u64) -> Vec<ResourceMetrics>;
+
+    /// Number of distinct traces.
+    fn trace_count(&self) -> usize;
+    /// Number of stored ResourceLogs.
+    fn log_count(&self) -> usize;
+    /// Number of stored ResourceMetrics.
+    fn metric_count(&self) -> usize;
+
+    /// Encoded protobuf size of the stored data, per signal.
+    fn trace_bytes(&self) -> usize;
+    fn log_bytes(&self) -> usize;
+    fn metric_bytes(&self) -> usize;
+
+    /// The memory budget in bytes, `usize::MAX` when unlimited.
+    fn max_memory(&self) -> usize {
+        usize::MAX
+    }
+
+    /// Evict the oldest data while the memory budget this backend shares
+    /// with the other signals of a [`SharedStore`](super::SharedStore) is
+    /// exceeded and this backend uses the most of it. Returns whether
+    /// anything was evicted.
+    fn evict_for_budget(&mut self) -> bool {
+        false
+    }
+
+    /// Deduplication counters, when deduplication is enabled.
+    fn dedup_stats(&self) -> Option<DedupStats> {
+        None
+    }
+
+    fn clear_traces(&mut self);
+    fn clear_logs(&mut self);
+    fn clear_metrics(&mut self);
+
+    /// Drop every trace whose last span ended before `cutoff`. Returns the
+    /// number of traces removed.
+    fn expire_traces(&mut self, cutoff: u64) -> usize;
+    /// Drop every ResourceLogs whose newest record is older than `cutoff`.
+    /// Returns the number of ResourceLogs removed.
+    fn expire_logs(&mut self, cutoff: u64) -> usize;
+    /// Drop every ResourceMetrics whose newest data point is older than
+    /// `cutoff`. Returns the number of ResourceMetrics removed.
+    fn expire_metrics(&mut self, cutoff: u64) -> usize;
+
+    /// Remove the spans in `rows`, where a span's index counts the spans of
+    /// its ResourceSpans in order. Rows that no longer exist are skipped.
+    /// Returns the number of spans removed.
+    fn delete_spans(&mut self, rows: &BTreeSet<RowId>) -> usize;
+    /// Like [`delete_spans`](Self::delete_spans), per log record.
+    fn delete_log_records(&mut self, rows: &BTreeSet<RowId>) -> usize;
+    /// Like [`delete_spans`](Self::delete_spans), per metric data point.
+    fn delete_metric_points(&mut self, rows: &BTreeSet<RowId>) -> usize;
+
+    /// Write out whatever changed since the last snapshot, for backends that
+    /// persist data.
+    fn snapshot(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
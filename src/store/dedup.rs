@@ -0,0 +1,522 @@
+//! Deduplication for `server --dedup`: drops data an exporter sends again,
+//! typically when it retries an export that timed out.
+//!
+//! Spans are keyed on (trace_id, span_id). Log records and metric data points
+//! have no identity of their own, so they are keyed on their encoded content
+//! together with their resource and scope (and metric name), which are kept
+//! once per distinct value. Keys are exact, so the only records dropped are
+//! ones identical in every field, timestamp included; an application that
+//! logs the same line twice within one nanosecond loses the second copy.
+//! Keys are forgotten when the data leaves the store, so the same data sent
+//! after its eviction is stored again.
+//!
+//! The keys take about as much memory as the records they describe and count
+//! towards `--max-memory` (see [`Dedup::bytes`]).
+
+use std::collections::HashSet;
+use std::mem::size_of;
+use std::sync::Arc;
+
+use prost::Message;
+
+use crate::proto::opentelemetry::proto::{
+    common::v1::InstrumentationScope,
+    logs::v1::ResourceLogs,
+    metrics::v1::{metric, Metric, ResourceMetrics},
+    resource::v1::Resource,
+    trace::v1::ResourceSpans,
+};
+
+type SpanKey = (Vec<u8>, Vec<u8>);
+
+/// Encoded items grouped by the encoded resource, scope and metric name they
+/// belong to.
+type KeyGroups = Vec<(Vec<u8>, Vec<Vec<u8>>)>;
+
+/// Content keys of log records or data points. The resource, scope and
+/// metric name prefix is shared by every key under it.
+#[derive(Default)]
+struct ContentKeys {
+    keys: HashSet<(Arc<[u8]>, Vec<u8>)>,
+    scopes: HashSet<Arc<[u8]>>,
+    bytes: usize,
+}
+
+impl ContentKeys {
+    const KEY_SIZE: usize = size_of::<(Arc<[u8]>, Vec<u8>)>();
+
+    /// The shared copy of `scope`. Hand it back to [`release`](Self::release)
+    /// once done inserting.
+    fn scope(&mut self, scope: Vec<u8>) -> Arc<[u8]> {
+        if let Some(shared) = self.scopes.get(scope.as_slice()) {
+            return shared.clone();
+        }
+        self.bytes += scope.len();
+        let shared: Arc<[u8]> = scope.into();
+        self.scopes.insert(shared.clone());
+        shared
+    }
+
+    /// Drop `scope` when no key uses it.
+    fn release(&mut self, scope: Arc<[u8]>) {
+        // The set and `scope` itself are the only references left.
+        if Arc::strong_count(&scope) == 2 {
+            self.scopes.remove(&scope);
+            self.bytes -= scope.len();
+        }
+    }
+
+    /// Add a key, returning whether it was new.
+    fn insert(&mut self, scope: &Arc<[u8]>, item: Vec<u8>) -> bool {
+        let size = Self::KEY_SIZE + item.len();
+        let new = self.keys.insert((scope.clone(), item));
+        if new {
+            self.bytes += size;
+        }
+        new
+    }
+
+    fn remember(&mut self, groups: KeyGroups) {
+        for (scope, items) in groups {
+            let scope = self.scope(scope);
+            for item in items {
+                self.insert(&scope, item);
+            }
+            self.release(scope);
+        }
+    }
+
+    fn forget(&mut self, groups: KeyGroups) {
+        for (scope, items) in groups {
+            let Some(scope) = self.scopes.get(scope.as_slice()).cloned() else {
+                continue;
+            };
+            for item in items {
+                let size = Self::KEY_SIZE + item.len();
+                if self.keys.remove(&(scope.clone(), item)) {
+                    self.bytes -= size;
+                }
+            }
+            self.release(scope);
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = ContentKeys::default();
+    }
+}
+
+#[derive(Default)]
+pub struct Dedup {
+    spans: HashSet<SpanKey>,
+    span_bytes: usize,
+    log_records: ContentKeys,
+    metric_points: ContentKeys,
+    duplicate_spans: u64,
+    duplicate_log_records: u64,
+    duplicate_metric_points: u64,
+}
+
+impl Dedup {
+    /// Spans dropped as duplicates so far.
+    pub fn duplicate_spans(&self) -> u64 {
+        self.duplicate_spans
+    }
+
+    /// Log records dropped as duplicates so far.
+    pub fn duplicate_log_records(&self) -> u64 {
+        self.duplicate_log_records
+    }
+
+    /// Metric data points dropped as duplicates so far.
+    pub fn duplicate_metric_points(&self) -> u64 {
+        self.duplicate_metric_points
+    }
+
+    /// Split into one `Dedup` per signal, in [`Signal::index`] order, each
+    /// keeping the keys and counters of its own signal.
+    ///
+    /// [`Signal::index`]: super::persist::Signal::index
+    pub fn split(self) -> [Dedup; 3] {
+        [
+            Dedup {
+                spans: self.spans,
+                span_bytes: self.span_bytes,
+                duplicate_spans: self.duplicate_spans,
+                ..Dedup::default()
+            },
+            Dedup {
+                log_records: self.log_records,
+                duplicate_log_records: self.duplicate_log_records,
+                ..Dedup::default()
+            },
+            Dedup {
+                metric_points: self.metric_points,
+                duplicate_metric_points: self.duplicate_metric_points,
+                ..Dedup::default()
+            },
+        ]
+    }
+
+    /// Approximate memory held by the keys, in bytes.
+    pub fn bytes(&self) -> usize {
+        self.span_bytes + self.log_records.bytes + self.metric_points.bytes
+    }
+
+    /// Drop spans that are already stored or repeated within the batch.
+    /// ResourceSpans left without spans are dropped as well.
+    pub fn filter_traces(&mut self, resource_spans: &mut Vec<ResourceSpans>) {
+        let (seen, bytes) = (&mut self.spans, &mut self.span_bytes);
+        let duplicates = &mut self.duplicate_spans;
+        resource_spans.retain_mut(|rs| {
+            let before = span_count(rs);
+            for ss in &mut rs.scope_spans {
+                ss.spans.retain(|span| {
+                    insert_span_key(seen, bytes, (span.trace_id.clone(), span.span_id.clone()))
+                });
+            }
+            let after = span_count(rs);
+            *duplicates += (before - after) as u64;
+            after > 0 || before == 0
+        });
+    }
+
+    /// Drop log records that are already stored or repeated within the
+    /// batch. ResourceLogs left without records are dropped as well.
+    pub fn filter_logs(&mut self, resource_logs: &mut Vec<ResourceLogs>) {
+        let (seen, duplicates) = (&mut self.log_records, &mut self.duplicate_log_records);
+        resource_logs.retain_mut(|rl| {
+            let before = log_record_count(rl);
+            for sl in &mut rl.scope_logs {
+                let scope = seen.scope(scope_key(&rl.resource, &sl.scope, ""));
+                sl.log_records
+                    .retain(|lr| seen.insert(&scope, lr.encode_to_vec()));
+                seen.release(scope);
+            }
+            let after = log_record_count(rl);
+            *duplicates += (before - after) as u64;
+            after > 0 || before == 0
+        });
+    }
+
+    /// Drop metric data points that are already stored or repeated within the
+    /// batch. ResourceMetrics left without data points are dropped as well.
+    pub fn filter_metrics(&mut self, resource_metrics: &mut Vec<ResourceMetrics>) {
+        let (seen, duplicates) = (&mut self.metric_points, &mut self.duplicate_metric_points);
+        resource_metrics.retain_mut(|rm| {
+            let before = metric_point_count(rm);
+            for sm in &mut rm.scope_metrics {
+                for m in &mut sm.metrics {
+                    let scope = seen.scope(scope_key(&rm.resource, &sm.scope, &m.name));
+                    let mut keep = |item| seen.insert(&scope, item);
+                    match &mut m.data {
+                        Some(metric::Data::Gauge(g)) => {
+                            retain_points(&mut g.data_points, &mut keep)
+                        }
+                        Some(metric::Data::Sum(s)) => retain_points(&mut s.data_points, &mut keep),
+                        Some(metric::Data::Histogram(h)) => {
+                            retain_points(&mut h.data_points, &mut keep)
+                        }
+                        Some(metric::Data::ExponentialHistogram(eh)) => {
+                            retain_points(&mut eh.data_points, &mut keep)
+                        }
+                        Some(metric::Data::Summary(s)) => {
+                            retain_points(&mut s.data_points, &mut keep)
+                        }
+                        None => {}
+                    }
+                    seen.release(scope);
+                }
+            }
+            let after = metric_point_count(rm);
+            *duplicates += (before - after) as u64;
+            after > 0 || before == 0
+        });
+    }
+
+    /// Record stored data without filtering it, e.g. what was stored before
+    /// deduplication was enabled.
+    pub fn remember(
+        &mut self,
+        traces: &[&ResourceSpans],
+        logs: &[&ResourceLogs],
+        metrics: &[&ResourceMetrics],
+    ) {
+        for rs in traces {
+            for key in span_keys(rs) {
+                insert_span_key(&mut self.spans, &mut self.span_bytes, key);
+            }
+        }
+        for rl in logs {
+            self.log_records.remember(log_record_keys(rl));
+        }
+        for rm in metrics {
+            self.metric_points.remember(metric_point_keys(rm));
+        }
+    }
+
+    pub fn forget_traces<'a>(&mut self, removed: impl IntoIterator<Item = &'a ResourceSpans>) {
+        for rs in removed {
+            for key in span_keys(rs) {
+                let size = span_key_size(&key);
+                if self.spans.remove(&key) {
+                    self.span_bytes -= size;
+                }
+            }
+        }
+    }
+
+    pub fn forget_logs<'a>(&mut self, removed: impl IntoIterator<Item = &'a ResourceLogs>) {
+        for rl in removed {
+            self.log_records.forget(log_record_keys(rl));
+        }
+    }
+
+    pub fn forget_metrics<'a>(&mut self, removed: impl IntoIterator<Item = &'a ResourceMetrics>) {
+        for rm in removed {
+            self.metric_points.forget(metric_point_keys(rm));
+        }
+    }
+
+    pub fn clear_traces(&mut self) {
+        self.spans.clear();
+        self.span_bytes = 0;
+    }
+
+    pub fn clear_logs(&mut self) {
+        self.log_records.clear();
+    }
+
+    pub fn clear_metrics(&mut self) {
+        self.metric_points.clear();
+    }
+}
+
+fn span_key_size(key: &SpanKey) -> usize {
+    size_of::<SpanKey>() + key.0.len() + key.1.len()
+}
+
+/// Add a span key, returning whether it was new.
+fn insert_span_key(seen: &mut HashSet<SpanKey>, bytes: &mut usize, key: SpanKey) -> bool {
+    let size = span_key_size(&key);
+    let new = seen.insert(key);
+    if new {
+        *bytes += size;
+    }
+    new
+}
+
+fn span_count(rs: &ResourceSpans) -> usize {
+    rs.scope_spans.iter().map(|ss| ss.spans.len()).sum()
+}
+
+fn log_record_count(rl: &ResourceLogs) -> usize {
+    rl.scope_logs.iter().map(|sl| sl.log_records.len()).sum()
+}
+
+fn metric_point_count(rm: &ResourceMetrics) -> usize {
+    let mut count = 0;
+    super::for_each_data_point_time(rm, |_| count += 1);
+    count
+}
+
+fn span_keys(rs: &ResourceSpans) -> impl Iterator<Item = SpanKey> + '_ {
+    rs.scope_spans
+        .iter()
+        .flat_map(|ss| &ss.spans)
+        .map(|span| (span.trace_id.clone(), span.span_id.clone()))
+}
+
+fn log_record_keys(rl: &ResourceLogs) -> KeyGroups {
+    rl.scope_logs
+        .iter()
+        .map(|sl| {
+            let items = sl.log_records.iter().map(Message::encode_to_vec).collect();
+            (scope_key(&rl.resource, &sl.scope, ""), items)
+        })
+        .collect()
+}
+
+fn metric_point_keys(rm: &ResourceMetrics) -> KeyGroups {
+    let mut groups = Vec::new();
+    for sm in &rm.scope_metrics {
+        for m in &sm.metrics {
+            groups.push((scope_key(&rm.resource, &sm.scope, &m.name), point_keys(m)));
+        }
+    }
+    groups
+}
+
+fn point_keys(m: &Metric) -> Vec<Vec<u8>> {
+    fn encode<P: Message>(points: &[P]) -> Vec<Vec<u8>> {
+        points.iter().map(Message::encode_to_vec).collect()
+    }
+    match &m.data {
+        Some(metric::Data::Gauge(g)) => encode(&g.data_points),
+        Some(metric::Data::Sum(s)) => encode(&s.data_points),
+        Some(metric::Data::Histogram(h)) => encode(&h.data_points),
+        Some(metric::Data::ExponentialHistogram(eh)) => encode(&eh.data_points),
+        Some(metric::Data::Summary(s)) => encode(&s.data_points),
+        None => Vec::new(),
+    }
+}
+
+fn retain_points<P: Message>(points: &mut Vec<P>, keep: &mut impl FnMut(Vec<u8>) -> bool) {
+    points.retain(|p| keep(p.encode_to_vec()));
+}
+
+/// The resource, scope and metric name an item belongs to, encoded so that
+/// different values never give the same bytes.
+fn scope_key(
+    resource: &Option<Resource>,
+    scope: &Option<InstrumentationScope>,
+    name: &str,
+) -> Vec<u8> {
+    let mut key = Vec::new();
+    for part in [
+        resource.as_ref().map(Message::encode_to_vec),
+        scope.as_ref().map(Message::encode_to_vec),
+    ] {
+        match part {
+            Some(bytes) => {
+                key.push(1);
+                key.extend((bytes.len() as u64).to_le_bytes());
+                key.extend(bytes);
+            }
+            None => key.push(0),
+        }
+    }
+    key.extend(name.as_bytes());
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::opentelemetry::proto::{
+        logs::v1::{LogRecord, ScopeLogs},
+        metrics::v1::{Gauge, Metric, NumberDataPoint, ScopeMetrics},
+        trace::v1::{ScopeSpans, Span},
+    };
+
+    fn rs(span_ids: &[u8]) -> ResourceSpans {
+        ResourceSpans {
+            scope_spans: vec![ScopeSpans {
+                spans: span_ids
+                    .iter()
+                    .map(|id| Span {
+                        trace_id: vec![1; 16],
+                        span_id: vec![*id; 8],
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn rl(times: &[u64]) -> ResourceLogs {
+        ResourceLogs {
+            scope_logs: vec![ScopeLogs {
+                log_records: times
+                    .iter()
+                    .map(|t| LogRecord {
+                        time_unix_nano: *t,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn rm(name: &str, times: &[u64]) -> ResourceMetrics {
+        ResourceMetrics {
+            scope_metrics: vec![ScopeMetrics {
+                metrics: vec![Metric {
+                    name: name.into(),
+                    data: Some(metric::Data::Gauge(Gauge {
+                        data_points: times
+                            .iter()
+                            .map(|t| NumberDataPoint {
+                                time_unix_nano: *t,
+                                ..Default::default()
+                            })
+                            .collect(),
+                    })),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_filters_retransmitted_data() {
+        let mut dedup = Dedup::default();
+
+        let mut traces = vec![rs(&[1, 2])];
+        dedup.filter_traces(&mut traces);
+        assert_eq!(traces, vec![rs(&[1, 2])]);
+        let mut traces = vec![rs(&[2, 3]), rs(&[1])];
+        dedup.filter_traces(&mut traces);
+        assert_eq!(traces, vec![rs(&[3])]);
+        assert_eq!(dedup.duplicate_spans(), 2);
+
+        let mut logs = vec![rl(&[1, 2]), rl(&[2])];
+        dedup.filter_logs(&mut logs);
+        assert_eq!(logs, vec![rl(&[1, 2])]);
+        assert_eq!(dedup.duplicate_log_records(), 1);
+
+        // The same point under another metric name is not a duplicate.
+        let mut metrics = vec![rm("cpu", &[1]), rm("mem", &[1]), rm("cpu", &[1, 2])];
+        dedup.filter_metrics(&mut metrics);
+        assert_eq!(
+            metrics,
+            vec![rm("cpu", &[1]), rm("mem", &[1]), rm("cpu", &[2])]
+        );
+        assert_eq!(dedup.duplicate_metric_points(), 1);
+    }
+
+    #[test]
+    fn test_forgotten_data_is_accepted_again() {
+        let mut dedup = Dedup::default();
+        dedup.remember(&[&rs(&[1])], &[&rl(&[1])], &[]);
+
+        let mut logs = vec![rl(&[1])];
+        dedup.filter_logs(&mut logs);
+        assert!(logs.is_empty());
+        dedup.forget_logs([&rl(&[1])]);
+        let mut logs = vec![rl(&[1])];
+        dedup.filter_logs(&mut logs);
+        assert_eq!(logs.len(), 1);
+
+        dedup.forget_traces(&[rs(&[1])]);
+        let mut traces = vec![rs(&[1])];
+        dedup.filter_traces(&mut traces);
+        assert_eq!(traces.len(), 1);
+        assert_eq!(dedup.duplicate_spans(), 0);
+    }
+
+    #[test]
+    fn test_bytes_follow_stored_keys() {
+        let mut dedup = Dedup::default();
+        let mut metrics = vec![rm("cpu", &[1, 2])];
+        dedup.filter_metrics(&mut metrics);
+        dedup.filter_logs(&mut vec![rl(&[1])]);
+        dedup.filter_traces(&mut vec![rs(&[1])]);
+        assert!(dedup.bytes() > 0);
+
+        // Duplicates alone don't keep their scope around.
+        dedup.filter_logs(&mut vec![rl(&[1])]);
+        dedup.forget_metrics(&metrics);
+        dedup.forget_logs([&rl(&[1])]);
+        dedup.forget_traces([&rs(&[1])]);
+        assert_eq!(dedup.bytes(), 0);
+        assert!(dedup.metric_points.scopes.is_empty());
+        assert!(dedup.log_records.scopes.is_empty());
+    }
+}
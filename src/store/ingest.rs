@@ -0,0 +1,282 @@
+//! Ingestion path used by every receiver of `otel-cli server`.
+//!
+//! Receivers hand their batches to an [`Ingester`] instead of taking a store
+//! write lock, so a long SQL scan or a `view` client holding a read lock does
+//! not stall exporters. Every signal has its own lock, queue and worker task.
+//! A batch is inserted right away when its signal's lock is free and nothing
+//! of that signal is queued. Otherwise it is queued for the worker, which
+//! merges everything waiting and inserts it under a single write lock. A
+//! reader of one signal never holds up inserts of the others.
+//!
+//! The queues are bounded: when a signal stays locked long enough for its
+//! queue to fill up, receivers of that signal wait for room, which pushes
+//! back on exporters instead of growing memory without limit.
+//!
+//! Inserting returns once a batch is queued, so an OTLP success response means
+//! the data was accepted, not that queries already see it. Receivers whose
+//! protocol promises stored data (Fluent Forward acks) call
+//! [`Ingester::flush`] before answering.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, Notify};
+
+use crate::proto::opentelemetry::proto::{
+    logs::v1::ResourceLogs, metrics::v1::ResourceMetrics, trace::v1::ResourceSpans,
+};
+
+use super::{Shard, SharedStore, StorageBackend};
+
+/// Batches of one signal that can wait for the worker before receivers have
+/// to.
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Most batches merged into one write.
+const MAX_BATCHES_PER_WRITE: usize = 256;
+
+/// Inserts a batch of one signal.
+type Insert<T> = fn(&mut dyn StorageBackend, Vec<T>);
+
+#[derive(Default)]
+struct QueueState {
+    /// Batches queued but not yet inserted.
+    pending: AtomicUsize,
+    /// Notified when `pending` drops to zero.
+    drained: Notify,
+}
+
+/// The queue of one signal, in front of that signal's store.
+struct Queue<T> {
+    store: Shard,
+    tx: mpsc::Sender<Vec<T>>,
+    state: Arc<QueueState>,
+    insert: Insert<T>,
+}
+
+impl<T> Clone for Queue<T> {
+    fn clone(&self) -> Self {
+        Queue {
+            store: self.store.clone(),
+            tx: self.tx.clone(),
+            state: self.state.clone(),
+            insert: self.insert,
+        }
+    }
+}
+
+impl<T: Send + 'static> Queue<T> {
+    fn new(store: Shard, insert: Insert<T>) -> Self {
+        let (tx, rx) = mpsc::channel(QUEUE_CAPACITY);
+        let state = Arc::new(QueueState::default());
+        tokio::spawn(run_worker(store.clone(), rx, state.clone(), insert));
+        Queue {
+            store,
+            tx,
+            state,
+            insert,
+        }
+    }
+
+    async fn submit(&self, batch: Vec<T>) {
+        // Skipping the queue while it is empty keeps batches in order and an
+        // idle server as fast as a plain insert.
+        if self.state.pending.load(Ordering::Acquire) == 0 {
+            if let Ok(mut store) = self.store.try_write() {
+                (self.insert)(&mut *store, batch);
+                return;
+            }
+        }
+        // Count the batch only once it has a slot, so a receiver dropped while
+        // waiting for room leaves `pending` as it was.
+        match self.tx.reserve().await {
+            Ok(permit) => {
+                self.state.pending.fetch_add(1, Ordering::AcqRel);
+                permit.send(batch);
+            }
+            // The worker is gone (it panicked); insert inline rather than drop data.
+            Err(_) => (self.insert)(&mut *self.store.write().await, batch),
+        }
+    }
+
+    async fn flush(&self) {
+        loop {
+            let drained = self.state.drained.notified();
+            if self.state.pending.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            drained.await;
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Ingester {
+    traces: Queue<ResourceSpans>,
+    logs: Queue<ResourceLogs>,
+    metrics: Queue<ResourceMetrics>,
+}
+
+impl Ingester {
+    /// Create an ingester for `store` and spawn its workers, which exit once
+    /// every clone of the ingester is dropped.
+    pub fn new(store: SharedStore) -> Self {
+        Ingester {
+            traces: Queue::new(store.traces, |s, items| s.insert_traces(items)),
+            logs: Queue::new(store.logs, |s, items| s.insert_logs(items)),
+            metrics: Queue::new(store.metrics, |s, items| s.insert_metrics(items)),
+        }
+    }
+
+    pub async fn insert_traces(&self, resource_spans: Vec<ResourceSpans>) {
+        self.traces.submit(resource_spans).await;
+    }
+
+    pub async fn insert_logs(&self, resource_logs: Vec<ResourceLogs>) {
+        self.logs.submit(resource_logs).await;
+    }
+
+    pub async fn insert_metrics(&self, resource_metrics: Vec<ResourceMetrics>) {
+        self.metrics.submit(resource_metrics).await;
+    }
+
+    /// Wait until every queued batch is in the store.
+    pub async fn flush(&self) {
+        self.traces.flush().await;
+        self.logs.flush().await;
+        self.metrics.flush().await;
+    }
+}
+
+async fn run_worker<T>(
+    store: Shard,
+    mut rx: mpsc::Receiver<Vec<T>>,
+    state: Arc<QueueState>,
+    insert: Insert<T>,
+) {
+    let mut batches = Vec::with_capacity(MAX_BATCHES_PER_WRITE);
+    while rx.recv_many(&mut batches, MAX_BATCHES_PER_WRITE).await > 0 {
+        let count = batches.len();
+        let items = batches.drain(..).flatten().collect();
+        insert(&mut *store.write().await, items);
+
+        tracing::debug!(batches = count, "inserted queued batches");
+        if state.pending.fetch_sub(count, Ordering::AcqRel) == count {
+            state.drained.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::opentelemetry::proto::{
+        logs::v1::{LogRecord, ScopeLogs},
+        trace::v1::{ScopeSpans, Span},
+    };
+    use crate::store;
+    use std::time::Duration;
+
+    fn logs(time: u64) -> Vec<ResourceLogs> {
+        vec![ResourceLogs {
+            scope_logs: vec![ScopeLogs {
+                log_records: vec![LogRecord {
+                    time_unix_nano: time,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }]
+    }
+
+    #[tokio::test]
+    async fn test_inserts_directly_when_unlocked() {
+        let (store, _rx) = store::new_shared(100, 100, 100, 100);
+        let ingester = Ingester::new(store.clone());
+        ingester.insert_logs(logs(1)).await;
+        assert_eq!(store.logs.read().await.log_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_queues_while_store_is_read() {
+        let (store, mut rx) = store::new_shared(100, 100, 100, 100);
+        let ingester = Ingester::new(store.clone());
+
+        let reader = store.logs.read().await;
+        for time in 1..=3 {
+            tokio::time::timeout(Duration::from_secs(1), ingester.insert_logs(logs(time)))
+                .await
+                .expect("insert must not wait for the reader");
+        }
+        assert_eq!(reader.log_count(), 0);
+        drop(reader);
+
+        ingester.flush().await;
+        assert_eq!(store.logs.read().await.log_count(), 3);
+        // The queued batches were merged into one insert.
+        assert_eq!(rx.recv().await.unwrap(), store::StoreEvent::LogsAdded(1..4));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_insert_does_not_block_flush() {
+        let (store, _rx) = store::new_shared(100, 100, 10 * QUEUE_CAPACITY, 100);
+        let ingester = Ingester::new(store.clone());
+
+        // Insert until the queue is full and an insert waits for room, then
+        // give up on that one.
+        let reader = store.logs.read().await;
+        let mut queued = 0;
+        while tokio::time::timeout(Duration::from_millis(50), ingester.insert_logs(logs(1)))
+            .await
+            .is_ok()
+        {
+            queued += 1;
+        }
+        assert!(queued >= QUEUE_CAPACITY);
+        drop(reader);
+
+        tokio::time::timeout(Duration::from_secs(5), ingester.flush())
+            .await
+            .expect("flush must not wait for the dropped insert");
+        assert_eq!(store.logs.read().await.log_count(), queued);
+    }
+
+    #[tokio::test]
+    async fn test_trace_reader_does_not_stall_logs() {
+        let (store, _rx) = store::new_shared(100, 100, 10 * QUEUE_CAPACITY, 100);
+        let ingester = Ingester::new(store.clone());
+
+        let reader = store.traces.read().await;
+        let spans = vec![ResourceSpans {
+            scope_spans: vec![ScopeSpans {
+                spans: vec![Span {
+                    trace_id: vec![1; 16],
+                    span_id: vec![1; 8],
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }];
+        ingester.insert_traces(spans).await;
+
+        // With traces queued behind the reader, more log batches than a queue
+        // holds still go in.
+        let batches = 2 * QUEUE_CAPACITY;
+        tokio::time::timeout(Duration::from_secs(5), async {
+            for time in 0..batches {
+                ingester.insert_logs(logs(time as u64)).await;
+            }
+        })
+        .await
+        .expect("log inserts must not wait for the trace reader");
+        assert_eq!(store.logs.read().await.log_count(), batches);
+        assert_eq!(reader.trace_count(), 0);
+        drop(reader);
+
+        ingester.flush().await;
+        assert_eq!(store.traces.read().await.trace_count(), 1);
+    }
+}
@@ -0,0 +1,1707 @@
+pub mod backend;
+pub mod dedup;
+pub mod ingest;
+pub mod persist;
+pub mod retention;
+pub mod sequenced;
+pub mod trace_index;
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, Weak};
+use tokio::sync::{broadcast, RwLock};
+use tracing::instrument;
+
+use crate::proto::opentelemetry::proto::{
+    logs::v1::ResourceLogs,
+    metrics::v1::{metric, Metric, ResourceMetrics},
+    trace::v1::ResourceSpans,
+};
+pub use backend::{DedupStats, RowId, Scan, SeqScan, StorageBackend, TimeBounds};
+use dedup::Dedup;
+use persist::Persistence;
+pub use persist::Signal;
+use prost::Message;
+use sequenced::Sequenced;
+use trace_index::TraceIndex;
+
+/// `*Added` events carry the ingest sequence numbers given to the batch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StoreEvent {
+    TracesAdded(Range<u64>),
+    LogsAdded(Range<u64>),
+    MetricsAdded(Range<u64>),
+    TracesCleared,
+    LogsCleared,
+    MetricsCleared,
+    TracesExpired,
+    LogsExpired,
+    MetricsExpired,
+    TracesEvicted,
+    LogsEvicted,
+    MetricsEvicted,
+    TracesDeleted,
+    LogsDeleted,
+    MetricsDeleted,
+}
+
+pub struct Store {
+    traces: TraceIndex,
+    logs: Sequenced<ResourceLogs>,
+    metrics: Sequenced<ResourceMetrics>,
+    /// Ingest sequence number of the next stored ResourceSpans,
+    /// ResourceLogs or ResourceMetrics. Starts at 1 and is shared by all
+    /// signals the store holds.
+    next_seq: u64,
+    max_traces: usize,
+    max_spans: usize,
+    max_logs: usize,
+    max_metrics: usize,
+    /// Encoded protobuf size of the stored data, per signal.
+    trace_bytes: usize,
+    log_bytes: usize,
+    metric_bytes: usize,
+    max_memory: usize,
+    event_tx: broadcast::Sender<StoreEvent>,
+    persist: Option<Persistence>,
+    dedup: Option<Dedup>,
+    /// Set on the stores of a [`SharedStore`]: the one signal this store
+    /// holds and the memory budget it shares with the other signals.
+    shard: Option<(Signal, Arc<MemoryBudget>)>,
+}
+
+/// The storage of one signal in a [`SharedStore`].
+pub type Shard = Arc<RwLock<dyn StorageBackend>>;
+
+/// Stored telemetry shared between the servers, the query API and the TUI.
+///
+/// Each signal has its own backend behind its own lock, so a long scan of
+/// one signal never holds up inserts of the others.
+#[derive(Clone)]
+pub struct SharedStore {
+    pub traces: Shard,
+    pub logs: Shard,
+    pub metrics: Shard,
+    event_tx: broadcast::Sender<StoreEvent>,
+}
+
+impl SharedStore {
+    /// Events for changes to any of the signals.
+    pub fn subscribe(&self) -> broadcast::Receiver<StoreEvent> {
+        self.event_tx.subscribe()
+    }
+
+    pub fn shard(&self, signal: Signal) -> &Shard {
+        match signal {
+            Signal::Traces => &self.traces,
+            Signal::Logs => &self.logs,
+            Signal::Metrics => &self.metrics,
+        }
+    }
+
+    /// Snapshot each signal in turn, so only one is locked at a time.
+    pub async fn snapshot(&self) -> anyhow::Result<()> {
+        for shard in [&self.traces, &self.logs, &self.metrics] {
+            shard.write().await.snapshot()?;
+        }
+        Ok(())
+    }
+}
+
+/// The `--max-memory` budget shared by the stores of a [`SharedStore`].
+struct MemoryBudget {
+    max: usize,
+    /// [`Store::memory_used`] of each signal's store, in [`Signal::index`]
+    /// order, as of its last insert or eviction.
+    used: [AtomicUsize; 3],
+    /// Each signal's store, to evict from whichever uses the most.
+    stores: OnceLock<[Weak<RwLock<dyn StorageBackend>>; 3]>,
+}
+
+pub fn rs_sort_key(rs: &ResourceSpans) -> u64 {
+    rs.scope_spans
+        .iter()
+        .flat_map(|ss| ss.spans.iter().map(|s| s.start_time_unix_nano))
+        .min()
+        .unwrap_or(0)
+}
+
+/// Returns the effective timestamp for a log record,
+/// falling back to observed_time_unix_nano when time_unix_nano is not set.
+pub fn log_timestamp(lr: &crate::proto::opentelemetry::proto::logs::v1::LogRecord) -> u64 {
+    if lr.time_unix_nano > 0 {
+        lr.time_unix_nano
+    } else {
+        lr.observed_time_unix_nano
+    }
+}
+
+pub fn log_sort_key(rl: &ResourceLogs) -> u64 {
+    rl.scope_logs
+        .iter()
+        .flat_map(|sl| sl.log_records.iter().map(log_timestamp))
+        .min()
+        .unwrap_or(0)
+}
+
+pub fn metric_sort_key(rm: &ResourceMetrics) -> u64 {
+    let mut min_ts = u64::MAX;
+    for_each_data_point_time(rm, |t| min_ts = min_ts.min(t));
+    if min_ts == u64::MAX {
+        0
+    } else {
+        min_ts
+    }
+}
+
+fn for_each_data_point_time(rm: &ResourceMetrics, mut f: impl FnMut(u64)) {
+    for sm in &rm.scope_metrics {
+        for m in &sm.metrics {
+            match &m.data {
+                Some(metric::Data::Gauge(g)) => {
+                    for dp in &g.data_points {
+                        f(dp.time_unix_nano);
+                    }
+                }
+                Some(metric::Data::Sum(s)) => {
+                    for dp in &s.data_points {
+                        f(dp.time_unix_nano);
+                    }
+                }
+                Some(metric::Data::Histogram(h)) => {
+                    for dp in &h.data_points {
+                        f(dp.time_unix_nano);
+                    }
+                }
+                Some(metric::Data::ExponentialHistogram(eh)) => {
+                    for dp in &eh.data_points {
+                        f(dp.time_unix_nano);
+                    }
+                }
+                Some(metric::Data::Summary(s)) => {
+                    for dp in &s.data_points {
+                        f(dp.time_unix_nano);
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+/// Keep the data points for which `keep` returns true, calling it once per
+/// data point in order (one per row of the `metrics` table).
+pub fn retain_data_points(metric: &mut Metric, mut keep: impl FnMut() -> bool) {
+    match &mut metric.data {
+        Some(metric::Data::Gauge(g)) => g.data_points.retain(|_| keep()),
+        Some(metric::Data::Sum(s)) => s.data_points.retain(|_| keep()),
+        Some(metric::Data::Histogram(h)) => h.data_points.retain(|_| keep()),
+        Some(metric::Data::ExponentialHistogram(h)) => h.data_points.retain(|_| keep()),
+        Some(metric::Data::Summary(s)) => s.data_points.retain(|_| keep()),
+        None => {}
+    }
+}
+
+/// Number of data points in `metric`.
+pub fn data_point_count(metric: &Metric) -> usize {
+    match &metric.data {
+        Some(metric::Data::Gauge(g)) => g.data_points.len(),
+        Some(metric::Data::Sum(s)) => s.data_points.len(),
+        Some(metric::Data::Histogram(h)) => h.data_points.len(),
+        Some(metric::Data::ExponentialHistogram(h)) => h.data_points.len(),
+        Some(metric::Data::Summary(s)) => s.data_points.len(),
+        None => 0,
+    }
+}
+
+/// `rows` grouped by batch: each sequence number with the sorted indexes of
+/// its rows.
+fn rows_by_seq(rows: &BTreeSet<RowId>) -> Vec<(u64, Vec<usize>)> {
+    let mut groups: Vec<(u64, Vec<usize>)> = Vec::new();
+    for row in rows {
+        match groups.last_mut() {
+            Some((seq, indexes)) if *seq == row.seq => indexes.push(row.index),
+            _ => groups.push((row.seq, vec![row.index])),
+        }
+    }
+    groups
+}
+
+/// A `retain` predicate, called once per item in order, that drops the items
+/// at the sorted `indexes` and counts them in `removed`.
+fn drop_indexes<'a>(indexes: &'a [usize], removed: &'a mut usize) -> impl FnMut() -> bool + 'a {
+    let mut index = 0;
+    move || {
+        let drop = indexes.binary_search(&index).is_ok();
+        index += 1;
+        *removed += usize::from(drop);
+        !drop
+    }
+}
+
+/// Split `items` into groups whose encoded size stays below `max_bytes`, so
+/// that each group fits in one gRPC message. An item larger than `max_bytes`
+/// gets a group of its own. Groups are built as the iterator is advanced.
+pub fn batches<T: prost::Message>(items: Vec<T>, max_bytes: usize) -> impl Iterator<Item = Vec<T>> {
+    let mut items = items.into_iter().peekable();
+    std::iter::from_fn(move || {
+        let mut batch = Vec::new();
+        let mut size = 0;
+        while let Some(len) = items.peek().map(prost::Message::encoded_len) {
+            if !batch.is_empty() && size + len > max_bytes {
+                break;
+            }
+            size += len;
+            batch.extend(items.next());
+        }
+        (!batch.is_empty()).then_some(batch)
+    })
+}
+
+pub fn severity_text_to_number(text: &str) -> Option<i32> {
+    match text.to_ascii_uppercase().as_str() {
+        "TRACE" => Some(1),
+        "DEBUG" => Some(5),
+        "INFO" => Some(9),
+        "WARN" | "WARNING" => Some(13),
+        "ERROR" => Some(17),
+        "FATAL" => Some(21),
+        _ => text.parse::<i32>().ok(),
+    }
+}
+
+impl Store {
+    pub fn new(
+        max_traces: usize,
+        max_spans: usize,
+        max_logs: usize,
+        max_metrics: usize,
+    ) -> (Self, broadcast::Receiver<StoreEvent>) {
+        let (event_tx, event_rx) = broadcast::channel(256);
+        let store = Store {
+            traces: TraceIndex::default(),
+            logs: Sequenced::default(),
+            metrics: Sequenced::default(),
+            next_seq: 1,
+            max_traces,
+            max_spans,
+            max_logs,
+            max_metrics,
+            trace_bytes: 0,
+            log_bytes: 0,
+            metric_bytes: 0,
+            max_memory: usize::MAX,
+            event_tx,
+            persist: None,
+            dedup: None,
+            shard: None,
+        };
+        (store, event_rx)
+    }
+
+    /// Create a store backed by `data_dir`, reloading whatever an earlier run
+    /// persisted there. The reloaded data goes through the normal insert path,
+    /// so the `max_*` limits apply to it as well.
+    pub fn open(
+        data_dir: &Path,
+        max_traces: usize,
+        max_spans: usize,
+        max_logs: usize,
+        max_metrics: usize,
+    ) -> anyhow::Result<(Self, broadcast::Receiver<StoreEvent>)> {
+        let persist = Persistence::open(data_dir)?;
+        let (mut store, event_rx) = Store::new(max_traces, max_spans, max_logs, max_metrics);
+        store.insert_traces(persist.load(Signal::Traces)?);
+        store.insert_logs(persist.load(Signal::Logs)?);
+        store.insert_metrics(persist.load(Signal::Metrics)?);
+        // Subscribers should only see data that arrives after the load.
+        let event_rx = event_rx.resubscribe();
+        tracing::info!(
+            traces = store.trace_count(),
+            logs = store.log_count(),
+            metrics = store.metric_count(),
+            data_dir = %data_dir.display(),
+            "store loaded"
+        );
+
+        // Rewrite the files so data evicted while loading leaves the disk.
+        store.persist = Some(persist);
+        for signal in [Signal::Traces, Signal::Logs, Signal::Metrics] {
+            store.compact(signal)?;
+        }
+        Ok((store, event_rx))
+    }
+
+    pub fn all_traces(&self) -> Vec<&ResourceSpans> {
+        self.traces.resource_spans().collect()
+    }
+
+    /// Every ResourceSpans holding a span of `trace_id`, in start time order.
+    pub fn get_trace(&self, trace_id: &[u8]) -> Vec<&ResourceSpans> {
+        self.traces.trace(trace_id)
+    }
+
+    pub fn all_logs(&self) -> Vec<&ResourceLogs> {
+        self.logs.items().collect()
+    }
+
+    pub fn all_metrics(&self) -> Vec<&ResourceMetrics> {
+        self.metrics.items().collect()
+    }
+
+    fn take_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    fn persist_batch<T: prost::Message>(&mut self, signal: Signal, items: &[T]) {
+        if let Some(persist) = &mut self.persist {
+            if let Err(e) = persist.append(signal, items) {
+                tracing::warn!(?signal, error = %e, "failed to persist batch");
+            }
+        }
+    }
+
+    fn compact_if_needed(&mut self, signal: Signal) {
+        let live = match signal {
+            Signal::Traces => self.traces.len(),
+            Signal::Logs => self.logs.len(),
+            Signal::Metrics => self.metrics.len(),
+        };
+        if self
+            .persist
+            .as_ref()
+            .is_some_and(|p| p.needs_compaction(signal, live))
+        {
+            if let Err(e) = self.compact(signal) {
+                tracing::warn!(?signal, error = %e, "failed to compact store");
+            }
+        }
+    }
+
+    /// Rewrite the on-disk data of `signal` from memory. A no-op without
+    /// `--data-dir`.
+    fn compact(&mut self, signal: Signal) -> anyhow::Result<()> {
+        let Some(persist) = &mut self.persist else {
+            return Ok(());
+        };
+        match signal {
+            Signal::Traces => persist.compact(signal, self.traces.resource_spans()),
+            Signal::Logs => persist.compact(signal, self.logs.items()),
+            Signal::Metrics => persist.compact(signal, self.metrics.items()),
+        }
+    }
+
+    fn evict_oldest_trace(&mut self) {
+        let removed = self.traces.evict_oldest();
+        self.trace_bytes -= removed.iter().map(|rs| rs.encoded_len()).sum::<usize>();
+        if let Some(dedup) = &mut self.dedup {
+            dedup.forget_traces(&removed);
+        }
+    }
+
+    fn evict_oldest_log(&mut self) {
+        if let Some(rl) = self.logs.pop_front() {
+            self.log_bytes -= rl.encoded_len();
+            if let Some(dedup) = &mut self.dedup {
+                dedup.forget_logs([&rl]);
+            }
+        }
+    }
+
+    fn evict_oldest_metric(&mut self) {
+        if let Some(rm) = self.metrics.pop_front() {
+            self.metric_bytes -= rm.encoded_len();
+            if let Some(dedup) = &mut self.dedup {
+                dedup.forget_metrics([&rm]);
+            }
+        }
+    }
+
+    /// Evict the oldest data of `signal`, returning false when there is none.
+    fn evict_oldest(&mut self, signal: Signal) -> bool {
+        match signal {
+            Signal::Traces if !self.traces.is_empty() => self.evict_oldest_trace(),
+            Signal::Logs if !self.logs.is_empty() => self.evict_oldest_log(),
+            Signal::Metrics if !self.metrics.is_empty() => self.evict_oldest_metric(),
+            _ => return false,
+        }
+        true
+    }
+
+    /// Drop spans, log records and metric data points that are already
+    /// stored, counting what was dropped. Data stored so far counts as seen.
+    pub fn enable_dedup(&mut self) {
+        let mut dedup = Dedup::default();
+        dedup.remember(
+            &self.traces.resource_spans().collect::<Vec<_>>(),
+            &self.logs.items().collect::<Vec<_>>(),
+            &self.metrics.items().collect::<Vec<_>>(),
+        );
+        self.dedup = Some(dedup);
+    }
+
+    /// Limit the encoded size of everything stored to `max_memory` bytes,
+    /// evicting immediately if the store is already larger.
+    pub fn set_max_memory(&mut self, max_memory: usize) {
+        self.max_memory = max_memory;
+        self.enforce_max_memory(None);
+    }
+
+    /// While over the memory budget, evict the oldest data of whichever
+    /// signal uses the most bytes, so one busy signal cannot starve the
+    /// others. `inserted` is the signal whose insert triggered this; other
+    /// signals that lose data get an `*Evicted` event.
+    fn enforce_max_memory(&mut self, inserted: Option<Signal>) {
+        if let Some((signal, budget)) = self.shard.clone() {
+            if self.enforce_shared_budget(signal, &budget, true) && inserted != Some(signal) {
+                let _ = self.event_tx.send(evicted_event(signal));
+            }
+            return;
+        }
+        let mut evicted = [false; 3];
+        while self.memory_used() > self.max_memory {
+            // Dedup keys go when the data they describe does.
+            if self.traces.is_empty() && self.logs.is_empty() && self.metrics.is_empty() {
+                break;
+            }
+            let (signal, index) =
+                if self.trace_bytes >= self.log_bytes && self.trace_bytes >= self.metric_bytes {
+                    (Signal::Traces, 0)
+                } else if self.log_bytes >= self.metric_bytes {
+                    (Signal::Logs, 1)
+                } else {
+                    (Signal::Metrics, 2)
+                };
+            match signal {
+                Signal::Traces => self.evict_oldest_trace(),
+                Signal::Logs => self.evict_oldest_log(),
+                Signal::Metrics => self.evict_oldest_metric(),
+            }
+            evicted[index] = true;
+            tracing::debug!(?signal, max_memory = self.max_memory, "evicted for memory");
+        }
+        let events = [
+            (Signal::Traces, StoreEvent::TracesEvicted),
+            (Signal::Logs, StoreEvent::LogsEvicted),
+            (Signal::Metrics, StoreEvent::MetricsEvicted),
+        ];
+        for ((signal, event), evicted) in events.into_iter().zip(evicted) {
+            if evicted {
+                self.mark_stale(signal);
+                if inserted != Some(signal) {
+                    let _ = self.event_tx.send(event);
+                }
+            }
+        }
+    }
+
+    /// [`enforce_max_memory`](Self::enforce_max_memory) for the store of one
+    /// signal of a [`SharedStore`]: while the signals together are over the
+    /// budget, evict from whichever uses the most. With `from_others`, that
+    /// may be another signal's store; when its lock is busy this store
+    /// evicts instead, so an insert never waits on another signal.
+    /// Otherwise this store only evicts while it is the largest. Returns
+    /// whether this store evicted anything.
+    fn enforce_shared_budget(
+        &mut self,
+        signal: Signal,
+        budget: &MemoryBudget,
+        from_others: bool,
+    ) -> bool {
+        let mut evicted = false;
+        loop {
+            budget.used[signal.index()].store(self.memory_used(), Ordering::Relaxed);
+            let used = budget.used.each_ref().map(|u| u.load(Ordering::Relaxed));
+            if used.iter().sum::<usize>() <= budget.max {
+                break;
+            }
+            let largest = (0..3).max_by_key(|&i| used[i]).unwrap_or(signal.index());
+            if largest != signal.index() {
+                if !from_others {
+                    break;
+                }
+                let other = budget.stores.get().and_then(|s| s[largest].upgrade());
+                let other_evicted = other
+                    .as_ref()
+                    .and_then(|o| o.try_write().ok())
+                    .is_some_and(|mut o| o.evict_for_budget());
+                if other_evicted {
+                    continue;
+                }
+            }
+            if !self.evict_oldest(signal) {
+                break;
+            }
+            evicted = true;
+            tracing::debug!(?signal, max_memory = budget.max, "evicted for memory");
+        }
+        if evicted {
+            self.mark_stale(signal);
+        }
+        evicted
+    }
+
+    /// Split a store holding all signals into one store per signal, in
+    /// [`Signal::index`] order, sharing the event channel and memory budget.
+    fn into_shards(self) -> ([Store; 3], Arc<MemoryBudget>) {
+        let Store {
+            traces,
+            logs,
+            metrics,
+            next_seq,
+            max_traces,
+            max_spans,
+            max_logs,
+            max_metrics,
+            trace_bytes,
+            log_bytes,
+            metric_bytes,
+            max_memory,
+            event_tx,
+            persist,
+            dedup,
+            shard: _,
+        } = self;
+        let budget = Arc::new(MemoryBudget {
+            max: max_memory,
+            used: Default::default(),
+            stores: OnceLock::new(),
+        });
+        let [trace_files, log_files, metric_files] = match persist {
+            Some(persist) => persist.split().map(Some),
+            None => [None, None, None],
+        };
+        let [trace_keys, log_keys, metric_keys] = match dedup {
+            Some(dedup) => dedup.split().map(Some),
+            None => [None, None, None],
+        };
+        let shard = |signal: Signal, persist, dedup| Store {
+            traces: TraceIndex::default(),
+            logs: Sequenced::default(),
+            metrics: Sequenced::default(),
+            next_seq,
+            max_traces,
+            max_spans,
+            max_logs,
+            max_metrics,
+            trace_bytes: 0,
+            log_bytes: 0,
+            metric_bytes: 0,
+            max_memory,
+            event_tx: event_tx.clone(),
+            persist,
+            dedup,
+            shard: Some((signal, budget.clone())),
+        };
+        let mut shards = [
+            Store {
+                traces,
+                trace_bytes,
+                ..shard(Signal::Traces, trace_files, trace_keys)
+            },
+            Store {
+                logs,
+                log_bytes,
+                ..shard(Signal::Logs, log_files, log_keys)
+            },
+            Store {
+                metrics,
+                metric_bytes,
+                ..shard(Signal::Metrics, metric_files, metric_keys)
+            },
+        ];
+        for (store, used) in shards.iter_mut().zip(&budget.used) {
+            used.store(store.memory_used(), Ordering::Relaxed);
+        }
+        (shards, budget)
+    }
+
+    fn mark_stale(&mut self, signal: Signal) {
+        if let Some(persist) = &mut self.persist {
+            persist.mark_stale(signal);
+        }
+    }
+
+    /// Encoded protobuf size of all stored data plus the size of the dedup
+    /// keys, the figure `--max-memory` limits.
+    pub fn memory_used(&self) -> usize {
+        let dedup_bytes = self.dedup.as_ref().map_or(0, Dedup::bytes);
+        self.trace_bytes + self.log_bytes + self.metric_bytes + dedup_bytes
+    }
+}
+
+impl StorageBackend for Store {
+    fn subscribe(&self) -> broadcast::Receiver<StoreEvent> {
+        self.event_tx.subscribe()
+    }
+
+    #[instrument(name = "store.insert_traces", skip_all, fields(count = resource_spans.len()))]
+    fn insert_traces(&mut self, mut resource_spans: Vec<ResourceSpans>) {
+        if let Some(dedup) = &mut self.dedup {
+            dedup.filter_traces(&mut resource_spans);
+            if resource_spans.is_empty() {
+                return;
+            }
+        }
+        self.persist_batch(Signal::Traces, &resource_spans);
+        let first_seq = self.next_seq;
+        for rs in resource_spans {
+            self.trace_bytes += rs.encoded_len();
+            let seq = self.take_seq();
+            self.traces.insert(rs, seq);
+        }
+        while self.traces.trace_count() > self.max_traces || self.traces.len() > self.max_spans {
+            self.evict_oldest_trace();
+            tracing::debug!(max_traces = self.max_traces, "trace evicted");
+        }
+        self.enforce_max_memory(Some(Signal::Traces));
+        self.compact_if_needed(Signal::Traces);
+        let _ = self
+            .event_tx
+            .send(StoreEvent::TracesAdded(first_seq..self.next_seq));
+    }
+
+    #[instrument(name = "store.insert_logs", skip_all, fields(count = resource_logs.len()))]
+    fn insert_logs(&mut self, mut resource_logs: Vec<ResourceLogs>) {
+        if let Some(dedup) = &mut self.dedup {
+            dedup.filter_logs(&mut resource_logs);
+            if resource_logs.is_empty() {
+                return;
+            }
+        }
+        self.persist_batch(Signal::Logs, &resource_logs);
+        let first_seq = self.next_seq;
+        for rl in resource_logs {
+            let ts = log_sort_key(&rl);
+            self.log_bytes += rl.encoded_len();
+            let seq = self.take_seq();
+            self.logs.insert(ts, rl, seq);
+            if self.logs.len() > self.max_logs {
+                self.evict_oldest_log();
+                tracing::debug!(max_logs = self.max_logs, "log evicted");
+            }
+        }
+        self.enforce_max_memory(Some(Signal::Logs));
+        self.compact_if_needed(Signal::Logs);
+        let _ = self
+            .event_tx
+            .send(StoreEvent::LogsAdded(first_seq..self.next_seq));
+    }
+
+    #[instrument(name = "store.insert_metrics", skip_all, fields(count = resource_metrics.len()))]
+    fn insert_metrics(&mut self, mut resource_metrics: Vec<ResourceMetrics>) {
+        if let Some(dedup) = &mut self.dedup {
+            dedup.filter_metrics(&mut resource_metrics);
+            if resource_metrics.is_empty() {
+                return;
+            }
+        }
+        self.persist_batch(Signal::Metrics, &resource_metrics);
+        let first_seq = self.next_seq;
+        for rm in resource_metrics {
+            let ts = metric_sort_key(&rm);
+            self.metric_bytes += rm.encoded_len();
+            let seq = self.take_seq();
+            self.metrics.insert(ts, rm, seq);
+            if self.metrics.len() > self.max_metrics {
+                self.evict_oldest_metric();
+                tracing::debug!(max_metrics = self.max_metrics, "metric evicted");
+            }
+        }
+        self.enforce_max_memory(Some(Signal::Metrics));
+        self.compact_if_needed(Signal::Metrics);
+        let _ = self
+            .event_tx
+            .send(StoreEvent::MetricsAdded(first_seq..self.next_seq));
+    }
+
+    fn scan_traces_with_seq(&self, bounds: TimeBounds) -> SeqScan<'_, ResourceSpans> {
+        Box::new(
+            self.traces
+                .range(bounds)
+                .map(|(seq, rs)| (seq, Cow::Borrowed(rs))),
+        )
+    }
+
+    fn scan_logs_with_seq(&self, bounds: TimeBounds) -> SeqScan<'_, ResourceLogs> {
+        Box::new(
+            self.logs
+                .range(bounds)
+                .map(|(seq, rl)| (seq, Cow::Borrowed(rl))),
+        )
+    }
+
+    fn scan_metrics_with_seq(&self, bounds: TimeBounds) -> SeqScan<'_, ResourceMetrics> {
+        Box::new(
+            self.metrics
+                .range(bounds)
+                .map(|(seq, rm)| (seq, Cow::Borrowed(rm))),
+        )
+    }
+
+    fn last_seq(&self) -> u64 {
+        self.next_seq - 1
+    }
+
+    fn traces_after(&self, seq: u64) -> Vec<ResourceSpans> {
+        self.traces.after(seq).into_iter().cloned().collect()
+    }
+
+    fn logs_after(&self, seq: u64) -> Vec<ResourceLogs> {
+        self.logs.after(seq).into_iter().cloned().collect()
+    }
+
+    fn metrics_after(&self, seq: u64) -> Vec<ResourceMetrics> {
+        self.metrics.after(seq).into_iter().cloned().collect()
+    }
+
+    fn trace_count(&self) -> usize {
+        self.traces.trace_count()
+    }
+
+    fn log_count(&self) -> usize {
+        self.logs.len()
+    }
+
+    fn metric_count(&self) -> usize {
+        self.metrics.len()
+    }
+
+    fn trace_bytes(&self) -> usize {
+        self.trace_bytes
+    }
+
+    fn log_bytes(&self) -> usize {
+        self.log_bytes
+    }
+
+    fn metric_bytes(&self) -> usize {
+        self.metric_bytes
+    }
+
+    fn max_memory(&self) -> usize {
+        self.max_memory
+    }
+
+    fn evict_for_budget(&mut self) -> bool {
+        let Some((signal, budget)) = self.shard.clone() else {
+            return false;
+        };
+        let evicted = self.enforce_shared_budget(signal, &budget, false);
+        if evicted {
+            let _ = self.event_tx.send(evicted_event(signal));
+        }
+        evicted
+    }
+
+    fn dedup_stats(&self) -> Option<DedupStats> {
+        self.dedup.as_ref().map(|dedup| DedupStats {
+            duplicate_spans: dedup.duplicate_spans(),
+            duplicate_log_records: dedup.duplicate_log_records(),
+            duplicate_metric_points: dedup.duplicate_metric_points(),
+        })
+    }
+
+    #[instrument(name = "store.clear_traces", skip_all)]
+    fn clear_traces(&mut self) {
+        self.traces.clear();
+        self.trace_bytes = 0;
+        if let Some(dedup) = &mut self.dedup {
+            dedup.clear_traces();
+        }
+        if let Err(e) = self.compact(Signal::Traces) {
+            tracing::warn!(error = %e, "failed to clear persisted traces");
+        }
+        let _ = self.event_tx.send(StoreEvent::TracesCleared);
+    }
+
+    #[instrument(name = "store.clear_logs", skip_all)]
+    fn clear_logs(&mut self) {
+        self.logs.clear();
+        self.log_bytes = 0;
+        if let Some(dedup) = &mut self.dedup {
+            dedup.clear_logs();
+        }
+        if let Err(e) = self.compact(Signal::Logs) {
+            tracing::warn!(error = %e, "failed to clear persisted logs");
+        }
+        let _ = self.event_tx.send(StoreEvent::LogsCleared);
+    }
+
+    #[instrument(name = "store.clear_metrics", skip_all)]
+    fn clear_metrics(&mut self) {
+        self.metrics.clear();
+        self.metric_bytes = 0;
+        if let Some(dedup) = &mut self.dedup {
+            dedup.clear_metrics();
+        }
+        if let Err(e) = self.compact(Signal::Metrics) {
+            tracing::warn!(error = %e, "failed to clear persisted metrics");
+        }
+        let _ = self.event_tx.send(StoreEvent::MetricsCleared);
+    }
+
+    #[instrument(name = "store.expire_traces", skip_all)]
+    fn expire_traces(&mut self, cutoff: u64) -> usize {
+        let before = self.traces.trace_count();
+        let removed = self.traces.evict_ended_before(cutoff);
+        if removed.is_empty() {
+            return 0;
+        }
+        self.trace_bytes -= removed.iter().map(|rs| rs.encoded_len()).sum::<usize>();
+        if let Some(dedup) = &mut self.dedup {
+            dedup.forget_traces(&removed);
+        }
+        self.mark_stale(Signal::Traces);
+        let _ = self.event_tx.send(StoreEvent::TracesExpired);
+        before - self.traces.trace_count()
+    }
+
+    #[instrument(name = "store.expire_logs", skip_all)]
+    fn expire_logs(&mut self, cutoff: u64) -> usize {
+        let before = self.logs.len();
+        let log_bytes = &mut self.log_bytes;
+        let dedup = &mut self.dedup;
+        self.logs.retain(|rl| {
+            let keep = rl
+                .scope_logs
+                .iter()
+                .flat_map(|sl| sl.log_records.iter().map(log_timestamp))
+                .any(|t| t >= cutoff);
+            if !keep {
+                *log_bytes -= rl.encoded_len();
+                if let Some(dedup) = dedup {
+                    dedup.forget_logs([rl]);
+                }
+            }
+            keep
+        });
+        let expired = before - self.logs.len();
+        if expired > 0 {
+            self.mark_stale(Signal::Logs);
+            let _ = self.event_tx.send(StoreEvent::LogsExpired);
+        }
+        expired
+    }
+
+    #[instrument(name = "store.expire_metrics", skip_all)]
+    fn expire_metrics(&mut self, cutoff: u64) -> usize {
+        let before = self.metrics.len();
+        let metric_bytes = &mut self.metric_bytes;
+        let dedup = &mut self.dedup;
+        self.metrics.retain(|rm| {
+            let mut max_ts = 0;
+            for_each_data_point_time(rm, |t| max_ts = max_ts.max(t));
+            if max_ts < cutoff {
+                *metric_bytes -= rm.encoded_len();
+                if let Some(dedup) = dedup {
+                    dedup.forget_metrics([rm]);
+                }
+            }
+            max_ts >= cutoff
+        });
+        let expired = before - self.metrics.len();
+        if expired > 0 {
+            self.mark_stale(Signal::Metrics);
+            let _ = self.event_tx.send(StoreEvent::MetricsExpired);
+        }
+        expired
+    }
+
+    #[instrument(name = "store.delete_spans", skip_all)]
+    fn delete_spans(&mut self, rows: &BTreeSet<RowId>) -> usize {
+        let mut removed = 0;
+        for (seq, indexes) in rows_by_seq(rows) {
+            let (trace_bytes, dedup) = (&mut self.trace_bytes, &mut self.dedup);
+            self.traces.edit(seq, |rs| {
+                *trace_bytes -= rs.encoded_len();
+                if let Some(dedup) = dedup.as_mut() {
+                    dedup.forget_traces([&*rs]);
+                }
+                let mut dropped = 0;
+                {
+                    let mut keep = drop_indexes(&indexes, &mut dropped);
+                    for ss in &mut rs.scope_spans {
+                        ss.spans.retain(|_| keep());
+                    }
+                }
+                removed += dropped;
+                if dropped > 0 {
+                    rs.scope_spans.retain(|ss| !ss.spans.is_empty());
+                }
+                // An empty ResourceSpans is dropped by the index.
+                if !rs.scope_spans.is_empty() {
+                    *trace_bytes += rs.encoded_len();
+                    if let Some(dedup) = dedup.as_mut() {
+                        dedup.remember(&[&*rs], &[], &[]);
+                    }
+                }
+            });
+        }
+        if removed == 0 {
+            return 0;
+        }
+        self.mark_stale(Signal::Traces);
+        let _ = self.event_tx.send(StoreEvent::TracesDeleted);
+        removed
+    }
+
+    #[instrument(name = "store.delete_log_records", skip_all)]
+    fn delete_log_records(&mut self, rows: &BTreeSet<RowId>) -> usize {
+        let mut removed = 0;
+        for (seq, indexes) in rows_by_seq(rows) {
+            let Some(mut rl) = self.logs.remove(seq) else {
+                continue;
+            };
+            self.log_bytes -= rl.encoded_len();
+            if let Some(dedup) = &mut self.dedup {
+                dedup.forget_logs([&rl]);
+            }
+            let mut dropped = 0;
+            {
+                let mut keep = drop_indexes(&indexes, &mut dropped);
+                for sl in &mut rl.scope_logs {
+                    sl.log_records.retain(|_| keep());
+                }
+            }
+            removed += dropped;
+            if dropped > 0 {
+                rl.scope_logs.retain(|sl| !sl.log_records.is_empty());
+                if rl.scope_logs.is_empty() {
+                    continue;
+                }
+            }
+            self.log_bytes += rl.encoded_len();
+            if let Some(dedup) = &mut self.dedup {
+                dedup.remember(&[], &[&rl], &[]);
+            }
+            // The earliest record may be gone, so the time key can change.
+            self.logs.insert(log_sort_key(&rl), rl, seq);
+        }
+        if removed == 0 {
+            return 0;
+        }
+        self.mark_stale(Signal::Logs);
+        let _ = self.event_tx.send(StoreEvent::LogsDeleted);
+        removed
+    }
+
+    #[instrument(name = "store.delete_metric_points", skip_all)]
+    fn delete_metric_points(&mut self, rows: &BTreeSet<RowId>) -> usize {
+        let mut removed = 0;
+        for (seq, indexes) in rows_by_seq(rows) {
+            let Some(mut rm) = self.metrics.remove(seq) else {
+                continue;
+            };
+            self.metric_bytes -= rm.encoded_len();
+            if let Some(dedup) = &mut self.dedup {
+                dedup.forget_metrics([&rm]);
+            }
+            let mut dropped = 0;
+            {
+                let mut keep = drop_indexes(&indexes, &mut dropped);
+                for sm in &mut rm.scope_metrics {
+                    sm.metrics.retain_mut(|m| {
+                        let points = data_point_count(m);
+                        retain_data_points(m, &mut keep);
+                        points == 0 || data_point_count(m) > 0
+                    });
+                }
+            }
+            removed += dropped;
+            if dropped > 0 {
+                rm.scope_metrics.retain(|sm| !sm.metrics.is_empty());
+                if rm.scope_metrics.is_empty() {
+                    continue;
+                }
+            }
+            self.metric_bytes += rm.encoded_len();
+            if let Some(dedup) = &mut self.dedup {
+                dedup.remember(&[], &[], &[&rm]);
+            }
+            self.metrics.insert(metric_sort_key(&rm), rm, seq);
+        }
+        if removed == 0 {
+            return 0;
+        }
+        self.mark_stale(Signal::Metrics);
+        let _ = self.event_tx.send(StoreEvent::MetricsDeleted);
+        removed
+    }
+
+    /// Compact every signal that changed since the last compaction, dropping
+    /// evicted and expired data from disk.
+    fn snapshot(&mut self) -> anyhow::Result<()> {
+        for signal in [Signal::Traces, Signal::Logs, Signal::Metrics] {
+            if self.persist.as_ref().is_some_and(|p| p.is_dirty(signal)) {
+                self.compact(signal)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn evicted_event(signal: Signal) -> StoreEvent {
+    match signal {
+        Signal::Traces => StoreEvent::TracesEvicted,
+        Signal::Logs => StoreEvent::LogsEvicted,
+        Signal::Metrics => StoreEvent::MetricsEvicted,
+    }
+}
+
+/// Share a store between the servers, the query API and the TUI, splitting
+/// it into one store per signal.
+pub fn share(store: Store) -> SharedStore {
+    let event_tx = store.event_tx.clone();
+    let ([traces, logs, metrics], budget) = store.into_shards();
+    let shards: [Shard; 3] = [
+        Arc::new(RwLock::new(traces)),
+        Arc::new(RwLock::new(logs)),
+        Arc::new(RwLock::new(metrics)),
+    ];
+    let _ = budget.stores.set(shards.each_ref().map(Arc::downgrade));
+    let [traces, logs, metrics] = shards;
+    SharedStore {
+        traces,
+        logs,
+        metrics,
+        event_tx,
+    }
+}
+
+pub fn new_shared(
+    max_traces: usize,
+    max_spans: usize,
+    max_logs: usize,
+    max_metrics: usize,
+) -> (SharedStore, broadcast::Receiver<StoreEvent>) {
+    let (store, rx) = Store::new(max_traces, max_spans, max_logs, max_metrics);
+    (share(store), rx)
+}
+
+/// Like [`new_shared`], persisting to and reloading from `data_dir`.
+pub fn open_shared(
+    data_dir: &Path,
+    max_traces: usize,
+    max_spans: usize,
+    max_logs: usize,
+    max_metrics: usize,
+) -> anyhow::Result<(SharedStore, broadcast::Receiver<StoreEvent>)> {
+    let (store, rx) = Store::open(data_dir, max_traces, max_spans, max_logs, max_metrics)?;
+    Ok((share(store), rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::opentelemetry::proto::{
+        common::v1::{any_value, AnyValue, KeyValue},
+        logs::v1::{LogRecord, ResourceLogs, ScopeLogs},
+        metrics::v1::{Metric, ResourceMetrics, ScopeMetrics},
+        resource::v1::Resource,
+        trace::v1::{ResourceSpans, ScopeSpans, Span},
+    };
+
+    fn make_kv(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(value.to_string())),
+            }),
+        }
+    }
+
+    fn make_resource(service_name: &str) -> Option<Resource> {
+        Some(Resource {
+            attributes: vec![make_kv("service.name", service_name)],
+            dropped_attributes_count: 0,
+            entity_refs: vec![],
+        })
+    }
+
+    #[test]
+    fn test_batches_split_by_encoded_size() {
+        let items: Vec<KeyValue> = (0..5).map(|i| make_kv("k", &"v".repeat(i * 10))).collect();
+        let sizes: Vec<usize> = items.iter().map(prost::Message::encoded_len).collect();
+        let groups: Vec<Vec<KeyValue>> = batches(items, sizes[3] - 1).collect();
+        let lens: Vec<usize> = groups.iter().map(Vec::len).collect();
+        // The last two items are larger than the limit and get a group each.
+        assert_eq!(lens, vec![2, 1, 1, 1]);
+        assert_eq!(batches(Vec::<KeyValue>::new(), 10).count(), 0);
+    }
+
+    fn make_resource_spans(
+        service_name: &str,
+        trace_id: &[u8],
+        attrs: &[(&str, &str)],
+    ) -> ResourceSpans {
+        make_resource_spans_full(service_name, trace_id, attrs, 0, 0)
+    }
+
+    fn make_resource_spans_full(
+        service_name: &str,
+        trace_id: &[u8],
+        attrs: &[(&str, &str)],
+        start_time_unix_nano: u64,
+        end_time_unix_nano: u64,
+    ) -> ResourceSpans {
+        let span_attrs: Vec<KeyValue> = attrs.iter().map(|(k, v)| make_kv(k, v)).collect();
+        ResourceSpans {
+            resource: make_resource(service_name),
+            scope_spans: vec![ScopeSpans {
+                scope: None,
+                spans: vec![Span {
+                    trace_id: trace_id.to_vec(),
+                    span_id: vec![0, 0, 0, 0, 0, 0, 0, 1],
+                    trace_state: String::new(),
+                    parent_span_id: vec![],
+                    flags: 0,
+                    name: "test-span".to_string(),
+                    kind: 0,
+                    start_time_unix_nano,
+                    end_time_unix_nano,
+                    attributes: span_attrs,
+                    dropped_attributes_count: 0,
+                    events: vec![],
+                    dropped_events_count: 0,
+                    links: vec![],
+                    dropped_links_count: 0,
+                    status: None,
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }
+    }
+
+    fn make_resource_logs(
+        service_name: &str,
+        severity: &str,
+        attrs: &[(&str, &str)],
+    ) -> ResourceLogs {
+        make_resource_logs_full(service_name, severity, attrs, 0)
+    }
+
+    fn make_resource_logs_full(
+        service_name: &str,
+        severity: &str,
+        attrs: &[(&str, &str)],
+        time_unix_nano: u64,
+    ) -> ResourceLogs {
+        let severity_number = severity_text_to_number(severity).unwrap_or(0);
+        let log_attrs: Vec<KeyValue> = attrs.iter().map(|(k, v)| make_kv(k, v)).collect();
+        ResourceLogs {
+            resource: make_resource(service_name),
+            scope_logs: vec![ScopeLogs {
+                scope: None,
+                log_records: vec![LogRecord {
+                    time_unix_nano,
+                    observed_time_unix_nano: 0,
+                    severity_number,
+                    severity_text: severity.to_string(),
+                    body: None,
+                    attributes: log_attrs,
+                    dropped_attributes_count: 0,
+                    flags: 0,
+                    trace_id: vec![],
+                    span_id: vec![],
+                    event_name: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }
+    }
+
+    fn make_resource_metrics(service_name: &str, metric_name: &str) -> ResourceMetrics {
+        ResourceMetrics {
+            resource: make_resource(service_name),
+            scope_metrics: vec![ScopeMetrics {
+                scope: None,
+                metrics: vec![Metric {
+                    name: metric_name.to_string(),
+                    description: String::new(),
+                    unit: String::new(),
+                    data: None,
+                    metadata: vec![],
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }
+    }
+
+    fn get_svc_name(rs: &ResourceSpans) -> String {
+        rs.resource
+            .as_ref()
+            .and_then(|r| {
+                r.attributes
+                    .iter()
+                    .find(|kv| kv.key == "service.name")
+                    .and_then(|kv| kv.value.as_ref())
+                    .and_then(|v| match &v.value {
+                        Some(any_value::Value::StringValue(s)) => Some(s.clone()),
+                        _ => None,
+                    })
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn insert_and_all_traces() {
+        let (mut store, _rx) = Store::new(100, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_traces(vec![make_resource_spans("svc-a", &[1; 16], &[])]);
+        assert_eq!(store.all_traces().len(), 1);
+    }
+
+    #[test]
+    fn insert_and_all_logs() {
+        let (mut store, _rx) = Store::new(100, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_logs(vec![make_resource_logs("svc-a", "INFO", &[])]);
+        assert_eq!(store.all_logs().len(), 1);
+    }
+
+    #[test]
+    fn insert_and_all_metrics() {
+        let (mut store, _rx) = Store::new(100, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_metrics(vec![make_resource_metrics("svc-a", "http.duration")]);
+        assert_eq!(store.all_metrics().len(), 1);
+    }
+
+    #[test]
+    fn eviction_traces() {
+        let (mut store, _rx) = Store::new(3, usize::MAX, usize::MAX, usize::MAX);
+        for i in 0..5u8 {
+            store.insert_traces(vec![make_resource_spans_full(
+                &format!("svc-{i}"),
+                &[i; 16],
+                &[],
+                i as u64 * 100,
+                i as u64 * 100 + 50,
+            )]);
+        }
+        assert_eq!(store.trace_count(), 3);
+        assert_eq!(store.all_traces().len(), 3);
+        let names: Vec<_> = store
+            .all_traces()
+            .iter()
+            .map(|rs| get_svc_name(rs))
+            .collect();
+        assert_eq!(names, vec!["svc-2", "svc-3", "svc-4"]);
+    }
+
+    #[test]
+    fn eviction_traces_flat() {
+        // Same trace_id across 2 ResourceSpans should count as 1 trace
+        let (mut store, _rx) = Store::new(2, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_traces(vec![
+            make_resource_spans_full("svc-a", &[1; 16], &[], 100, 200),
+            make_resource_spans_full("svc-b", &[1; 16], &[], 200, 300),
+        ]);
+        // 1 distinct trace_id, so inserting another trace_id keeps both
+        store.insert_traces(vec![make_resource_spans_full(
+            "svc-c",
+            &[2; 16],
+            &[],
+            300,
+            400,
+        )]);
+
+        assert_eq!(store.trace_count(), 2);
+        // All 3 ResourceSpans are retained (2 trace_ids <= max_items=2)
+        assert_eq!(store.all_traces().len(), 3);
+        let names: Vec<_> = store
+            .all_traces()
+            .iter()
+            .map(|rs| get_svc_name(rs))
+            .collect();
+        assert_eq!(names, vec!["svc-a", "svc-b", "svc-c"]);
+    }
+
+    #[test]
+    fn eviction_multi_resource_spans_same_trace() {
+        // Multiple ResourceSpans with the same trace_id are evicted together
+        let (mut store, _rx) = Store::new(1, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_traces(vec![
+            make_resource_spans_full("svc-a", &[1; 16], &[], 100, 200),
+            make_resource_spans_full("svc-b", &[1; 16], &[], 150, 250),
+        ]);
+        // trace_id [1;16] is the only trace, fits in max_items=1
+        assert_eq!(store.trace_count(), 1);
+        assert_eq!(store.all_traces().len(), 2);
+
+        // Insert a new trace_id, evicting the old one
+        store.insert_traces(vec![make_resource_spans_full(
+            "svc-c",
+            &[2; 16],
+            &[],
+            300,
+            400,
+        )]);
+        assert_eq!(store.trace_count(), 1);
+        // Both ResourceSpans of trace_id [1;16] should be gone
+        assert_eq!(store.all_traces().len(), 1);
+        assert_eq!(get_svc_name(store.all_traces()[0]), "svc-c");
+    }
+
+    #[test]
+    fn eviction_by_end_time() {
+        // The trace with the oldest max end_time is evicted first
+        let (mut store, _rx) = Store::new(2, usize::MAX, usize::MAX, usize::MAX);
+        // trace A: start=100, end=150 (oldest end_time)
+        store.insert_traces(vec![make_resource_spans_full(
+            "svc-a",
+            &[1; 16],
+            &[],
+            100,
+            150,
+        )]);
+        // trace B: start=50, end=500 (newer end_time, despite earlier start)
+        store.insert_traces(vec![make_resource_spans_full(
+            "svc-b",
+            &[2; 16],
+            &[],
+            50,
+            500,
+        )]);
+        // trace C: start=200, end=300
+        store.insert_traces(vec![make_resource_spans_full(
+            "svc-c",
+            &[3; 16],
+            &[],
+            200,
+            300,
+        )]);
+
+        assert_eq!(store.trace_count(), 2);
+        let names: Vec<_> = store
+            .all_traces()
+            .iter()
+            .map(|rs| get_svc_name(rs))
+            .collect();
+        // trace A (end_time=150) should be evicted, not trace B (end_time=500)
+        assert_eq!(names, vec!["svc-b", "svc-c"]);
+    }
+
+    #[test]
+    fn eviction_logs() {
+        let (mut store, _rx) = Store::new(usize::MAX, usize::MAX, 3, usize::MAX);
+        for i in 0..5 {
+            store.insert_logs(vec![make_resource_logs(&format!("svc-{i}"), "INFO", &[])]);
+        }
+        assert_eq!(store.all_logs().len(), 3);
+    }
+
+    #[test]
+    fn eviction_metrics() {
+        let (mut store, _rx) = Store::new(usize::MAX, usize::MAX, usize::MAX, 3);
+        for i in 0..5 {
+            store.insert_metrics(vec![make_resource_metrics(&format!("svc-{i}"), "cpu")]);
+        }
+        assert_eq!(store.all_metrics().len(), 3);
+    }
+
+    #[test]
+    fn event_notification() {
+        let (mut store, mut rx) = Store::new(100, usize::MAX, usize::MAX, usize::MAX);
+
+        store.insert_traces(vec![make_resource_spans("svc", &[1; 16], &[])]);
+        assert_eq!(rx.try_recv().unwrap(), StoreEvent::TracesAdded(1..2));
+
+        store.insert_logs(vec![make_resource_logs("svc", "INFO", &[])]);
+        assert_eq!(rx.try_recv().unwrap(), StoreEvent::LogsAdded(2..3));
+
+        store.insert_metrics(vec![make_resource_metrics("svc", "cpu")]);
+        assert_eq!(rx.try_recv().unwrap(), StoreEvent::MetricsAdded(3..4));
+    }
+
+    #[test]
+    fn insert_traces_sorted_by_timestamp() {
+        let (mut store, _rx) = Store::new(100, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_traces(vec![make_resource_spans_full(
+            "svc-300",
+            &[0; 16],
+            &[],
+            300,
+            400,
+        )]);
+        store.insert_traces(vec![make_resource_spans_full(
+            "svc-100",
+            &[0; 16],
+            &[],
+            100,
+            200,
+        )]);
+        store.insert_traces(vec![make_resource_spans_full(
+            "svc-200",
+            &[0; 16],
+            &[],
+            200,
+            300,
+        )]);
+
+        assert_eq!(store.all_traces().len(), 3);
+        let names: Vec<_> = store.all_traces().into_iter().map(get_svc_name).collect();
+        assert_eq!(names, vec!["svc-100", "svc-200", "svc-300"]);
+    }
+
+    #[test]
+    fn insert_logs_sorted_by_timestamp() {
+        let (mut store, _rx) = Store::new(100, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_logs(vec![make_resource_logs_full("svc-300", "INFO", &[], 300)]);
+        store.insert_logs(vec![make_resource_logs_full("svc-100", "INFO", &[], 100)]);
+        store.insert_logs(vec![make_resource_logs_full("svc-200", "INFO", &[], 200)]);
+
+        assert_eq!(store.all_logs().len(), 3);
+    }
+
+    #[test]
+    fn max_memory_evicts_largest_signal() {
+        let (mut store, mut rx) = Store::new(100, usize::MAX, 100, 100);
+        let big = "x".repeat(1000);
+        for i in 0..3u8 {
+            store.insert_traces(vec![make_resource_spans_full(
+                &format!("svc-{i}"),
+                &[i + 1; 16],
+                &[("payload", big.as_str())],
+                i as u64 * 100 + 100,
+                i as u64 * 100 + 150,
+            )]);
+        }
+        let trace_bytes = store.trace_bytes();
+        assert_eq!(
+            trace_bytes,
+            store
+                .all_traces()
+                .iter()
+                .map(|rs| rs.encoded_len())
+                .sum::<usize>()
+        );
+
+        // Shrinking the budget evicts the oldest traces right away.
+        store.set_max_memory(trace_bytes * 2 / 3 + 1);
+        assert_eq!(store.trace_count(), 2);
+        assert_eq!(get_svc_name(store.all_traces()[0]), "svc-1");
+        while rx.try_recv().is_ok() {}
+
+        // Logs push out traces, the largest signal, and followers hear about it.
+        store.insert_logs(vec![make_resource_logs(
+            "svc",
+            "INFO",
+            &[("payload", &big)],
+        )]);
+        assert_eq!(store.log_count(), 1);
+        assert_eq!(store.trace_count(), 1);
+        assert!(store.memory_used() <= store.max_memory());
+        assert_eq!(rx.try_recv().unwrap(), StoreEvent::TracesEvicted);
+        assert!(matches!(rx.try_recv().unwrap(), StoreEvent::LogsAdded(_)));
+
+        store.clear_traces();
+        store.clear_logs();
+        assert_eq!(store.memory_used(), 0);
+    }
+
+    #[tokio::test]
+    async fn shared_max_memory_evicts_across_signals_without_waiting() {
+        let (mut store, _rx) = Store::new(100, usize::MAX, 100, 100);
+        let big = "x".repeat(1000);
+        for i in 0..3u8 {
+            store.insert_traces(vec![make_resource_spans_full(
+                &format!("svc-{i}"),
+                &[i + 1; 16],
+                &[("payload", big.as_str())],
+                i as u64 * 100 + 100,
+                i as u64 * 100 + 150,
+            )]);
+        }
+        let trace_bytes = store.trace_bytes();
+        store.set_max_memory(trace_bytes + trace_bytes / 6);
+        let store = share(store);
+        let mut rx = store.subscribe();
+        let log = || make_resource_logs("svc", "INFO", &[("payload", &big)]);
+
+        // Logs push out traces, the largest signal, behind its own lock.
+        store.logs.write().await.insert_logs(vec![log()]);
+        assert_eq!(store.traces.read().await.trace_count(), 2);
+        assert_eq!(rx.try_recv().unwrap(), StoreEvent::TracesEvicted);
+        assert!(matches!(rx.try_recv().unwrap(), StoreEvent::LogsAdded(_)));
+
+        // While traces are being read, logs evict their own oldest data
+        // rather than wait.
+        let reader = store.traces.read().await;
+        store.logs.write().await.insert_logs(vec![log()]);
+        assert_eq!(reader.trace_count(), 2);
+        drop(reader);
+        assert_eq!(store.logs.read().await.log_count(), 1);
+    }
+
+    #[test]
+    fn data_dir_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let (mut store, _rx) = Store::open(dir.path(), 100, usize::MAX, 100, 100).unwrap();
+            for i in 0..4u8 {
+                store.insert_traces(vec![make_resource_spans_full(
+                    &format!("svc-{i}"),
+                    &[i + 1; 16],
+                    &[],
+                    i as u64 * 100,
+                    i as u64 * 100 + 50,
+                )]);
+            }
+            store.insert_logs(vec![make_resource_logs("svc", "INFO", &[])]);
+            store.insert_metrics(vec![make_resource_metrics("svc", "cpu")]);
+            store.clear_metrics();
+        }
+
+        // Reloading with a smaller limit evicts the oldest traces, on disk too.
+        let (store, _rx) = Store::open(dir.path(), 2, usize::MAX, 100, 100).unwrap();
+        assert_eq!(store.trace_count(), 2);
+        let names: Vec<_> = store.all_traces().into_iter().map(get_svc_name).collect();
+        assert_eq!(names, vec!["svc-2", "svc-3"]);
+        assert_eq!(store.traces.end_time(&[4; 16]), Some(350));
+        assert_eq!(store.log_count(), 1);
+        assert_eq!(store.metric_count(), 0);
+        drop(store);
+
+        let (store, _rx) = Store::open(dir.path(), 100, usize::MAX, 100, 100).unwrap();
+        assert_eq!(store.trace_count(), 2);
+    }
+
+    #[test]
+    fn scan_traces_since() {
+        let (mut store, _rx) = Store::new(100, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_traces(vec![
+            make_resource_spans_full("svc-a", &[1; 16], &[], 100, 200),
+            make_resource_spans_full("svc-b", &[1; 16], &[], 200, 300),
+            make_resource_spans_full("svc-c", &[2; 16], &[], 300, 400),
+        ]);
+        assert_eq!(store.scan_traces(TimeBounds::since(200)).count(), 2);
+        assert_eq!(store.scan_traces(TimeBounds::since(301)).count(), 0);
+    }
+
+    #[test]
+    fn scan_logs_since() {
+        let (mut store, _rx) = Store::new(100, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_logs(vec![
+            make_resource_logs_full("svc", "INFO", &[], 100),
+            make_resource_logs_full("svc", "INFO", &[], 200),
+            make_resource_logs_full("svc", "INFO", &[], 300),
+        ]);
+        assert_eq!(store.scan_logs(TimeBounds::since(200)).count(), 2);
+        assert_eq!(store.scan_logs(TimeBounds::since(301)).count(), 0);
+        let bounds = TimeBounds {
+            start: 150,
+            end: 250,
+        };
+        assert_eq!(store.scan_logs(bounds).count(), 1);
+    }
+
+    #[test]
+    fn scan_metrics_since() {
+        use crate::proto::opentelemetry::proto::metrics::v1::{
+            Gauge, Metric, NumberDataPoint, ResourceMetrics, ScopeMetrics,
+        };
+
+        fn make_resource_metrics_with_ts(
+            service_name: &str,
+            metric_name: &str,
+            time_unix_nano: u64,
+        ) -> ResourceMetrics {
+            ResourceMetrics {
+                resource: make_resource(service_name),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: None,
+                    metrics: vec![Metric {
+                        name: metric_name.to_string(),
+                        description: String::new(),
+                        unit: String::new(),
+                        data: Some(metric::Data::Gauge(Gauge {
+                            data_points: vec![NumberDataPoint {
+                                time_unix_nano,
+                                ..Default::default()
+                            }],
+                        })),
+                        metadata: vec![],
+                    }],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }
+        }
+
+        let (mut store, _rx) = Store::new(100, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_metrics(vec![
+            make_resource_metrics_with_ts("svc", "cpu", 100),
+            make_resource_metrics_with_ts("svc", "cpu", 200),
+            make_resource_metrics_with_ts("svc", "cpu", 300),
+        ]);
+        assert_eq!(store.scan_metrics(TimeBounds::since(200)).count(), 2);
+        assert_eq!(store.scan_metrics(TimeBounds::since(301)).count(), 0);
+    }
+
+    #[test]
+    fn dedup_drops_retransmits_until_evicted() {
+        let (mut store, _rx) = Store::new(2, usize::MAX, usize::MAX, usize::MAX);
+        store.insert_traces(vec![make_resource_spans("svc", &[1; 16], &[])]);
+        store.enable_dedup();
+
+        // Already stored before dedup was enabled.
+        store.insert_traces(vec![make_resource_spans("svc", &[1; 16], &[])]);
+        store.insert_traces(vec![make_resource_spans("svc", &[2; 16], &[])]);
+        store.insert_traces(vec![make_resource_spans("svc", &[2; 16], &[])]);
+        assert_eq!(store.all_traces().len(), 2);
+        assert_eq!(store.dedup_stats().unwrap().duplicate_spans, 2);
+
+        // Once evicted, the same span is no longer a duplicate.
+        store.insert_traces(vec![make_resource_spans("svc", &[3; 16], &[])]);
+        assert!(store.get_trace(&[1; 16]).is_empty());
+        store.insert_traces(vec![make_resource_spans("svc", &[1; 16], &[])]);
+        store.insert_traces(vec![make_resource_spans("svc", &[3; 16], &[])]);
+        assert_eq!(store.dedup_stats().unwrap().duplicate_spans, 3);
+
+        store.insert_logs(vec![make_resource_logs_full("svc", "INFO", &[], 100)]);
+        store.insert_logs(vec![make_resource_logs_full("svc", "INFO", &[], 100)]);
+        assert_eq!(store.log_count(), 1);
+        store.clear_logs();
+        store.insert_logs(vec![make_resource_logs_full("svc", "INFO", &[], 100)]);
+        assert_eq!(store.log_count(), 1);
+        assert_eq!(store.dedup_stats().unwrap().duplicate_log_records, 1);
+
+        // The keys count towards the memory budget.
+        let data_bytes = store.trace_bytes() + store.log_bytes() + store.metric_bytes();
+        assert!(store.memory_used() > data_bytes);
+    }
+
+    #[test]
+    fn test_delete_by_row_id_ignores_later_inserts() {
+        let (mut store, _rx) = Store::new(100, usize::MAX, usize::MAX, usize::MAX);
+        let mut rl = make_resource_logs_full("svc", "INFO", &[], 200);
+        let mut second = rl.scope_logs[0].log_records[0].clone();
+        second.severity_text = "WARN".to_string();
+        rl.scope_logs[0].log_records.push(second);
+        store.insert_logs(vec![rl]);
+        let (seq, _) = store.scan_logs_with_seq(TimeBounds::ALL).next().unwrap();
+
+        // An earlier batch sorts first but leaves the row ids alone.
+        store.insert_logs(vec![make_resource_logs_full("svc", "ERROR", &[], 100)]);
+        let rows = BTreeSet::from([RowId { seq, index: 1 }, RowId { seq, index: 5 }]);
+        assert_eq!(store.delete_log_records(&rows), 1);
+
+        let severities: Vec<String> = store
+            .scan_logs(TimeBounds::ALL)
+            .flat_map(|rl| rl.into_owned().scope_logs)
+            .flat_map(|sl| sl.log_records)
+            .map(|lr| lr.severity_text)
+            .collect();
+        assert_eq!(severities, ["ERROR", "INFO"]);
+        let bytes: usize = store.all_logs().iter().map(|rl| rl.encoded_len()).sum();
+        assert_eq!(store.log_bytes(), bytes);
+        assert_eq!(store.delete_log_records(&BTreeSet::new()), 0);
+    }
+}
@@ -0,0 +1,383 @@
+//! On-disk persistence for `server --data-dir`.
+//!
+//! Every signal has a snapshot and a segment file in the data directory, both
+//! holding length-delimited protobuf `ResourceSpans`/`ResourceLogs`/
+//! `ResourceMetrics` messages. Incoming batches are appended to the segment.
+//! Compaction writes the in-memory store to a new snapshot and starts an empty
+//! segment, which is how evicted and cleared data leaves the disk.
+//!
+//! The snapshot header carries a generation number naming the segment that
+//! belongs to it, so a crash between writing the snapshot and removing the old
+//! segment never replays records twice.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use prost::Message;
+use tokio_util::sync::CancellationToken;
+
+use super::SharedStore;
+
+const MAGIC: &[u8; 8] = b"OTELCLI1";
+const HEADER_LEN: usize = MAGIC.len() + 8;
+
+/// Segments shorter than this are never compacted on insert, so small stores
+/// are not rewritten on every batch.
+const MIN_COMPACTION_RECORDS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Traces,
+    Logs,
+    Metrics,
+}
+
+impl Signal {
+    fn name(self) -> &'static str {
+        match self {
+            Signal::Traces => "traces",
+            Signal::Logs => "logs",
+            Signal::Metrics => "metrics",
+        }
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            Signal::Traces => 0,
+            Signal::Logs => 1,
+            Signal::Metrics => 2,
+        }
+    }
+}
+
+struct SignalFiles {
+    generation: u64,
+    segment: File,
+    /// Records appended to the segment since the last compaction.
+    appended: usize,
+    /// Whether data was removed from memory since the last compaction.
+    stale: bool,
+}
+
+/// The files of each signal. A signal without files (see [`split`]) is never
+/// written and loads as empty.
+///
+/// [`split`]: Persistence::split
+pub struct Persistence {
+    dir: PathBuf,
+    traces: Option<SignalFiles>,
+    logs: Option<SignalFiles>,
+    metrics: Option<SignalFiles>,
+}
+
+impl Persistence {
+    /// Open (creating if needed) the data directory. Segments left behind by
+    /// an interrupted compaction are removed.
+    pub fn open(dir: &Path) -> anyhow::Result<Self> {
+        fs::create_dir_all(dir).map_err(|e| {
+            anyhow::anyhow!("Failed to create data directory {}: {}", dir.display(), e)
+        })?;
+        Ok(Persistence {
+            dir: dir.to_path_buf(),
+            traces: Some(open_signal(dir, Signal::Traces)?),
+            logs: Some(open_signal(dir, Signal::Logs)?),
+            metrics: Some(open_signal(dir, Signal::Metrics)?),
+        })
+    }
+
+    /// Split into one `Persistence` per signal, in [`Signal::index`] order,
+    /// each writing only the files of its own signal.
+    pub fn split(self) -> [Persistence; 3] {
+        let only = |traces, logs, metrics| Persistence {
+            dir: self.dir.clone(),
+            traces,
+            logs,
+            metrics,
+        };
+        [
+            only(self.traces, None, None),
+            only(None, self.logs, None),
+            only(None, None, self.metrics),
+        ]
+    }
+
+    fn files(&self, signal: Signal) -> Option<&SignalFiles> {
+        match signal {
+            Signal::Traces => self.traces.as_ref(),
+            Signal::Logs => self.logs.as_ref(),
+            Signal::Metrics => self.metrics.as_ref(),
+        }
+    }
+
+    fn files_mut(&mut self, signal: Signal) -> Option<&mut SignalFiles> {
+        match signal {
+            Signal::Traces => self.traces.as_mut(),
+            Signal::Logs => self.logs.as_mut(),
+            Signal::Metrics => self.metrics.as_mut(),
+        }
+    }
+
+    /// Read the snapshot and segment of `signal`, in the order they were
+    /// written. A truncated trailing record (from a crash mid-append) is
+    /// dropped with a warning.
+    pub fn load<T: Message + Default>(&self, signal: Signal) -> anyhow::Result<Vec<T>> {
+        let mut items = Vec::new();
+        let Some(files) = self.files(signal) else {
+            return Ok(items);
+        };
+        let snapshot = snapshot_path(&self.dir, signal);
+        if let Some(bytes) = read_optional(&snapshot)? {
+            decode_records(
+                &snapshot,
+                bytes.get(HEADER_LEN..).unwrap_or(&[]),
+                &mut items,
+            );
+        }
+        let segment = segment_path(&self.dir, signal, files.generation);
+        if let Some(bytes) = read_optional(&segment)? {
+            decode_records(&segment, &bytes, &mut items);
+        }
+        Ok(items)
+    }
+
+    /// Append a batch to the segment of `signal` with a single write.
+    pub fn append<T: Message>(&mut self, signal: Signal, items: &[T]) -> io::Result<()> {
+        let Some(files) = self.files_mut(signal) else {
+            return Ok(());
+        };
+        let mut buf = Vec::new();
+        for item in items {
+            item.encode_length_delimited(&mut buf)?;
+        }
+        files.segment.write_all(&buf)?;
+        files.appended += items.len();
+        Ok(())
+    }
+
+    /// Whether the segment has outgrown the live data, i.e. a compaction
+    /// would at least halve what is on disk beyond the snapshot.
+    pub fn needs_compaction(&self, signal: Signal, live: usize) -> bool {
+        self.files(signal)
+            .is_some_and(|files| files.appended > live.max(MIN_COMPACTION_RECORDS))
+    }
+
+    /// Record that data was removed from memory outside of an insert, so the
+    /// next snapshot rewrites the files even without new data.
+    pub fn mark_stale(&mut self, signal: Signal) {
+        if let Some(files) = self.files_mut(signal) {
+            files.stale = true;
+        }
+    }
+
+    /// Whether the files differ from memory, i.e. anything was appended or
+    /// removed since the last compaction.
+    pub fn is_dirty(&self, signal: Signal) -> bool {
+        self.files(signal)
+            .is_some_and(|files| files.appended > 0 || files.stale)
+    }
+
+    /// Replace the on-disk data of `signal` with `items` (the live contents
+    /// of the store) and start a new, empty segment.
+    pub fn compact<'a, T: Message + 'a>(
+        &mut self,
+        signal: Signal,
+        items: impl IntoIterator<Item = &'a T>,
+    ) -> anyhow::Result<()> {
+        let dir = self.dir.clone();
+        let Some(files) = self.files_mut(signal) else {
+            return Ok(());
+        };
+        let generation = files.generation + 1;
+
+        let snapshot = snapshot_path(&dir, signal);
+        let tmp = snapshot.with_extension("snapshot.tmp");
+        let mut buf = Vec::with_capacity(HEADER_LEN);
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&generation.to_le_bytes());
+        for item in items {
+            item.encode_length_delimited(&mut buf)?;
+        }
+        write_synced(&tmp, &buf)
+            .and_then(|_| fs::rename(&tmp, &snapshot))
+            .map_err(|e| anyhow::anyhow!("Failed to write {}: {}", snapshot.display(), e))?;
+
+        let segment = open_segment(&dir, signal, generation)?;
+        let old = segment_path(&dir, signal, files.generation);
+        files.segment = segment;
+        files.generation = generation;
+        files.appended = 0;
+        files.stale = false;
+        if let Err(e) = fs::remove_file(&old) {
+            if e.kind() != io::ErrorKind::NotFound {
+                tracing::warn!(path = %old.display(), error = %e, "failed to remove old segment");
+            }
+        }
+        tracing::debug!(signal = signal.name(), generation, "store compacted");
+        Ok(())
+    }
+}
+
+fn snapshot_path(dir: &Path, signal: Signal) -> PathBuf {
+    dir.join(format!("{}.snapshot", signal.name()))
+}
+
+fn segment_path(dir: &Path, signal: Signal, generation: u64) -> PathBuf {
+    dir.join(format!("{}-{}.segment", signal.name(), generation))
+}
+
+fn open_signal(dir: &Path, signal: Signal) -> anyhow::Result<SignalFiles> {
+    let snapshot = snapshot_path(dir, signal);
+    let generation = match read_optional(&snapshot)? {
+        Some(bytes) => match bytes.get(..HEADER_LEN) {
+            Some(header) if header.starts_with(MAGIC) => {
+                u64::from_le_bytes(header[MAGIC.len()..].try_into().unwrap_or_default())
+            }
+            _ => anyhow::bail!("{} is not an otel-cli snapshot", snapshot.display()),
+        },
+        None => 0,
+    };
+
+    let current = segment_path(dir, signal, generation);
+    let prefix = format!("{}-", signal.name());
+    let entries = fs::read_dir(dir)
+        .map_err(|e| anyhow::anyhow!("Failed to read data directory {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(&prefix) && name.ends_with(".segment") && path != current {
+            tracing::debug!(path = %path.display(), "removing stale segment");
+            let _ = fs::remove_file(&path);
+        }
+    }
+
+    Ok(SignalFiles {
+        generation,
+        segment: open_segment(dir, signal, generation)?,
+        appended: 0,
+        stale: false,
+    })
+}
+
+fn open_segment(dir: &Path, signal: Signal, generation: u64) -> anyhow::Result<File> {
+    let path = segment_path(dir, signal, generation);
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| anyhow::anyhow!("Failed to open {}: {}", path.display(), e))
+}
+
+fn read_optional(path: &Path) -> anyhow::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(anyhow::anyhow!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn decode_records<T: Message + Default>(path: &Path, mut bytes: &[u8], items: &mut Vec<T>) {
+    while !bytes.is_empty() {
+        match T::decode_length_delimited(&mut bytes) {
+            Ok(item) => items.push(item),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "dropping truncated record");
+                return;
+            }
+        }
+    }
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+/// Compact every signal with new data each `interval`, so evicted data does
+/// not linger on disk.
+pub async fn run_snapshots(store: SharedStore, interval: Duration, shutdown: CancellationToken) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                if let Err(e) = store.snapshot().await {
+                    tracing::warn!(error = %e, "failed to write snapshot");
+                }
+            }
+            _ = shutdown.cancelled() => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::opentelemetry::proto::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+
+    fn logs(time: u64) -> ResourceLogs {
+        ResourceLogs {
+            scope_logs: vec![ScopeLogs {
+                log_records: vec![LogRecord {
+                    time_unix_nano: time,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_append_compact_and_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut p = Persistence::open(dir.path()).unwrap();
+        p.append(Signal::Logs, &[logs(1), logs(2)]).unwrap();
+        assert!(p.is_dirty(Signal::Logs));
+        assert_eq!(
+            Persistence::open(dir.path())
+                .unwrap()
+                .load::<ResourceLogs>(Signal::Logs)
+                .unwrap(),
+            vec![logs(1), logs(2)]
+        );
+
+        // Compacting to a subset drops the rest from disk.
+        p.compact(Signal::Logs, &[logs(2)]).unwrap();
+        p.append(Signal::Logs, &[logs(3)]).unwrap();
+        let reopened = Persistence::open(dir.path()).unwrap();
+        assert_eq!(
+            reopened.load::<ResourceLogs>(Signal::Logs).unwrap(),
+            vec![logs(2), logs(3)]
+        );
+        assert!(reopened
+            .load::<ResourceLogs>(Signal::Traces)
+            .unwrap()
+            .is_empty());
+        assert!(!dir.path().join("logs-0.segment").exists());
+    }
+
+    #[test]
+    fn test_truncated_segment_and_stale_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut p = Persistence::open(dir.path()).unwrap();
+        p.append(Signal::Logs, &[logs(1)]).unwrap();
+        drop(p);
+
+        // A half-written record at the tail is dropped.
+        let segment = dir.path().join("logs-0.segment");
+        let mut bytes = fs::read(&segment).unwrap();
+        let whole = bytes.len();
+        bytes.extend_from_within(..whole - 2);
+        fs::write(&segment, &bytes).unwrap();
+        // A segment from a newer generation than the snapshot is stale.
+        fs::write(dir.path().join("logs-7.segment"), &bytes).unwrap();
+
+        let p = Persistence::open(dir.path()).unwrap();
+        assert_eq!(p.load::<ResourceLogs>(Signal::Logs).unwrap(), vec![logs(1)]);
+        assert!(!dir.path().join("logs-7.segment").exists());
+    }
+}
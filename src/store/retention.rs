@@ -0,0 +1,187 @@
+//! Time-based retention (`server --trace-retention` etc.).
+//!
+//! A background sweeper periodically drops data older than the configured
+//! window. It runs alongside the `max_*` count limits, which are still
+//! enforced on insert, so whichever limit is reached first removes the data.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio_util::sync::CancellationToken;
+
+use super::{SharedStore, Signal, StorageBackend};
+
+const MIN_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+const MAX_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long each signal is kept, measured from its own timestamps (span end
+/// time, log record time, data point time). `None` keeps data until a count
+/// limit evicts it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Retention {
+    pub traces: Option<Duration>,
+    pub logs: Option<Duration>,
+    pub metrics: Option<Duration>,
+}
+
+impl Retention {
+    pub fn is_enabled(&self) -> bool {
+        self.traces.is_some() || self.logs.is_some() || self.metrics.is_some()
+    }
+
+    /// A tenth of the shortest window, so data outlives its retention by at
+    /// most ~10%, within one second to one minute.
+    fn sweep_interval(&self) -> Duration {
+        [self.traces, self.logs, self.metrics]
+            .into_iter()
+            .flatten()
+            .min()
+            .map_or(MAX_SWEEP_INTERVAL, |d| {
+                (d / 10).clamp(MIN_SWEEP_INTERVAL, MAX_SWEEP_INTERVAL)
+            })
+    }
+
+    /// Expire everything older than the configured windows as of `now`
+    /// (nanoseconds since the Unix epoch).
+    pub fn sweep(&self, store: &mut dyn StorageBackend, now: u64) {
+        for signal in [Signal::Traces, Signal::Logs, Signal::Metrics] {
+            self.sweep_signal(signal, store, now);
+        }
+    }
+
+    /// [`sweep`](Self::sweep) for one signal only.
+    pub fn sweep_signal(&self, signal: Signal, store: &mut dyn StorageBackend, now: u64) {
+        let window = match signal {
+            Signal::Traces => self.traces,
+            Signal::Logs => self.logs,
+            Signal::Metrics => self.metrics,
+        };
+        let Some(window) = window else {
+            return;
+        };
+        let cutoff = now.saturating_sub(window.as_nanos() as u64);
+        let expired = match signal {
+            Signal::Traces => store.expire_traces(cutoff),
+            Signal::Logs => store.expire_logs(cutoff),
+            Signal::Metrics => store.expire_metrics(cutoff),
+        };
+        if expired > 0 {
+            tracing::debug!(?signal, expired, "expired");
+        }
+    }
+}
+
+fn now_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+/// Sweep `store` until `shutdown` is cancelled, locking one signal at a time.
+pub async fn run_retention(store: SharedStore, retention: Retention, shutdown: CancellationToken) {
+    let mut ticker = tokio::time::interval(retention.sweep_interval());
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {
+                for signal in [Signal::Traces, Signal::Logs, Signal::Metrics] {
+                    let mut shard = store.shard(signal).write().await;
+                    retention.sweep_signal(signal, &mut *shard, now_nanos());
+                }
+            }
+            _ = shutdown.cancelled() => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::opentelemetry::proto::{
+        logs::v1::{LogRecord, ResourceLogs, ScopeLogs},
+        trace::v1::{ResourceSpans, ScopeSpans, Span},
+    };
+    use crate::store::{Store, StoreEvent};
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn spans(trace: u8, end: u64) -> ResourceSpans {
+        ResourceSpans {
+            scope_spans: vec![ScopeSpans {
+                spans: vec![Span {
+                    trace_id: vec![trace; 16],
+                    span_id: vec![trace; 8],
+                    start_time_unix_nano: end - SECOND,
+                    end_time_unix_nano: end,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn logs(times: &[u64]) -> ResourceLogs {
+        ResourceLogs {
+            scope_logs: vec![ScopeLogs {
+                log_records: times
+                    .iter()
+                    .map(|&t| LogRecord {
+                        time_unix_nano: t,
+                        ..Default::default()
+                    })
+                    .collect(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_sweep_expires_by_newest_timestamp() {
+        let (mut store, mut rx) = Store::new(100, usize::MAX, 100, 100);
+        let now = 10_000 * SECOND;
+        // Trace 1 started before the window but its last span is inside it.
+        store.insert_traces(vec![
+            spans(1, now - 3_000 * SECOND),
+            spans(1, now - 60 * SECOND),
+            spans(2, now - 3_000 * SECOND),
+        ]);
+        store.insert_logs(vec![
+            logs(&[now - 3_000 * SECOND]),
+            logs(&[now - 3_000 * SECOND, now]),
+        ]);
+        while rx.try_recv().is_ok() {}
+
+        let retention = Retention {
+            traces: Some(Duration::from_secs(3_600)),
+            logs: Some(Duration::from_secs(2_000)),
+            metrics: None,
+        };
+        retention.sweep(&mut store, now + 1_000 * SECOND);
+        assert_eq!(store.trace_count(), 1);
+        assert_eq!(store.all_traces().len(), 2);
+        assert_eq!(store.log_count(), 1);
+        assert_eq!(rx.try_recv().unwrap(), StoreEvent::TracesExpired);
+        assert_eq!(rx.try_recv().unwrap(), StoreEvent::LogsExpired);
+
+        // Nothing left to expire: no events.
+        retention.sweep(&mut store, now + 1_000 * SECOND);
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_sweep_interval() {
+        assert_eq!(Retention::default().sweep_interval(), MAX_SWEEP_INTERVAL);
+        let retention = Retention {
+            logs: Some(Duration::from_secs(300)),
+            metrics: Some(Duration::from_secs(7_200)),
+            ..Default::default()
+        };
+        assert_eq!(retention.sweep_interval(), Duration::from_secs(30));
+        let retention = Retention {
+            traces: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+        assert_eq!(retention.sweep_interval(), MIN_SWEEP_INTERVAL);
+    }
+}
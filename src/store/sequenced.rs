@@ -0,0 +1,121 @@
+//! Stored batches ordered by a time key, with the ingest sequence number of
+//! each one kept alongside, for signals without an index of their own.
+//!
+//! Batches are keyed by `(time key, seq)`, which is unique and keeps batches
+//! with the same time in ingest order. A second map from sequence number to
+//! time key lets [`after`](Sequenced::after) take the newest batches without
+//! looking at the rest.
+
+use std::collections::BTreeMap;
+
+use super::TimeBounds;
+
+pub struct Sequenced<T> {
+    items: BTreeMap<(u64, u64), T>,
+    by_seq: BTreeMap<u64, u64>,
+}
+
+impl<T> Default for Sequenced<T> {
+    fn default() -> Self {
+        Sequenced {
+            items: BTreeMap::new(),
+            by_seq: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T> Sequenced<T> {
+    /// Every item, in time key order.
+    pub fn items(&self) -> impl ExactSizeIterator<Item = &T> {
+        self.items.values()
+    }
+
+    /// The items whose time key is within `bounds` with their sequence
+    /// numbers, in time key order.
+    pub fn range(&self, bounds: TimeBounds) -> impl Iterator<Item = (u64, &T)> {
+        let end = bounds.end.max(bounds.start);
+        self.items
+            .range((bounds.start, 0)..=(end, u64::MAX))
+            .map(|((_, seq), item)| (*seq, item))
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn insert(&mut self, key: u64, item: T, seq: u64) {
+        self.items.insert((key, seq), item);
+        self.by_seq.insert(seq, key);
+    }
+
+    /// Remove the item stored with sequence number `seq`.
+    pub fn remove(&mut self, seq: u64) -> Option<T> {
+        let key = self.by_seq.remove(&seq)?;
+        self.items.remove(&(key, seq))
+    }
+
+    /// Remove the item with the earliest time key.
+    pub fn pop_front(&mut self) -> Option<T> {
+        let ((_, seq), item) = self.items.pop_first()?;
+        self.by_seq.remove(&seq);
+        Some(item)
+    }
+
+    /// Keep only the items for which `keep` returns true.
+    pub fn retain(&mut self, mut keep: impl FnMut(&T) -> bool) {
+        let by_seq = &mut self.by_seq;
+        self.items.retain(|(_, seq), item| {
+            let k = keep(item);
+            if !k {
+                by_seq.remove(seq);
+            }
+            k
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.by_seq.clear();
+    }
+
+    /// Items inserted after sequence number `seq`, in ingest order.
+    pub fn after(&self, seq: u64) -> Vec<&T> {
+        self.by_seq
+            .range(seq.saturating_add(1)..)
+            .filter_map(|(seq, key)| self.items.get(&(*key, *seq)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_after_returns_ingest_order() {
+        let mut items = Sequenced::default();
+        items.insert(20, "b", 1);
+        items.insert(10, "a", 2);
+        items.insert(30, "c", 3);
+        assert_eq!(items.items().copied().collect::<Vec<_>>(), ["a", "b", "c"]);
+        assert_eq!(items.after(0), vec![&"b", &"a", &"c"]);
+        assert_eq!(items.after(2), vec![&"c"]);
+        let bounds = TimeBounds { start: 15, end: 30 };
+        assert_eq!(
+            items.range(bounds).collect::<Vec<_>>(),
+            vec![(1, &"b"), (3, &"c")]
+        );
+
+        items.retain(|item| *item != "a");
+        assert_eq!(items.after(0), vec![&"b", &"c"]);
+        assert_eq!(items.pop_front(), Some("b"));
+        assert_eq!(items.after(0), vec![&"c"]);
+        assert_eq!(items.remove(2), None);
+        assert_eq!(items.remove(3), Some("c"));
+        assert!(items.after(0).is_empty());
+    }
+}
@@ -0,0 +1,382 @@
+//! Stored traces with the indexes needed to evict and look up whole traces
+//! without scanning every ResourceSpans.
+//!
+//! ResourceSpans are kept in a map ordered by a unique [`EntryKey`], their
+//! start time first (the order `all_traces` and scans expect), so inserting,
+//! finding and removing one is logarithmic. On top of that:
+//!
+//! - `traces` maps a trace_id to its end time and the keys of every
+//!   ResourceSpans holding one of its spans.
+//! - `by_end_time` orders trace_ids by end time, so the trace to evict next is
+//!   the first element.
+//! - `by_seq` maps each entry's ingest sequence number to its start time, so
+//!   the entries stored after a given sequence number are a range of it.
+//!
+//! A ResourceSpans with spans from several traces belongs to all of them.
+//! Evicting one of those traces removes the whole ResourceSpans, and the other
+//! traces are updated (or dropped when nothing of them is left).
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::proto::opentelemetry::proto::trace::v1::ResourceSpans;
+
+use super::{rs_sort_key, TimeBounds};
+
+/// Position of a ResourceSpans in the sort order: its start time, then its
+/// ingest sequence number to keep entries unique and inserts stable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct EntryKey {
+    start: u64,
+    seq: u64,
+}
+
+struct TraceEntry {
+    end_time: u64,
+    keys: Vec<EntryKey>,
+}
+
+#[derive(Default)]
+pub struct TraceIndex {
+    resource_spans: BTreeMap<EntryKey, ResourceSpans>,
+    by_seq: BTreeMap<u64, u64>,
+    traces: HashMap<Vec<u8>, TraceEntry>,
+    by_end_time: BTreeSet<(u64, Vec<u8>)>,
+}
+
+/// The latest span end time of each trace with spans in `rs`.
+fn trace_end_times(rs: &ResourceSpans) -> HashMap<&[u8], u64> {
+    let mut end_times: HashMap<&[u8], u64> = HashMap::new();
+    for span in rs.scope_spans.iter().flat_map(|ss| &ss.spans) {
+        let end = end_times.entry(span.trace_id.as_slice()).or_insert(0);
+        *end = (*end).max(span.end_time_unix_nano);
+    }
+    end_times
+}
+
+impl TraceIndex {
+    /// Every ResourceSpans, in start time order.
+    pub fn resource_spans(&self) -> impl ExactSizeIterator<Item = &ResourceSpans> {
+        self.resource_spans.values()
+    }
+
+    /// The ResourceSpans starting within `bounds` with their sequence
+    /// numbers, in start time order.
+    pub fn range(&self, bounds: TimeBounds) -> impl Iterator<Item = (u64, &ResourceSpans)> {
+        let first = EntryKey {
+            start: bounds.start,
+            seq: 0,
+        };
+        let last = EntryKey {
+            start: bounds.end,
+            seq: u64::MAX,
+        };
+        self.resource_spans
+            .range(first..=last.max(first))
+            .map(|(key, rs)| (key.seq, rs))
+    }
+
+    /// Number of ResourceSpans.
+    pub fn len(&self) -> usize {
+        self.resource_spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resource_spans.is_empty()
+    }
+
+    /// Number of distinct traces.
+    pub fn trace_count(&self) -> usize {
+        self.traces.len()
+    }
+
+    /// Latest span end time of a trace.
+    pub fn end_time(&self, trace_id: &[u8]) -> Option<u64> {
+        self.traces.get(trace_id).map(|entry| entry.end_time)
+    }
+
+    /// Every ResourceSpans holding a span of `trace_id`, in start time order.
+    pub fn trace(&self, trace_id: &[u8]) -> Vec<&ResourceSpans> {
+        let Some(entry) = self.traces.get(trace_id) else {
+            return Vec::new();
+        };
+        let mut keys = entry.keys.clone();
+        keys.sort();
+        keys.iter()
+            .filter_map(|key| self.resource_spans.get(key))
+            .collect()
+    }
+
+    /// ResourceSpans inserted after sequence number `seq`, in ingest order.
+    pub fn after(&self, seq: u64) -> Vec<&ResourceSpans> {
+        self.by_seq
+            .range(seq.saturating_add(1)..)
+            .filter_map(|(&seq, &start)| self.resource_spans.get(&EntryKey { start, seq }))
+            .collect()
+    }
+
+    /// Insert `rs` with its ingest sequence number, which must be greater
+    /// than that of every earlier insert.
+    pub fn insert(&mut self, rs: ResourceSpans, seq: u64) {
+        let key = EntryKey {
+            start: rs_sort_key(&rs),
+            seq,
+        };
+        for (trace_id, end_time) in trace_end_times(&rs) {
+            match self.traces.get_mut(trace_id) {
+                Some(entry) => {
+                    if end_time > entry.end_time {
+                        self.by_end_time
+                            .remove(&(entry.end_time, trace_id.to_vec()));
+                        self.by_end_time.insert((end_time, trace_id.to_vec()));
+                        entry.end_time = end_time;
+                    }
+                    entry.keys.push(key);
+                }
+                None => {
+                    self.traces.insert(
+                        trace_id.to_vec(),
+                        TraceEntry {
+                            end_time,
+                            keys: vec![key],
+                        },
+                    );
+                    self.by_end_time.insert((end_time, trace_id.to_vec()));
+                }
+            }
+        }
+        self.resource_spans.insert(key, rs);
+        self.by_seq.insert(seq, key.start);
+    }
+
+    /// Evict the trace that ended first, returning the removed ResourceSpans.
+    /// ResourceSpans without any span are evicted from the front once no
+    /// trace is left.
+    pub fn evict_oldest(&mut self) -> Vec<ResourceSpans> {
+        match self.by_end_time.first() {
+            Some((_, trace_id)) => {
+                let trace_id = trace_id.clone();
+                self.evict(&trace_id)
+            }
+            None => self
+                .resource_spans
+                .pop_first()
+                .map(|(key, rs)| {
+                    self.by_seq.remove(&key.seq);
+                    rs
+                })
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Evict every trace that ended before `cutoff`, returning the removed
+    /// ResourceSpans.
+    pub fn evict_ended_before(&mut self, cutoff: u64) -> Vec<ResourceSpans> {
+        let mut removed = Vec::new();
+        while let Some((end_time, trace_id)) = self.by_end_time.first() {
+            if *end_time >= cutoff {
+                break;
+            }
+            let trace_id = trace_id.clone();
+            removed.extend(self.evict(&trace_id));
+        }
+        removed
+    }
+
+    /// Remove a trace and every ResourceSpans holding one of its spans.
+    pub fn evict(&mut self, trace_id: &[u8]) -> Vec<ResourceSpans> {
+        let Some(entry) = self.traces.remove(trace_id) else {
+            return Vec::new();
+        };
+        self.by_end_time
+            .remove(&(entry.end_time, trace_id.to_vec()));
+
+        let mut removed = Vec::with_capacity(entry.keys.len());
+        let mut affected: HashSet<Vec<u8>> = HashSet::new();
+        for key in &entry.keys {
+            if let Some(rs) = self.resource_spans.remove(key) {
+                self.by_seq.remove(&key.seq);
+                affected.extend(
+                    trace_end_times(&rs)
+                        .into_keys()
+                        .filter(|other| *other != trace_id)
+                        .map(<[u8]>::to_vec),
+                );
+                removed.push(rs);
+            }
+        }
+        for other in affected {
+            self.refresh(&other);
+        }
+        removed
+    }
+
+    /// Drop the keys of a trace that no longer exist and recompute its end
+    /// time, removing the trace when none of its spans are left.
+    fn refresh(&mut self, trace_id: &[u8]) {
+        let Some(mut entry) = self.traces.remove(trace_id) else {
+            return;
+        };
+        self.by_end_time
+            .remove(&(entry.end_time, trace_id.to_vec()));
+        // An edited ResourceSpans can be stored again under its old key.
+        entry.keys.sort_unstable();
+        entry.keys.dedup();
+        entry
+            .keys
+            .retain(|key| self.resource_spans.contains_key(key));
+        if entry.keys.is_empty() {
+            return;
+        }
+        entry.end_time = entry
+            .keys
+            .iter()
+            .filter_map(|key| self.resource_spans.get(key))
+            .flat_map(|rs| rs.scope_spans.iter())
+            .flat_map(|ss| &ss.spans)
+            .filter(|span| span.trace_id == trace_id)
+            .map(|span| span.end_time_unix_nano)
+            .max()
+            .unwrap_or(0);
+        self.by_end_time.insert((entry.end_time, trace_id.to_vec()));
+        self.traces.insert(trace_id.to_vec(), entry);
+    }
+
+    /// Apply `edit` to the ResourceSpans stored with sequence number `seq`
+    /// and recompute the end time of every trace it held spans of. The
+    /// ResourceSpans is dropped when `edit` leaves it without spans. Does
+    /// nothing when no such ResourceSpans is stored.
+    pub fn edit(&mut self, seq: u64, edit: impl FnOnce(&mut ResourceSpans)) {
+        let Some(start) = self.by_seq.remove(&seq) else {
+            return;
+        };
+        let Some(mut rs) = self.resource_spans.remove(&EntryKey { start, seq }) else {
+            return;
+        };
+        let trace_ids: Vec<Vec<u8>> = trace_end_times(&rs)
+            .into_keys()
+            .map(<[u8]>::to_vec)
+            .collect();
+        edit(&mut rs);
+        if rs.scope_spans.iter().any(|ss| !ss.spans.is_empty()) {
+            self.insert(rs, seq);
+        }
+        for trace_id in trace_ids {
+            self.refresh(&trace_id);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.resource_spans.clear();
+        self.by_seq.clear();
+        self.traces.clear();
+        self.by_end_time.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::opentelemetry::proto::trace::v1::{ScopeSpans, Span};
+
+    fn span(trace: u8, start: u64, end: u64) -> Span {
+        Span {
+            trace_id: vec![trace; 16],
+            span_id: vec![trace; 8],
+            start_time_unix_nano: start,
+            end_time_unix_nano: end,
+            ..Default::default()
+        }
+    }
+
+    fn rs(spans: Vec<Span>) -> ResourceSpans {
+        ResourceSpans {
+            scope_spans: vec![ScopeSpans {
+                spans,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_evicts_by_end_time_and_looks_up_traces() {
+        let mut index = TraceIndex::default();
+        index.insert(rs(vec![span(1, 100, 150)]), 1);
+        index.insert(rs(vec![span(2, 50, 500)]), 2);
+        index.insert(rs(vec![span(1, 120, 200)]), 3);
+        assert_eq!(index.trace_count(), 2);
+        assert_eq!(index.end_time(&[1; 16]), Some(200));
+        let starts: Vec<u64> = index.resource_spans().map(rs_sort_key).collect();
+        assert_eq!(starts, vec![50, 100, 120]);
+        assert_eq!(index.trace(&[1; 16]).len(), 2);
+        let bounds = TimeBounds {
+            start: 60,
+            end: 120,
+        };
+        let starts: Vec<u64> = index.range(bounds).map(|(_, rs)| rs_sort_key(rs)).collect();
+        assert_eq!(starts, vec![100, 120]);
+        let after: Vec<u64> = index.after(1).into_iter().map(rs_sort_key).collect();
+        assert_eq!(after, vec![50, 120]);
+
+        let removed = index.evict_oldest();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(index.trace_count(), 1);
+        assert!(index.trace(&[1; 16]).is_empty());
+        assert_eq!(index.evict_ended_before(501).len(), 1);
+        assert_eq!(index.len(), 0);
+    }
+
+    #[test]
+    fn test_shared_resource_spans_update_other_traces() {
+        let mut index = TraceIndex::default();
+        // Trace 2 has spans in a ResourceSpans shared with trace 1 and in its own.
+        index.insert(rs(vec![span(1, 100, 150), span(2, 100, 900)]), 1);
+        index.insert(rs(vec![span(2, 200, 300)]), 2);
+        index.insert(rs(vec![span(3, 300, 400), span(1, 300, 160)]), 3);
+
+        // Trace 1 ends first. Both ResourceSpans holding it go, and with them
+        // all of trace 3 and trace 2's span ending at 900.
+        index.evict_oldest();
+        assert_eq!(index.trace_count(), 1);
+        assert_eq!(index.end_time(&[2; 16]), Some(300));
+        assert_eq!(index.len(), 1);
+
+        // ResourceSpans without spans are evicted once no trace is left.
+        index.insert(rs(vec![]), 4);
+        index.evict_oldest();
+        assert_eq!(index.len(), 1);
+        index.evict_oldest();
+        assert_eq!(index.len(), 0);
+        assert_eq!(index.trace_count(), 0);
+    }
+
+    #[test]
+    fn test_edit_updates_end_times() {
+        let mut index = TraceIndex::default();
+        index.insert(rs(vec![span(1, 100, 150), span(2, 100, 900)]), 1);
+        index.insert(rs(vec![span(2, 200, 300)]), 2);
+        index.insert(rs(vec![span(3, 300, 400)]), 3);
+
+        let drop_late = |rs: &mut ResourceSpans| {
+            for ss in &mut rs.scope_spans {
+                ss.spans.retain(|span| span.end_time_unix_nano < 400);
+            }
+        };
+        index.edit(1, drop_late);
+        index.edit(3, drop_late);
+        index.edit(4, drop_late);
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.trace(&[1; 16]).len(), 1);
+        assert_eq!(index.trace_count(), 2);
+        assert_eq!(index.end_time(&[2; 16]), Some(300));
+        assert_eq!(index.end_time(&[3; 16]), None);
+        let after: Vec<u64> = index.after(1).into_iter().map(rs_sort_key).collect();
+        assert_eq!(after, vec![200]);
+
+        // Trace 2 no longer holds its span ending at 900.
+        index.evict_ended_before(301);
+        assert_eq!(index.trace_count(), 0);
+        assert_eq!(index.len(), 0);
+    }
+}
@@ -99,7 +99,7 @@ pub fn register_store_metrics(
 
     let meter = guard.meter_provider.meter("otel-cli");
 
-    let store_traces = store.clone();
+    let store_traces = store.traces.clone();
     let trace_gauge = meter
         .u64_observable_gauge("otel_cli.store.trace_count")
         .with_description("Number of trace groups in the store")
@@ -112,7 +112,7 @@ pub fn register_store_metrics(
         )
         .build();
 
-    let store_logs = store.clone();
+    let store_logs = store.logs.clone();
     let log_gauge = meter
         .u64_observable_gauge("otel_cli.store.log_count")
         .with_description("Number of log entries in the store")
@@ -125,7 +125,7 @@ pub fn register_store_metrics(
         )
         .build();
 
-    let store_metrics = store;
+    let store_metrics = store.metrics.clone();
     let metric_gauge = meter
         .u64_observable_gauge("otel_cli.store.metric_count")
         .with_description("Number of metric entries in the store")
@@ -138,7 +138,37 @@ pub fn register_store_metrics(
         )
         .build();
 
-    vec![trace_gauge, log_gauge, metric_gauge]
+    let store_bytes = store;
+    let bytes_gauge = meter
+        .u64_observable_gauge("otel_cli.store.bytes")
+        .with_description("Encoded size of the data in the store, by signal")
+        .with_unit("By")
+        .with_callback(
+            move |observer: &dyn opentelemetry::metrics::AsyncInstrument<u64>| {
+                let bytes = [
+                    (
+                        "traces",
+                        store_bytes.traces.try_read().map(|s| s.trace_bytes()),
+                    ),
+                    ("logs", store_bytes.logs.try_read().map(|s| s.log_bytes())),
+                    (
+                        "metrics",
+                        store_bytes.metrics.try_read().map(|s| s.metric_bytes()),
+                    ),
+                ];
+                for (signal, bytes) in bytes {
+                    if let Ok(bytes) = bytes {
+                        observer.observe(
+                            bytes as u64,
+                            &[opentelemetry::KeyValue::new("signal", signal)],
+                        );
+                    }
+                }
+            },
+        )
+        .build();
+
+    vec![trace_gauge, log_gauge, metric_gauge, bytes_gauge]
 }
 
 pub fn shutdown(guard: Option<TelemetryGuard>) {
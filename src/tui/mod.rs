@@ -2,6 +2,7 @@ pub mod event;
 pub mod tabs;
 pub mod ui;
 
+use std::borrow::Cow;
 use std::io;
 
 use crossterm::{
@@ -22,7 +23,7 @@ use crate::proto::opentelemetry::proto::{
     metrics::v1::{metric, number_data_point, ResourceMetrics},
     trace::v1::ResourceSpans,
 };
-use crate::store::{SharedStore, StoreEvent};
+use crate::store::{SharedStore, StoreEvent, TimeBounds};
 
 // --- Local filter types (used only by TUI, converted to SQL internally) ---
 
@@ -348,9 +349,21 @@ impl App {
 
     fn mark_dirty(&mut self, event: StoreEvent) {
         match event {
-            StoreEvent::TracesAdded | StoreEvent::TracesCleared => self.dirty_traces = true,
-            StoreEvent::LogsAdded | StoreEvent::LogsCleared => self.dirty_logs = true,
-            StoreEvent::MetricsAdded | StoreEvent::MetricsCleared => self.dirty_metrics = true,
+            StoreEvent::TracesAdded(_)
+            | StoreEvent::TracesCleared
+            | StoreEvent::TracesExpired
+            | StoreEvent::TracesEvicted
+            | StoreEvent::TracesDeleted => self.dirty_traces = true,
+            StoreEvent::LogsAdded(_)
+            | StoreEvent::LogsCleared
+            | StoreEvent::LogsExpired
+            | StoreEvent::LogsEvicted
+            | StoreEvent::LogsDeleted => self.dirty_logs = true,
+            StoreEvent::MetricsAdded(_)
+            | StoreEvent::MetricsCleared
+            | StoreEvent::MetricsExpired
+            | StoreEvent::MetricsEvicted
+            | StoreEvent::MetricsDeleted => self.dirty_metrics = true,
         }
     }
 
@@ -1028,13 +1041,11 @@ impl App {
     }
 
     async fn clear_current_tab(&mut self) {
-        let mut store = self.store.write().await;
         match self.current_tab {
-            tabs::Tab::Traces => store.clear_traces(),
-            tabs::Tab::Logs => store.clear_logs(),
-            tabs::Tab::Metrics => store.clear_metrics(),
+            tabs::Tab::Traces => self.store.traces.write().await.clear_traces(),
+            tabs::Tab::Logs => self.store.logs.write().await.clear_logs(),
+            tabs::Tab::Metrics => self.store.metrics.write().await.clear_metrics(),
         }
-        drop(store);
         self.table_state = TableState::default();
         if self.current_tab == tabs::Tab::Traces {
             self.trace_view = TraceView::List;
@@ -1053,19 +1064,31 @@ impl App {
         refresh_logs: bool,
         refresh_metrics: bool,
     ) {
-        let store = self.store.read().await;
+        let store = self.store.traces.read().await;
         self.trace_count = store.trace_count();
-        self.log_count = store.log_count();
-        self.metric_count = store.metric_count();
-
         let traces = if refresh_traces {
-            Some(store.all_traces().iter().cloned().collect::<Vec<_>>())
+            Some(
+                store
+                    .scan_traces(TimeBounds::ALL)
+                    .map(Cow::into_owned)
+                    .collect::<Vec<_>>(),
+            )
         } else {
             None
         };
+        drop(store);
+
+        self.log_count = self.store.logs.read().await.log_count();
 
+        let store = self.store.metrics.read().await;
+        self.metric_count = store.metric_count();
         let metrics = if refresh_metrics {
-            Some(store.all_metrics().iter().cloned().collect::<Vec<_>>())
+            Some(
+                store
+                    .scan_metrics(TimeBounds::ALL)
+                    .map(Cow::into_owned)
+                    .collect::<Vec<_>>(),
+            )
         } else {
             None
         };
@@ -13,6 +13,7 @@ use otel_cli::proto::opentelemetry::proto::{
 use otel_cli::proto::otelcli::query::v1::{
     query_service_client::QueryServiceClient, SqlQueryRequest,
 };
+use otel_cli::server::ServerOptions;
 use otel_cli::store;
 use tokio_util::sync::CancellationToken;
 
@@ -54,9 +55,14 @@ async fn start_servers(grpc_port: u16, query_port: u16) -> (store::SharedStore,
     let store_clone = shared_store.clone();
     let shutdown_clone = shutdown.clone();
     tokio::spawn(async move {
-        otel_cli::server::run_grpc_server(grpc_listener, store_clone, shutdown_clone)
-            .await
-            .unwrap();
+        otel_cli::server::run_grpc_server(
+            grpc_listener,
+            store_clone,
+            ServerOptions::default(),
+            shutdown_clone,
+        )
+        .await
+        .unwrap();
     });
 
     let query_addr: std::net::SocketAddr = format!("127.0.0.1:{}", query_port).parse().unwrap();
@@ -65,9 +71,15 @@ async fn start_servers(grpc_port: u16, query_port: u16) -> (store::SharedStore,
     let ctx = otel_cli::query::datafusion_ctx::create_context(shared_store.clone());
     let shutdown_clone = shutdown.clone();
     tokio::spawn(async move {
-        otel_cli::server::run_query_server(query_listener, store_clone, ctx, shutdown_clone)
-            .await
-            .unwrap();
+        otel_cli::server::run_query_server(
+            query_listener,
+            store_clone,
+            ctx,
+            ServerOptions::default(),
+            shutdown_clone,
+        )
+        .await
+        .unwrap();
     });
 
     tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -0,0 +1,285 @@
+use otel_cli::cli::ConnectionArgs;
+use otel_cli::proto::opentelemetry::proto::{
+    collector::{
+        logs::v1::ExportLogsServiceRequest,
+        trace::v1::{trace_service_client::TraceServiceClient, ExportTraceServiceRequest},
+    },
+    common::v1::{any_value, AnyValue, KeyValue},
+    logs::v1::{LogRecord, ResourceLogs, ScopeLogs},
+    resource::v1::Resource,
+    trace::v1::{ResourceSpans, ScopeSpans, Span},
+};
+use otel_cli::proto::otelcli::query::v1::{
+    ClearTracesRequest, FollowRequest, ShutdownRequest, SqlQueryRequest, StatusRequest,
+};
+use otel_cli::server::auth::{Access, Authenticator};
+use otel_cli::server::ServerOptions;
+use otel_cli::store;
+use prost::Message;
+use tokio_util::sync::CancellationToken;
+
+fn get_available_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+fn make_resource(service_name: &str) -> Option<Resource> {
+    Some(Resource {
+        attributes: vec![KeyValue {
+            key: "service.name".into(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(service_name.into())),
+            }),
+        }],
+        dropped_attributes_count: 0,
+        entity_refs: vec![],
+    })
+}
+
+fn make_trace_request() -> ExportTraceServiceRequest {
+    ExportTraceServiceRequest {
+        resource_spans: vec![ResourceSpans {
+            resource: make_resource("auth-service"),
+            scope_spans: vec![ScopeSpans {
+                scope: None,
+                spans: vec![Span {
+                    trace_id: vec![5; 16],
+                    span_id: vec![5; 8],
+                    name: "auth-span".into(),
+                    start_time_unix_nano: 1_000_000_000,
+                    end_time_unix_nano: 2_000_000_000,
+                    ..Default::default()
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+}
+
+fn auth_options() -> ServerOptions {
+    let mut ingest_auth = Authenticator::default();
+    ingest_auth
+        .add_bearer_token("ingest-secret", Access::Admin)
+        .unwrap();
+    ingest_auth
+        .add_header("x-api-key", "ingest-key", Access::Admin)
+        .unwrap();
+    let mut query_auth = Authenticator::default();
+    query_auth
+        .add_bearer_token("admin-secret", Access::Admin)
+        .unwrap();
+    query_auth
+        .add_bearer_token("read-secret", Access::ReadOnly)
+        .unwrap();
+    ServerOptions {
+        ingest_auth,
+        query_auth,
+        ..ServerOptions::default()
+    }
+}
+
+struct Ports {
+    grpc: u16,
+    http: u16,
+    query: u16,
+}
+
+async fn start_servers(options: ServerOptions) -> (store::SharedStore, Ports, CancellationToken) {
+    let ports = Ports {
+        grpc: get_available_port(),
+        http: get_available_port(),
+        query: get_available_port(),
+    };
+    let (shared_store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
+    let shutdown = CancellationToken::new();
+
+    let (grpc_listener, http_listener, query_listener) = otel_cli::server::bind_listeners(
+        format!("127.0.0.1:{}", ports.grpc).parse().unwrap(),
+        format!("127.0.0.1:{}", ports.http).parse().unwrap(),
+        format!("127.0.0.1:{}", ports.query).parse().unwrap(),
+    )
+    .await
+    .unwrap();
+    tokio::spawn(otel_cli::server::run_grpc_server(
+        grpc_listener,
+        shared_store.clone(),
+        options.clone(),
+        shutdown.clone(),
+    ));
+    tokio::spawn(otel_cli::server::run_http_server(
+        http_listener,
+        shared_store.clone(),
+        options.clone(),
+        shutdown.clone(),
+    ));
+    let ctx = otel_cli::query::datafusion_ctx::create_context(shared_store.clone());
+    tokio::spawn(otel_cli::server::run_query_server(
+        query_listener,
+        shared_store.clone(),
+        ctx,
+        options,
+        shutdown.clone(),
+    ));
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    (shared_store, ports, shutdown)
+}
+
+fn with_token(token: &str) -> ConnectionArgs {
+    ConnectionArgs {
+        token: Some(token.to_string()),
+        ..ConnectionArgs::default()
+    }
+}
+
+#[tokio::test]
+async fn test_grpc_ingest_requires_token() {
+    let (store, ports, _shutdown) = start_servers(auth_options()).await;
+    let mut client = TraceServiceClient::connect(format!("http://127.0.0.1:{}", ports.grpc))
+        .await
+        .unwrap();
+
+    let err = client.export(make_trace_request()).await.unwrap_err();
+    assert_eq!(err.code(), tonic::Code::Unauthenticated);
+    assert_eq!(store.traces.read().await.trace_count(), 0);
+
+    let mut request = tonic::Request::new(make_trace_request());
+    request
+        .metadata_mut()
+        .insert("authorization", "Bearer ingest-secret".parse().unwrap());
+    client.export(request).await.unwrap();
+    assert_eq!(store.traces.read().await.trace_count(), 1);
+}
+
+#[tokio::test]
+async fn test_http_ingest_requires_credentials() {
+    let (store, ports, _shutdown) = start_servers(auth_options()).await;
+    let url = format!("http://127.0.0.1:{}/v1/logs", ports.http);
+    let body = ExportLogsServiceRequest {
+        resource_logs: vec![ResourceLogs {
+            resource: make_resource("auth-http-svc"),
+            scope_logs: vec![ScopeLogs {
+                scope: None,
+                log_records: vec![LogRecord {
+                    severity_text: "INFO".into(),
+                    time_unix_nano: 1_000_000_000,
+                    ..Default::default()
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+    .encode_to_vec();
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(&url)
+        .header("Content-Type", "application/x-protobuf")
+        .body(body.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+
+    let response = client
+        .post(&url)
+        .header("Content-Type", "application/x-protobuf")
+        .header("Authorization", "Bearer wrong")
+        .body(body.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(store.logs.read().await.log_count(), 0);
+
+    let response = client
+        .post(&url)
+        .header("Content-Type", "application/x-protobuf")
+        .header("X-Api-Key", "ingest-key")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(store.logs.read().await.log_count(), 1);
+}
+
+#[tokio::test]
+async fn test_query_rejects_missing_token() {
+    let (_store, ports, _shutdown) = start_servers(auth_options()).await;
+    let server = format!("http://127.0.0.1:{}", ports.query);
+
+    let mut client = otel_cli::client::connect(&server, &ConnectionArgs::default())
+        .await
+        .unwrap();
+    let err = client
+        .sql_query(SqlQueryRequest {
+            query: "SELECT 1".into(),
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::Unauthenticated);
+
+    // The ingest token grants nothing on the query API.
+    let mut client = otel_cli::client::connect(&server, &with_token("ingest-secret"))
+        .await
+        .unwrap();
+    let err = client.status(StatusRequest {}).await.unwrap_err();
+    assert_eq!(err.code(), tonic::Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn test_read_only_token_cannot_clear_or_shutdown() {
+    let (store, ports, shutdown) = start_servers(auth_options()).await;
+    store
+        .traces
+        .write()
+        .await
+        .insert_traces(make_trace_request().resource_spans);
+    let server = format!("http://127.0.0.1:{}", ports.query);
+
+    let mut client = otel_cli::client::connect(&server, &with_token("read-secret"))
+        .await
+        .unwrap();
+    let rows = client
+        .sql_query(SqlQueryRequest {
+            query: "SELECT span_name FROM traces".into(),
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .rows;
+    assert_eq!(rows.len(), 1);
+    client.status(StatusRequest {}).await.unwrap();
+    client.follow_traces(FollowRequest {}).await.unwrap();
+
+    let err = client
+        .clear_traces(ClearTracesRequest {})
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::PermissionDenied);
+    let err = client.shutdown(ShutdownRequest {}).await.unwrap_err();
+    assert_eq!(err.code(), tonic::Code::PermissionDenied);
+    assert_eq!(store.traces.read().await.trace_count(), 1);
+    assert!(!shutdown.is_cancelled());
+}
+
+#[tokio::test]
+async fn test_admin_token_can_clear() {
+    let (store, ports, _shutdown) = start_servers(auth_options()).await;
+    store
+        .traces
+        .write()
+        .await
+        .insert_traces(make_trace_request().resource_spans);
+    let server = format!("http://127.0.0.1:{}", ports.query);
+
+    let mut client = otel_cli::client::connect(&server, &with_token("admin-secret"))
+        .await
+        .unwrap();
+    client.clear_traces(ClearTracesRequest {}).await.unwrap();
+    assert_eq!(store.traces.read().await.trace_count(), 0);
+}
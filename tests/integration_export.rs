@@ -0,0 +1,106 @@
+use otel_cli::cli::{ConnectionArgs, OtlpFileFormat};
+use otel_cli::client::import::{parse, ImportData};
+use otel_cli::proto::opentelemetry::proto::{
+    common::v1::{any_value, AnyValue, KeyValue},
+    resource::v1::Resource,
+    trace::v1::{ResourceSpans, ScopeSpans, Span},
+};
+use otel_cli::server::ServerOptions;
+use otel_cli::store;
+use tokio_util::sync::CancellationToken;
+
+fn make_resource(service_name: &str) -> Option<Resource> {
+    Some(Resource {
+        attributes: vec![KeyValue {
+            key: "service.name".into(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(service_name.into())),
+            }),
+        }],
+        dropped_attributes_count: 0,
+        entity_refs: vec![],
+    })
+}
+
+fn make_resource_spans(service_name: &str, id: u8) -> ResourceSpans {
+    ResourceSpans {
+        resource: make_resource(service_name),
+        scope_spans: vec![ScopeSpans {
+            scope: None,
+            spans: vec![Span {
+                trace_id: vec![id; 16],
+                span_id: vec![id; 8],
+                name: format!("{}-span", service_name),
+                start_time_unix_nano: 1_000_000_000,
+                end_time_unix_nano: 2_000_000_000,
+                ..Default::default()
+            }],
+            schema_url: "https://example.com/schema".into(),
+        }],
+        schema_url: String::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_export_filtered_traces_round_trip() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (shared_store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
+    shared_store.traces.write().await.insert_traces(vec![
+        make_resource_spans("service-a", 1),
+        make_resource_spans("service-b", 2),
+    ]);
+    let shutdown = CancellationToken::new();
+    let ctx = otel_cli::query::datafusion_ctx::create_context(shared_store.clone());
+    tokio::spawn(otel_cli::server::run_query_server(
+        listener,
+        shared_store.clone(),
+        ctx,
+        ServerOptions::default(),
+        shutdown.clone(),
+    ));
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let server = format!("http://127.0.0.1:{}", port);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("export.jsonl");
+    otel_cli::client::export::export(
+        &server,
+        &ConnectionArgs::default(),
+        Some(&path),
+        None,
+        true,
+        false,
+        false,
+        Some("service_name = 'service-b'".into()),
+    )
+    .await
+    .unwrap();
+
+    let mut data = ImportData::default();
+    parse(
+        &std::fs::read(&path).unwrap(),
+        OtlpFileFormat::Jsonl,
+        None,
+        &mut data,
+    )
+    .unwrap();
+    assert_eq!(data.traces, vec![make_resource_spans("service-b", 2)]);
+    assert!(data.logs.is_empty());
+
+    let err = otel_cli::client::export::export(
+        &server,
+        &ConnectionArgs::default(),
+        Some(&path),
+        None,
+        true,
+        false,
+        false,
+        Some("no_such_column = 1".into()),
+    )
+    .await
+    .unwrap_err();
+    assert!(err.to_string().contains("SQL error"), "{}", err);
+
+    shutdown.cancel();
+}
@@ -0,0 +1,91 @@
+use otel_cli::store;
+use rmpv::Value;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+
+fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    rmpv::encode::write_value(&mut out, value).unwrap();
+    out
+}
+
+fn record(log: &str) -> Value {
+    Value::Map(vec![
+        (Value::from("log"), Value::from(log)),
+        (Value::from("stream"), Value::from("stdout")),
+    ])
+}
+
+#[tokio::test]
+async fn test_fluent_forward_with_ack() {
+    let (store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
+    let shutdown = CancellationToken::new();
+    let listener = otel_cli::server::bind_tcp("Fluent Forward", "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(otel_cli::server::fluent::run_fluent_server(
+        listener,
+        store.clone(),
+        shutdown.clone(),
+    ));
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+    // Message mode without ack, then PackedForward split across two writes with ack.
+    stream
+        .write_all(&encode(&Value::Array(vec![
+            Value::from("app.one"),
+            Value::from(1_700_000_000u64),
+            record("first"),
+        ])))
+        .await
+        .unwrap();
+
+    let mut packed = encode(&Value::Array(vec![
+        Value::from(1_700_000_001u64),
+        record("second"),
+    ]));
+    packed.extend(encode(&Value::Array(vec![
+        Value::from(1_700_000_002u64),
+        record("third"),
+    ])));
+    let message = encode(&Value::Array(vec![
+        Value::from("app.two"),
+        Value::Binary(packed),
+        Value::Map(vec![
+            (Value::from("size"), Value::from(2)),
+            (Value::from("chunk"), Value::from("chunk-1")),
+        ]),
+    ]));
+    let (head, tail) = message.split_at(message.len() / 2);
+    stream.write_all(head).await.unwrap();
+    stream.flush().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream.write_all(tail).await.unwrap();
+
+    let mut ack = vec![0u8; 64];
+    let n = tokio::time::timeout(Duration::from_secs(2), stream.read(&mut ack))
+        .await
+        .unwrap()
+        .unwrap();
+    let ack = rmpv::decode::read_value(&mut &ack[..n]).unwrap();
+    assert_eq!(
+        ack,
+        Value::Map(vec![(Value::from("ack"), Value::from("chunk-1"))])
+    );
+
+    let store = store.logs.read().await;
+    assert_eq!(store.log_count(), 2);
+    let two = store
+        .scan_logs(store::TimeBounds::ALL)
+        .find(|rl| otel_cli::client::get_service_name(&rl.resource) == "app.two")
+        .unwrap();
+    let records = &two.scope_logs[0].log_records;
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].time_unix_nano, 1_700_000_002_000_000_000);
+    assert_eq!(records[1].attributes[0].key, "stream");
+
+    shutdown.cancel();
+}
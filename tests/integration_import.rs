@@ -0,0 +1,60 @@
+use otel_cli::cli::{ConnectionArgs, OtlpFileFormat};
+use otel_cli::server::ServerOptions;
+use otel_cli::store;
+use tokio_util::sync::CancellationToken;
+
+const TRACES_LINE: &str = r#"{"resourceSpans":[{"resource":{"attributes":[{"key":"service.name","value":{"stringValue":"imported"}}]},"scopeSpans":[{"spans":[{"traceId":"0102030405060708090a0b0c0d0e0f10","spanId":"0102030405060708","name":"GET /cart","startTimeUnixNano":"1000000000","endTimeUnixNano":"3000000000"}]}]}]}"#;
+const METRICS_LINE: &str = r#"{"resourceMetrics":[{"scopeMetrics":[{"metrics":[{"name":"queue_depth","gauge":{"dataPoints":[{"timeUnixNano":"2000000000","asInt":"3"},{"timeUnixNano":"4000000000","asInt":"5"}]}}]}]}]}"#;
+
+#[tokio::test]
+async fn test_import_jsonl_file() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (shared_store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
+    let shutdown = CancellationToken::new();
+    tokio::spawn(otel_cli::server::run_grpc_server(
+        listener,
+        shared_store.clone(),
+        ServerOptions::default(),
+        shutdown.clone(),
+    ));
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("capture");
+    std::fs::write(&path, format!("{}\n{}\n", TRACES_LINE, METRICS_LINE)).unwrap();
+
+    let before = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos() as u64;
+    otel_cli::client::import::import(
+        &format!("http://127.0.0.1:{}", port),
+        &ConnectionArgs::default(),
+        &[path],
+        Some(OtlpFileFormat::Jsonl),
+        None,
+        true,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(shared_store.metrics.read().await.metric_count(), 1);
+    let store = shared_store.traces.read().await;
+    assert_eq!(store.trace_count(), 1);
+    let traces: Vec<_> = store.scan_traces(store::TimeBounds::ALL).collect();
+    let span = &traces[0].scope_spans[0].spans[0];
+    assert_eq!(
+        otel_cli::client::get_service_name(&traces[0].resource),
+        "imported"
+    );
+    // The newest timestamp (the last data point) is shifted to now.
+    assert!(span.end_time_unix_nano >= before - 1_000_000_000);
+    assert!(span.end_time_unix_nano < before);
+    assert_eq!(
+        span.end_time_unix_nano - span.start_time_unix_nano,
+        2_000_000_000
+    );
+
+    shutdown.cancel();
+}
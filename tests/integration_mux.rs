@@ -0,0 +1,137 @@
+use otel_cli::cli::ConnectionArgs;
+use otel_cli::proto::opentelemetry::proto::{
+    collector::{
+        logs::v1::ExportLogsServiceRequest,
+        trace::v1::{trace_service_client::TraceServiceClient, ExportTraceServiceRequest},
+    },
+    common::v1::{any_value, AnyValue, KeyValue},
+    logs::v1::{LogRecord, ResourceLogs, ScopeLogs},
+    resource::v1::Resource,
+    trace::v1::{ResourceSpans, ScopeSpans, Span},
+};
+use otel_cli::proto::otelcli::query::v1::{SqlQueryRequest, StatusRequest};
+use otel_cli::server::ServerOptions;
+use otel_cli::store;
+use prost::Message;
+use tokio_util::sync::CancellationToken;
+
+fn make_resource(service_name: &str) -> Option<Resource> {
+    Some(Resource {
+        attributes: vec![KeyValue {
+            key: "service.name".into(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(service_name.into())),
+            }),
+        }],
+        dropped_attributes_count: 0,
+        entity_refs: vec![],
+    })
+}
+
+#[tokio::test]
+async fn test_mux_server_routes_grpc_http_and_query() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (shared_store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
+    let shutdown = CancellationToken::new();
+    let ctx = otel_cli::query::datafusion_ctx::create_context(shared_store.clone());
+    let handle = tokio::spawn(otel_cli::server::run_mux_server(
+        listener,
+        shared_store.clone(),
+        ctx,
+        ServerOptions::default(),
+        shutdown.clone(),
+    ));
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    let base = format!("http://127.0.0.1:{}", port);
+
+    // OTLP gRPC
+    let mut trace_client = TraceServiceClient::connect(base.clone()).await.unwrap();
+    trace_client
+        .export(ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: make_resource("mux-service"),
+                scope_spans: vec![ScopeSpans {
+                    scope: None,
+                    spans: vec![Span {
+                        trace_id: vec![9; 16],
+                        span_id: vec![9; 8],
+                        name: "mux-span".into(),
+                        start_time_unix_nano: 1_000_000_000,
+                        end_time_unix_nano: 2_000_000_000,
+                        ..Default::default()
+                    }],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        })
+        .await
+        .unwrap();
+
+    // OTLP/HTTP on the same port
+    let body = ExportLogsServiceRequest {
+        resource_logs: vec![ResourceLogs {
+            resource: make_resource("mux-service"),
+            scope_logs: vec![ScopeLogs {
+                scope: None,
+                log_records: vec![LogRecord {
+                    time_unix_nano: 1_000_000_000,
+                    body: Some(AnyValue {
+                        value: Some(any_value::Value::StringValue("one port".into())),
+                    }),
+                    ..Default::default()
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+    .encode_to_vec();
+    let http = reqwest::Client::new();
+    let resp = http
+        .post(format!("{}/v1/logs", base))
+        .header("Content-Type", "application/x-protobuf")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    // A gRPC method path without a gRPC content type is not routed to the gRPC services
+    let resp = http
+        .post(format!(
+            "{}/opentelemetry.proto.collector.trace.v1.TraceService/Export",
+            base
+        ))
+        .header("Content-Type", "application/x-protobuf")
+        .body(Vec::new())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 404);
+
+    // Query API on the same port
+    let mut client = otel_cli::client::connect(&base, &ConnectionArgs::default())
+        .await
+        .unwrap();
+    let status = client.status(StatusRequest {}).await.unwrap().into_inner();
+    assert_eq!(status.trace_count, 1);
+    assert_eq!(status.log_count, 1);
+    assert!(status.trace_bytes > 0 && status.log_bytes > 0);
+    assert_eq!(status.metric_bytes, 0);
+    assert_eq!(status.max_memory, 0);
+    assert!(!status.dedup);
+    let rows = client
+        .sql_query(SqlQueryRequest {
+            query: "SELECT span_name FROM traces".into(),
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .rows;
+    assert_eq!(rows.len(), 1);
+
+    shutdown.cancel();
+    handle.await.unwrap().unwrap();
+}
@@ -13,8 +13,10 @@ use otel_cli::proto::opentelemetry::proto::{
 use otel_cli::proto::otelcli::query::v1::{
     query_service_client::QueryServiceClient, SqlQueryRequest,
 };
+use otel_cli::server::ServerOptions;
 use otel_cli::store;
 use tokio_util::sync::CancellationToken;
+use tonic::codec::CompressionEncoding;
 
 fn get_row_string(row: &otel_cli::proto::otelcli::query::v1::Row, name: &str) -> Option<String> {
     row.columns.iter().find(|c| c.name == name).and_then(|c| {
@@ -46,6 +48,14 @@ fn make_resource(service_name: &str) -> Option<Resource> {
 }
 
 async fn start_servers(grpc_port: u16, query_port: u16) -> (store::SharedStore, CancellationToken) {
+    start_servers_with_options(grpc_port, query_port, ServerOptions::default()).await
+}
+
+async fn start_servers_with_options(
+    grpc_port: u16,
+    query_port: u16,
+    options: ServerOptions,
+) -> (store::SharedStore, CancellationToken) {
     let (shared_store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
     let shutdown = CancellationToken::new();
 
@@ -53,8 +63,9 @@ async fn start_servers(grpc_port: u16, query_port: u16) -> (store::SharedStore,
     let grpc_listener = tokio::net::TcpListener::bind(grpc_addr).await.unwrap();
     let store_clone = shared_store.clone();
     let shutdown_clone = shutdown.clone();
+    let grpc_options = options.clone();
     tokio::spawn(async move {
-        otel_cli::server::run_grpc_server(grpc_listener, store_clone, shutdown_clone)
+        otel_cli::server::run_grpc_server(grpc_listener, store_clone, grpc_options, shutdown_clone)
             .await
             .unwrap();
     });
@@ -65,9 +76,15 @@ async fn start_servers(grpc_port: u16, query_port: u16) -> (store::SharedStore,
     let ctx = otel_cli::query::datafusion_ctx::create_context(shared_store.clone());
     let shutdown_clone = shutdown.clone();
     tokio::spawn(async move {
-        otel_cli::server::run_query_server(query_listener, store_clone, ctx, shutdown_clone)
-            .await
-            .unwrap();
+        otel_cli::server::run_query_server(
+            query_listener,
+            store_clone,
+            ctx,
+            options,
+            shutdown_clone,
+        )
+        .await
+        .unwrap();
     });
 
     tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -93,6 +110,8 @@ async fn test_grpc_trace_ingest_and_query() {
                     trace_id: vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
                     span_id: vec![0, 1, 2, 3, 4, 5, 6, 7],
                     name: "test-span".into(),
+                    start_time_unix_nano: 1_000_000_000,
+                    end_time_unix_nano: 2_000_000_000,
                     ..Default::default()
                 }],
                 schema_url: String::new(),
@@ -137,6 +156,7 @@ async fn test_grpc_logs_ingest_and_query() {
                 scope: None,
                 log_records: vec![LogRecord {
                     severity_text: "ERROR".into(),
+                    time_unix_nano: 1_000_000_000,
                     ..Default::default()
                 }],
                 schema_url: String::new(),
@@ -186,6 +206,7 @@ async fn test_grpc_metrics_ingest_and_query() {
                     metadata: vec![],
                     data: Some(metric::Data::Gauge(Gauge {
                         data_points: vec![NumberDataPoint {
+                            time_unix_nano: 1_000_000_000,
                             ..Default::default()
                         }],
                     })),
@@ -214,3 +235,227 @@ async fn test_grpc_metrics_ingest_and_query() {
         Some("cpu_usage".to_string())
     );
 }
+
+#[tokio::test]
+async fn test_grpc_compressed_export_and_query() {
+    let grpc_port = get_available_port();
+    let query_port = get_available_port();
+    let options = ServerOptions {
+        query_compression: Some(CompressionEncoding::Zstd),
+        ..ServerOptions::default()
+    };
+    let (_store, _shutdown) = start_servers_with_options(grpc_port, query_port, options).await;
+    let addr = format!("http://127.0.0.1:{}", grpc_port);
+    let query_addr = format!("http://127.0.0.1:{}", query_port);
+
+    let mut trace_client = TraceServiceClient::connect(addr.clone())
+        .await
+        .unwrap()
+        .send_compressed(CompressionEncoding::Gzip)
+        .accept_compressed(CompressionEncoding::Gzip);
+    let request = ExportTraceServiceRequest {
+        resource_spans: vec![ResourceSpans {
+            resource: make_resource("gzip-service"),
+            scope_spans: vec![ScopeSpans {
+                scope: None,
+                spans: vec![Span {
+                    trace_id: vec![7; 16],
+                    span_id: vec![7; 8],
+                    name: "gzip-span".into(),
+                    start_time_unix_nano: 1_000_000_000,
+                    end_time_unix_nano: 2_000_000_000,
+                    ..Default::default()
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    };
+    trace_client.export(request).await.unwrap();
+
+    let mut log_client = LogsServiceClient::connect(addr)
+        .await
+        .unwrap()
+        .send_compressed(CompressionEncoding::Zstd);
+    log_client
+        .export(ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: make_resource("zstd-service"),
+                scope_logs: vec![ScopeLogs {
+                    scope: None,
+                    log_records: vec![LogRecord {
+                        severity_text: "INFO".into(),
+                        time_unix_nano: 1_000_000_000,
+                        ..Default::default()
+                    }],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        })
+        .await
+        .unwrap();
+
+    let mut query_client = QueryServiceClient::connect(query_addr)
+        .await
+        .unwrap()
+        .accept_compressed(CompressionEncoding::Zstd);
+    let response = query_client
+        .sql_query(SqlQueryRequest {
+            query: "SELECT span_name FROM traces".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        response
+            .metadata()
+            .get("grpc-encoding")
+            .map(|v| v.to_str().unwrap()),
+        Some("zstd")
+    );
+    let rows = response.into_inner().rows;
+    assert_eq!(rows.len(), 1);
+    assert_eq!(
+        get_row_string(&rows[0], "span_name"),
+        Some("gzip-span".to_string())
+    );
+
+    let rows = query_client
+        .sql_query(SqlQueryRequest {
+            query: "SELECT service_name FROM logs".into(),
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .rows;
+    assert_eq!(rows.len(), 1);
+}
+
+#[tokio::test]
+async fn test_grpc_partial_success() {
+    let grpc_port = get_available_port();
+    let query_port = get_available_port();
+    let (store, _shutdown) = start_servers(grpc_port, query_port).await;
+    let addr = format!("http://127.0.0.1:{}", grpc_port);
+
+    let mut logs_client = LogsServiceClient::connect(addr.clone()).await.unwrap();
+    let response = logs_client
+        .export(ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: make_resource("partial-svc"),
+                scope_logs: vec![ScopeLogs {
+                    scope: None,
+                    log_records: vec![
+                        LogRecord {
+                            time_unix_nano: 1_000_000_000,
+                            ..Default::default()
+                        },
+                        LogRecord::default(),
+                    ],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        })
+        .await
+        .unwrap();
+    let partial = response.into_inner().partial_success.unwrap();
+    assert_eq!(partial.rejected_log_records, 1);
+    assert_eq!(
+        partial.error_message,
+        "log record: neither time nor observed time is set"
+    );
+    assert_eq!(store.logs.read().await.log_count(), 1);
+
+    let mut metrics_client = MetricsServiceClient::connect(addr).await.unwrap();
+    let response = metrics_client
+        .export(ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: make_resource("partial-svc"),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: None,
+                    metrics: vec![Metric {
+                        name: String::new(),
+                        data: Some(metric::Data::Gauge(Gauge {
+                            data_points: vec![NumberDataPoint {
+                                time_unix_nano: 1_000_000_000,
+                                ..Default::default()
+                            }],
+                        })),
+                        ..Default::default()
+                    }],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        })
+        .await
+        .unwrap();
+    let partial = response.into_inner().partial_success.unwrap();
+    assert_eq!(partial.rejected_data_points, 1);
+    assert_eq!(partial.error_message, "metric name is empty");
+    assert_eq!(store.metrics.read().await.metric_count(), 0);
+}
+
+#[tokio::test]
+async fn test_grpc_jaeger_post_spans() {
+    use otel_cli::proto::jaeger::api_v2::{
+        collector_service_client::CollectorServiceClient, Batch, KeyValue as JaegerKeyValue,
+        PostSpansRequest, Process, Span as JaegerSpan, SpanRef, SpanRefType,
+    };
+
+    let grpc_port = get_available_port();
+    let query_port = get_available_port();
+    let (store, _shutdown) = start_servers(grpc_port, query_port).await;
+
+    let mut client = CollectorServiceClient::connect(format!("http://127.0.0.1:{}", grpc_port))
+        .await
+        .unwrap();
+    client
+        .post_spans(PostSpansRequest {
+            batch: Some(Batch {
+                process: Some(Process {
+                    service_name: "jaeger-grpc-svc".into(),
+                    tags: vec![JaegerKeyValue {
+                        key: "hostname".into(),
+                        v_str: "host-1".into(),
+                        ..Default::default()
+                    }],
+                }),
+                spans: vec![JaegerSpan {
+                    trace_id: vec![1; 16],
+                    span_id: vec![2; 8],
+                    operation_name: "grpc-op".into(),
+                    references: vec![SpanRef {
+                        trace_id: vec![1; 16],
+                        span_id: vec![3; 8],
+                        ref_type: SpanRefType::ChildOf as i32,
+                    }],
+                    start_time: Some(prost_types::Timestamp {
+                        seconds: 1,
+                        nanos: 0,
+                    }),
+                    duration: Some(prost_types::Duration {
+                        seconds: 0,
+                        nanos: 5000,
+                    }),
+                    ..Default::default()
+                }],
+            }),
+        })
+        .await
+        .unwrap();
+
+    let store = store.traces.read().await;
+    let traces: Vec<_> = store.scan_traces(store::TimeBounds::ALL).collect();
+    let rs = &traces[0];
+    assert_eq!(
+        otel_cli::client::get_service_name(&rs.resource),
+        "jaeger-grpc-svc"
+    );
+    assert_eq!(rs.resource.as_ref().unwrap().attributes[1].key, "hostname");
+    let span = &rs.scope_spans[0].spans[0];
+    assert_eq!(span.name, "grpc-op");
+    assert_eq!(span.parent_span_id, vec![3; 8]);
+    assert_eq!(span.end_time_unix_nano, 1_000_005_000);
+}
@@ -1,7 +1,8 @@
 use otel_cli::proto::opentelemetry::proto::{
     collector::{
-        logs::v1::ExportLogsServiceRequest, metrics::v1::ExportMetricsServiceRequest,
-        trace::v1::ExportTraceServiceRequest,
+        logs::v1::ExportLogsServiceRequest,
+        metrics::v1::ExportMetricsServiceRequest,
+        trace::v1::{ExportTraceServiceRequest, ExportTraceServiceResponse},
     },
     common::v1::{any_value, AnyValue, KeyValue},
     logs::v1::{LogRecord, ResourceLogs, ScopeLogs},
@@ -9,11 +10,22 @@ use otel_cli::proto::opentelemetry::proto::{
     resource::v1::Resource,
     trace::v1::{ResourceSpans, ScopeSpans, Span},
 };
+use otel_cli::server::ServerOptions;
 use otel_cli::store;
 use prost::Message;
 use serde_json::json;
+use std::io::Write;
 use tokio_util::sync::CancellationToken;
 
+/// `google.rpc.Status` as returned in OTLP/HTTP error bodies.
+#[derive(Clone, PartialEq, prost::Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+}
+
 fn get_available_port() -> u16 {
     let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
     listener.local_addr().unwrap().port()
@@ -33,6 +45,13 @@ fn make_resource(service_name: &str) -> Option<Resource> {
 }
 
 async fn start_http_server(port: u16) -> (store::SharedStore, CancellationToken) {
+    start_http_server_with_options(port, ServerOptions::default()).await
+}
+
+async fn start_http_server_with_options(
+    port: u16,
+    options: ServerOptions,
+) -> (store::SharedStore, CancellationToken) {
     let (shared_store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
     let addr: std::net::SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
@@ -40,7 +59,7 @@ async fn start_http_server(port: u16) -> (store::SharedStore, CancellationToken)
     let shutdown = CancellationToken::new();
     let shutdown_clone = shutdown.clone();
     tokio::spawn(async move {
-        otel_cli::server::run_http_server(listener, store_clone, shutdown_clone)
+        otel_cli::server::run_http_server(listener, store_clone, options, shutdown_clone)
             .await
             .unwrap();
     });
@@ -51,7 +70,7 @@ async fn start_http_server(port: u16) -> (store::SharedStore, CancellationToken)
 #[tokio::test]
 async fn test_http_trace_ingest() {
     let port = get_available_port();
-    let (_store, _shutdown) = start_http_server(port).await;
+    let (store, _shutdown) = start_http_server(port).await;
 
     let request = ExportTraceServiceRequest {
         resource_spans: vec![ResourceSpans {
@@ -59,9 +78,11 @@ async fn test_http_trace_ingest() {
             scope_spans: vec![ScopeSpans {
                 scope: None,
                 spans: vec![Span {
-                    trace_id: vec![0; 16],
+                    trace_id: vec![1; 16],
                     span_id: vec![1; 8],
                     name: "http-span".into(),
+                    start_time_unix_nano: 1_000_000_000,
+                    end_time_unix_nano: 2_000_000_000,
                     ..Default::default()
                 }],
                 schema_url: String::new(),
@@ -87,6 +108,54 @@ async fn test_http_trace_ingest() {
         .to_str()
         .unwrap();
     assert_eq!(content_type, "application/x-protobuf");
+    let body =
+        ExportTraceServiceResponse::decode(response.bytes().await.unwrap().as_ref()).unwrap();
+    assert!(body.partial_success.is_none());
+
+    let store = store.traces.read().await;
+    let traces: Vec<_> = store.scan_traces(store::TimeBounds::ALL).collect();
+    assert_eq!(traces.len(), 1);
+    assert_eq!(traces[0].scope_spans[0].spans[0].name, "http-span");
+}
+
+#[tokio::test]
+async fn test_http_rejects_all_zero_trace_id() {
+    let port = get_available_port();
+    let (store, _shutdown) = start_http_server(port).await;
+
+    let request = ExportTraceServiceRequest {
+        resource_spans: vec![ResourceSpans {
+            resource: make_resource("http-trace-svc"),
+            scope_spans: vec![ScopeSpans {
+                scope: None,
+                spans: vec![Span {
+                    trace_id: vec![0; 16],
+                    span_id: vec![1; 8],
+                    name: "zero-trace-id".into(),
+                    start_time_unix_nano: 1_000_000_000,
+                    end_time_unix_nano: 2_000_000_000,
+                    ..Default::default()
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    };
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/v1/traces", port))
+        .header("Content-Type", "application/x-protobuf")
+        .body(request.encode_to_vec())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let body =
+        ExportTraceServiceResponse::decode(response.bytes().await.unwrap().as_ref()).unwrap();
+    assert_eq!(body.partial_success.unwrap().rejected_spans, 1);
+    assert_eq!(store.traces.read().await.trace_count(), 0);
 }
 
 #[tokio::test]
@@ -101,6 +170,7 @@ async fn test_http_logs_ingest() {
                 scope: None,
                 log_records: vec![LogRecord {
                     severity_text: "WARN".into(),
+                    time_unix_nano: 1_000_000_000,
                     ..Default::default()
                 }],
                 schema_url: String::new(),
@@ -138,6 +208,7 @@ async fn test_http_metrics_ingest() {
                     metadata: vec![],
                     data: Some(metric::Data::Gauge(Gauge {
                         data_points: vec![NumberDataPoint {
+                            time_unix_nano: 1_000_000_000,
                             ..Default::default()
                         }],
                     })),
@@ -175,6 +246,10 @@ async fn test_http_invalid_body() {
         .unwrap();
 
     assert_eq!(response.status(), 400);
+    assert_eq!(response.headers()["content-type"], "application/x-protobuf");
+    let status = RpcStatus::decode(response.bytes().await.unwrap()).unwrap();
+    assert_eq!(status.code, tonic::Code::InvalidArgument as i32);
+    assert!(status.message.starts_with("invalid OTLP protobuf"));
 }
 
 #[tokio::test]
@@ -221,8 +296,8 @@ async fn test_http_json_trace_ingest() {
         .unwrap();
     assert_eq!(content_type, "application/json");
 
-    let s = store.read().await;
-    let traces = s.query_traces_since(0);
+    let s = store.traces.read().await;
+    let traces: Vec<_> = s.scan_traces(store::TimeBounds::since(0)).collect();
     assert_eq!(traces.len(), 1);
     let span = &traces[0].scope_spans[0].spans[0];
     assert_eq!(span.name, "json-span");
@@ -248,6 +323,7 @@ async fn test_http_json_logs_ingest() {
             },
             "scopeLogs": [{
                 "logRecords": [{
+                    "timeUnixNano": "1000000",
                     "severityText": "ERROR",
                     "body": { "stringValue": "something failed" }
                 }]
@@ -335,4 +411,584 @@ async fn test_http_json_invalid_body() {
         .unwrap();
 
     assert_eq!(response.status(), 400);
+    let status: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(status["code"], 3);
+    assert!(status["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("invalid OTLP JSON"));
+}
+
+#[tokio::test]
+async fn test_http_partial_success() {
+    let port = get_available_port();
+    let (store, _shutdown) = start_http_server(port).await;
+
+    let body = json!({
+        "resourceSpans": [{
+            "scopeSpans": [{
+                "spans": [
+                    {
+                        "traceId": "0102030405060708090a0b0c0d0e0f10",
+                        "spanId": "0102030405060708",
+                        "name": "good",
+                        "startTimeUnixNano": "1000000",
+                        "endTimeUnixNano": "2000000"
+                    },
+                    {
+                        "traceId": "0102",
+                        "spanId": "0102030405060708",
+                        "name": "short-trace-id",
+                        "startTimeUnixNano": "1000000",
+                        "endTimeUnixNano": "2000000"
+                    },
+                    {
+                        "traceId": "0102030405060708090a0b0c0d0e0f10",
+                        "spanId": "0102030405060709",
+                        "name": "backwards",
+                        "startTimeUnixNano": "2000000",
+                        "endTimeUnixNano": "1000000"
+                    }
+                ]
+            }]
+        }]
+    });
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/v1/traces", port))
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["partialSuccess"]["rejectedSpans"], "2");
+    assert_eq!(
+        body["partialSuccess"]["errorMessage"],
+        "span \"short-trace-id\": trace_id must be 16 bytes, got 2 (and 1 more)"
+    );
+    let store = store.traces.read().await;
+    let traces: Vec<_> = store.scan_traces(store::TimeBounds::ALL).collect();
+    let spans = &traces[0].scope_spans[0].spans;
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].name, "good");
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[tokio::test]
+async fn test_http_gzip_protobuf_ingest() {
+    let port = get_available_port();
+    let (store, _shutdown) = start_http_server(port).await;
+
+    let request = ExportTraceServiceRequest {
+        resource_spans: vec![ResourceSpans {
+            resource: make_resource("gzip-trace-svc"),
+            scope_spans: vec![ScopeSpans {
+                scope: None,
+                spans: vec![Span {
+                    trace_id: vec![1; 16],
+                    span_id: vec![1; 8],
+                    name: "gzip-span".into(),
+                    start_time_unix_nano: 1_000_000_000,
+                    end_time_unix_nano: 2_000_000_000,
+                    ..Default::default()
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    };
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/v1/traces", port))
+        .header("Content-Type", "application/x-protobuf")
+        .header("Content-Encoding", "gzip")
+        .body(gzip(&request.encode_to_vec()))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let s = store.traces.read().await;
+    let traces: Vec<_> = s.scan_traces(store::TimeBounds::ALL).collect();
+    assert_eq!(traces.len(), 1);
+    assert_eq!(traces[0].scope_spans[0].spans[0].name, "gzip-span");
+}
+
+#[tokio::test]
+async fn test_http_deflate_json_ingest() {
+    let port = get_available_port();
+    let (store, _shutdown) = start_http_server(port).await;
+
+    let body = json!({
+        "resourceLogs": [{
+            "scopeLogs": [{
+                "logRecords": [{
+                    "timeUnixNano": "1000000",
+                    "severityText": "INFO",
+                    "body": { "stringValue": "compressed" }
+                }]
+            }]
+        }]
+    });
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(body.to_string().as_bytes()).unwrap();
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/v1/logs", port))
+        .header("Content-Type", "application/json")
+        .header("Content-Encoding", "deflate")
+        .body(encoder.finish().unwrap())
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    assert_eq!(store.logs.read().await.log_count(), 1);
+}
+
+#[tokio::test]
+async fn test_http_unsupported_content_encoding() {
+    let port = get_available_port();
+    let (_store, _shutdown) = start_http_server(port).await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/v1/traces", port))
+        .header("Content-Type", "application/x-protobuf")
+        .header("Content-Encoding", "compress")
+        .body(vec![0u8; 8])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 415);
+}
+
+#[tokio::test]
+async fn test_http_decompressed_body_limit() {
+    let port = get_available_port();
+    let options = ServerOptions {
+        max_body_size: 1024,
+        ..ServerOptions::default()
+    };
+    let (store, _shutdown) = start_http_server_with_options(port, options).await;
+
+    // Highly compressible payload: small on the wire, large once decompressed.
+    let request = ExportLogsServiceRequest {
+        resource_logs: vec![ResourceLogs {
+            resource: make_resource("big-log-svc"),
+            scope_logs: vec![ScopeLogs {
+                scope: None,
+                log_records: vec![LogRecord {
+                    severity_text: "x".repeat(64 * 1024),
+                    time_unix_nano: 1_000_000_000,
+                    ..Default::default()
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    };
+    let compressed = gzip(&request.encode_to_vec());
+    assert!(compressed.len() < 1024);
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/v1/logs", port))
+        .header("Content-Type", "application/x-protobuf")
+        .header("Content-Encoding", "gzip")
+        .body(compressed)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 413);
+    assert_eq!(store.logs.read().await.log_count(), 0);
+}
+
+fn cors_options(origins: &[&str]) -> ServerOptions {
+    let origins: Vec<String> = origins.iter().map(|o| o.to_string()).collect();
+    let headers = vec!["content-type".to_string(), "authorization".to_string()];
+    ServerOptions {
+        cors: Some(otel_cli::server::otlp_http::CorsConfig::new(&origins, &headers).unwrap()),
+        ..ServerOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn test_http_cors_preflight() {
+    let port = get_available_port();
+    let (_store, _shutdown) =
+        start_http_server_with_options(port, cors_options(&["http://app.example.com"])).await;
+
+    let client = reqwest::Client::new();
+    for path in ["/v1/traces", "/v1/logs", "/v1/metrics"] {
+        let response = client
+            .request(
+                reqwest::Method::OPTIONS,
+                format!("http://127.0.0.1:{}{}", port, path),
+            )
+            .header("Origin", "http://app.example.com")
+            .header("Access-Control-Request-Method", "POST")
+            .header("Access-Control-Request-Headers", "content-type")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        let headers = response.headers();
+        assert_eq!(
+            headers["access-control-allow-origin"],
+            "http://app.example.com"
+        );
+        assert!(headers["access-control-allow-methods"]
+            .to_str()
+            .unwrap()
+            .contains("POST"));
+        assert!(headers["access-control-allow-headers"]
+            .to_str()
+            .unwrap()
+            .contains("content-type"));
+    }
+
+    // Unlisted origins get no CORS grant.
+    let response = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("http://127.0.0.1:{}/v1/traces", port),
+        )
+        .header("Origin", "http://evil.example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .send()
+        .await
+        .unwrap();
+    assert!(response
+        .headers()
+        .get("access-control-allow-origin")
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_http_cors_post_with_wildcard_origin() {
+    let port = get_available_port();
+    let (store, _shutdown) = start_http_server_with_options(port, cors_options(&["*"])).await;
+
+    let body = json!({
+        "resourceLogs": [{
+            "resource": {"attributes": [{"key": "service.name", "value": {"stringValue": "browser"}}]},
+            "scopeLogs": [{"logRecords": [{"timeUnixNano": "1000000", "severityText": "INFO"}]}]
+        }]
+    });
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/v1/logs", port))
+        .header("Origin", "http://localhost:3000")
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["access-control-allow-origin"], "*");
+    assert_eq!(store.logs.read().await.log_count(), 1);
+}
+
+#[tokio::test]
+async fn test_http_cors_preflight_skips_auth() {
+    let port = get_available_port();
+    let mut ingest_auth = otel_cli::server::auth::Authenticator::default();
+    ingest_auth
+        .add_bearer_token("secret", otel_cli::server::auth::Access::Admin)
+        .unwrap();
+    let options = ServerOptions {
+        ingest_auth,
+        ..cors_options(&["http://app.example.com"])
+    };
+    let (_store, _shutdown) = start_http_server_with_options(port, options).await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("http://127.0.0.1:{}/v1/traces", port),
+        )
+        .header("Origin", "http://app.example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .header(
+            "Access-Control-Request-Headers",
+            "authorization,content-type",
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    // Rejections still carry CORS headers so the browser can surface the 401.
+    let response = client
+        .post(format!("http://127.0.0.1:{}/v1/traces", port))
+        .header("Origin", "http://app.example.com")
+        .header("Content-Type", "application/x-protobuf")
+        .body(Vec::new())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 401);
+    assert_eq!(
+        response.headers()["access-control-allow-origin"],
+        "http://app.example.com"
+    );
+}
+
+#[tokio::test]
+async fn test_http_options_without_cors() {
+    let port = get_available_port();
+    let (_store, _shutdown) = start_http_server(port).await;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("http://127.0.0.1:{}/v1/traces", port),
+        )
+        .header("Origin", "http://app.example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 405);
+}
+
+#[tokio::test]
+async fn test_http_zipkin_json_ingest() {
+    let port = get_available_port();
+    let (store, _shutdown) = start_http_server(port).await;
+
+    let body = json!([{
+        "traceId": "5af7183fb1d4cf5f6b221d5bc9e6496c",
+        "id": "352bff9a74ca9ad2",
+        "kind": "SERVER",
+        "name": "get /api",
+        "timestamp": 1556604172355737u64,
+        "duration": 1431,
+        "localEndpoint": {"serviceName": "zipkin-svc"},
+        "tags": {"http.method": "GET"}
+    }]);
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/api/v2/spans", port))
+        .header("Content-Type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+
+    let store = store.traces.read().await;
+    assert_eq!(store.trace_count(), 1);
+    let traces: Vec<_> = store.scan_traces(store::TimeBounds::ALL).collect();
+    let rs = &traces[0];
+    assert_eq!(
+        otel_cli::client::get_service_name(&rs.resource),
+        "zipkin-svc"
+    );
+    assert_eq!(rs.scope_spans[0].spans[0].name, "get /api");
+}
+
+#[tokio::test]
+async fn test_http_zipkin_proto3_ingest() {
+    use otel_cli::proto::zipkin::proto3;
+
+    let port = get_available_port();
+    let (store, _shutdown) = start_http_server(port).await;
+
+    let request = proto3::ListOfSpans {
+        spans: vec![proto3::Span {
+            trace_id: vec![1; 8],
+            id: vec![2; 8],
+            kind: proto3::span::Kind::Client as i32,
+            name: "proto-span".into(),
+            timestamp: 1_000_000,
+            duration: 10,
+            local_endpoint: Some(proto3::Endpoint {
+                service_name: "zipkin-proto-svc".into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }],
+    };
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/api/v2/spans", port))
+        .header("Content-Type", "application/x-protobuf")
+        .body(request.encode_to_vec())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+
+    let store = store.traces.read().await;
+    let traces: Vec<_> = store.scan_traces(store::TimeBounds::ALL).collect();
+    let span = &traces[0].scope_spans[0].spans[0];
+    assert_eq!(span.trace_id, [vec![0; 8], vec![1; 8]].concat());
+    assert_eq!(span.end_time_unix_nano, 1_000_010_000);
+
+    let response = client
+        .post(format!("http://127.0.0.1:{}/api/v2/spans", port))
+        .header("Content-Type", "application/json")
+        .body("{\"not\": \"a list\"}")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_http_jaeger_thrift_ingest() {
+    fn field(buf: &mut Vec<u8>, ty: u8, id: i16) {
+        buf.push(ty);
+        buf.extend(id.to_be_bytes());
+    }
+    fn string(buf: &mut Vec<u8>, id: i16, v: &str) {
+        field(buf, 11, id);
+        buf.extend((v.len() as i32).to_be_bytes());
+        buf.extend(v.as_bytes());
+    }
+    fn i64(buf: &mut Vec<u8>, id: i16, v: i64) {
+        field(buf, 10, id);
+        buf.extend(v.to_be_bytes());
+    }
+
+    let port = get_available_port();
+    let (store, _shutdown) = start_http_server(port).await;
+
+    // Batch { process: Process { serviceName }, spans: [Span { ... }] }
+    let mut body = Vec::new();
+    field(&mut body, 12, 1);
+    string(&mut body, 1, "jaeger-thrift-svc");
+    body.push(0);
+    field(&mut body, 15, 2);
+    body.push(12);
+    body.extend(1i32.to_be_bytes());
+    i64(&mut body, 1, 1);
+    i64(&mut body, 2, 0);
+    i64(&mut body, 3, 2);
+    i64(&mut body, 4, 0);
+    string(&mut body, 5, "thrift-op");
+    i64(&mut body, 8, 1_000_000);
+    i64(&mut body, 9, 10);
+    body.push(0);
+    body.push(0);
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/api/traces", port))
+        .header("Content-Type", "application/x-thrift")
+        .body(body.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+
+    {
+        let store = store.traces.read().await;
+        let traces: Vec<_> = store.scan_traces(store::TimeBounds::ALL).collect();
+        let rs = &traces[0];
+        assert_eq!(
+            otel_cli::client::get_service_name(&rs.resource),
+            "jaeger-thrift-svc"
+        );
+        let span = &rs.scope_spans[0].spans[0];
+        assert_eq!(span.name, "thrift-op");
+        assert_eq!(span.trace_id, [vec![0; 15], vec![1]].concat());
+        assert_eq!(span.end_time_unix_nano, 1_000_010_000);
+    }
+
+    let response = client
+        .post(format!("http://127.0.0.1:{}/api/traces", port))
+        .header("Content-Type", "application/x-thrift")
+        .body(body[..body.len() / 2].to_vec())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
+
+#[tokio::test]
+async fn test_http_prometheus_remote_write() {
+    use otel_cli::proto::opentelemetry::proto::metrics::v1::metric::Data;
+    use otel_cli::proto::prometheus::{Label, Sample, TimeSeries, WriteRequest};
+
+    let port = get_available_port();
+    let (store, _shutdown) = start_http_server(port).await;
+
+    let label = |name: &str, value: &str| Label {
+        name: name.into(),
+        value: value.into(),
+    };
+    let request = WriteRequest {
+        timeseries: vec![TimeSeries {
+            labels: vec![
+                label("__name__", "http_requests_total"),
+                label("instance", "sidecar:9090"),
+                label("job", "sidecar"),
+                label("method", "GET"),
+            ],
+            samples: vec![Sample {
+                value: 42.0,
+                timestamp: 1_700_000_000_000,
+            }],
+        }],
+        metadata: vec![],
+    };
+    let body = snap::raw::Encoder::new()
+        .compress_vec(&request.encode_to_vec())
+        .unwrap();
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(format!("http://127.0.0.1:{}/api/v1/write", port))
+        .header("Content-Type", "application/x-protobuf")
+        .header("Content-Encoding", "snappy")
+        .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+        .body(body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+
+    {
+        let store = store.metrics.read().await;
+        assert_eq!(store.metric_count(), 1);
+        let metrics: Vec<_> = store.scan_metrics(store::TimeBounds::ALL).collect();
+        let rm = &metrics[0];
+        assert_eq!(otel_cli::client::get_service_name(&rm.resource), "sidecar");
+        let metric = &rm.scope_metrics[0].metrics[0];
+        assert_eq!(metric.name, "http_requests_total");
+        let Some(Data::Sum(sum)) = &metric.data else {
+            panic!("expected a sum: {:?}", metric);
+        };
+        assert_eq!(sum.data_points[0].time_unix_nano, 1_700_000_000_000_000_000);
+        assert_eq!(sum.data_points[0].attributes[0].key, "method");
+    }
+
+    let response = client
+        .post(format!("http://127.0.0.1:{}/api/v1/write", port))
+        .header("Content-Encoding", "snappy")
+        .body(request.encode_to_vec())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
 }
@@ -11,8 +11,10 @@ use otel_cli::proto::opentelemetry::proto::{
     trace::v1::{ResourceSpans, ScopeSpans, Span},
 };
 use otel_cli::proto::otelcli::query::v1::{
-    query_service_client::QueryServiceClient, SqlQueryRequest,
+    query_service_client::QueryServiceClient, DeleteRequest, FollowRequest, SqlQueryRequest,
+    SqlQueryResponse,
 };
+use otel_cli::server::ServerOptions;
 use otel_cli::store;
 use tokio::time::{timeout, Duration};
 use tokio_util::sync::CancellationToken;
@@ -44,9 +46,14 @@ async fn start_servers(grpc_port: u16, query_port: u16) -> (store::SharedStore,
     let store_clone = shared_store.clone();
     let shutdown_clone = shutdown.clone();
     tokio::spawn(async move {
-        otel_cli::server::run_grpc_server(grpc_listener, store_clone, shutdown_clone)
-            .await
-            .unwrap();
+        otel_cli::server::run_grpc_server(
+            grpc_listener,
+            store_clone,
+            ServerOptions::default(),
+            shutdown_clone,
+        )
+        .await
+        .unwrap();
     });
 
     let query_addr: std::net::SocketAddr = format!("127.0.0.1:{}", query_port).parse().unwrap();
@@ -55,9 +62,15 @@ async fn start_servers(grpc_port: u16, query_port: u16) -> (store::SharedStore,
     let ctx = otel_cli::query::datafusion_ctx::create_context(shared_store.clone());
     let shutdown_clone = shutdown.clone();
     tokio::spawn(async move {
-        otel_cli::server::run_query_server(query_listener, store_clone, ctx, shutdown_clone)
-            .await
-            .unwrap();
+        otel_cli::server::run_query_server(
+            query_listener,
+            store_clone,
+            ctx,
+            ServerOptions::default(),
+            shutdown_clone,
+        )
+        .await
+        .unwrap();
     });
 
     tokio::time::sleep(std::time::Duration::from_millis(100)).await;
@@ -96,6 +109,8 @@ async fn test_sql_query_traces_with_service_filter() {
                             trace_id: vec![1; 16],
                             span_id: vec![1; 8],
                             name: "span-a".into(),
+                            start_time_unix_nano: 1_000_000_000,
+                            end_time_unix_nano: 2_000_000_000,
                             ..Default::default()
                         }],
                         schema_url: String::new(),
@@ -110,6 +125,8 @@ async fn test_sql_query_traces_with_service_filter() {
                             trace_id: vec![2; 16],
                             span_id: vec![2; 8],
                             name: "span-b".into(),
+                            start_time_unix_nano: 1_000_000_000,
+                            end_time_unix_nano: 2_000_000_000,
                             ..Default::default()
                         }],
                         schema_url: String::new(),
@@ -155,6 +172,7 @@ async fn test_sql_query_logs_with_severity_filter() {
                         log_records: vec![LogRecord {
                             severity_text: "ERROR".into(),
                             severity_number: 17,
+                            time_unix_nano: 1_000_000_000,
                             ..Default::default()
                         }],
                         schema_url: String::new(),
@@ -168,6 +186,7 @@ async fn test_sql_query_logs_with_severity_filter() {
                         log_records: vec![LogRecord {
                             severity_text: "INFO".into(),
                             severity_number: 9,
+                            time_unix_nano: 1_000_000_000,
                             ..Default::default()
                         }],
                         schema_url: String::new(),
@@ -214,6 +233,7 @@ async fn test_sql_query_logs_severity_ge() {
                         log_records: vec![LogRecord {
                             severity_text: text.to_string(),
                             severity_number: *num,
+                            time_unix_nano: 1_000_000_000,
                             ..Default::default()
                         }],
                         schema_url: String::new(),
@@ -264,6 +284,7 @@ async fn test_sql_query_logs_with_service_name_filter() {
                         log_records: vec![LogRecord {
                             severity_text: "INFO".into(),
                             severity_number: 9,
+                            time_unix_nano: 1_000_000_000,
                             ..Default::default()
                         }],
                         schema_url: String::new(),
@@ -311,7 +332,10 @@ async fn test_sql_query_metrics_with_name_filter() {
                             unit: String::new(),
                             metadata: vec![],
                             data: Some(metric::Data::Gauge(Gauge {
-                                data_points: vec![NumberDataPoint::default()],
+                                data_points: vec![NumberDataPoint {
+                                    time_unix_nano: 1_000_000_000,
+                                    ..Default::default()
+                                }],
                             })),
                         }],
                         schema_url: String::new(),
@@ -328,7 +352,10 @@ async fn test_sql_query_metrics_with_name_filter() {
                             unit: String::new(),
                             metadata: vec![],
                             data: Some(metric::Data::Gauge(Gauge {
-                                data_points: vec![NumberDataPoint::default()],
+                                data_points: vec![NumberDataPoint {
+                                    time_unix_nano: 1_000_000_000,
+                                    ..Default::default()
+                                }],
                             })),
                         }],
                         schema_url: String::new(),
@@ -445,6 +472,252 @@ async fn test_follow_sql_traces() {
     assert!(!delta.rows.is_empty());
 }
 
+fn make_log(body: &str, time: u64) -> ResourceLogs {
+    ResourceLogs {
+        resource: make_resource("svc-a"),
+        scope_logs: vec![ScopeLogs {
+            log_records: vec![LogRecord {
+                time_unix_nano: time,
+                body: Some(AnyValue {
+                    value: Some(any_value::Value::StringValue(body.into())),
+                }),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+fn log_bodies(resource_logs: &[ResourceLogs]) -> Vec<String> {
+    resource_logs
+        .iter()
+        .flat_map(|rl| &rl.scope_logs)
+        .flat_map(|sl| &sl.log_records)
+        .filter_map(|lr| match lr.body.as_ref()?.value.as_ref()? {
+            any_value::Value::StringValue(s) => Some(s.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_follow_logs_delivers_late_data_once() {
+    let grpc_port = get_available_port();
+    let query_port = get_available_port();
+    let (store, _shutdown) = start_servers(grpc_port, query_port).await;
+    store
+        .logs
+        .write()
+        .await
+        .insert_logs(vec![make_log("first", 300)]);
+
+    let mut query_client = QueryServiceClient::connect(format!("http://127.0.0.1:{}", query_port))
+        .await
+        .unwrap();
+    let mut stream = query_client
+        .follow_logs(FollowRequest {})
+        .await
+        .unwrap()
+        .into_inner();
+    let initial = timeout(Duration::from_secs(2), stream.message())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(log_bodies(&initial.resource_logs), vec!["first"]);
+
+    // Data older than, or as old as, what was already sent still arrives,
+    // in the order it was stored.
+    store
+        .logs
+        .write()
+        .await
+        .insert_logs(vec![make_log("late", 100), make_log("same-time", 300)]);
+    store
+        .logs
+        .write()
+        .await
+        .insert_logs(vec![make_log("next", 400)]);
+
+    let mut received = Vec::new();
+    while received.len() < 3 {
+        let msg = timeout(Duration::from_secs(2), stream.message())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        received.extend(log_bodies(&msg.resource_logs));
+    }
+    assert_eq!(received, vec!["late", "same-time", "next"]);
+    assert!(timeout(Duration::from_millis(200), stream.message())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_follow_logs_resets_after_delete() {
+    let grpc_port = get_available_port();
+    let query_port = get_available_port();
+    let (store, _shutdown) = start_servers(grpc_port, query_port).await;
+    store
+        .logs
+        .write()
+        .await
+        .insert_logs(vec![make_log("old", 100), make_log("keep", 400)]);
+
+    let mut query_client = QueryServiceClient::connect(format!("http://127.0.0.1:{}", query_port))
+        .await
+        .unwrap();
+    let mut stream = query_client
+        .follow_logs(FollowRequest {})
+        .await
+        .unwrap()
+        .into_inner();
+    let initial = timeout(Duration::from_secs(2), stream.message())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(log_bodies(&initial.resource_logs), vec!["old", "keep"]);
+    assert!(!initial.reset);
+
+    query_client
+        .delete(DeleteRequest {
+            logs: true,
+            before: 200,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let msg = timeout(Duration::from_secs(2), stream.message())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(msg.reset);
+    assert_eq!(log_bodies(&msg.resource_logs), vec!["keep"]);
+
+    store
+        .logs
+        .write()
+        .await
+        .insert_logs(vec![make_log("new", 500)]);
+    let msg = timeout(Duration::from_secs(2), stream.message())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert!(!msg.reset);
+    assert_eq!(log_bodies(&msg.resource_logs), vec!["new"]);
+}
+
+/// The `body` column of the next `FollowSql` result.
+async fn next_bodies(stream: &mut tonic::Streaming<SqlQueryResponse>) -> Vec<String> {
+    let msg = timeout(Duration::from_secs(2), stream.message())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    msg.rows
+        .iter()
+        .map(|row| get_row_string(row, "body").unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_follow_sql_updates_after_delete() {
+    let grpc_port = get_available_port();
+    let query_port = get_available_port();
+    let (store, _shutdown) = start_servers(grpc_port, query_port).await;
+    store
+        .logs
+        .write()
+        .await
+        .insert_logs(vec![make_log("old", 100), make_log("keep", 400)]);
+
+    let mut query_client = QueryServiceClient::connect(format!("http://127.0.0.1:{}", query_port))
+        .await
+        .unwrap();
+    let mut stream = query_client
+        .follow_sql(SqlQueryRequest {
+            query: "SELECT body FROM logs ORDER BY timestamp".into(),
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(next_bodies(&mut stream).await, vec!["old", "keep"]);
+
+    for (before, expected) in [(200, vec!["keep"]), (500, vec![])] {
+        query_client
+            .delete(DeleteRequest {
+                logs: true,
+                before,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(next_bodies(&mut stream).await, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_delete_logs_before_and_where() {
+    let grpc_port = get_available_port();
+    let query_port = get_available_port();
+    let (store, _shutdown) = start_servers(grpc_port, query_port).await;
+    store.logs.write().await.insert_logs(vec![
+        make_log("old", 100),
+        make_log("noisy", 300),
+        make_log("keep", 400),
+    ]);
+
+    let mut query_client = QueryServiceClient::connect(format!("http://127.0.0.1:{}", query_port))
+        .await
+        .unwrap();
+    let response = query_client
+        .delete(DeleteRequest {
+            logs: true,
+            before: 200,
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.log_records, 1);
+    let response = query_client
+        .delete(DeleteRequest {
+            logs: true,
+            service: "svc-a".into(),
+            filter: "body = 'noisy'".into(),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .into_inner();
+    assert_eq!(response.log_records, 1);
+
+    let remaining: Vec<ResourceLogs> = store
+        .logs
+        .read()
+        .await
+        .scan_logs(store::TimeBounds::ALL)
+        .map(|rl| rl.into_owned())
+        .collect();
+    assert_eq!(log_bodies(&remaining), vec!["keep"]);
+
+    // A delete without any condition is refused rather than clearing everything.
+    let err = query_client
+        .delete(DeleteRequest {
+            logs: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    assert_eq!(store.logs.read().await.log_count(), 1);
+}
+
 #[tokio::test]
 async fn test_sql_query_traces_with_trace_id_filter() {
     let grpc_port = get_available_port();
@@ -466,6 +739,8 @@ async fn test_sql_query_traces_with_trace_id_filter() {
                             trace_id: vec![0xaa; 16],
                             span_id: vec![1; 8],
                             name: "span-aa".into(),
+                            start_time_unix_nano: 1_000_000_000,
+                            end_time_unix_nano: 2_000_000_000,
                             ..Default::default()
                         }],
                         schema_url: String::new(),
@@ -480,6 +755,8 @@ async fn test_sql_query_traces_with_trace_id_filter() {
                             trace_id: vec![0xbb; 16],
                             span_id: vec![2; 8],
                             name: "span-bb".into(),
+                            start_time_unix_nano: 1_000_000_000,
+                            end_time_unix_nano: 2_000_000_000,
                             ..Default::default()
                         }],
                         schema_url: String::new(),
@@ -531,6 +808,8 @@ async fn test_sql_query_with_column_projection() {
                         span_id: vec![1; 8],
                         name: "test-span".into(),
                         kind: 2,
+                        start_time_unix_nano: 1_000_000_000,
+                        end_time_unix_nano: 2_000_000_000,
                         ..Default::default()
                     }],
                     schema_url: String::new(),
@@ -0,0 +1,78 @@
+use otel_cli::proto::opentelemetry::proto::metrics::v1::{metric, number_data_point};
+use otel_cli::store;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+async fn start_statsd_server(
+    flush_interval: Duration,
+) -> (store::SharedStore, std::net::SocketAddr, CancellationToken) {
+    let (shared_store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
+    let socket = otel_cli::server::bind_udp("StatsD", "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let addr = socket.local_addr().unwrap();
+    let store_clone = shared_store.clone();
+    let shutdown = CancellationToken::new();
+    let shutdown_clone = shutdown.clone();
+    tokio::spawn(async move {
+        otel_cli::server::statsd::run_statsd_server(
+            socket,
+            store_clone,
+            flush_interval,
+            shutdown_clone,
+        )
+        .await
+        .unwrap();
+    });
+    (shared_store, addr, shutdown)
+}
+
+#[tokio::test]
+async fn test_statsd_flush_interval() {
+    let (store, addr, _shutdown) = start_statsd_server(Duration::from_millis(200)).await;
+
+    let client = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    client
+        .send_to(
+            b"checkout.count:1|c|#service:shop\ncheckout.count:2|c|#service:shop\n\
+              checkout.latency:120|ms|#service:shop\nbad line\n",
+            addr,
+        )
+        .await
+        .unwrap();
+
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let store = store.metrics.read().await;
+    assert_eq!(store.metric_count(), 1);
+    let metrics: Vec<_> = store.scan_metrics(store::TimeBounds::ALL).collect();
+    let rm = &metrics[0];
+    assert_eq!(otel_cli::client::get_service_name(&rm.resource), "shop");
+    let metrics = &rm.scope_metrics[0].metrics;
+    assert_eq!(metrics.len(), 2);
+    let Some(metric::Data::Sum(sum)) = &metrics[0].data else {
+        panic!("expected a sum: {:?}", metrics[0]);
+    };
+    assert_eq!(
+        sum.data_points[0].value,
+        Some(number_data_point::Value::AsDouble(3.0))
+    );
+    let Some(metric::Data::Histogram(histogram)) = &metrics[1].data else {
+        panic!("expected a histogram: {:?}", metrics[1]);
+    };
+    assert_eq!(histogram.data_points[0].count, 1);
+}
+
+#[tokio::test]
+async fn test_statsd_flushes_on_shutdown() {
+    let (store, addr, shutdown) = start_statsd_server(Duration::from_secs(3600)).await;
+
+    let client = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    client.send_to(b"queue.depth:7|g", addr).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(store.metrics.read().await.metric_count(), 0);
+
+    shutdown.cancel();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert_eq!(store.metrics.read().await.metric_count(), 1);
+}
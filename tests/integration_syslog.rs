@@ -0,0 +1,74 @@
+use otel_cli::proto::opentelemetry::proto::common::v1::any_value;
+use otel_cli::store;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn test_syslog_udp_and_tcp_ingest() {
+    let (store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
+    let shutdown = CancellationToken::new();
+
+    let socket = otel_cli::server::bind_udp("syslog UDP", "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let udp_addr = socket.local_addr().unwrap();
+    tokio::spawn(otel_cli::server::syslog::run_syslog_udp_server(
+        socket,
+        store.clone(),
+        shutdown.clone(),
+    ));
+    let listener = otel_cli::server::bind_tcp("syslog TCP", "127.0.0.1:0".parse().unwrap())
+        .await
+        .unwrap();
+    let tcp_addr = listener.local_addr().unwrap();
+    tokio::spawn(otel_cli::server::syslog::run_syslog_tcp_server(
+        listener,
+        store.clone(),
+        shutdown.clone(),
+    ));
+
+    let client = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    client
+        .send_to(
+            b"<11>1 2024-01-01T00:00:00Z router sshd 77 - [auth@1 user=\"bob\"] login failed",
+            udp_addr,
+        )
+        .await
+        .unwrap();
+
+    let mut stream = tokio::net::TcpStream::connect(tcp_addr).await.unwrap();
+    let framed = "<14>1 2024-01-01T00:00:01Z db postgres - - - checkpoint";
+    stream
+        .write_all(format!("{} {}", framed.len(), framed).as_bytes())
+        .await
+        .unwrap();
+    stream
+        .write_all(b"<30>Jan  1 00:00:02 db cron[9]: job done\n")
+        .await
+        .unwrap();
+    stream.flush().await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let store = store.logs.read().await;
+    assert_eq!(store.log_count(), 3);
+    let logs: Vec<_> = store.scan_logs(store::TimeBounds::ALL).collect();
+    let sshd = logs
+        .iter()
+        .find(|rl| otel_cli::client::get_service_name(&rl.resource) == "sshd")
+        .unwrap();
+    let record = &sshd.scope_logs[0].log_records[0];
+    assert_eq!(record.severity_text, "err");
+    assert_eq!(record.time_unix_nano, 1_704_067_200_000_000_000);
+    assert!(record.attributes.iter().any(|a| a.key == "auth@1.user"));
+    assert!(matches!(
+        record.body.as_ref().and_then(|b| b.value.as_ref()),
+        Some(any_value::Value::StringValue(s)) if s == "login failed"
+    ));
+    assert!(logs
+        .iter()
+        .any(|rl| otel_cli::client::get_service_name(&rl.resource) == "cron"));
+
+    shutdown.cancel();
+}
@@ -0,0 +1,354 @@
+use otel_cli::cli::ConnectionArgs;
+use otel_cli::proto::opentelemetry::proto::{
+    collector::{
+        logs::v1::ExportLogsServiceRequest,
+        trace::v1::{trace_service_client::TraceServiceClient, ExportTraceServiceRequest},
+    },
+    common::v1::{any_value, AnyValue, KeyValue},
+    logs::v1::{LogRecord, ResourceLogs, ScopeLogs},
+    resource::v1::Resource,
+    trace::v1::{ResourceSpans, ScopeSpans, Span},
+};
+use otel_cli::proto::otelcli::query::v1::SqlQueryRequest;
+use otel_cli::server::{tls::TlsConfig, ServerOptions};
+use otel_cli::store;
+use prost::Message;
+use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
+use tokio_util::sync::CancellationToken;
+use tonic::transport::{Certificate, ClientTlsConfig, Endpoint, Identity};
+
+struct Pki {
+    dir: tempfile::TempDir,
+    ca_pem: String,
+    client_cert_pem: String,
+    client_key_pem: String,
+}
+
+impl Pki {
+    fn path(&self, name: &str) -> std::path::PathBuf {
+        self.dir.path().join(name)
+    }
+}
+
+/// Generate a CA plus server and client certificates signed by it.
+fn generate_pki() -> Pki {
+    // reqwest (dev-dependency) enables aws-lc-rs alongside the ring provider the
+    // server uses, so rustls cannot pick a process default on its own.
+    let _ = tokio_rustls::rustls::crypto::ring::default_provider().install_default();
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+
+    let server_key = KeyPair::generate().unwrap();
+    let server_cert = CertificateParams::new(vec!["localhost".into(), "127.0.0.1".into()])
+        .unwrap()
+        .signed_by(&server_key, &ca_cert, &ca_key)
+        .unwrap();
+
+    let client_key = KeyPair::generate().unwrap();
+    let client_cert = CertificateParams::new(vec!["otel-cli-client".into()])
+        .unwrap()
+        .signed_by(&client_key, &ca_cert, &ca_key)
+        .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let pki = Pki {
+        dir,
+        ca_pem: ca_cert.pem(),
+        client_cert_pem: client_cert.pem(),
+        client_key_pem: client_key.serialize_pem(),
+    };
+    std::fs::write(pki.path("ca.pem"), &pki.ca_pem).unwrap();
+    std::fs::write(pki.path("server.pem"), server_cert.pem()).unwrap();
+    std::fs::write(pki.path("server.key"), server_key.serialize_pem()).unwrap();
+    std::fs::write(pki.path("client.pem"), &pki.client_cert_pem).unwrap();
+    std::fs::write(pki.path("client.key"), &pki.client_key_pem).unwrap();
+    pki
+}
+
+fn get_available_port() -> u16 {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+fn make_resource(service_name: &str) -> Option<Resource> {
+    Some(Resource {
+        attributes: vec![KeyValue {
+            key: "service.name".into(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(service_name.into())),
+            }),
+        }],
+        dropped_attributes_count: 0,
+        entity_refs: vec![],
+    })
+}
+
+struct Ports {
+    grpc: u16,
+    http: u16,
+    query: u16,
+}
+
+async fn start_tls_servers(pki: &Pki) -> (store::SharedStore, Ports, CancellationToken) {
+    let tls = TlsConfig::load(
+        &pki.path("server.pem"),
+        &pki.path("server.key"),
+        Some(&pki.path("ca.pem")),
+    )
+    .unwrap();
+    let options = ServerOptions {
+        tls: Some(tls),
+        ..ServerOptions::default()
+    };
+    let ports = Ports {
+        grpc: get_available_port(),
+        http: get_available_port(),
+        query: get_available_port(),
+    };
+    let (shared_store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
+    let shutdown = CancellationToken::new();
+
+    let (grpc_listener, http_listener, query_listener) = otel_cli::server::bind_listeners(
+        format!("127.0.0.1:{}", ports.grpc).parse().unwrap(),
+        format!("127.0.0.1:{}", ports.http).parse().unwrap(),
+        format!("127.0.0.1:{}", ports.query).parse().unwrap(),
+    )
+    .await
+    .unwrap();
+    tokio::spawn(otel_cli::server::run_grpc_server(
+        grpc_listener,
+        shared_store.clone(),
+        options.clone(),
+        shutdown.clone(),
+    ));
+    tokio::spawn(otel_cli::server::run_http_server(
+        http_listener,
+        shared_store.clone(),
+        options.clone(),
+        shutdown.clone(),
+    ));
+    let ctx = otel_cli::query::datafusion_ctx::create_context(shared_store.clone());
+    tokio::spawn(otel_cli::server::run_query_server(
+        query_listener,
+        shared_store.clone(),
+        ctx,
+        options,
+        shutdown.clone(),
+    ));
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    (shared_store, ports, shutdown)
+}
+
+fn client_connection(pki: &Pki) -> ConnectionArgs {
+    ConnectionArgs {
+        ca_cert: Some(pki.path("ca.pem")),
+        client_cert: Some(pki.path("client.pem")),
+        client_key: Some(pki.path("client.key")),
+        ..ConnectionArgs::default()
+    }
+}
+
+#[tokio::test]
+async fn test_tls_grpc_export_and_query_with_client_cert() {
+    let pki = generate_pki();
+    let (_store, ports, _shutdown) = start_tls_servers(&pki).await;
+
+    let tls = ClientTlsConfig::new()
+        .ca_certificate(Certificate::from_pem(&pki.ca_pem))
+        .identity(Identity::from_pem(
+            &pki.client_cert_pem,
+            &pki.client_key_pem,
+        ));
+    let channel = Endpoint::from_shared(format!("https://127.0.0.1:{}", ports.grpc))
+        .unwrap()
+        .tls_config(tls)
+        .unwrap()
+        .connect()
+        .await
+        .unwrap();
+    let mut trace_client = TraceServiceClient::new(channel);
+    trace_client
+        .export(ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: make_resource("tls-service"),
+                scope_spans: vec![ScopeSpans {
+                    scope: None,
+                    spans: vec![Span {
+                        trace_id: vec![3; 16],
+                        span_id: vec![3; 8],
+                        name: "tls-span".into(),
+                        start_time_unix_nano: 1_000_000_000,
+                        end_time_unix_nano: 2_000_000_000,
+                        ..Default::default()
+                    }],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        })
+        .await
+        .unwrap();
+
+    let server = format!("https://127.0.0.1:{}", ports.query);
+    let mut query_client = otel_cli::client::connect(&server, &client_connection(&pki))
+        .await
+        .unwrap();
+    let rows = query_client
+        .sql_query(SqlQueryRequest {
+            query: "SELECT span_name FROM traces WHERE service_name = 'tls-service'".into(),
+        })
+        .await
+        .unwrap()
+        .into_inner()
+        .rows;
+    assert_eq!(rows.len(), 1);
+}
+
+#[tokio::test]
+async fn test_tls_query_rejects_missing_client_cert() {
+    let pki = generate_pki();
+    let (_store, ports, _shutdown) = start_tls_servers(&pki).await;
+
+    let server = format!("https://127.0.0.1:{}", ports.query);
+    let connection = ConnectionArgs {
+        ca_cert: Some(pki.path("ca.pem")),
+        ..ConnectionArgs::default()
+    };
+    let result = match otel_cli::client::connect(&server, &connection).await {
+        Ok(mut client) => client
+            .sql_query(SqlQueryRequest {
+                query: "SELECT 1".into(),
+            })
+            .await
+            .map(|_| ()),
+        Err(_) => return,
+    };
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_tls_plaintext_client_rejected() {
+    let pki = generate_pki();
+    let (_store, ports, _shutdown) = start_tls_servers(&pki).await;
+
+    let server = format!("http://127.0.0.1:{}", ports.query);
+    let result = match otel_cli::client::connect(&server, &ConnectionArgs::default()).await {
+        Ok(mut client) => client
+            .sql_query(SqlQueryRequest {
+                query: "SELECT 1".into(),
+            })
+            .await
+            .map(|_| ()),
+        Err(_) => return,
+    };
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_tls_http_ingest_with_client_cert() {
+    let pki = generate_pki();
+    let (store, ports, _shutdown) = start_tls_servers(&pki).await;
+
+    let identity = reqwest::Identity::from_pem(
+        format!("{}{}", pki.client_cert_pem, pki.client_key_pem).as_bytes(),
+    )
+    .unwrap();
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(pki.ca_pem.as_bytes()).unwrap())
+        .identity(identity)
+        .build()
+        .unwrap();
+
+    let request = ExportLogsServiceRequest {
+        resource_logs: vec![ResourceLogs {
+            resource: make_resource("tls-http-svc"),
+            scope_logs: vec![ScopeLogs {
+                scope: None,
+                log_records: vec![LogRecord {
+                    severity_text: "INFO".into(),
+                    time_unix_nano: 1_000_000_000,
+                    ..Default::default()
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    };
+    let response = client
+        .post(format!("https://localhost:{}/v1/logs", ports.http))
+        .header("Content-Type", "application/x-protobuf")
+        .body(request.encode_to_vec())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(store.logs.read().await.log_count(), 1);
+
+    // Without a client certificate the handshake is refused.
+    let anonymous = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(pki.ca_pem.as_bytes()).unwrap())
+        .build()
+        .unwrap();
+    let result = anonymous
+        .post(format!("https://localhost:{}/v1/logs", ports.http))
+        .header("Content-Type", "application/x-protobuf")
+        .body(request.encode_to_vec())
+        .send()
+        .await;
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_tls_http_idle_connection_does_not_block_others() {
+    let pki = generate_pki();
+    let (store, ports, _shutdown) = start_tls_servers(&pki).await;
+
+    // Connects but never starts a handshake.
+    let _idle = tokio::net::TcpStream::connect(("127.0.0.1", ports.http))
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let identity = reqwest::Identity::from_pem(
+        format!("{}{}", pki.client_cert_pem, pki.client_key_pem).as_bytes(),
+    )
+    .unwrap();
+    let client = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(pki.ca_pem.as_bytes()).unwrap())
+        .identity(identity)
+        .build()
+        .unwrap();
+    let request = ExportLogsServiceRequest {
+        resource_logs: vec![ResourceLogs {
+            resource: make_resource("tls-http-svc"),
+            scope_logs: vec![ScopeLogs {
+                scope: None,
+                log_records: vec![LogRecord {
+                    time_unix_nano: 1_000_000_000,
+                    ..Default::default()
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    };
+    // Well within the handshake timeout the idle connection would otherwise
+    // hold the listener for.
+    let response = tokio::time::timeout(
+        std::time::Duration::from_secs(3),
+        client
+            .post(format!("https://localhost:{}/v1/logs", ports.http))
+            .header("Content-Type", "application/x-protobuf")
+            .body(request.encode_to_vec())
+            .send(),
+    )
+    .await
+    .expect("request blocked behind the idle connection")
+    .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(store.logs.read().await.log_count(), 1);
+}
@@ -0,0 +1,147 @@
+use otel_cli::cli::ConnectionArgs;
+use otel_cli::proto::opentelemetry::proto::{
+    collector::{
+        logs::v1::ExportLogsServiceRequest,
+        trace::v1::{trace_service_client::TraceServiceClient, ExportTraceServiceRequest},
+    },
+    common::v1::{any_value, AnyValue, KeyValue},
+    logs::v1::{LogRecord, ResourceLogs, ScopeLogs},
+    resource::v1::Resource,
+    trace::v1::{ResourceSpans, ScopeSpans, Span},
+};
+use otel_cli::proto::otelcli::query::v1::StatusRequest;
+use otel_cli::server::{ListenAddr, ServerOptions};
+use otel_cli::store;
+use prost::Message;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+
+fn make_resource(service_name: &str) -> Option<Resource> {
+    Some(Resource {
+        attributes: vec![KeyValue {
+            key: "service.name".into(),
+            value: Some(AnyValue {
+                value: Some(any_value::Value::StringValue(service_name.into())),
+            }),
+        }],
+        dropped_attributes_count: 0,
+        entity_refs: vec![],
+    })
+}
+
+#[tokio::test]
+async fn test_unix_socket_listeners() {
+    let dir = tempfile::tempdir().unwrap();
+    let grpc_addr = ListenAddr::Unix(dir.path().join("grpc.sock"));
+    let http_addr = ListenAddr::Unix(dir.path().join("http.sock"));
+    let query_addr = ListenAddr::Unix(dir.path().join("query.sock"));
+
+    let (shared_store, _rx) = store::new_shared(1000, 100000, 1000, 1000);
+    let shutdown = CancellationToken::new();
+    let options = ServerOptions::default();
+
+    let (grpc_listener, http_listener, query_listener) =
+        otel_cli::server::bind_listeners(grpc_addr.clone(), http_addr.clone(), query_addr.clone())
+            .await
+            .unwrap();
+    let grpc_handle = tokio::spawn(otel_cli::server::run_grpc_server(
+        grpc_listener,
+        shared_store.clone(),
+        options.clone(),
+        shutdown.clone(),
+    ));
+    let http_handle = tokio::spawn(otel_cli::server::run_http_server(
+        http_listener,
+        shared_store.clone(),
+        options.clone(),
+        shutdown.clone(),
+    ));
+    let ctx = otel_cli::query::datafusion_ctx::create_context(shared_store.clone());
+    let query_handle = tokio::spawn(otel_cli::server::run_query_server(
+        query_listener,
+        shared_store.clone(),
+        ctx,
+        options,
+        shutdown.clone(),
+    ));
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    // OTLP gRPC over the socket
+    let mut trace_client = TraceServiceClient::connect(grpc_addr.to_string())
+        .await
+        .unwrap();
+    trace_client
+        .export(ExportTraceServiceRequest {
+            resource_spans: vec![ResourceSpans {
+                resource: make_resource("unix-service"),
+                scope_spans: vec![ScopeSpans {
+                    scope: None,
+                    spans: vec![Span {
+                        trace_id: vec![7; 16],
+                        span_id: vec![7; 8],
+                        name: "unix-span".into(),
+                        start_time_unix_nano: 1_000_000_000,
+                        end_time_unix_nano: 2_000_000_000,
+                        ..Default::default()
+                    }],
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        })
+        .await
+        .unwrap();
+
+    // OTLP/HTTP over the socket
+    let body = ExportLogsServiceRequest {
+        resource_logs: vec![ResourceLogs {
+            resource: make_resource("unix-service"),
+            scope_logs: vec![ScopeLogs {
+                scope: None,
+                log_records: vec![LogRecord {
+                    time_unix_nano: 1_000_000_000,
+                    body: Some(AnyValue {
+                        value: Some(any_value::Value::StringValue("over a socket".into())),
+                    }),
+                    ..Default::default()
+                }],
+                schema_url: String::new(),
+            }],
+            schema_url: String::new(),
+        }],
+    }
+    .encode_to_vec();
+    let mut stream = tokio::net::UnixStream::connect(dir.path().join("http.sock"))
+        .await
+        .unwrap();
+    let head = format!(
+        "POST /v1/logs HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/x-protobuf\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&body).await.unwrap();
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .unwrap_or_default();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    // Query API over the socket, as the client subcommands connect
+    let mut client = otel_cli::client::connect(&query_addr.to_string(), &ConnectionArgs::default())
+        .await
+        .unwrap();
+    let status = client.status(StatusRequest {}).await.unwrap().into_inner();
+    assert_eq!(status.trace_count, 1);
+    assert_eq!(status.log_count, 1);
+
+    shutdown.cancel();
+    let _ = grpc_handle.await;
+    let _ = http_handle.await;
+    let _ = query_handle.await;
+    for addr in [&grpc_addr, &http_addr, &query_addr] {
+        addr.cleanup();
+    }
+    assert!(!dir.path().join("grpc.sock").exists());
+}